@@ -1 +1,3 @@
-pub mod server;
\ No newline at end of file
+pub mod routes;
+pub mod server;
+pub mod state;
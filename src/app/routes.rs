@@ -0,0 +1,41 @@
+use actix_web::web;
+
+use crate::controllers::{configuration, history, modbus, system, time};
+
+/// 注册 /cdu 下的 API 路由
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/cdu/config")
+            .route("/export", web::get().to(configuration::export_config))
+            .route("/import", web::post().to(configuration::import_config)),
+    )
+    .service(
+        web::scope("/cdu/time")
+            .route("/zone", web::get().to(time::get_time_zone))
+            .route("/zone", web::put().to(time::set_time_zone)),
+    )
+    .service(
+        web::scope("/cdu/system")
+            .route("/clock", web::get().to(system::get_clock))
+            .route("/clock", web::put().to(system::set_clock))
+            .route("/clock/sync", web::post().to(system::sync_clock))
+            .route("/audit", web::get().to(system::get_audit_log))
+            .route("/jobs", web::get().to(system::get_jobs))
+            .route("/jobs/{id}", web::put().to(system::set_job))
+            .route("/jobs/{id}", web::delete().to(system::cancel_job)),
+    )
+    .service(
+        web::scope("/cdu/history")
+            .route("", web::get().to(history::get_history))
+            .route("/series", web::get().to(history::get_series)),
+    )
+    .service(
+        web::scope("/cdu/modbus")
+            .route("/diagnostics", web::get().to(modbus::get_diagnostics))
+            .route("/register-map", web::get().to(modbus::get_register_map))
+            .route("/registers", web::get().to(modbus::read_registers))
+            .route("/registers", web::post().to(modbus::write_registers))
+            .route("/attributes/{id}", web::get().to(modbus::read_attribute))
+            .route("/attributes/{id}", web::put().to(modbus::write_attribute)),
+    );
+}
@@ -1,56 +1,66 @@
 use actix_files::Files;
 use actix_web::{web, App, HttpResponse, HttpServer};
 use log::{info, warn};
+use std::io::Write;
 use std::path::PathBuf;
+use crate::app::routes;
+use crate::app::state::AppState;
 use crate::utils::datetime;
 
-#[allow(dead_code)]
-const STATIC_FILES_PATH: &str = "./dist/";
-#[allow(dead_code)]
-const SERVER_ADDRESS: &str = "0.0.0.0:8080";
+pub struct Server {
+    static_dir: PathBuf,
+    state: web::Data<AppState>,
+}
 
-pub struct Server;
+/// 在 main 的第一条语句初始化日志，启动过程中的警告（配置从备份恢复、迁移、没有 Modbus 连接等）才能输出。
+/// 日志时间戳与 API 一致，按配置的时区和格式输出
+pub fn init_logger() {
+    // 没有设置 RUST_LOG 时输出 info 及以上，启动警告默认可见
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format(|buf, record| {
+            writeln!(buf, "[{}] [{}] {}", datetime::get_current_time(), record.level(), record.args())
+        })
+        .init();
+}
 
 impl Server {
-    pub fn new() -> Self {
-        Self
+    pub fn new(static_dir: impl Into<PathBuf>, state: AppState) -> Self {
+        Self {
+            static_dir: static_dir.into(),
+            state: web::Data::new(state),
+        }
     }
-    
-    pub async fn run(&self, host: &str, port: &str) -> std::io::Result<()> {
-        // let rt = tokio::runtime::Builder::new_current_thread()
-        //     .enable_all()
-        //     .build()?;
-        
-        // rt.block_on(main())
+
+    pub async fn run(&self, host: &str, port: u16) -> std::io::Result<()> {
         let server_address = format!("{}:{}", host, port);
-        env_logger::init();
-        
         // 使用 get_current_time 获取格式化的时间戳
         let current_time = datetime::get_current_time();
         info!("Starting server on http://{}", server_address);
         println!("[{}] [INFO] Running on http://{} (CTRL + C to quit)", current_time, server_address);
 
-        let static_path = PathBuf::from(STATIC_FILES_PATH);
+        let static_path = self.static_dir.clone();
+        let state = self.state.clone();
         if !static_path.exists() {
             warn!("Static files directory not found at: {:?}", static_path);
         }
 
-        HttpServer::new(|| {
+        HttpServer::new(move || {
+            let index_path = static_path.join("index.html");
+            // API 路由必须在静态文件服务之前注册，否则会被 "/" 挂载点截获
             App::new()
-                .service(Files::new("/", STATIC_FILES_PATH).index_file("index.html"))
+                .app_data(state.clone())
+                .configure(routes::configure)
                 .service(web::resource("/cdu/{tail:.*}").route(web::get().to(handle_api)))
-                .default_service(web::to(spa_index))
+                .service(Files::new("/", &static_path).index_file("index.html"))
+                .default_service(web::to(move || spa_index(index_path.clone())))
         })
-        .bind(SERVER_ADDRESS)?
+        .bind(&server_address)?
         .run()
         .await
     }
 }
 
-#[allow(dead_code)]
-async fn spa_index() -> HttpResponse {
-    let index_path = PathBuf::from(STATIC_FILES_PATH).join("index.html");
-
+async fn spa_index(index_path: PathBuf) -> HttpResponse {
     match std::fs::read_to_string(index_path) {
         Ok(content) => HttpResponse::Ok().content_type("text/html").body(content),
         Err(_) => HttpResponse::InternalServerError().body("Failed to read index.html"), // Handle the error as needed
@@ -70,4 +80,4 @@ async fn handle_api(_req: actix_web::HttpRequest, path: web::Path<String>) -> im
     info!("API request at {}: {}", request_time, api_path);
 
     HttpResponse::Ok().body("api index")
-}
\ No newline at end of file
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use crate::config::bundle::ConfigFiles;
+use crate::config::loader::NtpConfig;
+use crate::models::control_mode::{ControlMode, ControlModeState};
+use crate::services::audit::AuditLog;
+use crate::services::clock_service::{ClockService, OsClock};
+use crate::services::history_store::HistoryStore;
+use crate::services::modbus_diagnostics::ModbusDiagnostics;
+use crate::services::polling_service::PollingService;
+use crate::utils::async_scheduler::Scheduler;
+use crate::utils::file_store::FileStore;
+
+/// HTTP 处理函数共享的应用状态
+pub struct AppState {
+    pub config_files: ConfigFiles,
+    /// 全局配置，运行中通过 API 修改的设置写回这里
+    pub global: Arc<FileStore>,
+    pub clock: Arc<ClockService>,
+    pub audit: Arc<AuditLog>,
+    /// 特权接口的访问令牌，None 表示禁用特权接口
+    pub admin_token: Option<String>,
+    /// 传感器历史数据，history.enable 为 false 时为 None
+    pub history: Option<Arc<HistoryStore>>,
+    /// 每个 Modbus 连接的通信统计
+    pub modbus: Vec<Arc<ModbusDiagnostics>>,
+    /// 传感器轮询，调试时通过它的连接直接读写寄存器；没有 Modbus 连接时为 None
+    pub polling: Option<Arc<PollingService>>,
+    /// 当前控制模式，Auto 时禁止手动写寄存器
+    pub control_mode: Arc<ControlModeState>,
+    /// 轮询、历史数据落盘等定时任务，可以通过 API 查看和调整
+    pub scheduler: Arc<Scheduler>,
+}
+
+impl AppState {
+    pub fn new(config_files: ConfigFiles, global: Arc<FileStore>) -> Self {
+        Self {
+            config_files,
+            global,
+            clock: Arc::new(ClockService::new(Arc::new(OsClock), NtpConfig::default())),
+            audit: Arc::new(AuditLog::default()),
+            admin_token: None,
+            history: None,
+            modbus: Vec::new(),
+            polling: None,
+            control_mode: Arc::new(ControlModeState::new(ControlMode::Auto)),
+            scheduler: Arc::new(Scheduler::new()),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<ClockService>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_audit(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token = Some(token.to_string()).filter(|t| !t.is_empty());
+        self
+    }
+
+    pub fn with_history(mut self, history: Arc<HistoryStore>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    pub fn with_modbus_diagnostics(mut self, diagnostics: Vec<Arc<ModbusDiagnostics>>) -> Self {
+        self.modbus = diagnostics;
+        self
+    }
+
+    pub fn with_polling(mut self, polling: Arc<PollingService>) -> Self {
+        self.polling = Some(polling);
+        self
+    }
+
+    pub fn with_control_mode(mut self, control_mode: Arc<ControlModeState>) -> Self {
+        self.control_mode = control_mode;
+        self
+    }
+
+    pub fn with_scheduler(mut self, scheduler: Arc<Scheduler>) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+}
@@ -0,0 +1,142 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::loader::Overrides;
+
+/// CDU Workbench 命令行参数，优先级高于配置文件和 CDU_* 环境变量
+#[derive(Debug, Parser)]
+#[command(name = "cdu_workbench", version, about = "Industrial Control System Workbench")]
+pub struct Cli {
+    /// 全局配置文件路径，默认读取 CDU_CONFIG 或 configs/global.confi.yaml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// HTTP 监听地址
+    #[arg(long, value_name = "HOST")]
+    pub http_host: Option<String>,
+
+    /// HTTP 监听端口
+    #[arg(long, value_name = "PORT")]
+    pub http_port: Option<u16>,
+
+    /// 前端静态文件目录
+    #[arg(long, value_name = "DIR")]
+    pub static_dir: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// 代替 Web 服务运行的子命令
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 导出配置包（JSON），未指定 --output 时输出到标准输出
+    ExportConfig {
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// 校验并导入配置包，任何一个文件校验失败都不会修改现有配置
+    ImportConfig {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// 运行 CDU 模拟器：按 sensors.yaml 提供 Modbus TCP 和 RTU（伪终端）从站，不需要真实控制器
+    Simulate {
+        /// Modbus TCP 监听地址
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:5502")]
+        tcp: String,
+
+        /// RTU 从站地址
+        #[arg(long, default_value_t = 1)]
+        slave: u8,
+
+        /// 在这个路径创建指向 RTU 伪终端的符号链接，便于在配置中使用固定的串口路径
+        #[arg(long, value_name = "PATH")]
+        pty_link: Option<PathBuf>,
+
+        /// IT 负载（kW）
+        #[arg(long, default_value_t = 60.0)]
+        heat_load: f64,
+
+        /// 泵和阀门的初始占空比（%）
+        #[arg(long, default_value_t = 50.0)]
+        duty: f64,
+
+        /// 启动时注入的泄漏速率（L/min）
+        #[arg(long, default_value_t = 0.0)]
+        leak_rate: f64,
+
+        /// 模拟量寄存器的噪声幅度（原始值）
+        #[arg(long, default_value_t = 2)]
+        noise: u16,
+
+        /// 按时间注入这个场景文件中的故障
+        #[arg(long, value_name = "FILE")]
+        scenario: Option<PathBuf>,
+    },
+}
+
+impl Cli {
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            http_host: self.http_host.clone(),
+            http_port: self.http_port,
+            static_dir: self.static_dir.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flags() {
+        let cli = Cli::parse_from([
+            "cdu_workbench",
+            "--config",
+            "/etc/cdu/global.yaml",
+            "--http-port",
+            "8080",
+            "--static-dir",
+            "/srv/ui",
+        ]);
+        assert_eq!(cli.config, Some(PathBuf::from("/etc/cdu/global.yaml")));
+        let overrides = cli.overrides();
+        assert_eq!(overrides.http_port, Some(8080));
+        assert_eq!(overrides.static_dir.as_deref(), Some("/srv/ui"));
+        assert_eq!(overrides.http_host, None);
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = Cli::parse_from(["cdu_workbench", "export-config", "-o", "bundle.json"]);
+        assert!(matches!(cli.command, Some(Command::ExportConfig { output: Some(_) })));
+
+        let cli = Cli::parse_from(["cdu_workbench", "--config", "g.yaml", "import-config", "bundle.json"]);
+        match cli.command {
+            Some(Command::ImportConfig { file }) => assert_eq!(file, PathBuf::from("bundle.json")),
+            other => panic!("Expected ImportConfig, got {:?}", other),
+        }
+
+        let cli = Cli::parse_from(["cdu_workbench", "simulate", "--pty-link", "/tmp/cdu-rtu", "--heat-load", "80"]);
+        match cli.command {
+            Some(Command::Simulate {
+                tcp,
+                slave,
+                pty_link,
+                heat_load,
+                ..
+            }) => {
+                assert_eq!(tcp, "127.0.0.1:5502");
+                assert_eq!(slave, 1);
+                assert_eq!(pty_link, Some(PathBuf::from("/tmp/cdu-rtu")));
+                assert_eq!(heat_load, 80.0);
+            }
+            other => panic!("Expected Simulate, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,584 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::config::loader::DEFAULT_CONFIG_PATH;
+use crate::config::migrations::{self, Document};
+use crate::config::validator;
+use crate::utils::datetime;
+use crate::utils::file_store::{write_atomic, FileStore, DEFAULT_MAX_BACKUPS};
+
+/// 当前配置包格式版本
+pub const BUNDLE_VERSION: u32 = 1;
+
+pub const SENSORS_CONFIG_PATH: &str = "configs/sensors.yaml";
+pub const ALARMS_CONFIG_PATH: &str = "src/config/alarm_config/config.yaml";
+pub const ALARMS_DEFAULT_PATH: &str = "src/config/alarm_config/default.yaml";
+pub const PID_CONFIG_PATH: &str = "configs/pid_params_config/config.yaml";
+pub const CONTROL_MODE_CONFIG_PATH: &str = "configs/control_mode_config.yaml";
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("IO error on {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unsupported bundle version {0}, expected {BUNDLE_VERSION}")]
+    UnsupportedVersion(u32),
+
+    #[error("unknown config '{0}' in bundle")]
+    UnknownConfig(String),
+
+    #[error("checksum mismatch for {0}")]
+    ChecksumMismatch(String),
+
+    #[error("config '{name}' cannot be parsed: {message}")]
+    Parse { name: String, message: String },
+
+    #[error("config '{name}' is invalid: {message}")]
+    Invalid { name: String, message: String },
+}
+
+pub type Result<T> = std::result::Result<T, BundleError>;
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> BundleError + '_ {
+    move |source| BundleError::Io {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+/// 由配置包管理的配置文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigFiles {
+    pub global: PathBuf,
+    pub sensors: PathBuf,
+    pub alarms: PathBuf,
+    pub pid: PathBuf,
+    pub control_mode: PathBuf,
+}
+
+impl Default for ConfigFiles {
+    fn default() -> Self {
+        Self {
+            global: PathBuf::from(DEFAULT_CONFIG_PATH),
+            sensors: PathBuf::from(SENSORS_CONFIG_PATH),
+            alarms: PathBuf::from(ALARMS_CONFIG_PATH),
+            pid: PathBuf::from(PID_CONFIG_PATH),
+            control_mode: PathBuf::from(CONTROL_MODE_CONFIG_PATH),
+        }
+    }
+}
+
+impl ConfigFiles {
+    pub fn with_global(mut self, global: impl Into<PathBuf>) -> Self {
+        self.global = global.into();
+        self
+    }
+
+    /// 配置名和文件路径，配置名是配置包中的键
+    pub fn entries(&self) -> Vec<(&'static str, &Path)> {
+        vec![
+            ("global", self.global.as_path()),
+            ("sensors", self.sensors.as_path()),
+            ("alarms", self.alarms.as_path()),
+            ("pid", self.pid.as_path()),
+            ("control_mode", self.control_mode.as_path()),
+        ]
+    }
+
+    fn path_of(&self, name: &str) -> Option<&Path> {
+        self.entries()
+            .into_iter()
+            .find(|(entry, _)| *entry == name)
+            .map(|(_, path)| path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleFile {
+    /// 导出时的文件路径，仅供参考，导入时按配置名写入本机的路径
+    pub path: String,
+    pub content: String,
+    pub sha256: String,
+}
+
+/// 配置包：所有受管理配置文件的原文和校验和
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub bundle_version: u32,
+    pub created_at: String,
+    pub files: BTreeMap<String, BundleFile>,
+    pub checksum: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub applied: Vec<String>,
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 整包校验和覆盖每个文件的配置名和内容校验和
+fn bundle_checksum(files: &BTreeMap<String, BundleFile>) -> String {
+    let mut hasher = Sha256::new();
+    for (name, file) in files {
+        hasher.update(name.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(file.sha256.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 导出所有存在的受管理配置，全局配置中的管理员令牌会被清空
+pub fn export(files: &ConfigFiles) -> Result<ConfigBundle> {
+    let mut bundle_files = BTreeMap::new();
+    for (name, path) in files.entries() {
+        if !path.exists() {
+            continue;
+        }
+        let mut content = fs::read_to_string(path).map_err(io_error(path))?;
+        if name == "global" {
+            content = with_admin_token(path, &content, "")?;
+        }
+        bundle_files.insert(
+            name.to_string(),
+            BundleFile {
+                path: path.display().to_string(),
+                sha256: sha256_hex(content.as_bytes()),
+                content,
+            },
+        );
+    }
+    Ok(ConfigBundle {
+        bundle_version: BUNDLE_VERSION,
+        created_at: datetime::get_current_time(),
+        checksum: bundle_checksum(&bundle_files),
+        files: bundle_files,
+    })
+}
+
+/// 校验配置包：格式版本、校验和、文件内容和配置之间的引用。
+/// 引用按导入后实际生效的配置检查：包中没有的文件以设备上现有的文件为准
+pub fn verify(bundle: &ConfigBundle, files: &ConfigFiles) -> Result<()> {
+    if bundle.bundle_version != BUNDLE_VERSION {
+        return Err(BundleError::UnsupportedVersion(bundle.bundle_version));
+    }
+    for (name, file) in &bundle.files {
+        if files.path_of(name).is_none() {
+            return Err(BundleError::UnknownConfig(name.clone()));
+        }
+        if sha256_hex(file.content.as_bytes()) != file.sha256 {
+            return Err(BundleError::ChecksumMismatch(name.clone()));
+        }
+    }
+    if bundle_checksum(&bundle.files) != bundle.checksum {
+        return Err(BundleError::ChecksumMismatch("bundle".to_string()));
+    }
+
+    let mut parsed = BTreeMap::new();
+    for (name, file) in &bundle.files {
+        let path = files.path_of(name).unwrap_or(Path::new(""));
+        parsed.insert(name.as_str(), parse(name, path, &file.content)?);
+    }
+
+    let invalid = |name: &str, message: String| BundleError::Invalid {
+        name: name.to_string(),
+        message,
+    };
+    if let Some(global) = parsed.get("global") {
+        validator::validate_global(global).map_err(|e| invalid("global", e.to_string()))?;
+    }
+    // 旧版本的配置在下次加载时迁移，这里按迁移后的内容校验
+    let sensors = match parsed.get("sensors") {
+        Some(sensors) => Some(sensors.clone()),
+        None => current("sensors", &files.sensors),
+    };
+    let sensors = sensors
+        .map(|mut sensors| {
+            migrations::migrate(&mut sensors, Document::Sensors)
+                .map_err(|e| invalid("sensors", e.to_string()))?;
+            Ok::<_, BundleError>(sensors)
+        })
+        .transpose()?;
+    if parsed.contains_key("sensors") {
+        if let Some(ref sensors) = sensors {
+            validator::validate_sensors(sensors).map_err(|e| invalid("sensors", e.to_string()))?;
+        }
+    }
+    // 只导入传感器时，设备上现有的告警配置也必须仍然引用得到新的传感器
+    let (alarms, culprit) = match parsed.get("alarms") {
+        Some(alarms) => (Some(alarms.clone()), "alarms"),
+        None if parsed.contains_key("sensors") => (current("alarms", &files.alarms), "sensors"),
+        None => (None, "alarms"),
+    };
+    if let Some(mut alarms) = alarms {
+        migrations::migrate(&mut alarms, Document::Alarms).map_err(|e| invalid("alarms", e.to_string()))?;
+        let sensors = sensors.unwrap_or_else(|| Value::Object(Default::default()));
+        validator::validate_alarms(&alarms, &sensors).map_err(|e| match culprit {
+            "sensors" => invalid("sensors", format!("existing alarms config no longer matches: {}", e)),
+            _ => invalid("alarms", e.to_string()),
+        })?;
+    }
+    Ok(())
+}
+
+fn current(name: &str, path: &Path) -> Option<Value> {
+    let content = fs::read_to_string(path).ok()?;
+    parse(name, path, &content).ok()
+}
+
+/// 按目标文件的扩展名解析内容，空文件视为空映射
+fn parse(name: &str, path: &Path, content: &str) -> Result<Value> {
+    let parse_error = |message: String| BundleError::Parse {
+        name: name.to_string(),
+        message,
+    };
+    if content.trim().is_empty() {
+        return Ok(Value::Object(Default::default()));
+    }
+    // 与告警 FileStore 一样，解析前还原未加引号的 ">"
+    let content = match name {
+        "alarms" => migrations::quote_folded_comparators(content),
+        _ => content.to_string(),
+    };
+    let content = content.as_str();
+    let value: Value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(content).map_err(|e| parse_error(e.to_string()))?,
+        Some("yaml") | Some("yml") => serde_yaml::from_str(content).map_err(|e| parse_error(e.to_string()))?,
+        _ => return Err(parse_error(format!("unsupported file {}", path.display()))),
+    };
+    if !value.is_object() {
+        return Err(parse_error("config root must be a mapping".to_string()));
+    }
+    Ok(value)
+}
+
+/// 校验通过后整体替换配置文件：先把所有新内容写入临时文件，
+/// 再逐个重命名；任何一步失败都会恢复已经替换的文件
+pub fn import(bundle: &ConfigBundle, files: &ConfigFiles) -> Result<ImportReport> {
+    verify(bundle, files)?;
+
+    let mut staged = Vec::new();
+    let result = stage(bundle, files, &mut staged).and_then(|_| commit(&staged));
+    if result.is_err() {
+        for (_, tmp_path, _) in &staged {
+            let _ = fs::remove_file(tmp_path);
+        }
+    }
+    result?;
+
+    let applied: Vec<String> = bundle.files.keys().cloned().collect();
+    info!("Imported config bundle created at {}: {}", bundle.created_at, applied.join(", "));
+    Ok(ImportReport { applied })
+}
+
+/// 全局配置中 http.admin_token 的值，没有设置时为空
+fn admin_token(config: &Value) -> &str {
+    config
+        .pointer("/http/admin_token")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+/// 把全局配置的 http.admin_token 改为 token；已经相同时原样返回，保留注释和格式
+fn with_admin_token(path: &Path, content: &str, token: &str) -> Result<String> {
+    let mut config = parse("global", path, content)?;
+    if admin_token(&config) == token {
+        return Ok(content.to_string());
+    }
+    let http = config
+        .as_object_mut()
+        .map(|map| map.entry("http").or_insert_with(|| Value::Object(Default::default())));
+    match http.and_then(Value::as_object_mut) {
+        Some(http) => {
+            http.insert("admin_token".to_string(), Value::String(token.to_string()));
+        }
+        None => {
+            return Err(BundleError::Parse {
+                name: "global".to_string(),
+                message: "http must be a mapping".to_string(),
+            })
+        }
+    }
+    let serialized = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::to_string_pretty(&config)?,
+        _ => serde_yaml::to_string(&config).map_err(|e| BundleError::Parse {
+            name: "global".to_string(),
+            message: e.to_string(),
+        })?,
+    };
+    Ok(serialized)
+}
+
+/// (目标路径, 临时文件路径, 原内容)
+type Staged = (PathBuf, PathBuf, Option<Vec<u8>>);
+
+fn stage(bundle: &ConfigBundle, files: &ConfigFiles, staged: &mut Vec<Staged>) -> Result<()> {
+    for (name, file) in &bundle.files {
+        let path = files.path_of(name).ok_or_else(|| BundleError::UnknownConfig(name.clone()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_error(parent))?;
+        }
+        let original = if path.is_file() {
+            Some(fs::read(path).map_err(io_error(path))?)
+        } else {
+            None
+        };
+        let tmp_path = path.with_file_name(format!(
+            ".{}.import.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        // 导出时清空了管理员令牌，导入时保留本机现有的令牌；其他文件不需要读取现有配置
+        let existing = if name == "global" { current(name, path) } else { None };
+        let content = match existing {
+            Some(ref config) if !admin_token(config).is_empty() => {
+                let imported = parse(name, path, &file.content)?;
+                if admin_token(&imported).is_empty() {
+                    with_admin_token(path, &file.content, admin_token(config))?
+                } else {
+                    file.content.clone()
+                }
+            }
+            _ => file.content.clone(),
+        };
+        staged.push((path.to_path_buf(), tmp_path.clone(), original));
+
+        let mut tmp = fs::File::create(&tmp_path).map_err(io_error(&tmp_path))?;
+        tmp.write_all(content.as_bytes()).map_err(io_error(&tmp_path))?;
+        tmp.sync_all().map_err(io_error(&tmp_path))?;
+    }
+    Ok(())
+}
+
+fn commit(staged: &[Staged]) -> Result<()> {
+    for (i, (path, tmp_path, _)) in staged.iter().enumerate() {
+        let result = FileStore::backup_file(path, DEFAULT_MAX_BACKUPS)
+            .map_err(|e| BundleError::Io {
+                path: path.display().to_string(),
+                source: std::io::Error::other(e.to_string()),
+            })
+            .and_then(|_| fs::rename(tmp_path, path).map_err(io_error(path)));
+        if let Err(e) = result {
+            rollback(&staged[..i]);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn rollback(committed: &[Staged]) {
+    for (path, _, original) in committed {
+        let restored = match original {
+            Some(content) => write_atomic(path, content),
+            None => fs::remove_file(path),
+        };
+        if let Err(e) = restored {
+            warn!("Failed to restore {} after failed import: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const SENSORS: &str = "sensor_plugins:\n- name: Temperatures\n  defaults:\n    value:\n      read_method: read_holding_registers\n  sensors:\n  - name: T1\n    attrs:\n      value: 3328\n";
+    const ALARMS: &str = "single:\n  T1:\n  - address: 2604\n    comparator: >\n    value: 0\n";
+
+    fn fixture(dir: &Path) -> ConfigFiles {
+        let files = ConfigFiles {
+            global: dir.join("global.yaml"),
+            sensors: dir.join("sensors.yaml"),
+            alarms: dir.join("alarm_config/config.yaml"),
+            pid: dir.join("pid.yaml"),
+            control_mode: dir.join("control_mode.yaml"),
+        };
+        fs::create_dir_all(dir.join("alarm_config")).unwrap();
+        fs::write(&files.global, "http:\n  port: 5000\n").unwrap();
+        fs::write(&files.sensors, SENSORS).unwrap();
+        fs::write(&files.alarms, ALARMS).unwrap();
+        fs::write(&files.pid, "").unwrap();
+        files
+    }
+
+    #[test]
+    fn test_export_and_import_roundtrip() {
+        let source_dir = tempdir().unwrap();
+        let source = fixture(source_dir.path());
+        let bundle = export(&source).unwrap();
+
+        assert_eq!(bundle.bundle_version, BUNDLE_VERSION);
+        assert_eq!(
+            bundle.files.keys().collect::<Vec<_>>(),
+            vec!["alarms", "global", "pid", "sensors"]
+        );
+
+        // 通过 JSON 传输后导入到另一台设备
+        let json = serde_json::to_string(&bundle).unwrap();
+        let bundle: ConfigBundle = serde_json::from_str(&json).unwrap();
+
+        let target_dir = tempdir().unwrap();
+        let target = fixture(target_dir.path());
+        fs::write(&target.global, "http:\n  port: 6000\n").unwrap();
+
+        let report = import(&bundle, &target).unwrap();
+        assert_eq!(report.applied.len(), 4);
+        assert_eq!(fs::read_to_string(&target.global).unwrap(), "http:\n  port: 5000\n");
+        assert_eq!(fs::read_to_string(&target.alarms).unwrap(), ALARMS);
+        // 被替换的文件留有备份
+        assert_eq!(FileStore::list_backups(&target.global).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_admin_token_is_not_exported() {
+        let source_dir = tempdir().unwrap();
+        let source = fixture(source_dir.path());
+        fs::write(&source.global, "http:\n  port: 5000\n  admin_token: source-secret\n").unwrap();
+        let bundle = export(&source).unwrap();
+        let global = &bundle.files["global"].content;
+        assert!(!global.contains("source-secret"));
+        assert_eq!(parse("global", &source.global, global).unwrap()["http"]["port"], 5000);
+
+        // 导入时保留目标设备自己的令牌
+        let target_dir = tempdir().unwrap();
+        let target = fixture(target_dir.path());
+        fs::write(&target.global, "http:\n  port: 6000\n  admin_token: target-secret\n").unwrap();
+        import(&bundle, &target).unwrap();
+        let imported = parse("global", &target.global, &fs::read_to_string(&target.global).unwrap()).unwrap();
+        assert_eq!(imported["http"]["port"], 5000);
+        assert_eq!(imported["http"]["admin_token"], "target-secret");
+    }
+
+    #[test]
+    fn test_tampered_bundle_is_rejected() {
+        let dir = tempdir().unwrap();
+        let files = fixture(dir.path());
+        let mut bundle = export(&files).unwrap();
+
+        bundle.files.get_mut("global").unwrap().content = "http:\n  port: 1\n".to_string();
+        assert!(matches!(
+            import(&bundle, &files),
+            Err(BundleError::ChecksumMismatch(name)) if name == "global"
+        ));
+
+        let mut bundle = export(&files).unwrap();
+        bundle.files.remove("pid");
+        assert!(matches!(
+            import(&bundle, &files),
+            Err(BundleError::ChecksumMismatch(name)) if name == "bundle"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_bundle_changes_nothing() {
+        let dir = tempdir().unwrap();
+        let files = fixture(dir.path());
+
+        let mut bundle = export(&files).unwrap();
+        // 告警引用了不存在的传感器
+        let alarms = "single:\n  T9:\n  - address: 2604\n    comparator: '>'\n    value: 0\n".to_string();
+        let file = bundle.files.get_mut("alarms").unwrap();
+        file.sha256 = sha256_hex(alarms.as_bytes());
+        file.content = alarms;
+        let global = "http:\n  port: 7000\n".to_string();
+        let file = bundle.files.get_mut("global").unwrap();
+        file.sha256 = sha256_hex(global.as_bytes());
+        file.content = global;
+        bundle.checksum = bundle_checksum(&bundle.files);
+
+        match import(&bundle, &files) {
+            Err(BundleError::Invalid { name, message }) => {
+                assert_eq!(name, "alarms");
+                assert!(message.contains("single.T9"));
+            }
+            other => panic!("Expected Invalid, got {:?}", other),
+        }
+        assert_eq!(fs::read_to_string(&files.global).unwrap(), "http:\n  port: 5000\n");
+        assert!(FileStore::list_backups(&files.global).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sensors_only_bundle_checks_existing_alarms() {
+        let dir = tempdir().unwrap();
+        let files = fixture(dir.path());
+
+        let mut bundle = export(&files).unwrap();
+        bundle.files.retain(|name, _| name == "sensors");
+        // 新的传感器配置里没有 T1，设备上的告警仍然引用它
+        let sensors = SENSORS.replace("name: T1", "name: T2");
+        let file = bundle.files.get_mut("sensors").unwrap();
+        file.sha256 = sha256_hex(sensors.as_bytes());
+        file.content = sensors;
+        bundle.checksum = bundle_checksum(&bundle.files);
+
+        match import(&bundle, &files) {
+            Err(BundleError::Invalid { name, message }) => {
+                assert_eq!(name, "sensors");
+                assert!(message.contains("existing alarms config"), "{}", message);
+                assert!(message.contains("single.T1"), "{}", message);
+            }
+            other => panic!("Expected Invalid, got {:?}", other),
+        }
+        assert_eq!(fs::read_to_string(&files.sensors).unwrap(), SENSORS);
+
+        // 告警一起更新时可以导入
+        let mut bundle = export(&files).unwrap();
+        bundle.files.retain(|name, _| name == "sensors" || name == "alarms");
+        for (name, content) in [
+            ("sensors", SENSORS.replace("name: T1", "name: T2")),
+            ("alarms", ALARMS.replace("T1:", "T2:")),
+        ] {
+            let file = bundle.files.get_mut(name).unwrap();
+            file.sha256 = sha256_hex(content.as_bytes());
+            file.content = content;
+        }
+        bundle.checksum = bundle_checksum(&bundle.files);
+        assert!(import(&bundle, &files).is_ok());
+    }
+
+    #[test]
+    fn test_failed_commit_rolls_back() {
+        let dir = tempdir().unwrap();
+        let files = fixture(dir.path());
+        let bundle = export(&files).unwrap();
+
+        // "alarms" 排在最前面，先被替换；"sensors" 的目标是目录，重命名失败
+        let mut target = files.clone();
+        fs::write(&target.alarms, "single: {}\n").unwrap();
+        target.sensors = dir.path().join("sensors_dir.yaml");
+        fs::create_dir_all(target.sensors.join("occupied")).unwrap();
+
+        assert!(matches!(import(&bundle, &target), Err(BundleError::Io { .. })));
+        assert_eq!(fs::read_to_string(&target.alarms).unwrap(), "single: {}\n");
+        let leftovers: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".import.tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+}
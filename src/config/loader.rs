@@ -0,0 +1,501 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::config::validator::{self, ValidationErrors};
+use crate::utils::config_diff::ConfigChange;
+use crate::utils::config_path::{ConfigPath, Segment};
+use crate::utils::datetime::{self, TimeZoneError, TimestampFormat};
+use crate::utils::file_store::{Config, FileStore, FileStoreError, PathChangeCallback};
+
+pub const DEFAULT_CONFIG_PATH: &str = "configs/global.confi.yaml";
+
+/// 环境变量前缀，例如 CDU_HTTP_PORT 覆盖 http.port
+pub const ENV_PREFIX: &str = "CDU_";
+
+/// 指定配置文件路径的环境变量
+pub const CONFIG_PATH_ENV: &str = "CDU_CONFIG";
+
+#[derive(Debug, Error)]
+pub enum LoaderError {
+    #[error(transparent)]
+    FileStore(#[from] FileStoreError),
+
+    #[error("invalid global config: {0}")]
+    Invalid(#[from] ValidationErrors),
+
+    #[error("global config: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, LoaderError>;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlobalConfig {
+    pub http: HttpConfig,
+    pub modbus_client: ModbusClientConfig,
+    pub snmp: SnmpConfig,
+    pub modbus_server: ModbusServerConfig,
+    pub time: TimeConfig,
+    pub ntp: NtpConfig,
+    pub polling: PollingConfig,
+    pub history: HistoryConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub host: String,
+    pub port: u16,
+    pub static_dir: String,
+    /// 特权接口（设置时钟、写寄存器等）的访问令牌，为空时禁用这些接口
+    pub admin_token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TcpConfig {
+    pub enable: bool,
+    pub host: String,
+    pub port: u16,
+    /// 超时时间（秒）
+    pub timeout: f64,
+    /// 默认的从站地址（unit id），传感器可以用 slave_id 指定其他从站
+    pub slave_id: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Parity {
+    #[serde(rename = "N")]
+    None,
+    #[serde(rename = "E")]
+    Even,
+    #[serde(rename = "O")]
+    Odd,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialConfig {
+    pub enable: bool,
+    pub port: String,
+    pub baudrate: u32,
+    /// 超时时间（秒）
+    pub timeout: f64,
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: u8,
+    /// 默认的从站地址，同一总线上的其他从站由传感器的 slave_id 指定
+    pub slave_id: u8,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModbusClientConfig {
+    pub tcp: TcpConfig,
+    pub serial: SerialConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModbusServerConfig {
+    pub tcp: TcpConfig,
+    pub serial: SerialConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnmpConfig {
+    pub enable: bool,
+    pub host: String,
+    pub port: u16,
+    pub community: String,
+    pub version: String,
+}
+
+/// 时区和时间戳格式，日志、告警历史和 API 返回的时间都按此设置输出
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeConfig {
+    /// IANA 时区名，例如 "Asia/Shanghai"
+    pub zone: String,
+    pub format: TimestampFormat,
+}
+
+/// 局域网内的 NTP/SNTP 服务器
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtpConfig {
+    pub enable: bool,
+    pub host: String,
+    pub port: u16,
+    /// 同步间隔（秒）
+    pub interval: f64,
+    /// 超时时间（秒）
+    pub timeout: f64,
+}
+
+impl Default for NtpConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            host: "192.168.1.1".to_string(),
+            port: 123,
+            interval: 3600.0,
+            timeout: 2.0,
+        }
+    }
+}
+
+/// 传感器轮询
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PollingConfig {
+    /// 轮询间隔（秒）
+    pub interval: f64,
+    /// 读取失败后沿用上次值（质量为 Stale）的时间（秒），超过后质量为 CommError
+    pub stale_timeout: f64,
+}
+
+/// 传感器历史数据，原始数据保留 raw_days 天后降采样为 min/max/avg 桶
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    pub enable: bool,
+    pub dir: String,
+    /// 记录间隔（秒），同一序列在一个间隔内只记录第一个值
+    pub resolution: f64,
+    /// 原始数据保留天数
+    pub raw_days: u32,
+    /// 降采样桶的宽度（秒）
+    pub downsample_interval: f64,
+    /// 历史数据总保留天数
+    pub retention_days: u32,
+    /// 历史数据占用磁盘的上限（MB），超过时删除最早的数据
+    pub max_size_mb: u64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            interval: 1.0,
+            stale_timeout: 10.0,
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            dir: "data/history".to_string(),
+            resolution: 5.0,
+            raw_days: 7,
+            downsample_interval: 300.0,
+            retention_days: 365,
+            max_size_mb: 512,
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 5000,
+            static_dir: "./dist/".to_string(),
+            admin_token: String::new(),
+        }
+    }
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            host: "192.168.1.150".to_string(),
+            port: 502,
+            timeout: 4.0,
+            slave_id: 1,
+        }
+    }
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            port: "/dev/ttyUSB0".to_string(),
+            baudrate: 9600,
+            timeout: 4.0,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: 1,
+            slave_id: 1,
+        }
+    }
+}
+
+impl Default for ModbusServerConfig {
+    fn default() -> Self {
+        Self {
+            tcp: TcpConfig {
+                enable: false,
+                host: "0.0.0.0".to_string(),
+                port: 5020,
+                timeout: 4.0,
+                slave_id: 1,
+            },
+            serial: SerialConfig::default(),
+        }
+    }
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            host: "0.0.0.0".to_string(),
+            port: 1161,
+            community: "public".to_string(),
+            version: "2c".to_string(),
+        }
+    }
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        Self {
+            zone: datetime::DEFAULT_TIME_ZONE.to_string(),
+            format: TimestampFormat::Default,
+        }
+    }
+}
+
+impl TimeConfig {
+    /// 应用到 utils::datetime 的全局设置
+    pub fn apply(&self) -> std::result::Result<(), TimeZoneError> {
+        datetime::set_time_zone(&self.zone)?;
+        datetime::set_timestamp_format(self.format);
+        Ok(())
+    }
+}
+
+/// 全局配置中 time 段变化时（API 修改或直接编辑文件）立即生效
+pub struct TimeConfigListener;
+
+impl PathChangeCallback for TimeConfigListener {
+    fn on_path_change(&self, _changes: &[ConfigChange], new_config: &Config) {
+        let mut config = serde_json::to_value(TimeConfig::default()).unwrap_or_default();
+        if let Some(time) = new_config.get("time") {
+            merge(&mut config, time);
+        }
+        match serde_json::from_value::<TimeConfig>(config) {
+            Ok(time) => {
+                if let Err(e) = time.apply() {
+                    log::warn!("Failed to apply time settings: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Invalid time settings: {}", e),
+        }
+    }
+}
+
+/// 命令行参数的覆盖项，None 表示未指定
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub http_host: Option<String>,
+    pub http_port: Option<u16>,
+    pub static_dir: Option<String>,
+}
+
+/// 配置文件路径：命令行 --config 优先，其次是 CDU_CONFIG，最后是默认路径
+pub fn config_path(cli_path: Option<&Path>) -> PathBuf {
+    cli_path
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(CONFIG_PATH_ENV).map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+}
+
+/// 打开全局配置文件，加载和写入时都按 validate_global 校验
+pub fn open_store(path: &Path) -> Result<FileStore> {
+    let store = FileStore::new(path, None)?.with_validator(|config| {
+        validator::validate_global(config).map_err(|e| e.to_string())
+    });
+    store.validate(&store.get_config())?;
+    Ok(store)
+}
+
+/// 合并出最终生效的配置，优先级从低到高：
+/// 内置默认值 < 配置文件 < CDU_* 环境变量 < 命令行参数
+pub fn resolve<I>(file: &Value, env: I, overrides: &Overrides) -> Result<GlobalConfig>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut config = serde_json::to_value(GlobalConfig::default())?;
+    merge(&mut config, file);
+    apply_env(&mut config, env);
+    validator::validate_global(&config)?;
+
+    let mut config: GlobalConfig = serde_json::from_value(config)?;
+    if let Some(ref host) = overrides.http_host {
+        config.http.host = host.clone();
+    }
+    if let Some(port) = overrides.http_port {
+        config.http.port = port;
+    }
+    if let Some(ref static_dir) = overrides.static_dir {
+        config.http.static_dir = static_dir.clone();
+    }
+    Ok(config)
+}
+
+/// 读取配置文件并合并环境变量和命令行参数
+#[allow(dead_code)]
+pub fn load(path: &Path, overrides: &Overrides) -> Result<GlobalConfig> {
+    let store = open_store(path)?;
+    resolve(&store.get_config(), std::env::vars(), overrides)
+}
+
+/// 把 overlay 深度合并到 base 上
+fn merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// 每个叶子配置项对应一个环境变量，例如 modbus_client.tcp.host -> CDU_MODBUS_CLIENT_TCP_HOST，
+/// 值按 YAML 标量解析，"true"、"5020" 会得到对应的类型
+fn apply_env<I>(config: &mut Value, env: I)
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut leaves = Vec::new();
+    collect_leaves(config, &ConfigPath::root(), &mut leaves);
+
+    for (name, raw) in env {
+        let Some(suffix) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let Some(path) = leaves.iter().find(|path| env_suffix(path) == suffix) else {
+            continue;
+        };
+        let value = serde_yaml::from_str::<Value>(&raw).unwrap_or(Value::String(raw));
+        // 叶子路径来自默认配置，一定可以写入
+        let _ = path.set(config, value);
+    }
+}
+
+fn collect_leaves(value: &Value, path: &ConfigPath, leaves: &mut Vec<ConfigPath>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                collect_leaves(child, &path.child(Segment::Key(key.clone())), leaves);
+            }
+        }
+        _ => leaves.push(path.clone()),
+    }
+}
+
+fn env_suffix(path: &ConfigPath) -> String {
+    path.segments()
+        .iter()
+        .map(|segment| match segment {
+            Segment::Key(key) => key.to_uppercase(),
+            Segment::Index(index) => index.to_string(),
+            Segment::End => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_shipped_config() {
+        let content = std::fs::read_to_string(DEFAULT_CONFIG_PATH).unwrap();
+        let file: Value = serde_yaml::from_str(&content).unwrap();
+
+        let config = resolve(&file, Vec::new(), &Overrides::default()).unwrap();
+        assert_eq!(config.http.port, 5000);
+        assert_eq!(config.http.static_dir, "./dist/");
+        assert_eq!(config.modbus_client.tcp.host, "192.168.1.150");
+        assert_eq!(config.modbus_client.serial.parity, Parity::None);
+        assert_eq!(config.modbus_client.serial.baudrate, 115200);
+        assert!(!config.modbus_server.tcp.enable);
+        assert_eq!(config.snmp.version, "2c");
+        assert_eq!(config.time.zone, "Asia/Shanghai");
+        assert_eq!(config.time.format, TimestampFormat::Default);
+    }
+
+    #[test]
+    fn test_time_zone_is_validated() {
+        let config = resolve(&json!({}), env(&[("CDU_TIME_ZONE", "Europe/Berlin"), ("CDU_TIME_FORMAT", "rfc3339")]), &Overrides::default()).unwrap();
+        assert_eq!(config.time.zone, "Europe/Berlin");
+        assert_eq!(config.time.format, TimestampFormat::Rfc3339);
+
+        match resolve(&json!({"time": {"zone": "Mars/Base"}}), Vec::new(), &Overrides::default()) {
+            Err(LoaderError::Invalid(errors)) => assert_eq!(errors.0[0].path, "time.zone"),
+            other => panic!("Expected Invalid, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let file = json!({"http": {"host": "127.0.0.1", "port": 5000}, "modbus_server": {"tcp": {"enable": false}}});
+
+        let config = resolve(
+            &file,
+            env(&[
+                ("CDU_HTTP_PORT", "6000"),
+                ("CDU_MODBUS_SERVER_TCP_ENABLE", "true"),
+                ("CDU_MODBUS_CLIENT_SERIAL_PARITY", "E"),
+                ("CDU_UNKNOWN", "ignored"),
+                ("HTTP_PORT", "1"),
+            ]),
+            &Overrides::default(),
+        )
+        .unwrap();
+        assert_eq!(config.http.host, "127.0.0.1");
+        assert_eq!(config.http.port, 6000);
+        assert!(config.modbus_server.tcp.enable);
+        assert_eq!(config.modbus_client.serial.parity, Parity::Even);
+
+        let overrides = Overrides {
+            http_port: Some(7000),
+            static_dir: Some("/srv/ui".to_string()),
+            ..Default::default()
+        };
+        let config = resolve(&file, env(&[("CDU_HTTP_PORT", "6000")]), &overrides).unwrap();
+        assert_eq!(config.http.port, 7000);
+        assert_eq!(config.http.static_dir, "/srv/ui");
+    }
+
+    #[test]
+    fn test_invalid_env_value_is_reported_with_path() {
+        let result = resolve(&json!({}), env(&[("CDU_HTTP_PORT", "70000")]), &Overrides::default());
+        match result {
+            Err(LoaderError::Invalid(errors)) => assert_eq!(errors.0[0].path, "http.port"),
+            other => panic!("Expected Invalid, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use log::info;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::config::validator::{expression_references, Catalog};
+use crate::utils::file_store::{FileStore, FileStoreError};
+
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("{0} must be a non-negative integer")]
+    InvalidVersion(String),
+
+    #[error("schema version {found} is newer than the supported version {supported}")]
+    NewerVersion { found: u64, supported: u64 },
+
+    #[error(transparent)]
+    FileStore(#[from] FileStoreError),
+}
+
+pub type Result<T> = std::result::Result<T, MigrationError>;
+
+/// 单步迁移：把 from 版本的文档升级到 from + 1 版本。
+/// apply 必须是幂等的，对已经是新格式的内容不做修改
+pub struct Migration {
+    pub from: u64,
+    pub description: &'static str,
+    pub apply: fn(&mut Value),
+}
+
+/// 配置文档的种类，每种文档有自己的迁移链
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Document {
+    Sensors,
+    Alarms,
+}
+
+impl Document {
+    pub fn migrations(self) -> &'static [Migration] {
+        match self {
+            Document::Sensors => SENSORS_MIGRATIONS,
+            Document::Alarms => ALARMS_MIGRATIONS,
+        }
+    }
+
+    /// 当前代码支持的最新版本
+    pub fn current_version(self) -> u64 {
+        self.migrations().len() as u64
+    }
+}
+
+pub const SENSORS_MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "normalise attribute address shorthand to {address: N}",
+        apply: normalise_attr_addresses,
+    },
+    Migration {
+        from: 1,
+        description: "collapse doubled quotes in unresolved computed sensor references",
+        apply: collapse_expression_quotes,
+    },
+];
+
+pub const ALARMS_MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "normalise comparators",
+        apply: normalise_comparators,
+    },
+    Migration {
+        from: 1,
+        description: "rename alarm LI1 to the sensor name Li1",
+        apply: rename_li1,
+    },
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    pub from: u64,
+    pub to: u64,
+    pub applied: Vec<&'static str>,
+}
+
+impl MigrationReport {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty()
+    }
+}
+
+/// 文档的版本，没有 schema_version 的旧文档视为版本 0
+pub fn schema_version(config: &Value) -> Result<u64> {
+    match config.get(SCHEMA_VERSION_KEY) {
+        None | Some(Value::Null) => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| MigrationError::InvalidVersion(SCHEMA_VERSION_KEY.to_string())),
+    }
+}
+
+/// 依次执行迁移，把文档升级到当前版本
+pub fn migrate(config: &mut Value, document: Document) -> Result<MigrationReport> {
+    let from = schema_version(config)?;
+    let to = document.current_version();
+    if from > to {
+        return Err(MigrationError::NewerVersion {
+            found: from,
+            supported: to,
+        });
+    }
+
+    let mut applied = Vec::new();
+    for migration in document.migrations().iter().filter(|m| m.from >= from) {
+        (migration.apply)(config);
+        applied.push(migration.description);
+    }
+    if let Some(map) = config.as_object_mut() {
+        if from != to || !map.contains_key(SCHEMA_VERSION_KEY) {
+            map.insert(SCHEMA_VERSION_KEY.to_string(), Value::from(to));
+        }
+    }
+    Ok(MigrationReport { from, to, applied })
+}
+
+/// 迁移 FileStore 中的文档，有变化时写回文件（写入前会先备份原文件）。
+/// 没有需要迁移的内容时也按 store 的校验函数检查一次，启动时加载的配置同样要通过校验
+pub fn migrate_store(store: &FileStore, document: Document) -> Result<MigrationReport> {
+    let mut config = store.get_config();
+    let report = migrate(&mut config, document)?;
+    if report.is_empty() {
+        store.validate(&config)?;
+    } else {
+        store.set_config(config)?;
+        info!(
+            "Migrated {:?} config from schema version {} to {}: {}",
+            document,
+            report.from,
+            report.to,
+            report.applied.join(", ")
+        );
+    }
+    Ok(report)
+}
+
+/// sensors.yaml v0 -> v1：属性允许简写为地址（"value: 3328"），统一改为 {address: 3328}
+fn normalise_attr_addresses(config: &mut Value) {
+    let plugins = config
+        .get_mut("sensor_plugins")
+        .and_then(Value::as_array_mut);
+    for plugin in plugins.into_iter().flatten() {
+        let sensors = plugin.get_mut("sensors").and_then(Value::as_array_mut);
+        for sensor in sensors.into_iter().flatten() {
+            let attrs = sensor.get_mut("attrs").and_then(Value::as_object_mut);
+            for attr in attrs.into_iter().flat_map(|attrs| attrs.values_mut()) {
+                if attr.is_number() {
+                    let mut normalised = Map::new();
+                    normalised.insert("address".to_string(), attr.take());
+                    *attr = Value::Object(normalised);
+                }
+            }
+        }
+    }
+}
+
+/// 告警配置里未加引号的 "comparator: >" 会被 YAML 当作内容为空的折叠块，解析成空字符串。
+/// 在解析前把这种写法改成 "comparator: '>'"，后面跟着缩进更深的行（真正的折叠块）时不修改
+pub fn quote_folded_comparators(content: &str) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(content.len());
+    for (i, line) in lines.iter().enumerate() {
+        let body = line.trim_end();
+        let indent = body.len() - body.trim_start().len();
+        // "- comparator: >" 中键的缩进在 "- " 之后
+        let item = body.trim_start().strip_prefix("- ").map(str::trim_start);
+        let key_indent = item.map_or(indent, |item| body.len() - item.len());
+        let rest = item
+            .unwrap_or(body.trim_start())
+            .strip_prefix("comparator:")
+            .map(str::trim);
+        let is_block = lines[i + 1..]
+            .iter()
+            .map(|next| next.trim_end())
+            .find(|next| !next.trim_start().is_empty())
+            .is_some_and(|next| next.len() - next.trim_start().len() > key_indent);
+        if rest == Some(">") && !is_block {
+            output.push_str(&body[..body.len() - 1]);
+            output.push_str("'>'");
+            output.push_str(&line[body.len()..]);
+        } else {
+            output.push_str(line);
+        }
+    }
+    output
+}
+
+/// sensors.yaml v1 -> v2：出厂配置的表达式把 P5' 写成了 P5''，引用不到任何传感器。
+/// 只修正无法解析、而少一个引号后能解析的引用，T1'' 这样真实存在的名称保持不变
+fn collapse_expression_quotes(config: &mut Value) {
+    let catalog = Catalog::from_sensors(config);
+    let resolves = |reference: &str| match reference.split('.').collect::<Vec<_>>()[..] {
+        [plugin, sensor, _] => catalog.has_sensor(plugin, sensor),
+        _ => false,
+    };
+    let computed = config.get_mut("computed_sensors").and_then(Value::as_array_mut);
+    for sensor in computed.into_iter().flatten() {
+        if let Some(Value::String(expression)) = sensor.get_mut("expression") {
+            for reference in expression_references(expression) {
+                let collapsed = reference.replacen("''", "'", 1);
+                if collapsed != reference && !resolves(&reference) && resolves(&collapsed) {
+                    *expression = expression.replace(&reference, &collapsed);
+                }
+            }
+        }
+    }
+}
+
+/// 告警配置 v1 -> v2：早期的告警配置把液位传感器 Li1 写成了 LI1，
+/// 告警按名称查找传感器，大小写不一致时永远不会触发。已有 Li1 时保留原样
+fn rename_li1(config: &mut Value) {
+    let Some(single) = config.get_mut("single").and_then(Value::as_object_mut) else {
+        return;
+    };
+    if single.contains_key("Li1") {
+        return;
+    }
+    if let Some(rules) = single.remove("LI1") {
+        single.insert("Li1".to_string(), rules);
+    }
+}
+
+/// 告警配置 v0 -> v1：把 "="、"<>" 等写法统一为标准比较符。
+/// 未加引号的 ">" 解析后与空字符串无法区分，由 quote_folded_comparators 在解析前处理，
+/// 真正为空的比较符留给校验报告
+fn normalise_comparators(config: &mut Value) {
+    let single = config.get_mut("single").and_then(Value::as_object_mut);
+    for rules in single.into_iter().flat_map(|single| single.values_mut()) {
+        for rule in rules.as_array_mut().into_iter().flatten() {
+            normalise_comparator(rule);
+        }
+    }
+    let linkages = config.get_mut("Linkages").and_then(Value::as_array_mut);
+    for linkage in linkages.into_iter().flatten() {
+        let conditions = linkage.get_mut("sensors").and_then(Value::as_array_mut);
+        for condition in conditions.into_iter().flatten() {
+            normalise_comparator(condition);
+        }
+    }
+}
+
+fn normalise_comparator(rule: &mut Value) {
+    let Some(comparator) = rule.get_mut("comparator") else {
+        return;
+    };
+    let normalised = match comparator.as_str().map(str::trim) {
+        Some("=") => "==",
+        Some("<>") => "!=",
+        Some("=>") => ">=",
+        Some("=<") => "<=",
+        Some(other) => other,
+        None => return,
+    }
+    .to_string();
+    *comparator = Value::String(normalised);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::validator;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn load_yaml(path: &str) -> Value {
+        serde_yaml::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn load_alarms(path: &str) -> Value {
+        serde_yaml::from_str(&quote_folded_comparators(&fs::read_to_string(path).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_migrations_are_idempotent() {
+        for document in [Document::Sensors, Document::Alarms] {
+            let fixture = match document {
+                Document::Sensors => load_yaml("configs/sensors.yaml"),
+                Document::Alarms => load_alarms("src/config/alarm_config/default.yaml"),
+            };
+            for migration in document.migrations() {
+                let mut once = fixture.clone();
+                (migration.apply)(&mut once);
+                let mut twice = once.clone();
+                (migration.apply)(&mut twice);
+                assert_eq!(once, twice, "{} is not idempotent", migration.description);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalise_attr_addresses() {
+        let mut config = json!({
+            "sensor_plugins": [{"name": "Temperatures", "sensors": [
+                {"name": "T1", "attrs": {"value": 3328}},
+                {"name": "T1'", "attrs": {"value": {"address": 3329}}}
+            ]}]
+        });
+        let report = migrate(&mut config, Document::Sensors).unwrap();
+        assert_eq!(report.from, 0);
+        assert_eq!(report.to, 2);
+        assert_eq!(config["sensor_plugins"][0]["sensors"][0]["attrs"]["value"], json!({"address": 3328}));
+        assert_eq!(config["sensor_plugins"][0]["sensors"][1]["attrs"]["value"], json!({"address": 3329}));
+        assert_eq!(config[SCHEMA_VERSION_KEY], json!(2));
+
+        // 已是最新版本时不再迁移
+        let report = migrate(&mut config, Document::Sensors).unwrap();
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn test_unquoted_comparator_is_restored() {
+        let content = "single:\n  Cv:\n  - address: 2633\n    comparator: >\n    value: 15\n\
+                       Linkages:\n- address: 2637\n  sensors:\n  - comparator: >\n    value: 0\n  \
+                       - comparator: =\n    value: 0\n";
+        let config: Value = serde_yaml::from_str(content).unwrap();
+        assert_eq!(config["single"]["Cv"][0]["comparator"], json!(""));
+
+        let mut config: Value = serde_yaml::from_str(&quote_folded_comparators(content)).unwrap();
+        migrate(&mut config, Document::Alarms).unwrap();
+        assert_eq!(config["single"]["Cv"][0]["comparator"], json!(">"));
+        assert_eq!(config["single"]["Cv"][0]["value"], json!(15));
+        assert_eq!(config["Linkages"][0]["sensors"][0]["comparator"], json!(">"));
+        assert_eq!(config["Linkages"][0]["sensors"][1]["comparator"], json!("=="));
+    }
+
+    #[test]
+    fn test_empty_comparator_is_not_guessed() {
+        // 加了引号的空比较符和真正的折叠块都不是未加引号的 ">"
+        let content = "single:\n  Cv:\n  - address: 2633\n    comparator: ''\n    value: 15\n  \
+                       - address: 2634\n    comparator: >\n      <=\n    value: 15\n";
+        assert_eq!(quote_folded_comparators(content), content);
+
+        let mut config: Value = serde_yaml::from_str(content).unwrap();
+        migrate(&mut config, Document::Alarms).unwrap();
+        assert_eq!(config["single"]["Cv"][0]["comparator"], json!(""));
+        assert_eq!(config["single"]["Cv"][1]["comparator"], json!("<="));
+    }
+
+    #[test]
+    fn test_shipped_names_are_migrated() {
+        let mut sensors = load_yaml("configs/sensors.yaml");
+        let expression = |sensors: &Value| {
+            sensors["computed_sensors"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|s| s["name"] == json!("DP1"))
+                .map(|s| s["expression"].clone())
+                .unwrap()
+        };
+        assert_eq!(expression(&sensors), json!("Pressures.P4.value - Pressures.P5''.value"));
+        migrate(&mut sensors, Document::Sensors).unwrap();
+        assert_eq!(expression(&sensors), json!("Pressures.P4.value - Pressures.P5'.value"));
+
+        let mut alarms = load_yaml("src/config/alarm_config/default.yaml");
+        let rules = alarms["single"]["LI1"].clone();
+        assert!(alarms["single"].get("Li1").is_none());
+        migrate(&mut alarms, Document::Alarms).unwrap();
+        assert!(alarms["single"].get("LI1").is_none());
+        assert_eq!(alarms["single"]["Li1"], rules);
+
+        // 从版本 1 开始迁移时只改名
+        let mut alarms = json!({SCHEMA_VERSION_KEY: 1, "single": {"LI1": [{"address": 2635}]}});
+        let report = migrate(&mut alarms, Document::Alarms).unwrap();
+        assert_eq!(report.applied, vec!["rename alarm LI1 to the sensor name Li1"]);
+        assert_eq!(alarms["single"]["Li1"], json!([{"address": 2635}]));
+    }
+
+    #[test]
+    fn test_valid_double_prime_reference_survives() {
+        let mut sensors = json!({
+            SCHEMA_VERSION_KEY: 1,
+            "sensor_plugins": [{"name": "Temperatures", "sensors": [
+                {"name": "T1", "attrs": {"value": {"address": 3328}}},
+                {"name": "T1'", "attrs": {"value": {"address": 3329}}},
+                {"name": "T1''", "attrs": {"value": {"address": 3330}}}
+            ]}],
+            "computed_sensors": [
+                {"name": "DT1", "expression": "Temperatures.T1''.value - Temperatures.T1.value"},
+                {"name": "DT2", "expression": "Temperatures.T2''.value - Temperatures.T1.value"}
+            ]
+        });
+        migrate(&mut sensors, Document::Sensors).unwrap();
+        assert_eq!(
+            sensors["computed_sensors"][0]["expression"],
+            json!("Temperatures.T1''.value - Temperatures.T1.value")
+        );
+        // T2' 也不存在，无法确定本意，保持原样留给校验报告
+        assert_eq!(
+            sensors["computed_sensors"][1]["expression"],
+            json!("Temperatures.T2''.value - Temperatures.T1.value")
+        );
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let mut config = json!({SCHEMA_VERSION_KEY: 99});
+        assert!(matches!(
+            migrate(&mut config, Document::Alarms),
+            Err(MigrationError::NewerVersion { found: 99, supported: 2 })
+        ));
+        let mut config = json!({SCHEMA_VERSION_KEY: "one"});
+        assert!(matches!(
+            migrate(&mut config, Document::Alarms),
+            Err(MigrationError::InvalidVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_shipped_configs_are_valid_after_migration() {
+        let mut sensors = load_yaml("configs/sensors.yaml");
+        migrate(&mut sensors, Document::Sensors).unwrap();
+        assert_eq!(validator::validate_sensors(&sensors), Ok(()));
+
+        for path in ["src/config/alarm_config/config.yaml", "src/config/alarm_config/default.yaml"] {
+            let mut alarms = load_alarms(path);
+            migrate(&mut alarms, Document::Alarms).unwrap();
+            assert_eq!(validator::validate_alarms(&alarms, &sensors), Ok(()), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_migrate_store_backs_up_original() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("alarms.yaml");
+        let original = "single:\n  Cv:\n  - address: 2633\n    comparator: >\n    value: 15\n";
+        fs::write(&config_path, original).unwrap();
+
+        let store = FileStore::new(&config_path, None)
+            .unwrap()
+            .with_source_filter(quote_folded_comparators)
+            .unwrap();
+        let report = migrate_store(&store, Document::Alarms).unwrap();
+        assert_eq!(report.applied.len(), 2);
+
+        let backups = FileStore::list_backups(&config_path).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), original);
+
+        let migrated = load_yaml(config_path.to_str().unwrap());
+        assert_eq!(migrated["single"]["Cv"][0]["comparator"], json!(">"));
+        assert_eq!(migrated[SCHEMA_VERSION_KEY], json!(2));
+
+        // 第二次启动不会重写文件
+        let store = FileStore::new(&config_path, None).unwrap();
+        assert!(migrate_store(&store, Document::Alarms).unwrap().is_empty());
+        assert_eq!(FileStore::list_backups(&config_path).unwrap().len(), 1);
+    }
+}
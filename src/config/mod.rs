@@ -0,0 +1,4 @@
+pub mod bundle;
+pub mod loader;
+pub mod migrations;
+pub mod validator;
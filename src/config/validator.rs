@@ -0,0 +1,1177 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::utils::config_path::{self, ConfigPath, Segment};
+use crate::utils::datetime;
+
+pub const READ_METHODS: &[&str] = &[
+    "read_holding_registers",
+    "read_input_registers",
+    "read_coils",
+    "read_discrete_inputs",
+];
+
+pub const WRITE_METHODS: &[&str] = &[
+    "write_single_register",
+    "write_multiple_registers",
+    "write_single_coil",
+    "write_multiple_coils",
+    "read_write_multiple_registers",
+    // 旧名称
+    "write_register",
+    "write_registers",
+    "write_coil",
+    "write_coils",
+];
+
+pub const COMPARATORS: &[&str] = &[">", "<", ">=", "<=", "==", "!="];
+
+pub const ALARM_LEVELS: &[&str] = &["info", "warning", "error"];
+
+pub const GROUP_POLICIES: &[&str] = &["primary_with_fallback", "average", "min", "max", "median_of_three"];
+
+pub const PARITIES: &[&str] = &["N", "E", "O"];
+
+pub const BYTE_ORDERS: &[&str] = &["ABCD", "CDAB", "BADC", "DCBA"];
+
+pub const DATA_TYPES: &[&str] = &["u16", "i16", "u32", "i32", "f32", "f64", "bit", "string"];
+
+pub const SNMP_VERSIONS: &[&str] = &["1", "2c", "3"];
+pub const TIMESTAMP_FORMATS: &[&str] = &["default", "rfc3339"];
+
+/// 单条校验错误，path 指向出错的配置项
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// 一份配置的全部校验错误
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+pub type Result = std::result::Result<(), ValidationErrors>;
+
+/// 校验传感器配置（configs/sensors.yaml）
+pub fn validate_sensors(config: &Value) -> Result {
+    let mut checker = Checker::default();
+    checker.sensors(config);
+    checker.finish()
+}
+
+/// 校验告警配置，sensors 用于检查告警和联动引用的插件、传感器是否存在
+pub fn validate_alarms(config: &Value, sensors: &Value) -> Result {
+    let mut checker = Checker::default();
+    let catalog = Catalog::from_sensors(sensors);
+    checker.alarms(config, &catalog);
+    // 告警、联动和计算传感器共用 Modbus 服务端的地址空间
+    checker.computed_addresses(sensors);
+    checker.finish()
+}
+
+/// 校验全局配置（configs/global.confi.yaml）
+pub fn validate_global(config: &Value) -> Result {
+    let mut checker = Checker::default();
+    checker.global(config);
+    checker.finish()
+}
+
+/// 传感器配置中定义的插件、传感器、属性、分组和计算传感器
+#[derive(Debug, Default)]
+pub struct Catalog {
+    /// 插件名 -> 传感器名 -> 属性名
+    pub plugins: HashMap<String, HashMap<String, HashSet<String>>>,
+    /// 插件名 -> 分组名
+    pub groups: HashMap<String, HashSet<String>>,
+    pub computed: HashSet<String>,
+}
+
+impl Catalog {
+    pub fn from_sensors(config: &Value) -> Self {
+        let mut catalog = Catalog::default();
+        for plugin in array(config.get("sensor_plugins")) {
+            let Some(plugin_name) = plugin.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let defaults: Vec<String> = plugin
+                .get("defaults")
+                .and_then(Value::as_object)
+                .map(|d| d.keys().cloned().collect())
+                .unwrap_or_default();
+            let sensors = catalog.plugins.entry(plugin_name.to_string()).or_default();
+            for sensor in array(plugin.get("sensors")) {
+                let Some(name) = sensor.get("name").and_then(Value::as_str) else {
+                    continue;
+                };
+                let attrs = sensors.entry(name.to_string()).or_default();
+                attrs.extend(defaults.iter().cloned());
+                if let Some(own) = sensor.get("attrs").and_then(Value::as_object) {
+                    attrs.extend(own.keys().cloned());
+                }
+            }
+            let groups = catalog.groups.entry(plugin_name.to_string()).or_default();
+            for group in array(plugin.get("group_list")) {
+                if let Some(name) = group.get("name").and_then(Value::as_str) {
+                    groups.insert(name.to_string());
+                }
+            }
+        }
+        for computed in array(config.get("computed_sensors")) {
+            if let Some(name) = computed.get("name").and_then(Value::as_str) {
+                catalog.computed.insert(name.to_string());
+            }
+        }
+        catalog
+    }
+
+    /// 按传感器名或分组名查找属性，分组沿用第一个成员的属性
+    fn attrs_of(&self, plugin: Option<&str>, sensor: &str) -> Option<&HashSet<String>> {
+        self.plugins
+            .iter()
+            .filter(|(name, _)| plugin.is_none_or(|p| p == name.as_str()))
+            .find_map(|(_, sensors)| sensors.get(sensor))
+    }
+
+    /// 插件中是否有这个名称的传感器或分组
+    pub fn has_sensor(&self, plugin: &str, name: &str) -> bool {
+        self.attrs_of(Some(plugin), name).is_some() || self.has_group(Some(plugin), name)
+    }
+
+    fn has_group(&self, plugin: Option<&str>, name: &str) -> bool {
+        self.groups
+            .iter()
+            .filter(|(p, _)| plugin.is_none_or(|wanted| wanted == p.as_str()))
+            .any(|(_, groups)| groups.contains(name))
+    }
+}
+
+fn array(value: Option<&Value>) -> &[Value] {
+    value.and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn key(path: &ConfigPath, key: &str) -> ConfigPath {
+    path.child(Segment::Key(key.to_string()))
+}
+
+fn index(path: &ConfigPath, i: usize) -> ConfigPath {
+    path.child(Segment::Index(i))
+}
+
+#[derive(Default)]
+struct Checker {
+    errors: Vec<ValidationError>,
+    /// 地址 -> 第一次使用该地址的配置项
+    addresses: HashMap<i64, String>,
+    /// sensors.yaml 中定义的设备名
+    devices: HashSet<String>,
+}
+
+impl Checker {
+    fn finish(self) -> Result {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(self.errors))
+        }
+    }
+
+    fn error(&mut self, path: &ConfigPath, message: impl Into<String>) {
+        self.errors.push(ValidationError {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn required<'a>(&mut self, parent: &'a Value, path: &ConfigPath, name: &str) -> Option<&'a Value> {
+        let value = parent.get(name);
+        if value.is_none() || value == Some(&Value::Null) {
+            self.error(&key(path, name), "is required");
+            return None;
+        }
+        value
+    }
+
+    fn expect_type(&mut self, value: &Value, path: &ConfigPath, expected: &str) {
+        self.error(
+            path,
+            format!("expected {}, found {}", expected, config_path::type_name(value)),
+        );
+    }
+
+    fn object<'a>(&mut self, value: &'a Value, path: &ConfigPath) -> Option<&'a serde_json::Map<String, Value>> {
+        let object = value.as_object();
+        if object.is_none() {
+            self.expect_type(value, path, "object");
+        }
+        object
+    }
+
+    fn array<'a>(&mut self, value: &'a Value, path: &ConfigPath) -> Option<&'a Vec<Value>> {
+        let items = value.as_array();
+        if items.is_none() {
+            self.expect_type(value, path, "array");
+        }
+        items
+    }
+
+    fn string<'a>(&mut self, value: &'a Value, path: &ConfigPath) -> Option<&'a str> {
+        match value.as_str() {
+            Some(s) if s.trim().is_empty() => {
+                self.error(path, "must not be empty");
+                None
+            }
+            Some(s) => Some(s),
+            None => {
+                self.expect_type(value, path, "string");
+                None
+            }
+        }
+    }
+
+    fn boolean(&mut self, value: &Value, path: &ConfigPath) {
+        if !value.is_boolean() {
+            self.expect_type(value, path, "boolean");
+        }
+    }
+
+    fn number(&mut self, value: &Value, path: &ConfigPath) -> Option<f64> {
+        let number = value.as_f64();
+        if number.is_none() {
+            self.expect_type(value, path, "number");
+        }
+        number
+    }
+
+    fn integer_in(&mut self, value: &Value, path: &ConfigPath, min: i64, max: i64) -> Option<i64> {
+        match value.as_i64() {
+            Some(n) if n < min || n > max => {
+                self.error(path, format!("must be between {} and {}, got {}", min, max, n));
+                None
+            }
+            Some(n) => Some(n),
+            None => {
+                self.expect_type(value, path, "integer");
+                None
+            }
+        }
+    }
+
+    fn one_of(&mut self, value: &Value, path: &ConfigPath, allowed: &[&str]) -> Option<String> {
+        // YAML 中未加引号的 2c、1 等会被解析成数字
+        let text = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            other => {
+                self.expect_type(other, path, "string");
+                return None;
+            }
+        };
+        if !allowed.contains(&text.as_str()) {
+            self.error(
+                path,
+                format!("'{}' is not one of: {}", text, allowed.join(", ")),
+            );
+            return None;
+        }
+        Some(text)
+    }
+
+    fn optional<F>(&mut self, parent: &Value, path: &ConfigPath, name: &str, check: F)
+    where
+        F: FnOnce(&mut Self, &Value, &ConfigPath),
+    {
+        if let Some(value) = parent.get(name) {
+            check(self, value, &key(path, name));
+        }
+    }
+
+    /// Modbus 服务端地址必须唯一
+    fn unique_address(&mut self, value: &Value, path: &ConfigPath) {
+        if let Some(address) = self.integer_in(value, path, 0, 65535) {
+            let here = path.to_string();
+            match self.addresses.get(&address) {
+                Some(first) => {
+                    let message = format!("address {} is already used by {}", address, first);
+                    self.error(path, message);
+                }
+                None => {
+                    self.addresses.insert(address, here);
+                }
+            }
+        }
+    }
+
+    fn sensors(&mut self, config: &Value) {
+        let root = ConfigPath::root();
+        if self.object(config, &root).is_none() {
+            return;
+        }
+
+        if let Some(devices) = config.get("devices") {
+            let devices_path = key(&root, "devices");
+            for (i, device) in self.array(devices, &devices_path).into_iter().flatten().enumerate() {
+                self.device(device, &index(&devices_path, i));
+            }
+        }
+
+        let mut plugin_names = HashSet::new();
+        if let Some(plugins) = self.required(config, &root, "sensor_plugins") {
+            let plugins_path = key(&root, "sensor_plugins");
+            for (i, plugin) in self.array(plugins, &plugins_path).into_iter().flatten().enumerate() {
+                let path = index(&plugins_path, i);
+                if let Some(name) = self.plugin(plugin, &path) {
+                    if !plugin_names.insert(name.clone()) {
+                        self.error(&key(&path, "name"), format!("duplicate plugin name '{}'", name));
+                    }
+                }
+            }
+        }
+
+        let catalog = Catalog::from_sensors(config);
+        if let Some(computed) = config.get("computed_sensors") {
+            let computed_path = key(&root, "computed_sensors");
+            let mut names = HashSet::new();
+            for (i, sensor) in self.array(computed, &computed_path).into_iter().flatten().enumerate() {
+                let path = index(&computed_path, i);
+                if self.object(sensor, &path).is_none() {
+                    continue;
+                }
+                if let Some(name) = self.required(sensor, &path, "name") {
+                    if let Some(name) = self.string(name, &key(&path, "name")) {
+                        if !names.insert(name.to_string()) {
+                            self.error(&key(&path, "name"), format!("duplicate computed sensor '{}'", name));
+                        }
+                    }
+                }
+                if let Some(expression) = self.required(sensor, &path, "expression") {
+                    let expression_path = key(&path, "expression");
+                    if let Some(expression) = self.string(expression, &expression_path) {
+                        self.expression_refs(expression, &expression_path, &catalog);
+                    }
+                }
+                self.optional(sensor, &path, "address", |c, v, p| c.unique_address(v, p));
+                self.optional(sensor, &path, "decimal_places", |c, v, p| {
+                    c.integer_in(v, p, 0, 10);
+                });
+                self.optional(sensor, &path, "unit", |c, v, p| {
+                    if !v.is_string() {
+                        c.expect_type(v, p, "string");
+                    }
+                });
+            }
+        }
+    }
+
+    fn plugin(&mut self, plugin: &Value, path: &ConfigPath) -> Option<String> {
+        self.object(plugin, path)?;
+        let name = self
+            .required(plugin, path, "name")
+            .and_then(|name| self.string(name, &key(path, "name")))
+            .map(str::to_string);
+        self.optional(plugin, path, "type", |c, v, p| {
+            c.string(v, p);
+        });
+        self.optional(plugin, path, "can_write", |c, v, p| c.boolean(v, p));
+        self.optional(plugin, path, "device", |c, v, p| c.device_ref(v, p));
+
+        let empty = serde_json::Map::new();
+        let defaults = match plugin.get("defaults") {
+            Some(defaults) => {
+                let defaults_path = key(path, "defaults");
+                let defaults = self.object(defaults, &defaults_path).unwrap_or(&empty);
+                for (name, attr) in defaults {
+                    let attr_path = key(&defaults_path, name);
+                    if self.object(attr, &attr_path).is_some() {
+                        self.attr_fields(attr, &attr_path);
+                    }
+                }
+                defaults
+            }
+            None => &empty,
+        };
+
+        let mut sensor_names = HashSet::new();
+        if let Some(sensors) = self.required(plugin, path, "sensors") {
+            let sensors_path = key(path, "sensors");
+            for (i, sensor) in self.array(sensors, &sensors_path).into_iter().flatten().enumerate() {
+                let sensor_path = index(&sensors_path, i);
+                if self.object(sensor, &sensor_path).is_none() {
+                    continue;
+                }
+                if let Some(name) = self.required(sensor, &sensor_path, "name") {
+                    if let Some(name) = self.string(name, &key(&sensor_path, "name")) {
+                        if !sensor_names.insert(name.to_string()) {
+                            self.error(&key(&sensor_path, "name"), format!("duplicate sensor name '{}'", name));
+                        }
+                    }
+                }
+                self.optional(sensor, &sensor_path, "device", |c, v, p| c.device_ref(v, p));
+                if let Some(attrs) = self.required(sensor, &sensor_path, "attrs") {
+                    let attrs_path = key(&sensor_path, "attrs");
+                    for (name, attr) in self.object(attrs, &attrs_path).into_iter().flatten() {
+                        self.sensor_attr(attr, &key(&attrs_path, name), defaults.get(name));
+                    }
+                }
+            }
+        }
+
+        if let Some(groups) = plugin.get("group_list") {
+            let groups_path = key(path, "group_list");
+            for (i, group) in self.array(groups, &groups_path).into_iter().flatten().enumerate() {
+                let group_path = index(&groups_path, i);
+                if self.object(group, &group_path).is_none() {
+                    continue;
+                }
+                if let Some(name) = self.required(group, &group_path, "name") {
+                    self.string(name, &key(&group_path, "name"));
+                }
+                let policy = group
+                    .get("policy")
+                    .and_then(|policy| self.one_of(policy, &key(&group_path, "policy"), GROUP_POLICIES));
+                self.optional(group, &group_path, "tolerance", |c, v, p| {
+                    if let Some(n) = c.number(v, p) {
+                        if n < 0.0 {
+                            c.error(p, format!("must not be negative, got {}", n));
+                        }
+                    }
+                });
+                self.optional(group, &group_path, "deadband", |c, v, p| {
+                    if let Some(n) = c.number(v, p) {
+                        if n < 0.0 {
+                            c.error(p, format!("must not be negative, got {}", n));
+                        }
+                    }
+                });
+                // 偏差告警需要在 Modbus 告警表中有自己的地址
+                if group.get("tolerance").is_some() && group.get("alarm_address").is_none() {
+                    self.error(&key(&group_path, "alarm_address"), "required when tolerance is set");
+                }
+                self.optional(group, &group_path, "alarm_address", |c, v, p| c.unique_address(v, p));
+                self.optional(group, &group_path, "alarm_level", |c, v, p| {
+                    c.one_of(v, p, ALARM_LEVELS);
+                });
+                if let Some(devices) = self.required(group, &group_path, "devices") {
+                    let devices_path = key(&group_path, "devices");
+                    let devices = self.array(devices, &devices_path).cloned().unwrap_or_default();
+                    if devices.len() < 2 {
+                        self.error(&devices_path, "a group needs at least two devices");
+                    } else if devices.len() < 3 && policy.as_deref() == Some("median_of_three") {
+                        self.error(&devices_path, "median_of_three needs at least three devices");
+                    }
+                    for (j, device) in devices.iter().enumerate() {
+                        let device_path = index(&devices_path, j);
+                        if let Some(device) = self.string(device, &device_path) {
+                            if !sensor_names.contains(device) {
+                                self.error(&device_path, format!("unknown sensor '{}'", device));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        name
+    }
+
+    /// 一个 Modbus 从站设备：名称、TCP 或串口连接二选一、从站地址、字节序和轮询周期
+    fn device(&mut self, device: &Value, path: &ConfigPath) {
+        if self.object(device, path).is_none() {
+            return;
+        }
+        if let Some(name) = self.required(device, path, "name") {
+            if let Some(name) = self.string(name, &key(path, "name")) {
+                if !self.devices.insert(name.to_string()) {
+                    self.error(&key(path, "name"), format!("duplicate device name '{}'", name));
+                }
+            }
+        }
+        match (device.get("tcp"), device.get("serial")) {
+            (Some(tcp), None) => {
+                self.endpoint(tcp, &key(path, "tcp"));
+                if tcp.get("host").is_none() {
+                    self.error(&key(&key(path, "tcp"), "host"), "is required");
+                }
+            }
+            (None, Some(serial)) => self.serial(serial, &key(path, "serial")),
+            _ => self.error(path, "a device needs exactly one of tcp or serial"),
+        }
+        self.optional(device, path, "slave_id", |c, v, p| {
+            c.integer_in(v, p, 1, 247);
+        });
+        self.optional(device, path, "byte_order", |c, v, p| {
+            c.one_of(v, p, BYTE_ORDERS);
+        });
+        self.optional(device, path, "poll_interval", |c, v, p| c.positive(v, p));
+    }
+
+    fn device_ref(&mut self, value: &Value, path: &ConfigPath) {
+        if let Some(name) = self.string(value, path) {
+            if !self.devices.contains(name) {
+                self.error(path, format!("unknown device '{}'", name));
+            }
+        }
+    }
+
+    /// 属性可以简写为地址（"value: 3328"），否则与 defaults 中的同名属性合并后校验
+    fn sensor_attr(&mut self, attr: &Value, path: &ConfigPath, default: Option<&Value>) {
+        let mut merged = default.cloned().unwrap_or_else(|| Value::Object(Default::default()));
+        match attr {
+            Value::Number(_) => {
+                self.integer_in(attr, path, 0, 65535);
+                return;
+            }
+            Value::Object(own) => {
+                if let Some(merged) = merged.as_object_mut() {
+                    merged.extend(own.clone());
+                }
+            }
+            other => {
+                self.expect_type(other, path, "object or address");
+                return;
+            }
+        }
+        self.attr_fields(attr, path);
+        if self.required(attr, path, "address").is_none() {
+            return;
+        }
+        if merged.get("read_method").is_none() {
+            self.error(&key(path, "read_method"), "is required (in the attribute or plugin defaults)");
+        }
+        let data_type = merged.get("data_type").and_then(Value::as_str);
+        let read_method = merged.get("read_method").and_then(Value::as_str);
+        let bit_method = matches!(read_method, Some("read_coils" | "read_discrete_inputs"));
+        if let Some(write_method) = merged.get("write_method").and_then(Value::as_str) {
+            let coil_write = write_method.contains("coil");
+            let message = match read_method {
+                Some(method @ ("read_input_registers" | "read_discrete_inputs")) => {
+                    Some(format!("{} is read-only, it cannot use {}", method, write_method))
+                }
+                Some("read_coils") if !coil_write => Some(format!("{} cannot write coils", write_method)),
+                Some("read_holding_registers") if coil_write => {
+                    Some(format!("{} cannot write holding registers", write_method))
+                }
+                _ if matches!(write_method, "write_single_register" | "write_register")
+                    && matches!(data_type, Some("u32" | "i32" | "f32" | "f64" | "string")) =>
+                {
+                    Some(format!("{} spans several registers, use write_multiple_registers", data_type.unwrap_or_default()))
+                }
+                _ => None,
+            };
+            if let Some(message) = message {
+                self.error(&key(path, "write_method"), message);
+            }
+        }
+        if bit_method && data_type.is_some_and(|t| t != "u16") {
+            self.error(&key(path, "data_type"), "coils and discrete inputs have no data type");
+        }
+        if data_type == Some("string") && merged.get("length").is_none() {
+            self.error(&key(path, "length"), "is required for data_type string");
+        }
+        for (min_name, max_name) in [("min", "max"), ("raw_min", "raw_max")] {
+            if let (Some(min), Some(max)) = (
+                merged.get(min_name).and_then(Value::as_f64),
+                merged.get(max_name).and_then(Value::as_f64),
+            ) {
+                if min > max {
+                    self.error(path, format!("{} {} is greater than {} {}", min_name, min, max_name, max));
+                }
+            }
+        }
+    }
+
+    fn attr_fields(&mut self, attr: &Value, path: &ConfigPath) {
+        self.optional(attr, path, "address", |c, v, p| {
+            c.integer_in(v, p, 0, 65535);
+        });
+        self.optional(attr, path, "batch_address", |c, v, p| {
+            c.integer_in(v, p, 0, 65535);
+        });
+        self.optional(attr, path, "read_method", |c, v, p| {
+            c.one_of(v, p, READ_METHODS);
+        });
+        self.optional(attr, path, "write_method", |c, v, p| {
+            c.one_of(v, p, WRITE_METHODS);
+        });
+        self.optional(attr, path, "slave_id", |c, v, p| {
+            c.integer_in(v, p, 1, 247);
+        });
+        self.optional(attr, path, "data_type", |c, v, p| {
+            c.one_of(v, p, DATA_TYPES);
+        });
+        self.optional(attr, path, "byte_order", |c, v, p| {
+            c.one_of(v, p, BYTE_ORDERS);
+        });
+        self.optional(attr, path, "bit", |c, v, p| {
+            c.integer_in(v, p, 0, 15);
+        });
+        self.optional(attr, path, "length", |c, v, p| {
+            c.integer_in(v, p, 1, 125);
+        });
+        for name in ["formula", "write_formula"] {
+            self.optional(attr, path, name, |c, v, p| {
+                c.string(v, p);
+            });
+        }
+        self.optional(attr, path, "unit", |c, v, p| {
+            if !v.is_string() {
+                c.expect_type(v, p, "string");
+            }
+        });
+        for name in ["min", "max", "raw_min", "raw_max"] {
+            self.optional(attr, path, name, |c, v, p| {
+                c.number(v, p);
+            });
+        }
+        self.optional(attr, path, "frozen_timeout", |c, v, p| c.positive(v, p));
+        self.optional(attr, path, "decimal_places", |c, v, p| {
+            c.integer_in(v, p, 0, 10);
+        });
+    }
+
+    /// 表达式中形如 "Plugin.Sensor.attr" 的引用必须存在
+    fn expression_refs(&mut self, expression: &str, path: &ConfigPath, catalog: &Catalog) {
+        for reference in expression_references(expression) {
+            let parts: Vec<&str> = reference.split('.').collect();
+            let [plugin, sensor, attr] = parts[..] else {
+                continue;
+            };
+            if !catalog.plugins.contains_key(plugin) {
+                self.error(path, format!("unknown plugin '{}' in '{}'", plugin, reference));
+                continue;
+            }
+            match catalog.attrs_of(Some(plugin), sensor) {
+                Some(attrs) if !attrs.contains(attr) => {
+                    self.error(path, format!("unknown attribute '{}' in '{}'", attr, reference));
+                }
+                Some(_) => {}
+                None if catalog.has_group(Some(plugin), sensor) => {}
+                None => self.error(path, format!("unknown sensor '{}' in '{}'", sensor, reference)),
+            }
+        }
+    }
+
+    fn computed_addresses(&mut self, sensors: &Value) {
+        let computed_path = key(&ConfigPath::root(), "computed_sensors");
+        for (i, sensor) in array(sensors.get("computed_sensors")).iter().enumerate() {
+            if let Some(address) = sensor.get("address") {
+                let path = key(&index(&computed_path, i), "address");
+                self.unique_address(address, &path);
+            }
+        }
+    }
+
+    fn alarms(&mut self, config: &Value, catalog: &Catalog) {
+        let root = ConfigPath::root();
+        if self.object(config, &root).is_none() {
+            return;
+        }
+
+        if let Some(single) = config.get("single") {
+            let single_path = key(&root, "single");
+            for (name, rules) in self.object(single, &single_path).into_iter().flatten() {
+                let name_path = key(&single_path, name);
+                let attrs = catalog.attrs_of(None, name);
+                let known = attrs.is_some() || catalog.computed.contains(name) || catalog.has_group(None, name);
+                if !known {
+                    self.error(&name_path, format!("unknown sensor '{}'", name));
+                }
+                for (i, rule) in self.array(rules, &name_path).into_iter().flatten().enumerate() {
+                    let rule_path = index(&name_path, i);
+                    if self.object(rule, &rule_path).is_none() {
+                        continue;
+                    }
+                    self.rule(rule, &rule_path);
+                    if let Some(address) = self.required(rule, &rule_path, "address") {
+                        self.unique_address(address, &key(&rule_path, "address"));
+                    }
+                    self.optional(rule, &rule_path, "decimal_places", |c, v, p| {
+                        c.integer_in(v, p, 0, 10);
+                    });
+                    self.optional(rule, &rule_path, "level", |c, v, p| {
+                        c.one_of(v, p, ALARM_LEVELS);
+                    });
+                    if let (Some(attrs), Some(attr)) = (attrs, rule.get("key").and_then(Value::as_str)) {
+                        if !attrs.contains(attr) {
+                            self.error(&key(&rule_path, "key"), format!("unknown attribute '{}' of '{}'", attr, name));
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(linkages) = config.get("Linkages") {
+            let linkages_path = key(&root, "Linkages");
+            for (i, linkage) in self.array(linkages, &linkages_path).into_iter().flatten().enumerate() {
+                let path = index(&linkages_path, i);
+                if self.object(linkage, &path).is_none() {
+                    continue;
+                }
+                if let Some(name) = self.required(linkage, &path, "name") {
+                    self.string(name, &key(&path, "name"));
+                }
+                if let Some(address) = self.required(linkage, &path, "address") {
+                    self.unique_address(address, &key(&path, "address"));
+                }
+                let Some(conditions) = self.required(linkage, &path, "sensors") else {
+                    continue;
+                };
+                let conditions_path = key(&path, "sensors");
+                for (j, condition) in self.array(conditions, &conditions_path).into_iter().flatten().enumerate() {
+                    let condition_path = index(&conditions_path, j);
+                    if self.object(condition, &condition_path).is_none() {
+                        continue;
+                    }
+                    self.rule(condition, &condition_path);
+                    self.linkage_reference(condition, &condition_path, catalog);
+                }
+            }
+        }
+    }
+
+    /// 告警规则和联动条件共有的字段：comparator 和 value
+    fn rule(&mut self, rule: &Value, path: &ConfigPath) {
+        if let Some(comparator) = self.required(rule, path, "comparator") {
+            self.one_of(comparator, &key(path, "comparator"), COMPARATORS);
+        }
+        if let Some(value) = self.required(rule, path, "value") {
+            self.number(value, &key(path, "value"));
+        }
+    }
+
+    fn linkage_reference(&mut self, condition: &Value, path: &ConfigPath, catalog: &Catalog) {
+        let plugin = self
+            .required(condition, path, "plugin")
+            .and_then(|v| self.string(v, &key(path, "plugin")));
+        let sensor = self
+            .required(condition, path, "sensor")
+            .and_then(|v| self.string(v, &key(path, "sensor")));
+        let (Some(plugin), Some(sensor)) = (plugin, sensor) else {
+            return;
+        };
+        if !catalog.plugins.contains_key(plugin) {
+            self.error(&key(path, "plugin"), format!("unknown plugin '{}'", plugin));
+            return;
+        }
+        let attr = condition.get("key").and_then(Value::as_str).unwrap_or("value");
+        match catalog.attrs_of(Some(plugin), sensor) {
+            Some(attrs) if !attrs.contains(attr) => {
+                self.error(&key(path, "key"), format!("unknown attribute '{}' of '{}'", attr, sensor));
+            }
+            Some(_) => {}
+            None if catalog.has_group(Some(plugin), sensor) => {}
+            None => self.error(&key(path, "sensor"), format!("unknown sensor '{}' in plugin '{}'", sensor, plugin)),
+        }
+    }
+
+    fn global(&mut self, config: &Value) {
+        let root = ConfigPath::root();
+        if self.object(config, &root).is_none() {
+            return;
+        }
+        self.optional(config, &root, "http", |c, v, p| c.endpoint(v, p));
+        for section in ["modbus_client", "modbus_server"] {
+            self.optional(config, &root, section, |c, v, p| {
+                if c.object(v, p).is_none() {
+                    return;
+                }
+                c.optional(v, p, "tcp", |c, v, p| {
+                    c.endpoint(v, p);
+                    c.optional(v, p, "timeout", |c, v, p| c.positive(v, p));
+                });
+                c.optional(v, p, "serial", |c, v, p| c.serial(v, p));
+            });
+        }
+        self.optional(config, &root, "time", |c, v, p| {
+            if c.object(v, p).is_none() {
+                return;
+            }
+            c.optional(v, p, "zone", |c, v, p| {
+                if let Some(zone) = c.string(v, p) {
+                    if let Err(e) = datetime::Zone::load(zone) {
+                        c.error(p, e.to_string());
+                    }
+                }
+            });
+            c.optional(v, p, "format", |c, v, p| {
+                c.one_of(v, p, TIMESTAMP_FORMATS);
+            });
+        });
+        self.optional(config, &root, "ntp", |c, v, p| {
+            c.endpoint(v, p);
+            c.optional(v, p, "interval", |c, v, p| c.positive(v, p));
+            c.optional(v, p, "timeout", |c, v, p| c.positive(v, p));
+        });
+        self.optional(config, &root, "polling", |c, v, p| {
+            if c.object(v, p).is_none() {
+                return;
+            }
+            c.optional(v, p, "interval", |c, v, p| c.positive(v, p));
+            c.optional(v, p, "stale_timeout", |c, v, p| c.positive(v, p));
+        });
+        self.optional(config, &root, "history", |c, v, p| {
+            if c.object(v, p).is_none() {
+                return;
+            }
+            c.optional(v, p, "enable", |c, v, p| c.boolean(v, p));
+            c.optional(v, p, "dir", |c, v, p| {
+                c.string(v, p);
+            });
+            c.optional(v, p, "resolution", |c, v, p| c.positive(v, p));
+            c.optional(v, p, "downsample_interval", |c, v, p| c.positive(v, p));
+            c.optional(v, p, "raw_days", |c, v, p| {
+                c.integer_in(v, p, 1, 3650);
+            });
+            c.optional(v, p, "retention_days", |c, v, p| {
+                c.integer_in(v, p, 1, 36500);
+            });
+            c.optional(v, p, "max_size_mb", |c, v, p| {
+                c.integer_in(v, p, 1, i64::MAX);
+            });
+            if let (Some(raw), Some(retention)) = (
+                v.get("raw_days").and_then(Value::as_u64),
+                v.get("retention_days").and_then(Value::as_u64),
+            ) {
+                if raw > retention {
+                    c.error(&key(p, "raw_days"), format!("must not exceed retention_days ({})", retention));
+                }
+            }
+        });
+        self.optional(config, &root, "snmp", |c, v, p| {
+            c.endpoint(v, p);
+            c.optional(v, p, "community", |c, v, p| {
+                c.string(v, p);
+            });
+            c.optional(v, p, "version", |c, v, p| {
+                c.one_of(v, p, SNMP_VERSIONS);
+            });
+        });
+    }
+
+    fn endpoint(&mut self, value: &Value, path: &ConfigPath) {
+        if self.object(value, path).is_none() {
+            return;
+        }
+        self.optional(value, path, "enable", |c, v, p| c.boolean(v, p));
+        self.optional(value, path, "host", |c, v, p| {
+            c.string(v, p);
+        });
+        self.optional(value, path, "port", |c, v, p| {
+            c.integer_in(v, p, 1, 65535);
+        });
+        self.optional(value, path, "slave_id", |c, v, p| {
+            c.integer_in(v, p, 0, 255);
+        });
+    }
+
+    fn serial(&mut self, value: &Value, path: &ConfigPath) {
+        if self.object(value, path).is_none() {
+            return;
+        }
+        self.optional(value, path, "enable", |c, v, p| c.boolean(v, p));
+        if value.get("enable") != Some(&Value::Bool(false)) {
+            if let Some(port) = self.required(value, path, "port") {
+                self.string(port, &key(path, "port"));
+            }
+        }
+        self.optional(value, path, "baudrate", |c, v, p| {
+            c.integer_in(v, p, 300, 4_000_000);
+        });
+        self.optional(value, path, "timeout", |c, v, p| c.positive(v, p));
+        self.optional(value, path, "data_bits", |c, v, p| {
+            c.integer_in(v, p, 5, 8);
+        });
+        self.optional(value, path, "parity", |c, v, p| {
+            c.one_of(v, p, PARITIES);
+        });
+        self.optional(value, path, "stop_bits", |c, v, p| {
+            c.integer_in(v, p, 1, 2);
+        });
+        self.optional(value, path, "slave_id", |c, v, p| {
+            c.integer_in(v, p, 1, 247);
+        });
+    }
+
+    fn positive(&mut self, value: &Value, path: &ConfigPath) {
+        if let Some(n) = self.number(value, path) {
+            if n <= 0.0 {
+                self.error(path, format!("must be greater than 0, got {}", n));
+            }
+        }
+    }
+}
+
+/// 提取表达式中的 "Plugin.Sensor.attr" 引用，名称中允许出现 "'"
+pub fn expression_references(expression: &str) -> Vec<String> {
+    let mut references = Vec::new();
+    let mut current = String::new();
+    for c in expression.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' || c == '\'' || c == '.' {
+            current.push(c);
+            continue;
+        }
+        if current.matches('.').count() == 2
+            && current.split('.').all(|part| !part.is_empty())
+            && !current.starts_with(|c: char| c.is_ascii_digit())
+        {
+            references.push(current.clone());
+        }
+        current.clear();
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::migrations;
+    use serde_json::json;
+
+    fn sensors_fixture() -> Value {
+        json!({
+            "sensor_plugins": [
+                {
+                    "name": "Pumps",
+                    "defaults": {
+                        "Speed": {"read_method": "read_holding_registers", "unit": "RPM"},
+                        "Temperature": {"read_method": "read_holding_registers", "min": -40, "max": 80}
+                    },
+                    "sensors": [
+                        {"name": "Pump1", "attrs": {"Speed": {"address": 2080}, "Temperature": {"address": 2113}}}
+                    ]
+                },
+                {
+                    "name": "Temperatures",
+                    "defaults": {"value": {"read_method": "read_holding_registers", "formula": "$value / 10"}},
+                    "sensors": [
+                        {"name": "T1", "attrs": {"value": 3328}},
+                        {"name": "T1'", "attrs": {"value": {"address": 3329}}}
+                    ],
+                    "group_list": [{"name": "T1", "devices": ["T1", "T1'"]}]
+                }
+            ],
+            "computed_sensors": [
+                {"name": "T1-T1'", "address": 1601, "expression": "Temperatures.T1.value - Temperatures.T1'.value"}
+            ]
+        })
+    }
+
+    fn paths(result: Result) -> Vec<String> {
+        result.unwrap_err().0.into_iter().map(|e| e.path).collect()
+    }
+
+    #[test]
+    fn test_valid_sensors() {
+        assert_eq!(validate_sensors(&sensors_fixture()), Ok(()));
+    }
+
+    #[test]
+    fn test_sensor_errors_carry_paths() {
+        let mut config = sensors_fixture();
+        config["sensor_plugins"][0]["defaults"]["Speed"]["read_method"] = json!("read_everything");
+        config["sensor_plugins"][0]["sensors"][0]["attrs"]["Speed"]["address"] = json!(70000);
+        config["sensor_plugins"][0]["sensors"][0]["attrs"]["Speed"]["slave_id"] = json!(248);
+        config["sensor_plugins"][1]["sensors"][1]["name"] = json!("T1");
+        config["sensor_plugins"][1]["group_list"][0]["devices"][1] = json!("T9");
+        config["sensor_plugins"][1]["group_list"][0]["tolerance"] = json!(-1);
+        config["computed_sensors"][0]["expression"] = json!("Temperatures.T2.value * 2");
+
+        assert_eq!(
+            paths(validate_sensors(&config)),
+            vec![
+                "sensor_plugins[0].defaults.Speed.read_method",
+                "sensor_plugins[0].sensors[0].attrs.Speed.address",
+                "sensor_plugins[0].sensors[0].attrs.Speed.slave_id",
+                "sensor_plugins[1].sensors[1].name",
+                "sensor_plugins[1].group_list[0].tolerance",
+                "sensor_plugins[1].group_list[0].alarm_address",
+                "sensor_plugins[1].group_list[0].devices[1]",
+                "computed_sensors[0].expression",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_required_fields_and_ranges() {
+        let mut config = sensors_fixture();
+        config["sensor_plugins"][0]["defaults"]["Temperature"]["min"] = json!(100);
+        config["sensor_plugins"][1]["sensors"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("attrs");
+
+        let errors = validate_sensors(&config).unwrap_err();
+        assert_eq!(errors.0.len(), 2);
+        assert_eq!(errors.0[0].path, "sensor_plugins[0].sensors[0].attrs.Temperature");
+        assert!(errors.0[0].message.contains("greater than max"));
+        assert_eq!(errors.0[1].to_string(), "sensor_plugins[1].sensors[0].attrs: is required");
+
+        let mut config = sensors_fixture();
+        config["sensor_plugins"][0]["defaults"]["Speed"]["data_type"] = json!("f16");
+        config["sensor_plugins"][0]["defaults"]["Temperature"]["data_type"] = json!("string");
+        config["sensor_plugins"][0]["sensors"][0]["attrs"]["Speed"]["byte_order"] = json!("ACBD");
+        config["sensor_plugins"][1]["defaults"]["value"]["data_type"] = json!("bit");
+        config["sensor_plugins"][1]["defaults"]["value"]["bit"] = json!(16);
+        config["sensor_plugins"][0]["defaults"]["Speed"]["write_method"] = json!("write_single_coil");
+        config["sensor_plugins"][0]["defaults"]["Temperature"]["read_method"] = json!("read_input_registers");
+        config["sensor_plugins"][0]["defaults"]["Temperature"]["write_method"] = json!("write_multiple_registers");
+        assert_eq!(
+            paths(validate_sensors(&config)),
+            vec![
+                "sensor_plugins[0].defaults.Speed.data_type",
+                "sensor_plugins[0].sensors[0].attrs.Speed.byte_order",
+                "sensor_plugins[0].sensors[0].attrs.Speed.write_method",
+                "sensor_plugins[0].sensors[0].attrs.Temperature.write_method",
+                "sensor_plugins[0].sensors[0].attrs.Temperature.length",
+                "sensor_plugins[1].defaults.value.bit",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_policy() {
+        let mut config = sensors_fixture();
+        config["sensor_plugins"][1]["group_list"][0]["policy"] = json!("median_of_three");
+        config["sensor_plugins"][1]["group_list"][0]["alarm_address"] = json!(1601);
+        assert_eq!(
+            paths(validate_sensors(&config)),
+            // 计算传感器的地址与分组告警地址重复
+            vec!["sensor_plugins[1].group_list[0].devices", "computed_sensors[0].address"]
+        );
+
+        config["sensor_plugins"][1]["group_list"][0]["policy"] = json!("vote");
+        config["sensor_plugins"][1]["group_list"][0]["alarm_address"] = json!(1602);
+        assert_eq!(paths(validate_sensors(&config)), vec!["sensor_plugins[1].group_list[0].policy"]);
+    }
+
+    #[test]
+    fn test_devices() {
+        let mut config = sensors_fixture();
+        config["devices"] = json!([
+            {"name": "pump-drive", "tcp": {"host": "192.168.1.151", "port": 502}, "slave_id": 3, "byte_order": "CDAB"},
+            {"name": "rs485", "serial": {"port": "/dev/ttyS1", "baudrate": 19200}, "poll_interval": 0.5}
+        ]);
+        config["sensor_plugins"][0]["device"] = json!("pump-drive");
+        config["sensor_plugins"][1]["sensors"][1]["device"] = json!("rs485");
+        assert_eq!(validate_sensors(&config), Ok(()));
+
+        config["devices"][1]["name"] = json!("pump-drive");
+        config["devices"][1]["tcp"] = json!({"host": "10.0.0.2"});
+        config["devices"][0]["byte_order"] = json!("BACD");
+        config["devices"][0]["poll_interval"] = json!(0);
+        config["sensor_plugins"][1]["sensors"][1]["device"] = json!("rs485");
+        assert_eq!(
+            paths(validate_sensors(&config)),
+            vec![
+                "devices[0].byte_order",
+                "devices[0].poll_interval",
+                "devices[1].name",
+                "devices[1]",
+                "sensor_plugins[1].sensors[1].device",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alarms() {
+        let alarms = json!({
+            "single": {
+                "T1": [{"address": 2604, "comparator": ">", "value": 0}],
+                "T1-T1'": [{"address": 2605, "comparator": "<", "value": 5}],
+                "Pump1": [{"address": 2624, "comparator": ">", "key": "Temperature", "value": 0}]
+            },
+            "Linkages": [{
+                "address": 2637,
+                "name": "Pump1 dry running",
+                "sensors": [{"comparator": ">", "key": "Speed", "plugin": "Pumps", "sensor": "Pump1", "value": 100}]
+            }]
+        });
+        assert_eq!(validate_alarms(&alarms, &sensors_fixture()), Ok(()));
+
+        let broken = json!({
+            "single": {
+                "LI1": [{"address": 2635, "comparator": "!=", "value": 0}],
+                "T1": [{"address": 1601, "comparator": "", "value": 0, "level": "fatal"}],
+                "Pump1": [{"address": 2624, "comparator": ">", "key": "Voltage", "value": 0}]
+            },
+            "Linkages": [{
+                "address": 2635,
+                "name": "broken",
+                "sensors": [{"comparator": ">", "plugin": "Valves", "sensor": "Valve1", "value": 5}]
+            }]
+        });
+        assert_eq!(
+            paths(validate_alarms(&broken, &sensors_fixture())),
+            vec![
+                "single.LI1",
+                "single.Pump1[0].key",
+                "single.T1[0].comparator",
+                "single.T1[0].level",
+                "Linkages[0].address",
+                "Linkages[0].sensors[0].plugin",
+                "computed_sensors[0].address",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_global() {
+        let config = json!({
+            "http": {"host": "0.0.0.0", "port": 5000},
+            "modbus_client": {"serial": {"enable": true, "port": "/dev/com2", "parity": "X", "data_bits": 9, "slave_id": 0}},
+            "snmp": {"version": "2c", "port": 0},
+            "history": {"resolution": 0, "raw_days": 30, "retention_days": 7}
+        });
+        assert_eq!(
+            paths(validate_global(&config)),
+            vec![
+                "modbus_client.serial.data_bits",
+                "modbus_client.serial.parity",
+                "modbus_client.serial.slave_id",
+                "history.resolution",
+                "history.raw_days",
+                "snmp.port",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expression_references() {
+        assert_eq!(
+            expression_references("(Temperatures.T3.value - Temperatures.T4.value) * Flows.F2'.value * 4.2 / 60"),
+            vec!["Temperatures.T3.value", "Temperatures.T4.value", "Flows.F2'.value"]
+        );
+        assert!(expression_references("$value / 10 ** 0.5").is_empty());
+    }
+
+    #[test]
+    fn test_shipped_sensors_config_is_valid() {
+        let content = std::fs::read_to_string("configs/sensors.yaml").unwrap();
+        let mut config: Value = serde_yaml::from_str(&content).unwrap();
+        // 旧版本的名称错误在迁移前会被报告出来
+        let errors = validate_sensors(&config).unwrap_err();
+        assert!(errors.to_string().contains("P5''"), "{}", errors);
+
+        migrations::migrate(&mut config, migrations::Document::Sensors).unwrap();
+        assert_eq!(validate_sensors(&config), Ok(()));
+    }
+}
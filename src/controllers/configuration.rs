@@ -0,0 +1,150 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::info;
+use serde_json::json;
+
+use crate::app::state::AppState;
+use crate::config::bundle::{self, BundleError, ConfigBundle};
+use crate::controllers::utils::{client_addr, error_response, require_admin};
+use crate::utils::datetime;
+
+/// GET /cdu/config/export：下载包含所有受管理配置的配置包（需要管理员令牌），
+/// 管理员令牌不会被导出
+pub async fn export_config(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let result = bundle::export(&state.config_files);
+    let detail = match result {
+        Ok(ref bundle) => json!({"files": bundle.files.keys().collect::<Vec<_>>()}),
+        Err(_) => json!({}),
+    };
+    state.audit.record(&client_addr(&req), "config.export", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(bundle) => {
+            let filename = format!(
+                "cdu-config-{}.json",
+                datetime::get_current_time().replace([' ', ':', '+'], "")
+            );
+            HttpResponse::Ok()
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"{}\"", filename),
+                ))
+                .json(bundle)
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// POST /cdu/config/import：校验配置包后整体替换配置文件（需要管理员令牌）
+pub async fn import_config(req: HttpRequest, state: web::Data<AppState>, bundle: web::Json<ConfigBundle>) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let result = bundle::import(&bundle, &state.config_files);
+    let detail = json!({"created_at": bundle.created_at, "files": bundle.files.keys().collect::<Vec<_>>()});
+    state.audit.record(&client_addr(&req), "config.import", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(report) => {
+            info!("Config bundle imported via API: {:?}", report.applied);
+            HttpResponse::Ok().json(report)
+        }
+        Err(e @ BundleError::Io { .. }) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::routes;
+    use crate::config::bundle::ConfigFiles;
+    use crate::services::audit::AuditLog;
+    use actix_web::{test, App};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn files(dir: &std::path::Path) -> ConfigFiles {
+        let files = ConfigFiles {
+            global: dir.join("global.yaml"),
+            sensors: dir.join("sensors.yaml"),
+            alarms: dir.join("alarms.yaml"),
+            pid: dir.join("pid.yaml"),
+            control_mode: dir.join("control_mode.yaml"),
+        };
+        fs::write(&files.global, "http:\n  port: 5000\n  admin_token: secret\n").unwrap();
+        files
+    }
+
+    #[actix_web::test]
+    async fn test_export_then_import() {
+        let dir = tempdir().unwrap();
+        let files = files(dir.path());
+        let global_path = files.global.clone();
+        let global = std::sync::Arc::new(crate::utils::file_store::FileStore::new(&files.global, None).unwrap());
+        let audit = std::sync::Arc::new(AuditLog::new(dir.path().join("audit.log")));
+        let state = AppState::new(files, global)
+            .with_audit(std::sync::Arc::clone(&audit))
+            .with_admin_token("secret");
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let req = test::TestRequest::get().uri("/cdu/config/export").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/cdu/config/export")
+            .insert_header(("Authorization", "Bearer secret"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert!(resp.headers().get("Content-Disposition").is_some());
+        let mut bundle: ConfigBundle = test::read_body_json(resp).await;
+        assert_eq!(bundle.files.len(), 1);
+        assert!(!bundle.files["global"].content.contains("secret"));
+
+        let req = test::TestRequest::post()
+            .uri("/cdu/config/import")
+            .set_json(&bundle)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/cdu/config/import")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(&bundle)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        // 导入后本机的令牌仍然有效
+        assert!(fs::read_to_string(&global_path).unwrap().contains("admin_token: secret"));
+
+        bundle.checksum = "0".repeat(64);
+        let req = test::TestRequest::post()
+            .uri("/cdu/config/import")
+            .insert_header(("Authorization", "Bearer secret"))
+            .set_json(&bundle)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "checksum mismatch for bundle");
+
+        let actions: Vec<(String, bool)> = audit
+            .recent(10)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.action, e.success))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("config.export".to_string(), true),
+                ("config.import".to_string(), true),
+                ("config.import".to_string(), false)
+            ]
+        );
+    }
+}
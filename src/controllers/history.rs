@@ -0,0 +1,313 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::app::state::AppState;
+use crate::controllers::utils::{csv_field, error_response, streaming_body, ChunkWriter};
+use crate::services::history_store::{Aggregation, HistoryCursor, HistoryStore};
+use crate::utils::datetime;
+
+/// 不指定 from 时查询最近一小时
+const DEFAULT_RANGE: time::Duration = time::Duration::HOUR;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryFormat {
+    /// 按序列输出，给前端画曲线
+    #[default]
+    Json,
+    /// 每行一个时间点，表头带单位，下载后用 Excel 打开
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// 逗号分隔的序列名，例如 "Temperatures.T1.value,CAP"
+    pub sensors: String,
+    /// "2026-02-10 09:38:48 +0800" 或 RFC 3339，默认为 to 之前一小时
+    pub from: Option<String>,
+    /// 默认为当前时间
+    pub to: Option<String>,
+    /// 聚合的时间间隔（秒），不指定时返回每个记录点
+    pub step: Option<f64>,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    #[serde(default)]
+    pub format: HistoryFormat,
+}
+
+/// 校验后的查询参数
+struct Query {
+    names: Vec<String>,
+    units: Vec<String>,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    step_ms: Option<i64>,
+    aggregation: Aggregation,
+}
+
+fn history_store(state: &AppState) -> Result<Arc<HistoryStore>, HttpResponse> {
+    state
+        .history
+        .clone()
+        .ok_or_else(|| error_response(StatusCode::SERVICE_UNAVAILABLE, "history is disabled (history.enable)"))
+}
+
+fn parse_query(query: &HistoryQuery, history: &HistoryStore) -> Result<Query, String> {
+    let names: Vec<String> = query
+        .sensors
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    if names.is_empty() {
+        return Err("sensors must list at least one series".to_string());
+    }
+    let series = history.series();
+    let units = names
+        .iter()
+        .map(|name| {
+            series
+                .iter()
+                .find(|s| s.name == *name)
+                .map(|s| s.unit.clone())
+                .ok_or_else(|| format!("unknown series '{}'", name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let parse = |name: &str, value: &Option<String>| -> Result<Option<OffsetDateTime>, String> {
+        value
+            .as_deref()
+            .map(|time| datetime::parse_time(time).map_err(|e| format!("invalid {} {:?}: {}", name, time, e)))
+            .transpose()
+    };
+    let to = parse("to", &query.to)?.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = parse("from", &query.from)?.unwrap_or(to - DEFAULT_RANGE);
+    if from > to {
+        return Err("from must not be after to".to_string());
+    }
+    let step_ms = match query.step {
+        Some(step) if !step.is_finite() || step < 1.0 => return Err(format!("step must be at least 1 second, got {}", step)),
+        Some(step) => Some((step * 1000.0) as i64),
+        None => None,
+    };
+    Ok(Query {
+        names,
+        units,
+        from,
+        to,
+        step_ms,
+        aggregation: query.aggregation,
+    })
+}
+
+/// GET /cdu/history/series：所有记录中的序列及其单位
+pub async fn get_series(state: web::Data<AppState>) -> HttpResponse {
+    match history_store(&state) {
+        Ok(history) => HttpResponse::Ok().json(history.series()),
+        Err(response) => response,
+    }
+}
+
+/// GET /cdu/history?sensors=..&from=..&to=..&step=60&aggregation=avg&format=json|csv
+///
+/// 数据边读边发送，不会把整个时间范围读入内存
+pub async fn get_history(state: web::Data<AppState>, query: web::Query<HistoryQuery>) -> HttpResponse {
+    let history = match history_store(&state) {
+        Ok(history) => history,
+        Err(response) => return response,
+    };
+    let parsed = match parse_query(&query, &history) {
+        Ok(parsed) => parsed,
+        Err(message) => return error_response(StatusCode::BAD_REQUEST, message),
+    };
+    match query.format {
+        HistoryFormat::Json => {
+            // 每个序列单独读一遍，输出时不需要在内存中按序列缓存
+            let cursors = parsed
+                .names
+                .iter()
+                .map(|name| history.query(std::slice::from_ref(name), parsed.from, parsed.to))
+                .collect::<Result<Vec<_>, _>>();
+            match cursors {
+                Ok(cursors) => HttpResponse::Ok()
+                    .content_type("application/json")
+                    .body(streaming_body(move |writer| write_json(writer, &parsed, cursors))),
+                Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+            }
+        }
+        HistoryFormat::Csv => match history.query(&parsed.names, parsed.from, parsed.to) {
+            Ok(cursor) => {
+                let filename = format!(
+                    "cdu-history-{}.csv",
+                    datetime::get_current_time().replace([' ', ':', '+'], "")
+                );
+                HttpResponse::Ok()
+                    .content_type("text/csv; charset=utf-8")
+                    .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+                    .body(streaming_body(move |writer| write_csv(writer, &parsed, cursor)))
+            }
+            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+        },
+    }
+}
+
+/// {"from", "to", "step", "aggregation", "series": [{"name", "unit", "points": [[毫秒时间戳, 值], ...]}]}
+fn write_json(writer: &mut ChunkWriter, query: &Query, cursors: Vec<HistoryCursor>) -> io::Result<()> {
+    write!(
+        writer,
+        "{{\"from\":{},\"to\":{},\"step\":{},\"aggregation\":{},\"series\":[",
+        serde_json::to_string(&datetime::format_rfc3339(&query.from))?,
+        serde_json::to_string(&datetime::format_rfc3339(&query.to))?,
+        query.step_ms.map(|ms| (ms as f64 / 1000.0).to_string()).unwrap_or_else(|| "null".to_string()),
+        serde_json::to_string(&query.aggregation)?,
+    )?;
+    for (i, cursor) in cursors.into_iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        write!(
+            writer,
+            "{{\"name\":{},\"unit\":{},\"points\":[",
+            serde_json::to_string(&query.names[i])?,
+            serde_json::to_string(&query.units[i])?
+        )?;
+        let mut first = true;
+        for row in cursor.aggregate(1, query.step_ms, query.aggregation) {
+            let row = row.map_err(io::Error::other)?;
+            if let Some(value) = row.values[0] {
+                let separator = if first { "" } else { "," };
+                write!(writer, "{}[{},{}]", separator, row.time_ms, value)?;
+                first = false;
+            }
+        }
+        writer.write_all(b"]}")?;
+    }
+    writer.write_all(b"]}")
+}
+
+/// 第一列为本地时间，其余每列一个序列，表头为 "名称 (单位)"；
+/// 以 UTF-8 BOM 开头，Excel 才能正确显示 ℃ 等字符
+fn write_csv(writer: &mut ChunkWriter, query: &Query, cursor: HistoryCursor) -> io::Result<()> {
+    writer.write_all("\u{feff}time".as_bytes())?;
+    for (name, unit) in query.names.iter().zip(&query.units) {
+        let header = if unit.is_empty() {
+            name.clone()
+        } else {
+            format!("{} ({})", name, unit)
+        };
+        write!(writer, ",{}", csv_field(&header))?;
+    }
+    writer.write_all(b"\r\n")?;
+
+    for row in cursor.aggregate(query.names.len(), query.step_ms, query.aggregation) {
+        let row = row.map_err(io::Error::other)?;
+        let time = OffsetDateTime::from_unix_timestamp_nanos(row.time_ms as i128 * 1_000_000)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        writer.write_all(csv_field(&datetime::format_time(&datetime::to_local(time))).as_bytes())?;
+        for value in row.values {
+            match value {
+                Some(value) => write!(writer, ",{}", value)?,
+                None => writer.write_all(b",")?,
+            }
+        }
+        writer.write_all(b"\r\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::routes;
+    use crate::config::bundle::ConfigFiles;
+    use crate::config::loader::HistoryConfig;
+    use crate::utils::file_store::FileStore;
+    use actix_web::{test, App};
+    use serde_json::Value;
+    use tempfile::tempdir;
+
+    #[actix_web::test]
+    // CSV 中的本地时间依赖全局时区设置，整个测试期间持有锁
+    #[allow(clippy::await_holding_lock)]
+    async fn test_history_api() {
+        let _guard = datetime::SETTINGS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "{}").unwrap();
+        let history = Arc::new(
+            HistoryStore::open(&HistoryConfig {
+                dir: dir.path().join("history").to_string_lossy().to_string(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        history
+            .register(&[
+                ("Temperatures.T1.value".to_string(), "℃".to_string()),
+                ("CAP".to_string(), "kW".to_string()),
+            ])
+            .unwrap();
+        let start = datetime::parse_time("2026-02-10T00:00:00Z").unwrap();
+        for i in 0..120 {
+            let mut values = vec![("Temperatures.T1.value".to_string(), 20.0 + (i % 10) as f64)];
+            if i < 60 {
+                values.push(("CAP".to_string(), 5.0));
+            }
+            history.record(start + time::Duration::seconds(5 * i), &values).unwrap();
+        }
+        let state = AppState::new(ConfigFiles::default(), Arc::new(FileStore::new(&global_path, None).unwrap()))
+            .with_history(Arc::clone(&history));
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let req = test::TestRequest::get().uri("/cdu/history/series").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body[1], serde_json::json!({"name": "CAP", "unit": "kW"}));
+
+        let range = "from=2026-02-10T00:00:00Z&to=2026-02-10T00:09:59Z";
+        let req = test::TestRequest::get()
+            .uri(&format!("/cdu/history?sensors=Temperatures.T1.value,CAP&{}&step=300&aggregation=max", range))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["step"], 300.0);
+        assert_eq!(body["series"][0]["unit"], "℃");
+        let t0 = start.unix_timestamp() * 1000;
+        assert_eq!(body["series"][0]["points"], serde_json::json!([[t0, 29], [t0 + 300_000, 29]]));
+        assert_eq!(body["series"][1]["points"], serde_json::json!([[t0, 5]]));
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/cdu/history?sensors=Temperatures.T1.value,CAP&{}&step=300&format=csv", range))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("Content-Disposition").is_some());
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines[0], "\u{feff}time,Temperatures.T1.value (℃),CAP (kW)");
+        assert_eq!(lines[1], "2026-02-10 08:00:00 +0800,24.5,5");
+        assert_eq!(lines[2], "2026-02-10 08:05:00 +0800,24.5,");
+        assert_eq!(lines.len(), 3);
+
+        // 大范围的原始数据分块发送
+        let req = test::TestRequest::get()
+            .uri(&format!("/cdu/history?sensors=Temperatures.T1.value&{}&format=csv", range))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap().lines().count(), 121);
+
+        for uri in [
+            "/cdu/history?sensors=",
+            "/cdu/history?sensors=P9.value",
+            "/cdu/history?sensors=CAP&step=0",
+            "/cdu/history?sensors=CAP&from=2026-02-10T01:00:00Z&to=2026-02-10T00:00:00Z",
+            "/cdu/history?sensors=CAP&format=xml",
+        ] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+}
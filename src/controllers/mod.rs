@@ -0,0 +1,6 @@
+pub mod configuration;
+pub mod history;
+pub mod modbus;
+pub mod system;
+pub mod time;
+pub mod utils;
@@ -0,0 +1,705 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+
+use crate::app::state::AppState;
+use crate::config::bundle::{ConfigFiles, ALARMS_DEFAULT_PATH};
+use crate::controllers::utils::{client_addr, csv_field, error_response, require_admin};
+use crate::models::control_mode::ControlMode;
+use crate::models::modbus_server::{RegisterEntry, RegisterMap};
+use crate::models::sensor::{Quality, ReadMethod, SensorValue, WriteMethod};
+use crate::services::modbus_diagnostics::DiagnosticsSnapshot;
+use crate::services::modbus_service::{ModbusError, DEVICE_NAME};
+use crate::services::polling_service::{RegisterError, WriteError};
+use crate::utils::datetime;
+use crate::utils::file_store::FileStore;
+
+#[derive(Debug, Deserialize)]
+pub struct DiagnosticsQuery {
+    /// 只返回使用这个设备的连接
+    pub device: Option<String>,
+    /// 为 false 时不返回最近的原始帧
+    pub frames: Option<bool>,
+}
+
+/// GET /cdu/modbus/diagnostics?device=..&frames=false：每个 Modbus 连接的通信统计和最近的原始帧
+pub async fn get_diagnostics(state: web::Data<AppState>, query: web::Query<DiagnosticsQuery>) -> HttpResponse {
+    let mut snapshots: Vec<DiagnosticsSnapshot> = state.modbus.iter().map(|d| d.snapshot()).collect();
+    if let Some(device) = &query.device {
+        snapshots.retain(|s| s.devices.contains(device));
+        if snapshots.is_empty() {
+            return error_response(StatusCode::NOT_FOUND, format!("unknown Modbus device '{}'", device));
+        }
+    }
+    if query.frames == Some(false) {
+        for snapshot in &mut snapshots {
+            snapshot.frames.clear();
+        }
+    }
+    HttpResponse::Ok().json(snapshots)
+}
+
+/// 功能码，可以是数字（3）或 sensors.yaml 中的名称（"read_holding_registers"）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Function {
+    Code(u8),
+    Name(String),
+}
+
+impl Function {
+    /// 查询参数中的数字也是字符串
+    fn code(&self) -> Option<u8> {
+        match self {
+            Self::Code(code) => Some(*code),
+            Self::Name(name) => name.parse().ok(),
+        }
+    }
+
+    fn read_method(&self) -> Option<ReadMethod> {
+        match self {
+            Self::Name(name) if self.code().is_none() => ReadMethod::from_name(name),
+            _ => self.code().and_then(ReadMethod::from_code),
+        }
+    }
+
+    fn write_method(&self) -> Option<WriteMethod> {
+        match self {
+            Self::Name(name) if self.code().is_none() => WriteMethod::from_name(name),
+            _ => self.code().and_then(WriteMethod::from_code),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadRegistersQuery {
+    /// 默认为 modbus_client 配置的设备
+    pub device: Option<String>,
+    /// 默认为设备配置的 slave_id
+    pub unit_id: Option<u8>,
+    pub function: Function,
+    pub address: u16,
+    pub count: Option<u16>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WriteRegistersRequest {
+    pub device: Option<String>,
+    pub unit_id: Option<u8>,
+    pub function: Function,
+    pub address: u16,
+    /// 线圈写 0/1
+    pub values: Vec<u16>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegistersResponse {
+    pub device: String,
+    pub unit_id: Option<u8>,
+    pub address: u16,
+    /// 读取的值；写入时为功能码 23 读回的值
+    pub values: Vec<u16>,
+}
+
+/// 设备名 modbus_client 或省略为默认设备
+fn device_key(device: &Option<String>) -> Option<&str> {
+    device.as_deref().filter(|d| *d != DEVICE_NAME)
+}
+
+fn register_error_status(e: &RegisterError) -> StatusCode {
+    match e {
+        RegisterError::UnknownDevice(_) => StatusCode::NOT_FOUND,
+        RegisterError::Modbus(ModbusError::Timeout(_)) => StatusCode::GATEWAY_TIMEOUT,
+        RegisterError::Modbus(_) => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// 地址范围不能超出 65535
+fn check_range(address: u16, count: usize, max: u16) -> Result<(), String> {
+    if count == 0 || count > max as usize {
+        return Err(format!("count must be between 1 and {}", max));
+    }
+    if address as usize + count > 0x10000 {
+        return Err(format!("address range {}+{} exceeds 65535", address, count));
+    }
+    Ok(())
+}
+
+/// GET /cdu/modbus/registers?device=..&unit_id=..&function=3&address=..&count=..：
+/// 通过轮询的连接读取任意寄存器（需要管理员令牌）
+pub async fn read_registers(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ReadRegistersQuery>,
+) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let Some(method) = query.function.read_method() else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("{:?} is not a read function, expected 1-4 or read_* names", query.function),
+        );
+    };
+    let count = query.count.unwrap_or(1);
+    if let Err(message) = check_range(query.address, count as usize, method.max_count()) {
+        return error_response(StatusCode::BAD_REQUEST, message);
+    }
+    let Some(polling) = &state.polling else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "no Modbus connection configured");
+    };
+    let device = device_key(&query.device);
+    let detail = json!({
+        "device": device.unwrap_or(DEVICE_NAME),
+        "unit_id": query.unit_id,
+        "function": format!("{:?}", method),
+        "address": query.address,
+        "count": count,
+    });
+    let result = polling.read_registers(device, query.unit_id, method, query.address, count).await;
+    state.audit.record(&client_addr(&req), "modbus.read", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(values) => HttpResponse::Ok().json(RegistersResponse {
+            device: device.unwrap_or(DEVICE_NAME).to_string(),
+            unit_id: query.unit_id,
+            address: query.address,
+            values,
+        }),
+        Err(e) => error_response(register_error_status(&e), e),
+    }
+}
+
+/// POST /cdu/modbus/registers：通过轮询的连接写入任意寄存器或线圈（需要管理员令牌），
+/// 控制模式为 Auto 时拒绝，避免与控制逻辑同时操作执行器
+pub async fn write_registers(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    request: web::Json<WriteRegistersRequest>,
+) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let Some(method) = request.function.write_method() else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("{:?} is not a write function, expected 5, 6, 15, 16, 23 or write_* names", request.function),
+        );
+    };
+    if let Err(message) = check_range(request.address, request.values.len(), method.max_count()) {
+        return error_response(StatusCode::BAD_REQUEST, message);
+    }
+    if method.is_coil() && request.values.iter().any(|v| *v > 1) {
+        return error_response(StatusCode::BAD_REQUEST, "coil values must be 0 or 1");
+    }
+    let Some(polling) = &state.polling else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "no Modbus connection configured");
+    };
+    let actor = client_addr(&req);
+    let device = device_key(&request.device);
+    let detail = json!({
+        "device": device.unwrap_or(DEVICE_NAME),
+        "unit_id": request.unit_id,
+        "function": format!("{:?}", method),
+        "address": request.address,
+        "values": request.values,
+    });
+    if state.control_mode.get() == ControlMode::Auto {
+        let message = "register writes are blocked while the control mode is Auto";
+        state.audit.record(&actor, "modbus.write", detail, Err(message));
+        return error_response(StatusCode::CONFLICT, message);
+    }
+    let result = polling
+        .write_registers(device, request.unit_id, method, request.address, &request.values)
+        .await;
+    state.audit.record(&actor, "modbus.write", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(values) => HttpResponse::Ok().json(RegistersResponse {
+            device: device.unwrap_or(DEVICE_NAME).to_string(),
+            unit_id: request.unit_id,
+            address: request.address,
+            values,
+        }),
+        Err(e) => error_response(register_error_status(&e), e),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttributeResponse {
+    pub id: String,
+    /// 告警规则看到的值，同名分组优先；还没有读到时为 null
+    pub value: Option<SensorValue>,
+    pub quality: Option<Quality>,
+    pub unit: String,
+    /// 字符串属性（型号、序列号等）的内容
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WriteAttributeRequest {
+    /// 工程值，线圈和位属性可以写 true/false
+    pub value: SensorValue,
+}
+
+fn write_error_status(e: &WriteError) -> StatusCode {
+    match e {
+        WriteError::UnknownSensor(_) => StatusCode::NOT_FOUND,
+        WriteError::NotWritable(_) | WriteError::TooWide { .. } | WriteError::Formula { .. } => StatusCode::BAD_REQUEST,
+        WriteError::Modbus(ModbusError::Timeout(_)) => StatusCode::GATEWAY_TIMEOUT,
+        WriteError::Modbus(_) => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// GET /cdu/modbus/attributes/{id}：属性（"Plugin.Sensor.attr"）的最新值、质量和单位
+pub async fn read_attribute(state: web::Data<AppState>, id: web::Path<String>) -> HttpResponse {
+    let Some(polling) = &state.polling else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "no Modbus connection configured");
+    };
+    let Some(attr) = polling.attr(&id) else {
+        return error_response(StatusCode::NOT_FOUND, WriteError::UnknownSensor(id.into_inner()));
+    };
+    let reading = polling.value(Some(&attr.plugin), &attr.sensor, &attr.attr);
+    HttpResponse::Ok().json(AttributeResponse {
+        id: attr.id(),
+        value: reading.map(|r| attr.publish_value(r.value)),
+        quality: reading.map(|r| r.quality),
+        unit: attr.unit.clone(),
+        text: polling.text(&attr.plugin, &attr.sensor, &attr.attr),
+    })
+}
+
+/// PUT /cdu/modbus/attributes/{id}：按属性的 write_method 和 write_formula 写入工程值（需要管理员令牌），
+/// 与寄存器写入一样在 Auto 模式下拒绝
+pub async fn write_attribute(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    request: web::Json<WriteAttributeRequest>,
+) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let Some(polling) = &state.polling else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "no Modbus connection configured");
+    };
+    let actor = client_addr(&req);
+    let detail = json!({"sensor": id.as_str(), "value": request.value});
+    if state.control_mode.get() == ControlMode::Auto {
+        let message = "attribute writes are blocked while the control mode is Auto";
+        state.audit.record(&actor, "modbus.write_attribute", detail, Err(message));
+        return error_response(StatusCode::CONFLICT, message);
+    }
+    let result = polling.write(&id, request.value.as_f64()).await;
+    state.audit.record(&actor, "modbus.write_attribute", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(write_error_status(&e), e),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegisterMapFormat {
+    #[default]
+    Json,
+    /// 表头为英文，以 UTF-8 BOM 开头以便 Excel 打开
+    Csv,
+    /// 可直接打印的页面
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterMapQuery {
+    #[serde(default)]
+    pub format: RegisterMapFormat,
+}
+
+/// 按配置文件当前的内容生成，修改配置后不需要重启
+fn load_register_map(files: &ConfigFiles) -> Result<RegisterMap, String> {
+    let sensors = FileStore::new(files.sensors.as_path(), None).map_err(|e| e.to_string())?;
+    let alarms = FileStore::new(files.alarms.as_path(), Some(Path::new(ALARMS_DEFAULT_PATH))).map_err(|e| e.to_string())?;
+    RegisterMap::from_config(&sensors.get_config(), &alarms.get_config()).map_err(|e| e.to_string())
+}
+
+/// GET /cdu/modbus/register-map?format=json|csv|html：由传感器和告警配置生成的 Modbus 服务端寄存器表
+pub async fn get_register_map(state: web::Data<AppState>, query: web::Query<RegisterMapQuery>) -> HttpResponse {
+    let map = match load_register_map(&state.config_files) {
+        Ok(map) => map,
+        Err(message) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, message),
+    };
+    match query.format {
+        RegisterMapFormat::Json => HttpResponse::Ok().json(map),
+        RegisterMapFormat::Csv => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(("Content-Disposition", "attachment; filename=\"cdu-modbus-register-map.csv\""))
+            .body(register_map_csv(&map)),
+        RegisterMapFormat::Html => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(register_map_html(&map)),
+    }
+}
+
+const REGISTER_MAP_COLUMNS: [&str; 10] = [
+    "Table", "Address", "Count", "Function codes", "Data type", "Scale", "Unit", "Access", "Source", "Description",
+];
+
+fn register_map_row(entry: &RegisterEntry) -> [String; 10] {
+    let codes: Vec<String> = entry.function_codes.iter().map(|code| format!("{:02}", code)).collect();
+    [
+        entry.table.to_string(),
+        entry.address.to_string(),
+        entry.count.to_string(),
+        codes.join("/"),
+        entry.data_type.clone(),
+        entry.scale.map(|scale| scale.to_string()).unwrap_or_default(),
+        entry.unit.clone(),
+        format!("{:?}", entry.access),
+        entry.source.clone(),
+        entry.description.clone(),
+    ]
+}
+
+fn register_map_csv(map: &RegisterMap) -> String {
+    let mut csv = format!("\u{feff}{}\r\n", REGISTER_MAP_COLUMNS.join(","));
+    for entry in &map.entries {
+        let row: Vec<String> = register_map_row(entry).iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn register_map_html(map: &RegisterMap) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>CDU Modbus register map</title>\n<style>\n\
+         body { font-family: sans-serif; font-size: 12px; }\n\
+         table { border-collapse: collapse; width: 100%; }\n\
+         th, td { border: 1px solid #999; padding: 2px 6px; text-align: left; }\n\
+         thead { display: table-header-group; }\n\
+         tr { page-break-inside: avoid; }\n\
+         .conflicts { color: #b00; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    html.push_str(&format!(
+        "<h1>CDU Modbus register map</h1>\n<p>Generated {}</p>\n",
+        html_escape(&datetime::get_current_time())
+    ));
+    if !map.conflicts.is_empty() || !map.invalid.is_empty() {
+        html.push_str("<ul class=\"conflicts\">\n");
+        for problem in map.conflicts.iter().map(ToString::to_string).chain(map.invalid.iter().cloned()) {
+            html.push_str(&format!("<li>{}</li>\n", html_escape(&problem)));
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("<table>\n<thead><tr>");
+    for column in REGISTER_MAP_COLUMNS {
+        html.push_str(&format!("<th>{}</th>", column));
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for entry in &map.entries {
+        html.push_str("<tr>");
+        for field in register_map_row(entry) {
+            html.push_str(&format!("<td>{}</td>", html_escape(&field)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::routes;
+    use crate::config::bundle::ConfigFiles;
+    use crate::models::control_mode::ControlModeState;
+    use crate::models::event_bus::EventBus;
+    use crate::models::sensor::SensorCatalog;
+    use crate::services::audit::AuditLog;
+    use crate::services::modbus_diagnostics::{Direction, ModbusDiagnostics};
+    use crate::services::polling_service::tests::FakeRegisters;
+    use crate::services::polling_service::PollingService;
+    use crate::utils::file_store::FileStore;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[actix_web::test]
+    async fn test_diagnostics_api() {
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "{}").unwrap();
+        let default = Arc::new(ModbusDiagnostics::new("modbus_client", "192.168.1.150:502"));
+        default.connected();
+        default.request();
+        default.frame(Direction::Tx, &[0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x0D, 0x00, 0x00, 0x01], false);
+        default.response(Duration::from_millis(12), Some(tokio_modbus::ExceptionCode::IllegalDataAddress));
+        let meter = Arc::new(ModbusDiagnostics::new("meter", "/dev/ttyS1@9600 8N1"));
+        meter.request();
+        meter.timeout();
+        let state = AppState::new(ConfigFiles::default(), Arc::new(FileStore::new(&global_path, None).unwrap()))
+            .with_modbus_diagnostics(vec![default, meter]);
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let req = test::TestRequest::get().uri("/cdu/modbus/diagnostics").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(body[0]["exceptions"], json!([{"code": 2, "name": "IllegalDataAddress", "count": 1}]));
+        assert_eq!(body[0]["latency"]["buckets"][2], json!({"le": 25.0, "count": 1}));
+        assert_eq!(body[0]["frames"][0]["direction"], "tx");
+        assert_eq!(body[0]["frames"][0]["data"], "00 01 00 00 00 06 01 03 0D 00 00 01");
+        assert_eq!(body[1]["timeouts"], 1);
+
+        let req = test::TestRequest::get().uri("/cdu/modbus/diagnostics?device=modbus_client&frames=false").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["frames"], json!([]));
+
+        let req = test::TestRequest::get().uri("/cdu/modbus/diagnostics?device=pump").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_register_browser() {
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "{}").unwrap();
+        let registers = FakeRegisters::default();
+        registers.set(ReadMethod::HoldingRegisters, 3328, 250);
+        registers.set(ReadMethod::HoldingRegisters, 3329, 251);
+        registers.set(ReadMethod::Coils, 6, 1);
+        let catalog = SensorCatalog::from_config(&json!({"sensor_plugins": []})).unwrap();
+        let polling = PollingService::new(catalog, Box::new(registers.clone()), EventBus::new());
+        let audit = Arc::new(AuditLog::new(dir.path().join("audit.log")));
+        let control_mode = Arc::new(ControlModeState::new(ControlMode::Auto));
+        let state = AppState::new(ConfigFiles::default(), Arc::new(FileStore::new(&global_path, None).unwrap()))
+            .with_audit(Arc::clone(&audit))
+            .with_admin_token("secret")
+            .with_polling(Arc::new(polling))
+            .with_control_mode(Arc::clone(&control_mode));
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let read = |uri: &str, token: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+        let resp = test::call_service(&app, read("/cdu/modbus/registers?function=3&address=3328", "wrong")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = test::call_and_read_body_json(
+            &app,
+            read("/cdu/modbus/registers?function=read_holding_registers&address=3328&count=2&unit_id=2", "secret"),
+        )
+        .await;
+        assert_eq!(body, json!({"device": "modbus_client", "unit_id": 2, "address": 3328, "values": [250, 251]}));
+        let body: Value = test::call_and_read_body_json(&app, read("/cdu/modbus/registers?function=1&address=6", "secret")).await;
+        assert_eq!(body["values"], json!([1]));
+        for (uri, status) in [
+            ("/cdu/modbus/registers?function=6&address=6", StatusCode::BAD_REQUEST),
+            ("/cdu/modbus/registers?function=3&address=65535&count=2", StatusCode::BAD_REQUEST),
+            ("/cdu/modbus/registers?function=3&address=0&count=126", StatusCode::BAD_REQUEST),
+            ("/cdu/modbus/registers?function=3&address=9", StatusCode::BAD_GATEWAY),
+            ("/cdu/modbus/registers?function=3&address=0&device=pump", StatusCode::NOT_FOUND),
+        ] {
+            assert_eq!(test::call_service(&app, read(uri, "secret")).await.status(), status, "{}", uri);
+        }
+
+        let write = |body: Value| {
+            test::TestRequest::post()
+                .uri("/cdu/modbus/registers")
+                .insert_header(("Authorization", "Bearer secret"))
+                .set_json(body)
+                .to_request()
+        };
+        // Auto 模式下拒绝写入
+        let resp = test::call_service(&app, write(json!({"function": 6, "address": 3328, "values": [300]}))).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        assert!(registers.writes.lock().unwrap().is_empty());
+
+        control_mode.set(ControlMode::Manual);
+        let resp = test::call_service(&app, write(json!({"function": 6, "address": 3328, "values": [300, 301]}))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, write(json!({"function": "write_multiple_coils", "address": 6, "values": [0, 2]}))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::call_and_read_body_json(
+            &app,
+            write(json!({"function": "read_write_multiple_registers", "address": 3328, "values": [300, 301]})),
+        )
+        .await;
+        assert_eq!(body["values"], json!([300, 301]));
+        let resp = test::call_service(&app, write(json!({"function": 5, "address": 6, "values": [0]}))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            *registers.writes.lock().unwrap(),
+            vec![
+                (WriteMethod::ReadWriteMultipleRegisters, 3328, vec![300, 301]),
+                (WriteMethod::SingleCoil, 6, vec![0]),
+            ]
+        );
+
+        // 参数错误不记录，读写和被拒绝的写入都记录
+        let entries = audit.recent(100).unwrap();
+        let actions: Vec<(&str, bool)> = entries.iter().map(|e| (e.action.as_str(), e.success)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("modbus.read", true),
+                ("modbus.read", true),
+                ("modbus.read", false),
+                ("modbus.read", false),
+                ("modbus.write", false),
+                ("modbus.write", true),
+                ("modbus.write", true),
+            ]
+        );
+        assert_eq!(entries[4].detail["values"], json!([300]));
+    }
+
+    #[actix_web::test]
+    async fn test_attribute_api() {
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "{}").unwrap();
+        let catalog = SensorCatalog::from_config(&json!({
+            "sensor_plugins": [
+                {
+                    "name": "Pumps",
+                    "defaults": {
+                        "DutyCycle": {
+                            "read_method": "read_holding_registers",
+                            "write_method": "write_single_register",
+                            "formula": "$DutyCycle / 60",
+                            "write_formula": "$value * 60",
+                            "unit": "%"
+                        },
+                        "Speed": {"read_method": "read_holding_registers", "unit": "rpm"}
+                    },
+                    "sensors": [{"name": "Pump1", "attrs": {"DutyCycle": 2081, "Speed": 2082}}]
+                },
+                {
+                    "name": "Leakages",
+                    "defaults": {"value": {"read_method": "read_coils", "write_method": "write_single_coil"}},
+                    "sensors": [{"name": "LE1", "attrs": {"value": 10}}]
+                }
+            ]
+        }))
+        .unwrap();
+        let registers = FakeRegisters::default();
+        registers.set(ReadMethod::HoldingRegisters, 2081, 3000);
+        registers.set(ReadMethod::HoldingRegisters, 2082, 1450);
+        registers.set(ReadMethod::Coils, 10, 1);
+        let polling = Arc::new(PollingService::new(catalog, Box::new(registers.clone()), EventBus::new()));
+        polling.poll_once().await;
+        let audit = Arc::new(AuditLog::new(dir.path().join("audit.log")));
+        let control_mode = Arc::new(ControlModeState::new(ControlMode::Auto));
+        let state = AppState::new(ConfigFiles::default(), Arc::new(FileStore::new(&global_path, None).unwrap()))
+            .with_audit(Arc::clone(&audit))
+            .with_admin_token("secret")
+            .with_polling(Arc::clone(&polling))
+            .with_control_mode(Arc::clone(&control_mode));
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let read = |id: &str| test::TestRequest::get().uri(&format!("/cdu/modbus/attributes/{}", id)).to_request();
+        let body: Value = test::call_and_read_body_json(&app, read("Pumps.Pump1.DutyCycle")).await;
+        assert_eq!(
+            body,
+            json!({"id": "Pumps.Pump1.DutyCycle", "value": 50.0, "quality": "Good", "unit": "%", "text": null})
+        );
+        let body: Value = test::call_and_read_body_json(&app, read("Leakages.LE1.value")).await;
+        assert_eq!(body["value"], json!(true));
+        let resp = test::call_service(&app, read("Pumps.Pump9.DutyCycle")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let write = |id: &str, body: Value| {
+            test::TestRequest::put()
+                .uri(&format!("/cdu/modbus/attributes/{}", id))
+                .insert_header(("Authorization", "Bearer secret"))
+                .set_json(body)
+                .to_request()
+        };
+        // Auto 模式下拒绝写入
+        let resp = test::call_service(&app, write("Pumps.Pump1.DutyCycle", json!({"value": 60}))).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        assert!(registers.writes.lock().unwrap().is_empty());
+
+        control_mode.set(ControlMode::Manual);
+        let resp = test::call_service(&app, write("Pumps.Pump1.DutyCycle", json!({"value": 60}))).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = test::call_service(&app, write("Leakages.LE1.value", json!({"value": false}))).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        for (id, status) in [
+            ("Pumps.Pump1.Speed", StatusCode::BAD_REQUEST),
+            ("Pumps.Pump9.DutyCycle", StatusCode::NOT_FOUND),
+        ] {
+            let resp = test::call_service(&app, write(id, json!({"value": 1}))).await;
+            assert_eq!(resp.status(), status, "{}", id);
+        }
+        assert_eq!(
+            *registers.writes.lock().unwrap(),
+            vec![(WriteMethod::SingleRegister, 2081, vec![3600]), (WriteMethod::SingleCoil, 10, vec![0])]
+        );
+
+        let entries = audit.recent(100).unwrap();
+        let actions: Vec<(&str, bool)> = entries.iter().map(|e| (e.action.as_str(), e.success)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("modbus.write_attribute", false),
+                ("modbus.write_attribute", true),
+                ("modbus.write_attribute", true),
+                ("modbus.write_attribute", false),
+                ("modbus.write_attribute", false),
+            ]
+        );
+        assert_eq!(entries[1].detail, json!({"sensor": "Pumps.Pump1.DutyCycle", "value": 60.0}));
+    }
+
+    #[actix_web::test]
+    async fn test_register_map_formats() {
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "{}").unwrap();
+        let files = ConfigFiles {
+            sensors: dir.path().join("sensors.yaml"),
+            alarms: dir.path().join("alarms.yaml"),
+            ..ConfigFiles::default()
+        };
+        std::fs::write(
+            &files.sensors,
+            "sensor_plugins:\n  - name: Flows\n    defaults:\n      value:\n        read_method: read_holding_registers\n        unit: L/min\n        decimal_places: 1\n    sensors:\n      - name: F1\n        attrs:\n          value:\n            address: 3408\n",
+        )
+        .unwrap();
+        std::fs::write(&files.alarms, "single:\n  F1:\n    - address: 3408\n      comparator: '>'\n      value: 20\n").unwrap();
+        let state = AppState::new(files, Arc::new(FileStore::new(&global_path, None).unwrap()));
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let req = test::TestRequest::get().uri("/cdu/modbus/register-map").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["entries"][0]["source"], "Flows.F1.value");
+        assert_eq!(body["entries"][0]["function_codes"], json!([3]));
+        assert_eq!(body["entries"][0]["access"], "R");
+        assert_eq!(body["conflicts"][0]["second"], "single.F1[0]");
+
+        let req = test::TestRequest::get().uri("/cdu/modbus/register-map?format=csv").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get("content-type").unwrap(), "text/csv; charset=utf-8");
+        let csv = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "\u{feff}Table,Address,Count,Function codes,Data type,Scale,Unit,Access,Source,Description");
+        assert_eq!(lines[1], "holding register,3408,1,03,u16,0.1,L/min,R,Flows.F1.value,");
+        assert_eq!(lines[2], "holding register,3408,1,03,u16,,,R,single.F1[0],F1 > 20");
+
+        let req = test::TestRequest::get().uri("/cdu/modbus/register-map?format=html").to_request();
+        let html = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(html.contains("<td>F1 &gt; 20</td>"), "{}", html);
+        assert!(html.contains("<li>holding register 3408 of single.F1[0] overlaps Flows.F1.value</li>"));
+    }
+}
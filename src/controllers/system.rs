@@ -0,0 +1,370 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::app::state::AppState;
+use crate::config::loader::NtpConfig;
+use crate::controllers::utils::{client_addr, error_response, require_admin};
+use crate::services::clock_service::{ClockError, SyncStatus};
+use crate::utils::async_scheduler::{JobHandle, JobId, Schedule};
+use crate::utils::datetime::{self, ZoneInfo};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClockResponse {
+    /// RFC 3339 格式的 UTC 时间
+    pub utc: String,
+    /// 按配置的时区和格式输出的本地时间
+    pub local: String,
+    pub zone: ZoneInfo,
+    pub ntp: NtpConfig,
+    pub sync: SyncStatus,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetClockRequest {
+    /// "2026-02-10 09:38:48 +0800" 或 RFC 3339
+    pub time: String,
+}
+
+fn clock_response(state: &AppState) -> ClockResponse {
+    let now = state.clock.now();
+    ClockResponse {
+        utc: datetime::format_rfc3339(&now.to_offset(time::UtcOffset::UTC)),
+        local: datetime::format_time(&datetime::to_local(now)),
+        zone: datetime::time_zone().info_at(now),
+        ntp: state.clock.ntp().clone(),
+        sync: state.clock.status(),
+    }
+}
+
+fn clock_error_status(error: &ClockError) -> StatusCode {
+    match error {
+        ClockError::OutOfRange(_) | ClockError::Disabled => StatusCode::BAD_REQUEST,
+        ClockError::Timeout(_) | ClockError::InvalidResponse(_) => StatusCode::BAD_GATEWAY,
+        ClockError::PermissionDenied | ClockError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// GET /cdu/system/clock：系统时间、时区和 NTP 同步状态
+pub async fn get_clock(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(clock_response(&state))
+}
+
+/// PUT /cdu/system/clock：手动设置系统时间（需要管理员令牌）
+pub async fn set_clock(req: HttpRequest, state: web::Data<AppState>, request: web::Json<SetClockRequest>) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let actor = client_addr(&req);
+    let detail = json!({"time": request.time, "previous": datetime::format_rfc3339(&state.clock.now())});
+
+    let time = match datetime::parse_time(&request.time) {
+        Ok(time) => time,
+        Err(e) => {
+            let message = format!("invalid time {:?}: {}", request.time, e);
+            state.audit.record(&actor, "clock.set", detail, Err(&message));
+            return error_response(StatusCode::BAD_REQUEST, message);
+        }
+    };
+    let result = state.clock.set_time(time);
+    state.audit.record(&actor, "clock.set", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(()) => HttpResponse::Ok().json(clock_response(&state)),
+        Err(e) => error_response(clock_error_status(&e), e),
+    }
+}
+
+/// POST /cdu/system/clock/sync：立即从 NTP 服务器同步（需要管理员令牌）
+pub async fn sync_clock(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let ntp = state.clock.ntp();
+    let detail = json!({"server": format!("{}:{}", ntp.host, ntp.port)});
+    let result = state.clock.sync().await;
+    state.audit.record(&client_addr(&req), "clock.sync", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(_) => HttpResponse::Ok().json(clock_response(&state)),
+        Err(e) => error_response(clock_error_status(&e), e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,
+}
+
+/// GET /cdu/system/audit?limit=100：最近的审计记录（需要管理员令牌）
+pub async fn get_audit_log(req: HttpRequest, state: web::Data<AppState>, query: web::Query<AuditQuery>) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    match state.audit.recent(query.limit.unwrap_or(100)) {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// 定时任务的调度方式和运行统计
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub name: String,
+    pub schedule: Schedule,
+    pub cancelled: bool,
+    pub runs: u64,
+    /// 执行时间超过周期的次数
+    pub overruns: u64,
+    /// 因超时而跳过的周期数
+    pub skipped: u64,
+    /// 最近一次执行的耗时（毫秒）
+    pub last_duration_ms: Option<f64>,
+}
+
+impl From<&JobHandle> for JobInfo {
+    fn from(job: &JobHandle) -> Self {
+        let stats = job.stats();
+        Self {
+            id: job.id(),
+            name: job.name().to_string(),
+            schedule: job.schedule(),
+            cancelled: job.is_cancelled(),
+            runs: stats.runs,
+            overruns: stats.overruns,
+            skipped: stats.skipped,
+            last_duration_ms: stats.last_duration.map(|d| d.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+/// 只改周期（秒）时调度方式不变，或者换成新的调度方式
+#[derive(Debug, Deserialize)]
+pub struct SetJobRequest {
+    pub period: Option<f64>,
+    pub schedule: Option<Schedule>,
+}
+
+/// GET /cdu/system/jobs：定时任务及其运行统计
+pub async fn get_jobs(state: web::Data<AppState>) -> HttpResponse {
+    let jobs: Vec<JobInfo> = state.scheduler.jobs().iter().map(JobInfo::from).collect();
+    HttpResponse::Ok().json(jobs)
+}
+
+/// PUT /cdu/system/jobs/{id}：运行中修改任务的周期或调度方式（需要管理员令牌）
+pub async fn set_job(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    id: web::Path<JobId>,
+    request: web::Json<SetJobRequest>,
+) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let id = id.into_inner();
+    let Some(job) = state.scheduler.job(id) else {
+        return error_response(StatusCode::NOT_FOUND, format!("job {} not found", id));
+    };
+    let detail = json!({"job": job.name(), "period": request.period, "schedule": request.schedule, "previous": job.schedule()});
+    let result = match (request.period, &request.schedule) {
+        (Some(period), None) => std::time::Duration::try_from_secs_f64(period)
+            .map_err(|e| format!("invalid period {}: {}", period, e))
+            .and_then(|period| job.set_period(period).map_err(|e| e.to_string())),
+        (None, Some(schedule)) => job.set_schedule(schedule.clone()).map_err(|e| e.to_string()),
+        _ => Err("set either period or schedule".to_string()),
+    };
+    state.audit.record(&client_addr(&req), "job.schedule", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(()) => HttpResponse::Ok().json(JobInfo::from(&job)),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/// DELETE /cdu/system/jobs/{id}：取消任务，正在执行的那一次会执行完（需要管理员令牌）
+pub async fn cancel_job(req: HttpRequest, state: web::Data<AppState>, id: web::Path<JobId>) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let id = id.into_inner();
+    if !state.scheduler.cancel(id) {
+        return error_response(StatusCode::NOT_FOUND, format!("job {} not found", id));
+    }
+    state.audit.record::<String>(&client_addr(&req), "job.cancel", json!({"job": id}), Ok(()));
+    HttpResponse::NoContent().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::routes;
+    use crate::config::bundle::ConfigFiles;
+    use crate::services::audit::AuditLog;
+    use crate::services::clock_service::tests::{ntp_config, sntp_stub, FakeClock};
+    use crate::services::clock_service::{ClockService, SystemClock};
+    use crate::utils::file_store::FileStore;
+    use actix_web::{test, App};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[actix_web::test]
+    async fn test_clock_api() {
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "http:\n  port: 5000\n").unwrap();
+        let start = datetime::parse_time("2026-02-10 09:38:48 +0800").unwrap();
+        let clock = Arc::new(FakeClock::new(start));
+        let addr = sntp_stub(-2.0, 1).await;
+        let audit = Arc::new(AuditLog::new(dir.path().join("audit.log")));
+        let state = AppState::new(ConfigFiles::default(), Arc::new(FileStore::new(&global_path, None).unwrap()))
+            .with_clock(Arc::new(ClockService::new(clock.clone(), ntp_config(&addr))))
+            .with_audit(Arc::clone(&audit))
+            .with_admin_token("secret");
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let req = test::TestRequest::get().uri("/cdu/system/clock").to_request();
+        let body: ClockResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body.utc, "2026-02-10T01:38:48Z");
+        assert!(body.sync.last_success.is_none());
+
+        let set = |token: Option<&str>, time: &str| {
+            let mut req = test::TestRequest::put()
+                .uri("/cdu/system/clock")
+                .set_json(json!({"time": time}));
+            if let Some(token) = token {
+                req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+            }
+            req.to_request()
+        };
+        let resp = test::call_service(&app, set(None, "2026-03-01T00:00:00Z")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = test::call_service(&app, set(Some("wrong"), "2026-03-01T00:00:00Z")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = test::call_service(&app, set(Some("secret"), "not a time")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, set(Some("secret"), "1990-01-01T00:00:00Z")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(clock.now(), start);
+
+        let resp = test::call_service(&app, set(Some("secret"), "2026-03-01T00:00:00Z")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(datetime::format_rfc3339(&clock.now()), "2026-03-01T00:00:00Z");
+
+        let req = test::TestRequest::post()
+            .uri("/cdu/system/clock/sync")
+            .insert_header(("Authorization", "Bearer secret"))
+            .to_request();
+        let body: ClockResponse = test::call_and_read_body_json(&app, req).await;
+        assert!((body.sync.offset_ms.unwrap() + 2000.0).abs() < 1.0);
+        assert_eq!(body.sync.stratum, Some(1));
+
+        // 失败的尝试也要记录，未通过鉴权的请求不记录
+        let req = test::TestRequest::get()
+            .uri("/cdu/system/audit?limit=10")
+            .insert_header(("Authorization", "Bearer secret"))
+            .to_request();
+        let entries: Vec<crate::services::audit::AuditEntry> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(entries, audit.recent(10).unwrap());
+        let actions: Vec<(&str, bool)> = entries.iter().map(|e| (e.action.as_str(), e.success)).collect();
+        assert_eq!(
+            actions,
+            vec![("clock.set", false), ("clock.set", false), ("clock.set", true), ("clock.sync", true)]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_jobs_api() {
+        use crate::utils::async_scheduler::Scheduler;
+        use std::time::Duration;
+
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "{}").unwrap();
+        let scheduler = Arc::new(Scheduler::new());
+        let flush = scheduler
+            .schedule("history-flush", Schedule::FixedRate(Duration::from_secs(10)), || async {})
+            .unwrap();
+        let audit = Arc::new(AuditLog::new(dir.path().join("audit.log")));
+        let state = AppState::new(ConfigFiles::default(), Arc::new(FileStore::new(&global_path, None).unwrap()))
+            .with_audit(Arc::clone(&audit))
+            .with_admin_token("secret")
+            .with_scheduler(Arc::clone(&scheduler));
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let req = test::TestRequest::get().uri("/cdu/system/jobs").to_request();
+        let jobs: Vec<JobInfo> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].name, "history-flush");
+        assert_eq!(jobs[0].schedule, Schedule::FixedRate(Duration::from_secs(10)));
+
+        let put = |id: JobId, body: serde_json::Value| {
+            test::TestRequest::put()
+                .uri(&format!("/cdu/system/jobs/{}", id))
+                .insert_header(("Authorization", "Bearer secret"))
+                .set_json(body)
+                .to_request()
+        };
+        let resp = test::call_service(&app, put(flush.id(), json!({"period": 30}))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(flush.schedule(), Schedule::FixedRate(Duration::from_secs(30)));
+
+        let resp = test::call_service(&app, put(flush.id(), json!({"schedule": {"daily": ["02:00"]}}))).await;
+        let job: JobInfo = test::read_body_json(resp).await;
+        assert_eq!(job.schedule, Schedule::Daily(vec![time::Time::from_hms(2, 0, 0).unwrap()]));
+
+        let resp = test::call_service(&app, put(flush.id(), json!({"schedule": {"fixed_delay": 0}}))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, put(flush.id(), json!({}))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, put(99, json!({"period": 1}))).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let delete = |token: &str| {
+            test::TestRequest::delete()
+                .uri(&format!("/cdu/system/jobs/{}", flush.id()))
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+        let resp = test::call_service(&app, delete("wrong")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(!flush.is_cancelled());
+        let resp = test::call_service(&app, delete("secret")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(flush.is_cancelled());
+
+        let actions: Vec<(String, bool)> = audit
+            .recent(10)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.action, e.success))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("job.schedule".to_string(), true),
+                ("job.schedule".to_string(), true),
+                ("job.schedule".to_string(), false),
+                ("job.schedule".to_string(), false),
+                ("job.cancel".to_string(), true),
+            ]
+        );
+        assert_eq!(scheduler.shutdown(Duration::from_secs(1)).await, 0);
+    }
+
+    #[actix_web::test]
+    async fn test_privileged_api_disabled_without_token() {
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "{}").unwrap();
+        let state = AppState::new(ConfigFiles::default(), Arc::new(FileStore::new(&global_path, None).unwrap()))
+            .with_audit(Arc::new(AuditLog::new(dir.path().join("audit.log"))))
+            .with_admin_token("");
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/cdu/system/clock/sync")
+            .insert_header(("Authorization", "Bearer "))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,156 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use time::OffsetDateTime;
+
+use crate::app::state::AppState;
+use crate::config::loader::TimeConfig;
+use crate::controllers::utils::{client_addr, error_response, require_admin};
+use crate::utils::datetime::{self, TimestampFormat, Zone, ZoneInfo};
+use crate::utils::file_store::FileStoreError;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeZoneResponse {
+    #[serde(flatten)]
+    pub info: ZoneInfo,
+    pub format: TimestampFormat,
+    pub now: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimeZoneRequest {
+    pub zone: Option<String>,
+    pub format: Option<TimestampFormat>,
+}
+
+fn current() -> TimeZoneResponse {
+    TimeZoneResponse {
+        info: datetime::time_zone().info_at(OffsetDateTime::now_utc()),
+        format: datetime::timestamp_format(),
+        now: datetime::get_current_time(),
+    }
+}
+
+/// GET /cdu/time/zone：当前时区、偏移量和是否处于夏令时
+pub async fn get_time_zone() -> HttpResponse {
+    HttpResponse::Ok().json(current())
+}
+
+/// PUT /cdu/time/zone：修改时区或时间戳格式，校验后写入全局配置文件并立即生效（需要管理员令牌）
+pub async fn set_time_zone(req: HttpRequest, state: web::Data<AppState>, request: web::Json<TimeZoneRequest>) -> HttpResponse {
+    if let Err(response) = require_admin(&req, &state) {
+        return response;
+    }
+    let actor = client_addr(&req);
+    let mut time = TimeConfig {
+        zone: datetime::time_zone().name().to_string(),
+        format: datetime::timestamp_format(),
+    };
+    let detail = json!({"zone": request.zone, "format": request.format, "previous": time});
+    if let Some(ref zone) = request.zone {
+        if let Err(e) = Zone::load(zone) {
+            state.audit.record(&actor, "time_zone.set", detail, Err(&e));
+            return error_response(StatusCode::BAD_REQUEST, e);
+        }
+        time.zone = zone.clone();
+    }
+    if let Some(format) = request.format {
+        time.format = format;
+    }
+
+    let mut config = state.global.get_config();
+    let value = match serde_json::to_value(&time) {
+        Ok(value) => value,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    match config.as_object_mut() {
+        Some(map) => {
+            map.insert("time".to_string(), value);
+        }
+        None => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "global config root is not a mapping"),
+    }
+    // set_config 先校验再保存，保存后 time 订阅者会让新时区生效
+    let result = state.global.set_config(config).map(|_| ());
+    state.audit.record(&actor, "time_zone.set", detail, result.as_ref().map(|_| ()));
+    match result {
+        Ok(()) => {}
+        Err(e @ FileStoreError::Validation(_)) => return error_response(StatusCode::BAD_REQUEST, e),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+    if let Err(e) = time.apply() {
+        return error_response(StatusCode::BAD_REQUEST, e);
+    }
+    info!("Time zone set to {} ({:?})", time.zone, time.format);
+    HttpResponse::Ok().json(current())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::routes;
+    use crate::config::bundle::ConfigFiles;
+    use crate::config::loader;
+    use crate::services::audit::AuditLog;
+    use actix_web::{test, App};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    // 测试运行时是单线程的，持有锁跨 await 不会死锁
+    #[allow(clippy::await_holding_lock)]
+    #[actix_web::test]
+    async fn test_set_time_zone() {
+        let _guard = datetime::SETTINGS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempdir().unwrap();
+        let global_path = dir.path().join("global.yaml");
+        std::fs::write(&global_path, "http:\n  port: 5000\n").unwrap();
+        let global = Arc::new(loader::open_store(&global_path).unwrap());
+        let audit = Arc::new(AuditLog::new(dir.path().join("audit.log")));
+        let state = AppState::new(ConfigFiles::default(), Arc::clone(&global))
+            .with_audit(Arc::clone(&audit))
+            .with_admin_token("secret");
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(routes::configure)).await;
+
+        let set = |token: Option<&str>, body: serde_json::Value| {
+            let mut req = test::TestRequest::put().uri("/cdu/time/zone").set_json(body);
+            if let Some(token) = token {
+                req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+            }
+            req.to_request()
+        };
+        let resp = test::call_service(&app, set(None, json!({"zone": "America/New_York"}))).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = test::call_service(&app, set(Some("secret"), json!({"zone": "Mars/Olympus_Mons"}))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = set(Some("secret"), json!({"zone": "America/New_York", "format": "rfc3339"}));
+        let body: TimeZoneResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body.info.zone, "America/New_York");
+        assert_eq!(body.format, TimestampFormat::Rfc3339);
+        assert!(body.now.contains('T'));
+        // 写入了配置文件，其他配置保持不变
+        let saved: serde_json::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&global_path).unwrap()).unwrap();
+        assert_eq!(saved["time"]["zone"], "America/New_York");
+        assert_eq!(saved["time"]["format"], "rfc3339");
+        assert_eq!(saved["http"]["port"], 5000);
+
+        let actions: Vec<(String, bool)> = audit
+            .recent(10)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.action, e.success))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![("time_zone.set".to_string(), false), ("time_zone.set".to_string(), true)]
+        );
+
+        let req = test::TestRequest::get().uri("/cdu/time/zone").to_request();
+        let body: TimeZoneResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body.info.zone, "America/New_York");
+
+        TimeConfig::default().apply().unwrap();
+    }
+}
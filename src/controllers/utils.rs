@@ -0,0 +1,124 @@
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::http::{header, StatusCode};
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse};
+use serde_json::json;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+use crate::app::state::AppState;
+
+/// 统一的错误响应：{"error": "..."}
+pub fn error_response(status: StatusCode, message: impl std::fmt::Display) -> HttpResponse {
+    HttpResponse::build(status).json(json!({ "error": message.to_string() }))
+}
+
+/// 特权接口的访问检查，请求需要携带 "Authorization: Bearer <http.admin_token>"
+pub fn require_admin(req: &HttpRequest, state: &AppState) -> Result<(), HttpResponse> {
+    let Some(ref expected) = state.admin_token else {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            "privileged API is disabled, set http.admin_token to enable it",
+        ));
+    };
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Ok(()),
+        _ => Err(error_response(StatusCode::UNAUTHORIZED, "invalid or missing admin token")),
+    }
+}
+
+/// 审计日志中的操作者：连接的对端地址。
+/// 不使用 X-Forwarded-For/Forwarded，这些请求头由客户端提供，可以伪造
+pub fn client_addr(req: &HttpRequest) -> String {
+    req.peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// CSV 字段中的逗号、引号和换行需要加引号
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 流式响应每块的大小
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// 在阻塞线程中生成响应体并分块发送，导出大量数据时不需要整个放进内存；
+/// 客户端断开后写入返回 BrokenPipe，生成过程随之停止
+pub fn streaming_body<F>(produce: F) -> ChannelBody
+where
+    F: FnOnce(&mut ChunkWriter) -> io::Result<()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(4);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChunkWriter {
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            tx,
+        };
+        let result = produce(&mut writer).and_then(|_| writer.flush());
+        if let Err(e) = result {
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                log::warn!("Streaming response failed: {}", e);
+                let _ = writer.tx.blocking_send(Err(e));
+            }
+        }
+    });
+    ChannelBody { rx }
+}
+
+pub struct ChunkWriter {
+    buffer: Vec<u8>,
+    tx: mpsc::Sender<io::Result<Bytes>>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE)));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+}
+
+/// streaming_body 返回的响应体
+pub struct ChannelBody {
+    rx: mpsc::Receiver<io::Result<Bytes>>,
+}
+
+impl MessageBody for ChannelBody {
+    type Error = io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.rx.poll_recv(cx)
+    }
+}
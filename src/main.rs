@@ -1,31 +1,117 @@
 mod app;
+mod cli;
+mod config;
+mod controllers;
 mod utils;
 // use serde_json::Value;
 // use tokio_modbus::prelude::SyncReader;
 // mod models::modbus_client::{ModbusClient};
 mod models;
+mod services;
+
+use clap::Parser;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
+    app::server::init_logger();
+    let cli = cli::Cli::parse();
+    let config_path = config::loader::config_path(cli.config.as_deref());
+    let config_files = config::bundle::ConfigFiles::default().with_global(&config_path);
+    if let Some(command) = cli.command {
+        return run_command(command, &config_files).await;
+    }
+
+    let global_store = match config::loader::open_store(&config_path) {
+        Ok(store) => std::sync::Arc::new(store),
+        Err(e) => fatal(e),
+    };
+    report_loaded_from(&global_store, &config_path);
+    let global_config = match config::loader::resolve(&global_store.get_config(), std::env::vars(), &cli.overrides()) {
+        Ok(config) => config,
+        Err(e) => fatal(e),
+    };
+    if let Err(e) = global_config.time.apply() {
+        fatal(e);
+    }
+    // 时区可以通过 API 或直接编辑配置文件修改，运行中立即生效
+    if let Err(e) = global_store.subscribe("time", config::loader::TimeConfigListener) {
+        fatal(e);
+    }
+    let _global_watch = global_store.watch(std::time::Duration::from_secs(2));
+
     // let ctx = models::modbus_client::ModbusClient::new();
     // models::modbus_client::main();
 
     // app::server::main().unwrap();
     // let client = ModbusClient::new("192.168.1.150", 5000);
-    let _file_store =
-        utils::file_store::FileStore::new("configs/sensors.yaml", None::<&str>);
+    let _file_store = utils::file_store::FileStore::new(config_files.sensors.as_path(), None::<&std::path::Path>)
+        .map(|store| {
+            store
+                .with_migration(|config| {
+                    config::migrations::migrate(config, config::migrations::Document::Sensors)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
+                .with_validator(|config| {
+                    config::validator::validate_sensors(config).map_err(|e| e.to_string())
+                })
+        });
 
     if let Err(e) = _file_store {
-        println!("Error: {:?}", e);
-        return Ok(());
+        fatal(e);
     }
 
-    let file_store = _file_store.unwrap();
-    // let single = file_store.get::<Value>("single").unwrap();
-    // let cv = single.as_object().unwrap()["Cv"].clone();
-    println!("{:?}", file_store.config);
-
+    let events = models::event_bus::EventBus::new();
+    let file_store = std::sync::Arc::new(_file_store.unwrap());
+    report_loaded_from(&file_store, &config_files.sensors);
+    if let Err(e) = config::migrations::migrate_store(&file_store, config::migrations::Document::Sensors) {
+        fatal(e);
+    }
+    file_store.on_change(models::event_bus::ConfigChangePublisher::new(&events, "sensors"));
+    file_store.on_change(services::polling_service::RestartOnSensorsChange);
+    // 现场可能通过 SSH 直接修改配置文件
+    let _watch = file_store.watch(std::time::Duration::from_secs(2));
 
+    // 告警配置引用的传感器以当前的传感器配置为准
+    let sensors_store = std::sync::Arc::clone(&file_store);
+    let alarm_store = match utils::file_store::FileStore::new(
+        config_files.alarms.as_path(),
+        Some(std::path::Path::new(config::bundle::ALARMS_DEFAULT_PATH)),
+    )
+    .and_then(|store| store.with_source_filter(config::migrations::quote_folded_comparators))
+    {
+        Ok(store) => std::sync::Arc::new(
+            store
+                .with_migration(|config| {
+                    config::migrations::migrate(config, config::migrations::Document::Alarms)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
+                .with_validator(move |config| {
+                    config::validator::validate_alarms(config, &sensors_store.get_config())
+                        .map_err(|e| e.to_string())
+                }),
+        ),
+        Err(e) => fatal(e),
+    };
+    report_loaded_from(&alarm_store, &config_files.alarms);
+    if let Err(e) = config::migrations::migrate_store(&alarm_store, config::migrations::Document::Alarms) {
+        fatal(e);
+    }
+    alarm_store.on_change(models::event_bus::ConfigChangePublisher::new(&events, "alarms"));
+    let _alarm_watch = alarm_store.watch(std::time::Duration::from_secs(2));
+    // 传感器、计算传感器和告警共用 Modbus 服务端的寄存器表，启动时报告重叠的地址
+    match models::modbus_server::RegisterMap::from_config(&file_store.get_config(), &alarm_store.get_config()) {
+        Ok(map) => {
+            for conflict in &map.conflicts {
+                log::warn!("Modbus register map: {}", conflict);
+            }
+            for invalid in &map.invalid {
+                log::warn!("Modbus register map: {}", invalid);
+            }
+        }
+        Err(e) => log::warn!("Modbus register map: {}", e),
+    }
 
     // 注释掉同步的 Modbus 客户端代码，避免运行时冲突
     // let mut tcp_client: models::modbus_client::ModbusTcpClient = models::modbus_client::ModbusTcpClient::new("192.168.1.150", 5000);
@@ -34,6 +120,336 @@ async fn main() -> std::io::Result<()> {
 
     let time = utils::datetime::get_current_time();
     println!("time: {}", time);
-    let server = app::server::Server::new();
-    server.run("0.0.0.0", "8080").await
+    // 隔离网络中的 CDU 时钟会漂移，按配置定期从局域网 NTP 服务器同步
+    let clock = std::sync::Arc::new(services::clock_service::ClockService::new(
+        std::sync::Arc::new(services::clock_service::OsClock),
+        global_config.ntp.clone(),
+    ));
+    let scheduler = std::sync::Arc::new(utils::async_scheduler::Scheduler::new());
+    let polling = match start_polling(&scheduler, &global_config, &file_store.get_config(), &events) {
+        Ok(polling) => polling,
+        Err(e) => fatal(e),
+    };
+    if global_config.ntp.enable {
+        let clock = std::sync::Arc::clone(&clock);
+        let scheduled = scheduler.schedule(
+            "ntp-sync",
+            utils::async_scheduler::Schedule::FixedRate(std::time::Duration::from_secs_f64(global_config.ntp.interval)),
+            move || {
+                let clock = std::sync::Arc::clone(&clock);
+                async move {
+                    let _ = clock.sync().await;
+                }
+            },
+        );
+        if let Err(e) = scheduled {
+            fatal(e);
+        }
+    }
+
+    let mut state = app::state::AppState::new(config_files, global_store)
+        .with_clock(clock)
+        .with_admin_token(&global_config.http.admin_token)
+        .with_scheduler(std::sync::Arc::clone(&scheduler));
+    let history = polling.history;
+    if let Some(history) = &history {
+        state = state.with_history(std::sync::Arc::clone(history));
+    }
+    state = state.with_modbus_diagnostics(polling.diagnostics);
+    if let Some(service) = polling.service {
+        state = state.with_polling(service);
+    }
+    // 控制模式没有配置时按 Auto 处理，调试时需要在 control_mode 配置中设置 mode: manual。
+    // 配置文件在运行中被修改时，模式随 ModeChanged 事件更新
+    let mode_store = utils::file_store::FileStore::new(state.config_files.control_mode.as_path(), None)
+        .map(std::sync::Arc::new)
+        .inspect(|store| report_loaded_from(store, &state.config_files.control_mode))
+        .inspect_err(|e| log::warn!("Control mode config unavailable, using Auto: {}", e))
+        .ok();
+    let mode = mode_store.as_ref().and_then(|store| store.get::<String>("mode"));
+    let control_mode = models::control_mode::ControlMode::from_name(mode.as_deref().unwrap_or_default())
+        .unwrap_or(models::control_mode::ControlMode::Auto);
+    log::info!("Control mode: {:?}", control_mode);
+    let control_mode = std::sync::Arc::new(models::control_mode::ControlModeState::new(control_mode));
+    let _mode_follow = control_mode.follow(&events);
+    let _mode_watch = mode_store.map(|store| {
+        store.on_change(models::control_mode::ModeChangePublisher::new(&events));
+        store.watch(std::time::Duration::from_secs(2))
+    });
+    state = state.with_control_mode(control_mode);
+    let server = app::server::Server::new(&global_config.http.static_dir, state);
+    let result = server.run(&global_config.http.host, global_config.http.port).await;
+
+    // 服务停止后先停下轮询和定时任务，再把缓存中的历史数据写入磁盘
+    let aborted = scheduler.shutdown(std::time::Duration::from_secs(5)).await;
+    if aborted > 0 {
+        log::warn!("{} scheduled jobs aborted at shutdown", aborted);
+    }
+    if let Some(history) = &history {
+        if let Err(e) = history.flush() {
+            log::warn!("Failed to flush history: {}", e);
+        }
+    }
+    result
+}
+
+/// 主文件损坏或缺失时配置来自备份或默认文件，告诉运维实际使用的是哪个文件
+fn report_loaded_from(store: &utils::file_store::FileStore, path: &std::path::Path) {
+    if let Some(used) = store.loaded_from().filter(|used| *used != path) {
+        log::warn!("Config {} loaded from {}", path.display(), used.display());
+    }
+}
+
+/// 启动时的配置错误是致命的：报告错误并以非零状态退出，进程管理器才能发现启动失败
+fn fatal(error: impl std::fmt::Display) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(1)
+}
+
+/// 轮询服务中供 HTTP 接口使用的部分
+struct Polling {
+    history: Option<std::sync::Arc<services::history_store::HistoryStore>>,
+    /// 每个 Modbus 连接的通信统计
+    diagnostics: Vec<std::sync::Arc<services::modbus_diagnostics::ModbusDiagnostics>>,
+    /// 用于调试时直接读写寄存器
+    service: Option<std::sync::Arc<services::polling_service::PollingService>>,
+}
+
+/// 按传感器配置定期轮询，并把结果写入历史数据
+fn start_polling(
+    scheduler: &utils::async_scheduler::Scheduler,
+    global_config: &config::loader::GlobalConfig,
+    sensors_config: &serde_json::Value,
+    events: &models::event_bus::EventBus,
+) -> Result<Polling, Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use utils::async_scheduler::Schedule;
+
+    let catalog = models::sensor::SensorCatalog::from_config(sensors_config)?;
+    let history = if global_config.history.enable {
+        let history = Arc::new(services::history_store::HistoryStore::open(&global_config.history)?);
+        history.register(&catalog.series())?;
+        Some(history)
+    } else {
+        None
+    };
+
+    // 默认设备和 sensors.yaml 中的设备，连接目标相同的设备（同一串口上的多个从站）共用一个连接
+    let mut connections: Vec<(Vec<Option<String>>, services::modbus_service::ModbusService)> = Vec::new();
+    let default = services::modbus_service::ModbusService::from_config(&global_config.modbus_client);
+    if default.is_none() {
+        log::warn!("No Modbus client enabled, sensors without a device are not polled");
+    }
+    let devices = catalog.devices.iter().filter_map(|device| {
+        let modbus = services::modbus_service::ModbusService::from_config(&device.client)?;
+        Some((Some(device.name.clone()), modbus.with_name(&device.name)))
+    });
+    for (device, modbus) in default.map(|modbus| (None, modbus)).into_iter().chain(devices) {
+        match connections.iter_mut().find(|(_, shared)| shared.target() == modbus.target()) {
+            Some((names, shared)) => {
+                shared.diagnostics().add_device(device.as_deref().unwrap_or(services::modbus_service::DEVICE_NAME));
+                names.push(device);
+            }
+            None => connections.push((vec![device], modbus)),
+        }
+    }
+    let diagnostics = connections.iter().map(|(_, modbus)| modbus.diagnostics()).collect();
+    if connections.is_empty() {
+        log::warn!("No Modbus connection configured, sensor polling disabled");
+        return Ok(Polling {
+            history,
+            diagnostics,
+            service: None,
+        });
+    }
+
+    let intervals: Vec<(Option<String>, f64)> = connections
+        .iter()
+        .flat_map(|(names, _)| names.iter().cloned())
+        .map(|device| {
+            let interval = catalog
+                .devices
+                .iter()
+                .find(|d| Some(&d.name) == device.as_ref())
+                .and_then(|d| d.poll_interval)
+                .unwrap_or(global_config.polling.interval);
+            (device, interval)
+        })
+        .collect();
+    let connected: Vec<Option<&str>> = intervals.iter().map(|(device, _)| device.as_deref()).collect();
+    let mut connections = connections.into_iter();
+    // 第一个连接是所有设备的初始读取器，没有连接的设备随后被去掉，既不调度也不能直接读写
+    let (_, modbus) = connections.next().unwrap();
+    let mut polling = services::polling_service::PollingService::new(catalog, Box::new(modbus.with_events(events)), events.clone())
+        .with_stale_timeout(Duration::from_secs_f64(global_config.polling.stale_timeout))
+        .with_connected_devices(&connected);
+    for (names, modbus) in connections {
+        let names: Vec<Option<&str>> = names.iter().map(Option::as_deref).collect();
+        polling = polling.with_device_reader(&names, Box::new(modbus.with_events(events)));
+    }
+    if let Some(history) = &history {
+        polling = polling.with_history(Arc::clone(history));
+    }
+    let polling = Arc::new(polling);
+    // 每个设备按自己的周期轮询，一个设备超时不会推迟其他设备
+    for (device, interval) in intervals {
+        let name = match &device {
+            Some(device) => format!("sensor-polling:{}", device),
+            None => "sensor-polling".to_string(),
+        };
+        let polling = Arc::clone(&polling);
+        scheduler.schedule(&name, Schedule::FixedRate(Duration::from_secs_f64(interval)), move || {
+            let polling = Arc::clone(&polling);
+            let device = device.clone();
+            async move {
+                polling.poll_device(device.as_deref()).await;
+            }
+        })?;
+    }
+
+    if let Some(history) = &history {
+        let flush = Arc::clone(history);
+        scheduler.schedule("history-flush", Schedule::FixedRate(Duration::from_secs(10)), move || {
+            let history = Arc::clone(&flush);
+            async move {
+                match tokio::task::spawn_blocking(move || history.flush()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => log::warn!("Failed to flush history: {}", e),
+                    Err(e) => log::warn!("Failed to flush history: {}", e),
+                }
+            }
+        })?;
+        let maintenance = Arc::clone(history);
+        scheduler.schedule("history-maintenance", Schedule::FixedRate(Duration::from_secs(3600)), move || {
+            let history = Arc::clone(&maintenance);
+            async move {
+                match tokio::task::spawn_blocking(move || history.maintain(time::OffsetDateTime::now_utc())).await {
+                    Ok(Ok(report)) => log::info!("History maintenance: {:?}", report),
+                    Ok(Err(e)) => log::warn!("History maintenance failed: {}", e),
+                    Err(e) => log::warn!("History maintenance failed: {}", e),
+                }
+            }
+        })?;
+    }
+    Ok(Polling {
+        history,
+        diagnostics,
+        service: Some(polling),
+    })
+}
+
+async fn run_command(command: cli::Command, config_files: &config::bundle::ConfigFiles) -> std::io::Result<()> {
+    match command {
+        cli::Command::ExportConfig { output } => {
+            let bundle = config::bundle::export(config_files).unwrap_or_else(|e| fatal(e));
+            let content = serde_json::to_string_pretty(&bundle)?;
+            match output {
+                Some(path) => std::fs::write(path, content)?,
+                None => println!("{}", content),
+            }
+        }
+        cli::Command::ImportConfig { file } => {
+            let content = std::fs::read_to_string(&file).unwrap_or_else(|e| fatal(format!("{}: {}", file.display(), e)));
+            let bundle: config::bundle::ConfigBundle = serde_json::from_str(&content).unwrap_or_else(|e| fatal(e));
+            match config::bundle::import(&bundle, config_files) {
+                Ok(report) => println!("Imported: {}", report.applied.join(", ")),
+                Err(e) => fatal(e),
+            }
+        }
+        cli::Command::Simulate {
+            tcp,
+            slave,
+            pty_link,
+            heat_load,
+            duty,
+            leak_rate,
+            noise,
+            scenario,
+        } => {
+            let mut model = models::cdu_model::CduModel::default();
+            model.heat_load = heat_load;
+            model.leak_rate = leak_rate;
+            let result = match build_simulator(config_files, model, duty, noise) {
+                Ok(simulator) => run_simulator(simulator, &tcp, slave, pty_link, scenario).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                fatal(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 按 sensors.yaml 建立模拟器，泵和阀门的占空比都设为 duty
+fn build_simulator(
+    config_files: &config::bundle::ConfigFiles,
+    mut model: models::cdu_model::CduModel,
+    duty: f64,
+    noise: u16,
+) -> Result<std::sync::Arc<services::simulator::Simulator>, Box<dyn std::error::Error>> {
+    let store = utils::file_store::FileStore::new(config_files.sensors.as_path(), None)?;
+    // 与轮询服务一样先迁移并校验，模拟器按配置解码寄存器
+    let mut sensors = store.get_config();
+    config::migrations::migrate(&mut sensors, config::migrations::Document::Sensors)?;
+    config::validator::validate_sensors(&sensors)?;
+    let catalog = models::sensor::SensorCatalog::from_config(&sensors)?;
+    for attr in &catalog.attrs {
+        if attr.plugin != "Leakages" && models::cdu_model::CduModel::is_input(&attr.plugin, &attr.attr) {
+            model.set_input(&attr.plugin, &attr.sensor, duty);
+        }
+    }
+    Ok(std::sync::Arc::new(
+        services::simulator::Simulator::new(catalog, model).with_noise(noise),
+    ))
+}
+
+/// 模型按 1 秒的步长实时推进，直到 Ctrl-C。指定场景时按场景的时间注入故障
+async fn run_simulator(
+    simulator: std::sync::Arc<services::simulator::Simulator>,
+    tcp: &str,
+    slave: u8,
+    pty_link: Option<std::path::PathBuf>,
+    scenario: Option<std::path::PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let scenario = scenario.as_deref().map(services::scenario::Scenario::load).transpose()?;
+    let listener = tokio::net::TcpListener::bind(tcp).await?;
+    println!("Modbus TCP slave listening on {}", listener.local_addr()?);
+    let (pty, _rtu) = simulator.serve_rtu(slave)?;
+    println!("Modbus RTU slave {} on {}", slave, pty);
+    if let Some(link) = &pty_link {
+        let _ = std::fs::remove_file(link);
+        std::os::unix::fs::symlink(&pty, link)?;
+        println!("Linked {} -> {}", link.display(), pty);
+    }
+
+    let stepper = Arc::clone(&simulator);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            stepper.step(Duration::from_secs(1));
+        }
+    });
+    if let Some(scenario) = scenario {
+        println!("Playing scenario {}: {}", scenario.name, scenario.description);
+        let player = Arc::clone(&simulator);
+        tokio::spawn(async move {
+            if let Err(e) = scenario.play(&player).await {
+                println!("Error: {}", e);
+            }
+        });
+    }
+    tokio::select! {
+        result = Arc::clone(&simulator).serve_tcp(listener) => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    if let Some(link) = &pty_link {
+        let _ = std::fs::remove_file(link);
+    }
+    Ok(())
 }
@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// 水的比热容 kJ/(kg·K)，与计算传感器 CAP 的公式一致
+const WATER_CP: f64 = 4.2;
+/// 所有泵 100% 时的二次侧流量（L/min）
+const MAX_FLOW: f64 = 240.0;
+/// 阀门全开时的一次侧流量（L/min）
+const MAX_FACILITY_FLOW: f64 = 200.0;
+/// 泵 100% 时的扬程（PSI）
+const MAX_HEAD: f64 = 60.0;
+/// 储液罐满液位时的系统静压（PSI）
+const STATIC_PRESSURE: f64 = 15.0;
+/// 储液罐容积（L）
+const RESERVOIR: f64 = 50.0;
+/// 低于这个液位（%）液位开关断开，泵开始抽空
+const LOW_LEVEL: f64 = 20.0;
+/// 阀门全开时换热器的效率
+const MAX_EFFECTIVENESS: f64 = 0.9;
+/// 流量和温度的一阶惯性时间常数（秒）
+const FLOW_TIME_CONSTANT: f64 = 3.0;
+const THERMAL_TIME_CONSTANT: f64 = 30.0;
+/// 通过泄漏线圈注入泄漏时的默认泄漏速率（L/min）
+pub const DEFAULT_LEAK_RATE: f64 = 2.0;
+
+/// 模拟器使用的简化 CDU 热工水力模型，传感器名称与 configs/sensors.yaml 一致：
+/// 泵的占空比决定二次侧流量和压力，阀门开度决定换热器效率，从而决定供回水温度；
+/// 泄漏使储液罐液位和系统静压下降，液位过低时泵抽空
+#[derive(Debug, Clone)]
+pub struct CduModel {
+    /// IT 负载（kW）
+    pub heat_load: f64,
+    /// 一次侧供水温度 T1（℃）
+    pub facility_supply: f64,
+    /// 环境温度（℃）
+    pub ambient: f64,
+    /// 泄漏速率（L/min），0 表示没有泄漏
+    pub leak_rate: f64,
+    /// 泵和阀门的占空比（%），按传感器名
+    pumps: BTreeMap<String, f64>,
+    valves: BTreeMap<String, f64>,
+    /// 空转的泵：入口没有液体，不出水、电流下降、温度升高
+    dry: BTreeSet<String>,
+    /// 二次侧流量（L/min）
+    flow: f64,
+    /// 二次侧供水 T4 和回水 T3
+    supply: f64,
+    ret: f64,
+    /// 一次侧回水 T2
+    facility_return: f64,
+    /// 储液罐液位（%）
+    level: f64,
+}
+
+impl Default for CduModel {
+    fn default() -> Self {
+        Self {
+            heat_load: 60.0,
+            facility_supply: 18.0,
+            ambient: 25.0,
+            leak_rate: 0.0,
+            pumps: BTreeMap::new(),
+            valves: BTreeMap::new(),
+            dry: BTreeSet::new(),
+            flow: 0.0,
+            supply: 25.0,
+            ret: 25.0,
+            facility_return: 18.0,
+            level: 100.0,
+        }
+    }
+}
+
+/// dt 时间内一阶惯性环节向目标值靠近的比例
+fn lag(dt: f64, time_constant: f64) -> f64 {
+    1.0 - (-dt / time_constant).exp()
+}
+
+/// 冗余传感器（T1'、P1'）与主传感器取同一个值
+fn base_name(sensor: &str) -> &str {
+    sensor.trim_end_matches('\'')
+}
+
+fn mean(values: &BTreeMap<String, f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.values().sum::<f64>() / values.len() as f64
+}
+
+impl CduModel {
+    /// 由 Modbus 主站写入的属性：泵和阀门的占空比、泄漏线圈
+    pub fn is_input(plugin: &str, attr: &str) -> bool {
+        matches!((plugin, attr), ("Pumps" | "Valves", "DutyCycle") | ("Leakages", "value"))
+    }
+
+    /// 设置输入，value 为工程值
+    pub fn set_input(&mut self, plugin: &str, sensor: &str, value: f64) {
+        match plugin {
+            "Pumps" => {
+                self.pumps.insert(sensor.to_string(), value.clamp(0.0, 100.0));
+            }
+            "Valves" => {
+                self.valves.insert(sensor.to_string(), value.clamp(0.0, 100.0));
+            }
+            "Leakages" if value == 0.0 => self.leak_rate = 0.0,
+            "Leakages" if self.leak_rate == 0.0 => self.leak_rate = DEFAULT_LEAK_RATE,
+            _ => {}
+        }
+    }
+
+    /// 泵空转（dry = true）或恢复
+    pub fn set_dry_run(&mut self, pump: &str, dry: bool) {
+        if dry {
+            self.dry.insert(pump.to_string());
+        } else {
+            self.dry.remove(pump);
+        }
+    }
+
+    /// 出水的泵的平均转速比例（0–1），空转的泵按 0 计算
+    fn pump_ratio(&self) -> f64 {
+        if self.pumps.is_empty() {
+            return 0.0;
+        }
+        let wet: f64 = self
+            .pumps
+            .iter()
+            .filter(|(name, _)| !self.dry.contains(*name))
+            .map(|(_, duty)| duty)
+            .sum();
+        wet / self.pumps.len() as f64 / 100.0
+    }
+
+    /// 空转的泵没有负载，电流只有空载电流的三分之一
+    fn pump_load(&self, pump: &str) -> Option<f64> {
+        let duty = self.pumps.get(pump)? / 100.0;
+        Some(if self.dry.contains(pump) { duty / 3.0 } else { duty })
+    }
+
+    fn effectiveness(&self) -> f64 {
+        MAX_EFFECTIVENESS * mean(&self.valves) / 100.0
+    }
+
+    fn head(&self) -> f64 {
+        MAX_HEAD * (self.flow / MAX_FLOW).powi(2)
+    }
+
+    fn static_pressure(&self) -> f64 {
+        STATIC_PRESSURE * (self.level / LOW_LEVEL).min(1.0)
+    }
+
+    /// 推进 dt 秒
+    pub fn step(&mut self, dt: f64) {
+        if self.leak_rate > 0.0 {
+            self.level = (self.level - self.leak_rate * dt / 60.0 / RESERVOIR * 100.0).max(0.0);
+        }
+        let dry = (self.level / LOW_LEVEL).min(1.0);
+        let flow_target = MAX_FLOW * self.pump_ratio() * dry;
+        self.flow += (flow_target - self.flow) * lag(dt, FLOW_TIME_CONSTANT);
+
+        // IT 负载使回水升温，换热器按效率把回水冷却到一次侧供水温度
+        let rise = if self.flow > 1.0 {
+            (self.heat_load * 60.0 / (WATER_CP * self.flow)).min(40.0)
+        } else {
+            40.0
+        };
+        let effectiveness = self.effectiveness();
+        let alpha = lag(dt, THERMAL_TIME_CONSTANT);
+        let supply_target = self.ret - effectiveness * (self.ret - self.facility_supply);
+        self.supply += (supply_target - self.supply) * alpha;
+        self.ret += (self.supply + rise - self.ret) * alpha;
+
+        let facility_flow = MAX_FACILITY_FLOW * mean(&self.valves) / 100.0;
+        let facility_target = if facility_flow > 1.0 {
+            self.facility_supply + effectiveness * (self.ret - self.facility_supply) * self.flow / facility_flow
+        } else {
+            self.facility_supply
+        };
+        self.facility_return += (facility_target - self.facility_return) * alpha;
+    }
+
+    /// 属性的工程值，模型中没有的属性返回 None
+    pub fn value(&self, plugin: &str, sensor: &str, attr: &str) -> Option<f64> {
+        let head = self.head();
+        let base = self.static_pressure();
+        let value = match (plugin, base_name(sensor), attr) {
+            ("Pumps", _, "DutyCycle") => *self.pumps.get(sensor)?,
+            ("Pumps", _, "Speed") => 3000.0 * self.pumps.get(sensor)? / 100.0,
+            ("Pumps", _, "Current") => 0.3 + 4.7 * self.pump_load(sensor)?.powi(3),
+            ("Pumps", _, "Temperature") => {
+                let heating = if self.dry.contains(sensor) { 35.0 } else { 5.0 };
+                self.ambient + heating + 0.25 * self.pumps.get(sensor)?
+            }
+            ("Pumps", _, "Voltage") => 230.0,
+            ("Valves", _, "DutyCycle") => *self.valves.get(sensor)?,
+            ("Valves", _, "Voltage") => self.valves.get(sensor)? / 10.0,
+            ("Flows", _, "value") => self.flow,
+            // P1 泵出口，P2/P4 供液，P3 回液，P5 泵入口
+            ("Pressures", "P1", "value") => base + head,
+            ("Pressures", "P2", "value") => base + 0.9 * head,
+            ("Pressures", "P3", "value") => base + 0.3 * head,
+            ("Pressures", "P4", "value") => base + 0.8 * head,
+            ("Pressures", "P5", "value") => base,
+            ("Pressures", "DP1", "value") => 0.8 * head,
+            ("Temperatures", "T1", "value") => self.facility_supply,
+            ("Temperatures", "T2", "value") => self.facility_return,
+            ("Temperatures", "T3", "value") => self.ret,
+            ("Temperatures", "T4", "value") => self.supply,
+            ("Temperatures", "env", "value") => self.ambient,
+            ("PHs", _, "value") => 7.4,
+            ("PHTs", _, "Temperature") => self.ambient,
+            ("PHTs", _, "Humidity") => 45.0,
+            ("Leakages", _, "value") => f64::from(u8::from(self.leak_rate > 0.0)),
+            ("Liquids", _, "value") => f64::from(u8::from(self.level >= LOW_LEVEL)),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settle(model: &mut CduModel) {
+        for _ in 0..600 {
+            model.step(1.0);
+        }
+    }
+
+    #[test]
+    fn test_pumps_and_valve_drive_the_loop() {
+        let mut model = CduModel::default();
+        model.set_input("Pumps", "Pump1", 50.0);
+        model.set_input("Pumps", "Pump2", 50.0);
+        model.set_input("Valves", "Valve1", 50.0);
+        settle(&mut model);
+        let flow = model.value("Flows", "F1", "value").unwrap();
+        assert!((flow - 120.0).abs() < 0.1, "{}", flow);
+        let supply = model.value("Temperatures", "T4'", "value").unwrap();
+        let ret = model.value("Temperatures", "T3", "value").unwrap();
+        // 稳态时回水温升等于 IT 负载
+        assert!((ret - supply - 60.0 * 60.0 / (WATER_CP * flow)).abs() < 0.1);
+        let p1 = model.value("Pressures", "P1", "value").unwrap();
+
+        // 开大阀门，供水温度下降
+        model.set_input("Valves", "Valve1", 100.0);
+        settle(&mut model);
+        assert!(model.value("Temperatures", "T4", "value").unwrap() < supply - 1.0);
+
+        // 提高泵速，流量和压力上升
+        model.set_input("Pumps", "Pump1", 100.0);
+        model.set_input("Pumps", "Pump2", 100.0);
+        settle(&mut model);
+        assert!(model.value("Flows", "F2", "value").unwrap() > 239.0);
+        assert!(model.value("Pressures", "P1", "value").unwrap() > p1 + 30.0);
+        assert_eq!(model.value("Pumps", "Pump1", "Speed"), Some(3000.0));
+        assert_eq!(model.value("Unknown", "X", "value"), None);
+    }
+
+    #[test]
+    fn test_leak_drains_reservoir() {
+        let mut model = CduModel::default();
+        model.set_input("Pumps", "Pump1", 80.0);
+        settle(&mut model);
+        let pressure = model.value("Pressures", "P5", "value").unwrap();
+        assert_eq!(model.value("Leakages", "LE1", "value"), Some(0.0));
+
+        model.set_input("Leakages", "LE1", 1.0);
+        assert_eq!(model.leak_rate, DEFAULT_LEAK_RATE);
+        assert_eq!(model.value("Leakages", "LE1", "value"), Some(1.0));
+        // 2 L/min 的泄漏 20 分钟后液位降到 20% 以下
+        for _ in 0..1250 {
+            model.step(1.0);
+        }
+        assert_eq!(model.value("Liquids", "Li1", "value"), Some(0.0));
+        assert!(model.value("Pressures", "P5", "value").unwrap() < pressure);
+        settle(&mut model);
+        assert!(model.value("Flows", "F1", "value").unwrap() < 1.0);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let mut model = CduModel::default();
+        model.set_input("Pumps", "Pump1", 60.0);
+        model.set_input("Pumps", "Pump2", 60.0);
+        settle(&mut model);
+        let current = model.value("Pumps", "Pump1", "Current").unwrap();
+
+        model.set_dry_run("Pump1", true);
+        settle(&mut model);
+        assert!((model.value("Flows", "F1", "value").unwrap() - 72.0).abs() < 0.1);
+        assert!(model.value("Pumps", "Pump1", "Current").unwrap() < current);
+        assert_eq!(model.value("Pumps", "Pump2", "Current"), Some(current));
+        assert_eq!(model.value("Pumps", "Pump1", "Temperature"), Some(25.0 + 35.0 + 15.0));
+    }
+}
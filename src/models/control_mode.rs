@@ -0,0 +1,139 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+
+use crate::models::event_bus::{Event, EventBus, EventFilter, EventKind};
+use crate::utils::file_store::{Config, ConfigChangeCallback};
+
+/// CDU 的控制模式：Auto 时泵和阀门由控制逻辑驱动，Manual 时由操作员操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ControlMode {
+    Auto,
+    Manual,
+}
+
+impl ControlMode {
+    /// 不区分大小写，"automatic" 也是 Auto
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "auto" | "automatic" => Some(Self::Auto),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// 当前的控制模式，初始值来自 control_mode 配置的 mode，之后随 ModeChanged 事件更新。
+/// 无法识别的模式按 Auto 处理，宁可拒绝手动写寄存器
+#[derive(Debug)]
+pub struct ControlModeState {
+    mode: RwLock<ControlMode>,
+}
+
+impl ControlModeState {
+    pub fn new(mode: ControlMode) -> Self {
+        Self {
+            mode: RwLock::new(mode),
+        }
+    }
+
+    pub fn get(&self) -> ControlMode {
+        *self.mode.read().unwrap()
+    }
+
+    pub fn set(&self, mode: ControlMode) {
+        *self.mode.write().unwrap() = mode;
+    }
+
+    /// 在后台跟随 ModeChanged 事件，事件总线关闭后结束
+    pub fn follow(self: &Arc<Self>, events: &EventBus) -> tokio::task::JoinHandle<()> {
+        let mut subscription = events.subscribe(EventFilter::kinds(&[EventKind::ModeChanged]));
+        let state = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Some(event) = subscription.recv().await {
+                if let Event::ModeChanged { to, .. } = event {
+                    let mode = ControlMode::from_name(&to).unwrap_or_else(|| {
+                        log::warn!("Unknown control mode '{}', treating it as Auto", to);
+                        ControlMode::Auto
+                    });
+                    state.set(mode);
+                }
+            }
+        })
+    }
+}
+
+/// 把 control_mode 配置中 mode 的变化转成 ModeChanged 事件。
+/// 配置文件通过 SSH 或导入修改时，ControlModeState 随之更新，写寄存器的联锁不会停留在启动时的模式
+pub struct ModeChangePublisher {
+    bus: EventBus,
+}
+
+impl ModeChangePublisher {
+    pub fn new(bus: &EventBus) -> Self {
+        Self { bus: bus.clone() }
+    }
+}
+
+impl ConfigChangeCallback for ModeChangePublisher {
+    fn on_config_change(&self, new_config: &Config, old_config: &Config) {
+        let mode = |config: &Config| config.get("mode").and_then(Value::as_str).unwrap_or_default().to_string();
+        let (from, to) = (mode(old_config), mode(new_config));
+        if from != to {
+            self.bus.try_publish(Event::ModeChanged { from, to });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_store::FileStore;
+
+    #[tokio::test]
+    async fn test_follows_mode_changes() {
+        assert_eq!(ControlMode::from_name("MANUAL"), Some(ControlMode::Manual));
+        assert_eq!(ControlMode::from_name("automatic"), Some(ControlMode::Auto));
+        assert_eq!(ControlMode::from_name("standby"), None);
+
+        let events = EventBus::new();
+        let state = Arc::new(ControlModeState::new(ControlMode::Auto));
+        let _follow = state.follow(&events);
+        for (to, expected) in [("Manual", ControlMode::Manual), ("Auto", ControlMode::Auto), ("Manual", ControlMode::Manual), ("Standby", ControlMode::Auto)] {
+            events
+                .publish(Event::ModeChanged {
+                    from: "Auto".to_string(),
+                    to: to.to_string(),
+                })
+                .await;
+            tokio::task::yield_now().await;
+            assert_eq!(state.get(), expected, "{}", to);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_follows_mode_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control_mode_config.yaml");
+        std::fs::write(&path, "mode: auto\n").unwrap();
+        let store = FileStore::new(&path, None).unwrap();
+
+        let events = EventBus::new();
+        let state = Arc::new(ControlModeState::new(ControlMode::Auto));
+        let _follow = state.follow(&events);
+        store.on_change(ModeChangePublisher::new(&events));
+
+        // 文件被外部修改（SSH 或导入配置），监视器重新加载后模式随之改变
+        for (content, expected) in [("mode: manual\n", ControlMode::Manual), ("mode: automatic\n", ControlMode::Auto)] {
+            std::fs::write(&path, content).unwrap();
+            assert!(store.poll_changes());
+            for _ in 0..100 {
+                if state.get() == expected {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(state.get(), expected, "{}", content);
+        }
+    }
+}
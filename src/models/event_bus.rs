@@ -0,0 +1,436 @@
+use log::warn;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::models::sensor::{Quality, SensorValue};
+use crate::utils::config_diff::{self, ConfigChange};
+use crate::utils::file_store::{Config, ConfigChangeCallback};
+
+/// 每个订阅默认缓存的事件数
+#[allow(dead_code)]
+pub const DEFAULT_CAPACITY: usize = 256;
+
+pub type SubscriptionId = u64;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+    Reconnecting,
+}
+
+/// 系统内的领域事件
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// 线圈等布尔属性的 value 为 true/false
+    SensorUpdated {
+        plugin: String,
+        sensor: String,
+        attr: String,
+        value: SensorValue,
+        quality: Quality,
+    },
+    /// 冗余分组按策略合并后的值，sources 为参与合并的设备数
+    GroupUpdated {
+        plugin: String,
+        group: String,
+        attr: String,
+        value: f64,
+        quality: Quality,
+        sources: usize,
+    },
+    AlarmRaised {
+        address: u16,
+        name: String,
+        level: String,
+    },
+    AlarmCleared {
+        address: u16,
+        name: String,
+    },
+    ModeChanged {
+        from: String,
+        to: String,
+    },
+    ConfigChanged {
+        document: String,
+        changes: Vec<ConfigChange>,
+    },
+    ConnectionStateChanged {
+        device: String,
+        state: ConnectionState,
+    },
+}
+
+/// 事件种类，用于按种类过滤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    SensorUpdated,
+    GroupUpdated,
+    AlarmRaised,
+    AlarmCleared,
+    ModeChanged,
+    ConfigChanged,
+    ConnectionStateChanged,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::SensorUpdated { .. } => EventKind::SensorUpdated,
+            Event::GroupUpdated { .. } => EventKind::GroupUpdated,
+            Event::AlarmRaised { .. } => EventKind::AlarmRaised,
+            Event::AlarmCleared { .. } => EventKind::AlarmCleared,
+            Event::ModeChanged { .. } => EventKind::ModeChanged,
+            Event::ConfigChanged { .. } => EventKind::ConfigChanged,
+            Event::ConnectionStateChanged { .. } => EventKind::ConnectionStateChanged,
+        }
+    }
+}
+
+/// 订阅的过滤条件，返回 true 的事件才会投递给订阅者
+#[derive(Clone)]
+pub struct EventFilter(Arc<dyn Fn(&Event) -> bool + Send + Sync>);
+
+#[allow(dead_code)]
+impl EventFilter {
+    pub fn new<F>(filter: F) -> Self
+    where
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(filter))
+    }
+
+    pub fn all() -> Self {
+        Self::new(|_| true)
+    }
+
+    pub fn kinds(kinds: &[EventKind]) -> Self {
+        let kinds = kinds.to_vec();
+        Self::new(move |event| kinds.contains(&event.kind()))
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.0)(event)
+    }
+}
+
+/// 订阅者缓冲区满时的处理方式
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// publish 等待订阅者腾出空间，慢订阅者会拖慢发布者
+    Block,
+    /// 丢弃新事件并计数，发布者不受影响
+    DropNewest,
+}
+
+#[allow(dead_code)]
+struct Subscriber {
+    id: SubscriptionId,
+    filter: EventFilter,
+    sender: mpsc::Sender<Event>,
+    overflow: Overflow,
+    dropped: Arc<AtomicU64>,
+}
+
+#[allow(dead_code)]
+#[derive(Default)]
+struct Inner {
+    subscribers: Mutex<Vec<Subscriber>>,
+    next_id: AtomicU64,
+}
+
+/// 类型化的发布/订阅总线，可以在 tokio 任务之间共享。
+/// 每个订阅有独立的有界缓冲区，所有匹配过滤条件的订阅者都会收到同一事件
+#[derive(Clone, Default)]
+pub struct EventBus {
+    inner: Arc<Inner>,
+}
+
+#[allow(dead_code)]
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 订阅匹配 filter 的事件，缓冲区满时丢弃新事件
+    pub fn subscribe(&self, filter: EventFilter) -> Subscription {
+        self.subscribe_with(filter, DEFAULT_CAPACITY, Overflow::DropNewest)
+    }
+
+    pub fn subscribe_with(&self, filter: EventFilter, capacity: usize, overflow: Overflow) -> Subscription {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+        let dropped = Arc::new(AtomicU64::new(0));
+        self.inner.subscribers.lock().unwrap().push(Subscriber {
+            id,
+            filter,
+            sender,
+            overflow,
+            dropped: Arc::clone(&dropped),
+        });
+        Subscription {
+            id,
+            receiver,
+            dropped,
+            bus: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// 取消订阅，订阅者取完缓冲区中剩余的事件后 recv 返回 None
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        remove_subscriber(&self.inner, id)
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.inner.subscribers.lock().unwrap().len()
+    }
+
+    /// 发布事件，返回成功投递的订阅数。Overflow::Block 的订阅者缓冲区满时会等待
+    pub async fn publish(&self, event: Event) -> usize {
+        let mut delivered = 0;
+        for (sender, overflow, dropped) in self.targets(&event) {
+            let sent = match overflow {
+                Overflow::Block => sender.send(event.clone()).await.is_ok(),
+                Overflow::DropNewest => try_deliver(&sender, &dropped, event.clone()),
+            };
+            if sent {
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+
+    /// 在同步上下文（例如配置变更回调）中发布事件，缓冲区满的订阅者都按丢弃处理
+    pub fn try_publish(&self, event: Event) -> usize {
+        self.targets(&event)
+            .into_iter()
+            .filter(|(sender, _, dropped)| try_deliver(sender, dropped, event.clone()))
+            .count()
+    }
+
+    fn targets(&self, event: &Event) -> Vec<(mpsc::Sender<Event>, Overflow, Arc<AtomicU64>)> {
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        subscribers.retain(|s| !s.sender.is_closed());
+        subscribers
+            .iter()
+            .filter(|s| s.filter.matches(event))
+            .map(|s| (s.sender.clone(), s.overflow, Arc::clone(&s.dropped)))
+            .collect()
+    }
+}
+
+fn try_deliver(sender: &mpsc::Sender<Event>, dropped: &AtomicU64, event: Event) -> bool {
+    match sender.try_send(event) {
+        Ok(()) => true,
+        Err(TrySendError::Full(event)) => {
+            dropped.fetch_add(1, Ordering::Relaxed);
+            warn!("Event bus subscriber is full, dropping {:?}", event.kind());
+            false
+        }
+        Err(TrySendError::Closed(_)) => false,
+    }
+}
+
+#[allow(dead_code)]
+fn remove_subscriber(inner: &Inner, id: SubscriptionId) -> bool {
+    let mut subscribers = inner.subscribers.lock().unwrap();
+    let before = subscribers.len();
+    subscribers.retain(|s| s.id != id);
+    subscribers.len() != before
+}
+
+/// 一个订阅的接收端，drop 时自动取消订阅
+pub struct Subscription {
+    id: SubscriptionId,
+    receiver: mpsc::Receiver<Event>,
+    dropped: Arc<AtomicU64>,
+    bus: Weak<Inner>,
+}
+
+#[allow(dead_code)]
+impl Subscription {
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    pub async fn recv(&mut self) -> Option<Event> {
+        self.receiver.recv().await
+    }
+
+    pub fn try_recv(&mut self) -> Option<Event> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// 因缓冲区满而丢弃的事件数
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn unsubscribe(self) {
+        // 由 Drop 完成
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(inner) = self.bus.upgrade() {
+            remove_subscriber(&inner, self.id);
+        }
+    }
+}
+
+/// 把 FileStore 的配置变更转成 ConfigChanged 事件
+pub struct ConfigChangePublisher {
+    bus: EventBus,
+    document: String,
+}
+
+impl ConfigChangePublisher {
+    pub fn new(bus: &EventBus, document: impl Into<String>) -> Self {
+        Self {
+            bus: bus.clone(),
+            document: document.into(),
+        }
+    }
+}
+
+impl ConfigChangeCallback for ConfigChangePublisher {
+    fn on_config_change(&self, new_config: &Config, old_config: &Config) {
+        let changes = config_diff::diff(old_config, new_config);
+        if changes.is_empty() {
+            return;
+        }
+        self.bus.try_publish(Event::ConfigChanged {
+            document: self.document.clone(),
+            changes,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_store::FileStore;
+    use serde_json::json;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn sensor(value: f64) -> Event {
+        Event::SensorUpdated {
+            plugin: "Temperatures".to_string(),
+            sensor: "T1".to_string(),
+            attr: "value".to_string(),
+            value: SensorValue::Number(value),
+            quality: Quality::Good,
+        }
+    }
+
+    fn mode(to: &str) -> Event {
+        Event::ModeChanged {
+            from: "Manual".to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_and_filters() {
+        let bus = EventBus::new();
+        let mut all = bus.subscribe(EventFilter::all());
+        let mut modes = bus.subscribe(EventFilter::kinds(&[EventKind::ModeChanged]));
+        let mut hot = bus.subscribe(EventFilter::new(|e| matches!(e, Event::SensorUpdated { value, .. } if value.as_f64() > 40.0)));
+
+        assert_eq!(bus.publish(sensor(25.0)).await, 1);
+        assert_eq!(bus.publish(sensor(45.0)).await, 2);
+        assert_eq!(bus.publish(mode("Auto")).await, 2);
+
+        assert_eq!(all.recv().await, Some(sensor(25.0)));
+        assert_eq!(all.recv().await, Some(sensor(45.0)));
+        assert_eq!(all.recv().await, Some(mode("Auto")));
+        assert_eq!(modes.recv().await, Some(mode("Auto")));
+        assert_eq!(modes.try_recv(), None);
+        assert_eq!(hot.recv().await, Some(sensor(45.0)));
+        assert_eq!(hot.try_recv(), None);
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest_counts_dropped() {
+        let bus = EventBus::new();
+        let mut slow = bus.subscribe_with(EventFilter::all(), 2, Overflow::DropNewest);
+        for i in 0..5 {
+            bus.try_publish(sensor(i as f64));
+        }
+        assert_eq!(slow.dropped(), 3);
+        assert_eq!(slow.recv().await, Some(sensor(0.0)));
+        assert_eq!(slow.recv().await, Some(sensor(1.0)));
+        assert_eq!(slow.try_recv(), None);
+    }
+
+    #[tokio::test]
+    async fn test_block_applies_back_pressure() {
+        let bus = EventBus::new();
+        let mut slow = bus.subscribe_with(EventFilter::all(), 1, Overflow::Block);
+        bus.publish(sensor(1.0)).await;
+
+        let publisher = {
+            let bus = bus.clone();
+            tokio::spawn(async move { bus.publish(sensor(2.0)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!publisher.is_finished(), "publish should wait for the slow subscriber");
+
+        assert_eq!(slow.recv().await, Some(sensor(1.0)));
+        assert_eq!(publisher.await.unwrap(), 1);
+        assert_eq!(slow.recv().await, Some(sensor(2.0)));
+        assert_eq!(slow.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe() {
+        let bus = EventBus::new();
+        let mut kept = bus.subscribe(EventFilter::all());
+        let dropped = bus.subscribe(EventFilter::all());
+        assert_eq!(bus.subscriber_count(), 2);
+
+        dropped.unsubscribe();
+        assert_eq!(bus.subscriber_count(), 1);
+
+        bus.publish(sensor(1.0)).await;
+        assert!(bus.unsubscribe(kept.id()));
+        assert!(!bus.unsubscribe(kept.id()));
+        // 取消前已投递的事件仍可以取出
+        assert_eq!(kept.recv().await, Some(sensor(1.0)));
+        assert_eq!(kept.recv().await, None);
+        assert_eq!(bus.publish(sensor(2.0)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_config_change_publisher() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("alarms.yaml");
+        std::fs::write(&config_path, "single:\n  Cv:\n  - value: 15\n").unwrap();
+        let store = FileStore::new(&config_path, None).unwrap();
+
+        let bus = EventBus::new();
+        let mut events = bus.subscribe(EventFilter::kinds(&[EventKind::ConfigChanged]));
+        store.on_change(ConfigChangePublisher::new(&bus, "alarms"));
+
+        store.set_config(json!({"single": {"Cv": [{"value": 20}]}})).unwrap();
+        match events.recv().await {
+            Some(Event::ConfigChanged { document, changes }) => {
+                assert_eq!(document, "alarms");
+                assert_eq!(changes.len(), 1);
+                assert_eq!(changes[0].path.to_string(), "single.Cv[0].value");
+            }
+            other => panic!("Expected ConfigChanged, got {:?}", other),
+        }
+    }
+}
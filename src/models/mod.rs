@@ -1,2 +1,6 @@
+pub mod cdu_model;
+pub mod control_mode;
+pub mod event_bus;
 pub mod modbus_client;
-pub mod emiter;
+pub mod modbus_server;
+pub mod sensor;
@@ -1,7 +1,4 @@
-use tokio_modbus::{
-    prelude::{sync, SyncReader},
-    ExceptionCode, Slave,
-};
+use tokio_modbus::{prelude::sync, Slave};
 // use serialport::SerialPortBuilder;
 
 pub struct ModbusTcpClient {
@@ -0,0 +1,434 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+use crate::models::sensor::{AttrSpec, DataType, ReadMethod, SensorCatalog, SensorConfigError, WriteMethod};
+
+/// Modbus 服务端的四个地址空间，地址只在同一个表内冲突
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Table {
+    Coils,
+    DiscreteInputs,
+    InputRegisters,
+    HoldingRegisters,
+}
+
+impl Table {
+    fn of(method: ReadMethod) -> Self {
+        match method {
+            ReadMethod::Coils => Self::Coils,
+            ReadMethod::DiscreteInputs => Self::DiscreteInputs,
+            ReadMethod::InputRegisters => Self::InputRegisters,
+            ReadMethod::HoldingRegisters => Self::HoldingRegisters,
+        }
+    }
+
+    /// 按访问方式支持的功能码
+    fn function_codes(self, access: Access) -> Vec<u8> {
+        let (read, write): (&[u8], &[u8]) = match self {
+            Self::Coils => (&[1], &[5, 15]),
+            Self::DiscreteInputs => (&[2], &[]),
+            Self::InputRegisters => (&[4], &[]),
+            Self::HoldingRegisters => (&[3], &[6, 16]),
+        };
+        match access {
+            Access::R => read.to_vec(),
+            Access::W => write.to_vec(),
+            Access::RW => [read, write].concat(),
+        }
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Coils => "coil",
+            Self::DiscreteInputs => "discrete input",
+            Self::InputRegisters => "input register",
+            Self::HoldingRegisters => "holding register",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Access {
+    R,
+    W,
+    RW,
+}
+
+/// 寄存器表中的一项
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegisterEntry {
+    pub table: Table,
+    pub address: u16,
+    /// 占用的寄存器或线圈数
+    pub count: u16,
+    pub function_codes: Vec<u8>,
+    pub data_type: String,
+    /// 工程值 = 寄存器值 × scale，布尔、位和字符串为 None
+    pub scale: Option<f64>,
+    pub unit: String,
+    pub access: Access,
+    /// 配置中的来源："Plugin.Sensor.attr"、"computed.<name>"、"single.<name>[i]" 或 "Linkages[i]"
+    pub source: String,
+    pub description: String,
+    /// 寄存器中的位，同一寄存器的不同位不算冲突
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bit: Option<u8>,
+}
+
+impl RegisterEntry {
+    fn overlaps(&self, other: &RegisterEntry) -> bool {
+        if self.table != other.table {
+            return false;
+        }
+        let end = |e: &RegisterEntry| e.address as u32 + e.count.max(1) as u32;
+        let overlapping = (self.address as u32) < end(other) && (other.address as u32) < end(self);
+        match (self.bit, other.bit) {
+            (Some(a), Some(b)) => overlapping && a == b,
+            _ => overlapping,
+        }
+    }
+}
+
+/// 两项配置使用了重叠的地址
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Conflict {
+    pub table: Table,
+    pub address: u16,
+    pub first: String,
+    pub second: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} of {} overlaps {}", self.table, self.address, self.second, self.first)
+    }
+}
+
+/// 由 sensors.yaml（属性、batch_address、分组偏差告警、计算传感器）和告警配置（single、Linkages）生成的
+/// Modbus 服务端寄存器表。传感器属性按轮询的地址和读取方式对外提供工程值，整数类型按 decimal_places 放大；
+/// 计算传感器为 i16，告警和联动为 0/1
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RegisterMap {
+    /// 按表和地址排序
+    pub entries: Vec<RegisterEntry>,
+    pub conflicts: Vec<Conflict>,
+    /// 无法放入寄存器表的告警配置项，例如超出 0–65535 的地址
+    pub invalid: Vec<String>,
+}
+
+impl RegisterMap {
+    pub fn from_config(sensors: &Value, alarms: &Value) -> Result<Self, SensorConfigError> {
+        let catalog = SensorCatalog::from_config(sensors)?;
+        Ok(Self::build(&catalog, alarms))
+    }
+
+    pub fn build(catalog: &SensorCatalog, alarms: &Value) -> Self {
+        // 配置中的顺序，冲突报告中先出现的是 first
+        let mut entries: Vec<RegisterEntry> = Vec::new();
+        for attr in &catalog.attrs {
+            if let (Some(address), Some(method)) = (attr.address, attr.read_method) {
+                let access = if attr.write_method.is_some() { Access::RW } else { Access::R };
+                entries.push(attr_entry(attr, Table::of(method), address, access, attr.id(), String::new()));
+            }
+        }
+        let mut batches: Vec<(&str, &str, u16)> = Vec::new();
+        for attr in &catalog.attrs {
+            let (Some(address), Some(write_method)) = (attr.batch_address, attr.write_method) else {
+                continue;
+            };
+            if batches.contains(&(&attr.plugin, &attr.attr, address)) {
+                continue;
+            }
+            batches.push((&attr.plugin, &attr.attr, address));
+            let table = match write_method {
+                WriteMethod::SingleCoil | WriteMethod::MultipleCoils => Table::Coils,
+                _ => Table::HoldingRegisters,
+            };
+            let source = format!("{}.*.{}", attr.plugin, attr.attr);
+            let description = format!("{} of all {}", attr.attr, attr.plugin);
+            entries.push(attr_entry(attr, table, address, Access::W, source, description));
+        }
+        for group in &catalog.groups {
+            if let Some(address) = group.alarm_address {
+                let source = format!("{}.{}", group.plugin, group.name);
+                entries.push(status_entry(address, source.clone(), format!("{} discrepancy", source)));
+            }
+        }
+        for computed in &catalog.computed {
+            if let Some(address) = computed.address {
+                entries.push(RegisterEntry {
+                    table: Table::HoldingRegisters,
+                    address,
+                    count: 1,
+                    function_codes: Table::HoldingRegisters.function_codes(Access::R),
+                    data_type: "i16".to_string(),
+                    scale: Some(scale(computed.decimal_places)),
+                    unit: computed.unit.clone(),
+                    access: Access::R,
+                    source: format!("computed.{}", computed.name),
+                    description: computed.label.clone(),
+                    bit: None,
+                });
+            }
+        }
+        let mut invalid = Vec::new();
+        if let Some(single) = alarms.get("single").and_then(Value::as_object) {
+            for (name, rules) in single {
+                for (i, rule) in rules.as_array().into_iter().flatten().enumerate() {
+                    let Some(address) = rule.get("address").and_then(Value::as_u64) else {
+                        continue;
+                    };
+                    let subject = match rule.get("key").and_then(Value::as_str) {
+                        Some(key) => format!("{}.{}", name, key),
+                        None => name.clone(),
+                    };
+                    let description = format!(
+                        "{} {} {}",
+                        subject,
+                        rule.get("comparator").and_then(Value::as_str).unwrap_or_default(),
+                        rule.get("value").map(Value::to_string).unwrap_or_default()
+                    );
+                    let source = format!("single.{}[{}]", name, i);
+                    match u16::try_from(address) {
+                        Ok(address) => entries.push(status_entry(address, source, description)),
+                        Err(_) => invalid.push(format!("{}: address {} is out of range", source, address)),
+                    }
+                }
+            }
+        }
+        for (i, linkage) in alarms.get("Linkages").and_then(Value::as_array).into_iter().flatten().enumerate() {
+            if let Some(address) = linkage.get("address").and_then(Value::as_u64) {
+                let name = linkage.get("name").and_then(Value::as_str).unwrap_or_default();
+                let source = format!("Linkages[{}]", i);
+                match u16::try_from(address) {
+                    Ok(address) => entries.push(status_entry(address, source, name.to_string())),
+                    Err(_) => invalid.push(format!("{}: address {} is out of range", source, address)),
+                }
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if let Some(first) = entries[..i].iter().find(|first| first.overlaps(entry)) {
+                conflicts.push(Conflict {
+                    table: entry.table,
+                    address: entry.address.max(first.address),
+                    first: first.source.clone(),
+                    second: entry.source.clone(),
+                });
+            }
+        }
+        entries.sort_by_key(|e| (e.table, e.address, e.bit));
+        Self {
+            entries,
+            conflicts,
+            invalid,
+        }
+    }
+}
+
+fn scale(decimal_places: Option<u32>) -> f64 {
+    10f64.powi(-(decimal_places.unwrap_or(0) as i32))
+}
+
+fn attr_entry(attr: &AttrSpec, table: Table, address: u16, access: Access, source: String, description: String) -> RegisterEntry {
+    let boolean = matches!(table, Table::Coils | Table::DiscreteInputs);
+    let (data_type, scale, bit) = match attr.data_type {
+        _ if boolean => ("bool".to_string(), None, None),
+        DataType::U16 | DataType::I16 | DataType::U32 | DataType::I32 => {
+            (attr.data_type.to_string(), Some(scale(attr.decimal_places)), None)
+        }
+        DataType::F32 | DataType::F64 => (attr.data_type.to_string(), Some(1.0), None),
+        DataType::Bit(bit) => (attr.data_type.to_string(), None, Some(bit)),
+        DataType::String(_) => (attr.data_type.to_string(), None, None),
+    };
+    RegisterEntry {
+        table,
+        address,
+        count: if boolean { 1 } else { attr.width() },
+        function_codes: table.function_codes(access),
+        data_type,
+        scale,
+        unit: attr.unit.clone(),
+        access,
+        source,
+        description,
+        bit,
+    }
+}
+
+/// 告警和联动的状态：0 正常，1 触发
+fn status_entry(address: u16, source: String, description: String) -> RegisterEntry {
+    RegisterEntry {
+        table: Table::HoldingRegisters,
+        address,
+        count: 1,
+        function_codes: Table::HoldingRegisters.function_codes(Access::R),
+        data_type: "u16".to_string(),
+        scale: None,
+        unit: String::new(),
+        access: Access::R,
+        source,
+        description,
+        bit: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sensors() -> Value {
+        json!({
+            "sensor_plugins": [
+                {
+                    "name": "Pumps",
+                    "defaults": {
+                        "DutyCycle": {"read_method": "read_holding_registers", "write_method": "write_single_register", "batch_address": 30, "unit": "%"},
+                        "Current": {"read_method": "read_holding_registers", "unit": "A", "decimal_places": 2},
+                    },
+                    "sensors": [
+                        {"name": "Pump1", "attrs": {"DutyCycle": {"address": 2192}, "Current": {"address": 2081}}},
+                        {"name": "Pump2", "attrs": {"DutyCycle": {"address": 2193}, "Current": {"address": 2083}}},
+                    ],
+                },
+                {
+                    "name": "Meters",
+                    "defaults": {"Energy": {"read_method": "read_input_registers", "data_type": "f32", "unit": "kWh"}},
+                    "sensors": [{"name": "M1", "attrs": {"Energy": {"address": 2082}}}],
+                },
+                {
+                    "name": "Status",
+                    "defaults": {"value": {"read_method": "read_holding_registers", "data_type": "bit"}},
+                    "sensors": [
+                        {"name": "Run", "attrs": {"value": {"address": 40, "bit": 0}}},
+                        {"name": "Fault", "attrs": {"value": {"address": 40, "bit": 1}}},
+                    ],
+                },
+                {
+                    "name": "Leakages",
+                    "defaults": {"value": {"read_method": "read_coils"}},
+                    "sensors": [{"name": "LE1", "attrs": {"value": {"address": 10}}}],
+                    "group_list": [],
+                },
+                {
+                    "name": "Temperatures",
+                    "defaults": {"value": {"read_method": "read_holding_registers", "unit": "℃"}},
+                    "sensors": [
+                        {"name": "T1", "attrs": {"value": {"address": 3328}}},
+                        {"name": "T1'", "attrs": {"value": {"address": 3329}}},
+                    ],
+                    "group_list": [{"name": "T1", "devices": ["T1", "T1'"], "alarm_address": 1621}],
+                },
+            ],
+            "computed_sensors": [
+                {"name": "CAP", "label": "Cooling Capacity", "unit": "kW", "decimal_places": 1, "address": 1602, "expression": "Pumps.Pump1.Current * 2"},
+                {"name": "Dup", "label": "Duplicate", "address": 2081, "expression": "Pumps.Pump1.Current"},
+            ],
+        })
+    }
+
+    #[test]
+    fn test_register_map() {
+        let alarms = json!({
+            "single": {"CAP": [{"address": 2600, "comparator": ">", "value": 15}], "Pump1": [{"address": 2601, "comparator": "<", "key": "Current", "value": 0.5}]},
+            "Linkages": [{"address": 2637, "name": "Pump1 dry running", "sensors": []}],
+        });
+        let map = RegisterMap::from_config(&sensors(), &alarms).unwrap();
+        let rows: Vec<(Table, u16, &str)> = map.entries.iter().map(|e| (e.table, e.address, e.source.as_str())).collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Table::Coils, 10, "Leakages.LE1.value"),
+                (Table::InputRegisters, 2082, "Meters.M1.Energy"),
+                (Table::HoldingRegisters, 30, "Pumps.*.DutyCycle"),
+                (Table::HoldingRegisters, 40, "Status.Run.value"),
+                (Table::HoldingRegisters, 40, "Status.Fault.value"),
+                (Table::HoldingRegisters, 1602, "computed.CAP"),
+                (Table::HoldingRegisters, 1621, "Temperatures.T1"),
+                (Table::HoldingRegisters, 2081, "Pumps.Pump1.Current"),
+                (Table::HoldingRegisters, 2081, "computed.Dup"),
+                (Table::HoldingRegisters, 2083, "Pumps.Pump2.Current"),
+                (Table::HoldingRegisters, 2192, "Pumps.Pump1.DutyCycle"),
+                (Table::HoldingRegisters, 2193, "Pumps.Pump2.DutyCycle"),
+                (Table::HoldingRegisters, 2600, "single.CAP[0]"),
+                (Table::HoldingRegisters, 2601, "single.Pump1[0]"),
+                (Table::HoldingRegisters, 2637, "Linkages[0]"),
+                (Table::HoldingRegisters, 3328, "Temperatures.T1.value"),
+                (Table::HoldingRegisters, 3329, "Temperatures.T1'.value"),
+            ]
+        );
+
+        let entry = |source: &str| map.entries.iter().find(|e| e.source == source).unwrap();
+        let current = entry("Pumps.Pump1.Current");
+        assert_eq!((current.function_codes.clone(), current.data_type.as_str(), current.scale), (vec![3], "u16", Some(0.01)));
+        assert_eq!((current.unit.as_str(), current.access), ("A", Access::R));
+        let duty = entry("Pumps.Pump1.DutyCycle");
+        assert_eq!((duty.function_codes.clone(), duty.access), (vec![3, 6, 16], Access::RW));
+        let batch = entry("Pumps.*.DutyCycle");
+        assert_eq!((batch.function_codes.clone(), batch.access), (vec![6, 16], Access::W));
+        let energy = entry("Meters.M1.Energy");
+        assert_eq!((energy.count, energy.function_codes.clone(), energy.data_type.as_str()), (2, vec![4], "f32"));
+        assert_eq!((entry("Leakages.LE1.value").data_type.as_str(), entry("Status.Fault.value").data_type.as_str()), ("bool", "bit 1"));
+        let cap = entry("computed.CAP");
+        assert_eq!((cap.data_type.as_str(), cap.scale, cap.description.as_str()), ("i16", Some(0.1), "Cooling Capacity"));
+        assert_eq!(entry("single.Pump1[0]").description, "Pump1.Current < 0.5");
+        assert_eq!(entry("Temperatures.T1").description, "Temperatures.T1 discrepancy");
+
+        // 不同位共用一个寄存器不冲突；f32 占两个寄存器，与 2083 不重叠但与同地址的计算传感器冲突
+        assert_eq!(
+            map.conflicts,
+            vec![Conflict {
+                table: Table::HoldingRegisters,
+                address: 2081,
+                first: "Pumps.Pump1.Current".to_string(),
+                second: "computed.Dup".to_string(),
+            }]
+        );
+        assert_eq!(
+            map.conflicts[0].to_string(),
+            "holding register 2081 of computed.Dup overlaps Pumps.Pump1.Current"
+        );
+
+        // 多寄存器的值与后面的地址重叠
+        let mut config = sensors();
+        config["sensor_plugins"][0]["defaults"]["Current"]["data_type"] = json!("u32");
+        let map = RegisterMap::from_config(&config, &alarms).unwrap();
+        let conflicts: Vec<(u16, &str, &str)> =
+            map.conflicts.iter().map(|c| (c.address, c.first.as_str(), c.second.as_str())).collect();
+        assert_eq!(
+            conflicts,
+            vec![(2081, "Pumps.Pump1.Current", "computed.Dup")]
+        );
+        config["sensor_plugins"][0]["sensors"][1]["attrs"]["Current"]["address"] = json!(2082);
+        let map = RegisterMap::from_config(&config, &alarms).unwrap();
+        let conflicts: Vec<(u16, &str, &str)> =
+            map.conflicts.iter().map(|c| (c.address, c.first.as_str(), c.second.as_str())).collect();
+        assert_eq!(
+            conflicts,
+            vec![(2082, "Pumps.Pump1.Current", "Pumps.Pump2.Current"), (2081, "Pumps.Pump1.Current", "computed.Dup")]
+        );
+        assert!(map.invalid.is_empty());
+
+        // 超出范围的告警地址不截断成别的寄存器，单独报告
+        let alarms = json!({
+            "single": {"CAP": [{"address": 67136, "comparator": ">", "value": 15}]},
+            "Linkages": [{"address": 70000, "name": "Pump1 dry running", "sensors": []}],
+        });
+        let map = RegisterMap::from_config(&sensors(), &alarms).unwrap();
+        assert!(map.entries.iter().all(|e| !e.source.starts_with("single.") && !e.source.starts_with("Linkages")));
+        assert_eq!(
+            map.invalid,
+            vec![
+                "single.CAP[0]: address 67136 is out of range".to_string(),
+                "Linkages[0]: address 70000 is out of range".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1100 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::config::loader::ModbusClientConfig;
+use crate::utils::expression::{Expression, ExpressionError};
+
+/// 传感器属性的读取方式，对应 sensors.yaml 中的 read_method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReadMethod {
+    HoldingRegisters,
+    InputRegisters,
+    Coils,
+    DiscreteInputs,
+}
+
+impl ReadMethod {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "read_holding_registers" => Some(Self::HoldingRegisters),
+            "read_input_registers" => Some(Self::InputRegisters),
+            "read_coils" => Some(Self::Coils),
+            "read_discrete_inputs" => Some(Self::DiscreteInputs),
+            _ => None,
+        }
+    }
+
+    /// 按功能码：01、02、03、04
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Self::Coils),
+            2 => Some(Self::DiscreteInputs),
+            3 => Some(Self::HoldingRegisters),
+            4 => Some(Self::InputRegisters),
+            _ => None,
+        }
+    }
+
+    /// 一次请求最多读取的数量（Modbus 协议限制）
+    pub fn max_count(self) -> u16 {
+        match self {
+            Self::HoldingRegisters | Self::InputRegisters => 125,
+            Self::Coils | Self::DiscreteInputs => 2000,
+        }
+    }
+
+    /// 线圈和离散输入按位读取
+    pub fn is_bit(self) -> bool {
+        matches!(self, Self::Coils | Self::DiscreteInputs)
+    }
+}
+
+/// 写入方式，对应 sensors.yaml 中的 write_method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WriteMethod {
+    /// 功能码 06
+    SingleRegister,
+    /// 功能码 16
+    MultipleRegisters,
+    /// 功能码 05
+    SingleCoil,
+    /// 功能码 15
+    MultipleCoils,
+    /// 功能码 23：写入后在同一个请求中读回
+    ReadWriteMultipleRegisters,
+}
+
+impl WriteMethod {
+    /// write_register 等旧名称仍然可用
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "write_single_register" | "write_register" => Some(Self::SingleRegister),
+            "write_multiple_registers" | "write_registers" => Some(Self::MultipleRegisters),
+            "write_single_coil" | "write_coil" => Some(Self::SingleCoil),
+            "write_multiple_coils" | "write_coils" => Some(Self::MultipleCoils),
+            "read_write_multiple_registers" => Some(Self::ReadWriteMultipleRegisters),
+            _ => None,
+        }
+    }
+
+    /// 按功能码：05、06、15、16、23
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            5 => Some(Self::SingleCoil),
+            6 => Some(Self::SingleRegister),
+            15 => Some(Self::MultipleCoils),
+            16 => Some(Self::MultipleRegisters),
+            23 => Some(Self::ReadWriteMultipleRegisters),
+            _ => None,
+        }
+    }
+
+    /// 一个请求最多写入的数量（Modbus 协议限制）
+    pub fn max_count(self) -> u16 {
+        match self {
+            Self::SingleRegister | Self::SingleCoil => 1,
+            Self::MultipleRegisters => 123,
+            Self::ReadWriteMultipleRegisters => 121,
+            Self::MultipleCoils => 1968,
+        }
+    }
+
+    pub fn is_coil(self) -> bool {
+        matches!(self, Self::SingleCoil | Self::MultipleCoils)
+    }
+
+    /// 一次只写一个寄存器或线圈
+    pub fn is_single(self) -> bool {
+        matches!(self, Self::SingleRegister | Self::SingleCoil)
+    }
+}
+
+/// 寄存器数值的字节序，A 为最高字节：ABCD 为大端，CDAB 为字交换，BADC 为字内字节交换，DCBA 为小端
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ByteOrder {
+    #[default]
+    Abcd,
+    Cdab,
+    Badc,
+    Dcba,
+}
+
+impl ByteOrder {
+    /// 每个寄存器内的两个字节是否交换
+    pub fn swaps_bytes(self) -> bool {
+        matches!(self, Self::Badc | Self::Dcba)
+    }
+
+    /// 寄存器的顺序是否颠倒（低位字在前）
+    pub fn swaps_words(self) -> bool {
+        matches!(self, Self::Cdab | Self::Dcba)
+    }
+
+    /// 按读到的顺序排列的寄存器转换为大端字节
+    pub fn to_bytes(self, words: &[u16]) -> Vec<u8> {
+        let mut words = words.to_vec();
+        if self.swaps_words() {
+            words.reverse();
+        }
+        words
+            .iter()
+            .flat_map(|w| if self.swaps_bytes() { w.to_le_bytes() } else { w.to_be_bytes() })
+            .collect()
+    }
+
+    /// to_bytes 的逆运算
+    pub fn to_words(self, bytes: &[u8]) -> Vec<u16> {
+        let mut words: Vec<u16> = bytes
+            .chunks(2)
+            .map(|pair| {
+                let pair = [pair[0], pair.get(1).copied().unwrap_or(0)];
+                if self.swaps_bytes() {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            })
+            .collect();
+        if self.swaps_words() {
+            words.reverse();
+        }
+        words
+    }
+}
+
+/// 属性在寄存器中的数据类型，对应 sensors.yaml 中的 data_type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DataType {
+    #[default]
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    F64,
+    /// 寄存器中的一位（0–15），配置为 data_type: bit 和 bit: n
+    Bit(u8),
+    /// 每个寄存器两个字符，配置为 data_type: string 和 length（寄存器数）
+    String(u16),
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::U16 => write!(f, "u16"),
+            Self::I16 => write!(f, "i16"),
+            Self::U32 => write!(f, "u32"),
+            Self::I32 => write!(f, "i32"),
+            Self::F32 => write!(f, "f32"),
+            Self::F64 => write!(f, "f64"),
+            Self::Bit(bit) => write!(f, "bit {}", bit),
+            Self::String(length) => write!(f, "string({})", length),
+        }
+    }
+}
+
+impl DataType {
+    pub fn from_name(name: &str, bit: u8, length: u16) -> Option<Self> {
+        match name {
+            "u16" => Some(Self::U16),
+            "i16" => Some(Self::I16),
+            "u32" => Some(Self::U32),
+            "i32" => Some(Self::I32),
+            "f32" => Some(Self::F32),
+            "f64" => Some(Self::F64),
+            "bit" => Some(Self::Bit(bit)),
+            "string" => Some(Self::String(length)),
+            _ => None,
+        }
+    }
+
+    /// 占用的寄存器数
+    pub fn registers(self) -> u16 {
+        match self {
+            Self::U16 | Self::I16 | Self::Bit(_) => 1,
+            Self::U32 | Self::I32 | Self::F32 => 2,
+            Self::F64 => 4,
+            Self::String(length) => length.max(1),
+        }
+    }
+
+    /// 由寄存器解码数值，words 的长度应为 registers()；字符串没有数值
+    pub fn decode(self, words: &[u16], order: ByteOrder) -> Option<f64> {
+        if words.len() < self.registers() as usize {
+            return None;
+        }
+        let bytes = order.to_bytes(&words[..self.registers() as usize]);
+        Some(match self {
+            Self::U16 => u16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+            Self::I16 => i16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+            Self::U32 => u32::from_be_bytes(bytes[..4].try_into().ok()?) as f64,
+            Self::I32 => i32::from_be_bytes(bytes[..4].try_into().ok()?) as f64,
+            Self::F32 => f32::from_be_bytes(bytes[..4].try_into().ok()?) as f64,
+            Self::F64 => f64::from_be_bytes(bytes[..8].try_into().ok()?),
+            Self::Bit(bit) => ((words[0] >> bit) & 1) as f64,
+            Self::String(_) => return None,
+        })
+    }
+
+    /// 字符串按 ASCII 解码，去掉末尾的 NUL 和空格
+    pub fn decode_text(self, words: &[u16], order: ByteOrder) -> Option<String> {
+        match self {
+            Self::String(length) if words.len() >= length as usize => {
+                let bytes = order.to_bytes(&words[..length as usize]);
+                Some(String::from_utf8_lossy(&bytes).trim_end_matches(['\0', ' ']).to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// 数值编码为寄存器，整数四舍五入并限制在类型的范围内。
+    /// Bit 只返回这一位（0 或 1 << bit），由调用方和寄存器的其他位合并
+    pub fn encode(self, value: f64, order: ByteOrder) -> Vec<u16> {
+        let bytes = match self {
+            Self::U16 => (value.round().clamp(0.0, u16::MAX as f64) as u16).to_be_bytes().to_vec(),
+            Self::I16 => (value.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16).to_be_bytes().to_vec(),
+            Self::U32 => (value.round().clamp(0.0, u32::MAX as f64) as u32).to_be_bytes().to_vec(),
+            Self::I32 => (value.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32).to_be_bytes().to_vec(),
+            Self::F32 => (value as f32).to_be_bytes().to_vec(),
+            Self::F64 => value.to_be_bytes().to_vec(),
+            Self::Bit(bit) => return vec![u16::from(value != 0.0) << bit],
+            Self::String(length) => vec![0; length as usize * 2],
+        };
+        order.to_words(&bytes)
+    }
+}
+
+/// sensors.yaml 中的独立从站设备（电表、漏液检测器、远程 IO 等），有自己的连接和轮询周期。
+/// 没有指定 device 的传感器属于 global 配置中 modbus_client 的默认设备
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSpec {
+    pub name: String,
+    /// 只启用 tcp 或 serial 其中之一
+    pub client: ModbusClientConfig,
+    pub byte_order: ByteOrder,
+    /// 轮询周期（秒），为空时使用 polling.interval
+    pub poll_interval: Option<f64>,
+}
+
+#[derive(Debug, Error)]
+pub enum SensorConfigError {
+    #[error("{path}: {source}")]
+    Expression {
+        path: String,
+        #[source]
+        source: ExpressionError,
+    },
+
+    #[error("{path}: {message}")]
+    Invalid { path: String, message: String },
+}
+
+/// 传感器数值的质量状态，按严重程度从低到高排列
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Quality {
+    #[default]
+    Good,
+    /// 原始值超过 frozen_timeout 没有变化
+    Frozen,
+    /// 读取失败，沿用上次的值，未超过 polling.stale_timeout
+    Stale,
+    UnderRange,
+    OverRange,
+    /// 原始信号低于 raw_min，4–20 mA 回路断线
+    OpenLoop,
+    /// 读取失败超过 polling.stale_timeout
+    CommError,
+}
+
+impl Quality {
+    pub fn is_good(self) -> bool {
+        self == Quality::Good
+    }
+
+    /// 多个输入中最差的质量，计算传感器和冗余分组用
+    pub fn worst(qualities: impl IntoIterator<Item = Quality>) -> Quality {
+        qualities.into_iter().max().unwrap_or_default()
+    }
+}
+
+/// 带质量状态的工程值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    pub value: f64,
+    pub quality: Quality,
+}
+
+/// 对外发布的数值：线圈、离散输入和寄存器中的位是布尔值，其他为数值
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SensorValue {
+    Number(f64),
+    Bool(bool),
+}
+
+impl SensorValue {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Self::Number(value) => value,
+            Self::Bool(value) => f64::from(u8::from(value)),
+        }
+    }
+}
+
+/// 传感器的一个属性，defaults 与传感器自身的配置合并后的结果
+#[derive(Debug, Clone)]
+pub struct AttrSpec {
+    pub plugin: String,
+    pub sensor: String,
+    pub attr: String,
+    /// 没有地址的属性由同一传感器其他属性的原始值计算（例如 PHTs 的 DewPoint）
+    pub address: Option<u16>,
+    pub read_method: Option<ReadMethod>,
+    /// 可写的属性（泵、阀门的占空比等）的写入方式
+    pub write_method: Option<WriteMethod>,
+    /// 工程值 $value 换算为原始值，为空时直接写入工程值
+    pub write_formula: Option<Expression>,
+    /// 所属设备，为空时为默认设备
+    pub device: Option<String>,
+    /// 同一总线上的从站地址，为空时使用连接配置的 slave_id
+    pub slave_id: Option<u8>,
+    /// 寄存器中的数据类型，公式中的 $attr 是解码后的值
+    pub data_type: DataType,
+    /// Modbus 服务端上同时设置插件中所有传感器这个属性的寄存器
+    pub batch_address: Option<u16>,
+    pub byte_order: ByteOrder,
+    pub formula: Option<Expression>,
+    pub unit: String,
+    /// 工程值的有效范围
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// 原始信号的有效范围，例如 4–20 mA 变送器的 3800–20500
+    pub raw_min: Option<f64>,
+    pub raw_max: Option<f64>,
+    /// 原始值持续不变超过这个时间（秒）判定为 Frozen
+    pub frozen_timeout: Option<f64>,
+    pub decimal_places: Option<u32>,
+}
+
+impl AttrSpec {
+    /// "Plugin.Sensor.attr"，计算传感器表达式和历史数据都用这个名称
+    pub fn id(&self) -> String {
+        format!("{}.{}.{}", self.plugin, self.sensor, self.attr)
+    }
+
+    /// 线圈、离散输入和寄存器中的位以布尔值发布
+    pub fn is_boolean(&self) -> bool {
+        self.read_method.is_some_and(ReadMethod::is_bit) || matches!(self.data_type, DataType::Bit(_))
+    }
+
+    /// 工程值按 is_boolean 转换为对外发布的数值
+    pub fn publish_value(&self, value: f64) -> SensorValue {
+        if self.is_boolean() {
+            SensorValue::Bool(value != 0.0)
+        } else {
+            SensorValue::Number(value)
+        }
+    }
+
+    /// 由工程值计算要写入的原始值
+    pub fn unscale(&self, value: f64) -> Result<f64, ExpressionError> {
+        match &self.write_formula {
+            Some(formula) => formula.eval(|name| (name == "value").then_some(value)),
+            None => Ok(value),
+        }
+    }
+
+    /// 占用的寄存器或线圈数，线圈和离散输入不区分数据类型
+    pub fn width(&self) -> u16 {
+        match self.read_method {
+            Some(method) if method.is_bit() => 1,
+            _ => self.data_type.registers(),
+        }
+    }
+
+    /// 由读到的寄存器解码原始值，words 从属性的地址开始
+    pub fn decode(&self, words: &[u16]) -> Option<f64> {
+        match self.read_method {
+            Some(method) if method.is_bit() => words.first().map(|bit| *bit as f64),
+            _ => self.data_type.decode(words, self.byte_order),
+        }
+    }
+
+    /// 原始值编码为从属性地址开始写入的寄存器
+    pub fn encode(&self, raw: f64) -> Vec<u16> {
+        match self.read_method {
+            Some(method) if method.is_bit() => vec![u16::from(raw != 0.0)],
+            _ => self.data_type.encode(raw, self.byte_order),
+        }
+    }
+
+    /// 由原始值计算工程值，公式中的 $attr 取同一传感器各属性的原始值
+    pub fn scale<F>(&self, raw: F) -> Result<f64, ExpressionError>
+    where
+        F: Fn(&str) -> Option<f64>,
+    {
+        let value = match &self.formula {
+            Some(formula) => formula.eval(|name| raw(name))?,
+            None => raw(&self.attr).ok_or_else(|| ExpressionError::UnknownVariable(self.attr.clone()))?,
+        };
+        Ok(self.round(value))
+    }
+
+    /// 按原始信号和工程值的范围判断质量，raw 为属性自身的原始值
+    pub fn check_range(&self, raw: Option<f64>, value: f64) -> Quality {
+        match raw {
+            Some(raw) if self.raw_min.is_some_and(|min| raw < min) => Quality::OpenLoop,
+            Some(raw) if self.raw_max.is_some_and(|max| raw > max) => Quality::OverRange,
+            _ if self.min.is_some_and(|min| value < min) => Quality::UnderRange,
+            _ if self.max.is_some_and(|max| value > max) => Quality::OverRange,
+            _ => Quality::Good,
+        }
+    }
+
+    /// 按 decimal_places 舍入，冗余分组的平均值等也用所属属性的小数位数
+    pub fn round(&self, value: f64) -> f64 {
+        round(value, self.decimal_places)
+    }
+}
+
+/// computed_sensors 中的计算传感器
+#[derive(Debug, Clone)]
+pub struct ComputedSpec {
+    pub name: String,
+    pub label: String,
+    pub unit: String,
+    /// 在 Modbus 服务端寄存器表中的地址
+    pub address: Option<u16>,
+    pub decimal_places: Option<u32>,
+    pub expression: Expression,
+}
+
+impl ComputedSpec {
+    /// values 按 "Plugin.Sensor.attr" 提供工程值
+    pub fn evaluate<F>(&self, values: F) -> Result<f64, ExpressionError>
+    where
+        F: Fn(&str) -> Option<f64>,
+    {
+        Ok(round(self.expression.eval(values)?, self.decimal_places))
+    }
+
+    /// 输入中最差的质量就是计算结果的质量
+    pub fn quality<F>(&self, quality: F) -> Quality
+    where
+        F: Fn(&str) -> Option<Quality>,
+    {
+        Quality::worst(self.expression.variables().into_iter().filter_map(quality))
+    }
+}
+
+/// 没有配置 deadband 时偏差告警的回差占 tolerance 的比例
+pub const DEFAULT_DEADBAND_RATIO: f64 = 0.1;
+
+/// 冗余分组的取值策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupPolicy {
+    /// 取第一个设备的值，读取失败时依次取后面的设备
+    #[default]
+    PrimaryWithFallback,
+    Average,
+    Min,
+    Max,
+    /// 三取中，只剩两个设备有值时取平均
+    MedianOfThree,
+}
+
+/// group_list 中的冗余分组，例如 P1/P1'；告警和计算传感器可以用分组名代替传感器名
+#[derive(Debug, Clone)]
+pub struct GroupSpec {
+    pub plugin: String,
+    pub name: String,
+    /// 第一个为主设备
+    pub devices: Vec<String>,
+    pub policy: GroupPolicy,
+    /// 设备之间的最大允许偏差，超过时产生偏差告警；None 表示不检查
+    pub tolerance: Option<f64>,
+    /// 偏差告警的回差，偏差回到 tolerance - deadband 以内才解除，默认为 tolerance 的 10%
+    pub deadband: f64,
+    /// 偏差告警在 Modbus 告警表中的地址
+    pub alarm_address: Option<u16>,
+    pub alarm_level: String,
+}
+
+impl GroupSpec {
+    /// 按策略合并各设备的值，values 与 devices 一一对应，读取失败的为 None
+    pub fn combine(&self, values: &[Option<f64>]) -> Option<f64> {
+        let mut available: Vec<f64> = values.iter().flatten().copied().collect();
+        if available.is_empty() {
+            return None;
+        }
+        Some(match self.policy {
+            GroupPolicy::PrimaryWithFallback => available[0],
+            GroupPolicy::Average => available.iter().sum::<f64>() / available.len() as f64,
+            GroupPolicy::Min => available.iter().copied().fold(f64::INFINITY, f64::min),
+            GroupPolicy::Max => available.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            GroupPolicy::MedianOfThree => {
+                available.sort_by(f64::total_cmp);
+                let middle = available.len() / 2;
+                if available.len() % 2 == 1 {
+                    available[middle]
+                } else {
+                    (available[middle - 1] + available[middle]) / 2.0
+                }
+            }
+        })
+    }
+
+    /// 只合并质量为 Good 的设备；都不是 Good 时合并全部设备，质量取其中最好的一个
+    pub fn resolve(&self, readings: &[Option<Reading>]) -> Option<Reading> {
+        let good: Vec<Option<f64>> = readings
+            .iter()
+            .map(|r| r.filter(|r| r.quality.is_good()).map(|r| r.value))
+            .collect();
+        if let Some(value) = self.combine(&good) {
+            return Some(Reading {
+                value,
+                quality: Quality::Good,
+            });
+        }
+        let all: Vec<Option<f64>> = readings.iter().map(|r| r.map(|r| r.value)).collect();
+        Some(Reading {
+            value: self.combine(&all)?,
+            quality: readings.iter().flatten().map(|r| r.quality).min()?,
+        })
+    }
+
+    /// 偏差告警的新状态和当前偏差：偏差超过 tolerance 时告警，回到 tolerance - deadband 以内才解除，
+    /// 之间保持 active。不检查偏差，或少于两个设备有值（冗余丢失，无法比较）时返回 None，告警保持原状
+    pub fn discrepancy(&self, values: &[Option<f64>], active: bool) -> Option<(bool, f64)> {
+        let tolerance = self.tolerance?;
+        let available: Vec<f64> = values.iter().flatten().copied().collect();
+        if available.len() < 2 {
+            return None;
+        }
+        let min = available.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = available.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let spread = max - min;
+        let active = if active {
+            spread > tolerance - self.deadband
+        } else {
+            spread > tolerance
+        };
+        Some((active, spread))
+    }
+}
+
+/// 从 sensors.yaml 解析出的全部传感器属性、冗余分组和计算传感器
+#[derive(Debug, Clone, Default)]
+pub struct SensorCatalog {
+    pub devices: Vec<DeviceSpec>,
+    pub attrs: Vec<AttrSpec>,
+    pub groups: Vec<GroupSpec>,
+    pub computed: Vec<ComputedSpec>,
+}
+
+impl SensorCatalog {
+    /// 配置应当已经通过 validate_sensors 校验，这里只报告无法使用的公式
+    pub fn from_config(config: &Value) -> Result<Self, SensorConfigError> {
+        let mut catalog = SensorCatalog::default();
+        for (i, device) in array(config.get("devices")).iter().enumerate() {
+            catalog.devices.push(device_spec(device, &format!("devices[{}]", i))?);
+        }
+        for (i, plugin) in array(config.get("sensor_plugins")).iter().enumerate() {
+            let path = format!("sensor_plugins[{}]", i);
+            let plugin_name = str_field(plugin, "name", &path)?;
+            let empty = serde_json::Map::new();
+            let defaults = plugin.get("defaults").and_then(Value::as_object).unwrap_or(&empty);
+
+            for (j, sensor) in array(plugin.get("sensors")).iter().enumerate() {
+                let sensor_path = format!("{}.sensors[{}]", path, j);
+                let sensor_name = str_field(sensor, "name", &sensor_path)?;
+                let own = sensor.get("attrs").and_then(Value::as_object).unwrap_or(&empty);
+                let device_name = sensor.get("device").or(plugin.get("device")).and_then(Value::as_str);
+                let device = match device_name {
+                    Some(name) => Some(catalog.devices.iter().find(|d| d.name == name).ok_or_else(|| {
+                        SensorConfigError::Invalid {
+                            path: format!("{}.device", sensor_path),
+                            message: format!("unknown device '{}'", name),
+                        }
+                    })?),
+                    None => None,
+                };
+
+                // 属性顺序：先 defaults，再传感器自己新增的属性
+                let names = defaults.keys().chain(own.keys().filter(|name| !defaults.contains_key(*name)));
+                for name in names {
+                    let mut merged = defaults.get(name).cloned().unwrap_or_else(|| Value::Object(Default::default()));
+                    match own.get(name) {
+                        Some(Value::Number(address)) => merged["address"] = Value::Number(address.clone()),
+                        Some(Value::Object(attr)) => {
+                            if let Some(merged) = merged.as_object_mut() {
+                                merged.extend(attr.clone());
+                            }
+                        }
+                        _ => {}
+                    }
+                    let attr_path = format!("{}.attrs.{}", sensor_path, name);
+                    let mut spec = attr_spec(plugin_name, sensor_name, name, &merged, &attr_path)?;
+                    if let Some(device) = device {
+                        // 同一串口上的设备共用连接，所以属性上记录设备的从站地址
+                        spec.device = Some(device.name.clone());
+                        spec.slave_id = spec.slave_id.or(Some(device.slave_id()));
+                        if merged.get("byte_order").is_none() {
+                            spec.byte_order = device.byte_order;
+                        }
+                    }
+                    catalog.attrs.push(spec);
+                }
+            }
+
+            for (j, group) in array(plugin.get("group_list")).iter().enumerate() {
+                let group_path = format!("{}.group_list[{}]", path, j);
+                let policy = match group.get("policy") {
+                    Some(policy) => serde_json::from_value(policy.clone()).map_err(|e| SensorConfigError::Invalid {
+                        path: format!("{}.policy", group_path),
+                        message: e.to_string(),
+                    })?,
+                    None => GroupPolicy::default(),
+                };
+                let alarm_address = int_field(group, "alarm_address", &group_path)?;
+                catalog.groups.push(GroupSpec {
+                    plugin: plugin_name.to_string(),
+                    name: str_field(group, "name", &group_path)?.to_string(),
+                    devices: array(group.get("devices"))
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect(),
+                    policy,
+                    tolerance: group.get("tolerance").and_then(Value::as_f64),
+                    deadband: group.get("deadband").and_then(Value::as_f64).unwrap_or_else(|| {
+                        group.get("tolerance").and_then(Value::as_f64).unwrap_or_default() * DEFAULT_DEADBAND_RATIO
+                    }),
+                    alarm_address,
+                    alarm_level: group.get("alarm_level").and_then(Value::as_str).unwrap_or("warning").to_string(),
+                });
+            }
+        }
+
+        for (i, sensor) in array(config.get("computed_sensors")).iter().enumerate() {
+            let path = format!("computed_sensors[{}]", i);
+            let name = str_field(sensor, "name", &path)?;
+            let expression_path = format!("{}.expression", path);
+            let expression = str_field(sensor, "expression", &path)?;
+            catalog.computed.push(ComputedSpec {
+                name: name.to_string(),
+                label: sensor.get("label").and_then(Value::as_str).unwrap_or(name).to_string(),
+                unit: sensor.get("unit").and_then(Value::as_str).unwrap_or_default().to_string(),
+                address: int_field(sensor, "address", &path)?,
+                decimal_places: sensor.get("decimal_places").and_then(Value::as_u64).map(|d| d as u32),
+                expression: parse_expression(expression, &expression_path)?,
+            });
+        }
+        Ok(catalog)
+    }
+
+    pub fn attr(&self, id: &str) -> Option<&AttrSpec> {
+        self.attrs.iter().find(|attr| attr.id() == id)
+    }
+
+    /// 所有可记录的数据序列及其单位：属性为 "Plugin.Sensor.attr"，计算传感器为其名称
+    pub fn series(&self) -> Vec<(String, String)> {
+        self.attrs
+            .iter()
+            .map(|attr| (attr.id(), attr.unit.clone()))
+            .chain(self.computed.iter().map(|c| (c.name.clone(), c.unit.clone())))
+            .collect()
+    }
+}
+
+impl DeviceSpec {
+    /// 设备的从站地址，来自启用的连接配置
+    pub fn slave_id(&self) -> u8 {
+        if self.client.tcp.enable {
+            self.client.tcp.slave_id
+        } else {
+            self.client.serial.slave_id
+        }
+    }
+}
+
+fn device_spec(device: &Value, path: &str) -> Result<DeviceSpec, SensorConfigError> {
+    let invalid = |field: &str, e: serde_json::Error| SensorConfigError::Invalid {
+        path: format!("{}.{}", path, field),
+        message: e.to_string(),
+    };
+    let mut client = ModbusClientConfig::default();
+    client.tcp.enable = false;
+    if let Some(tcp) = device.get("tcp") {
+        client.tcp = serde_json::from_value(tcp.clone()).map_err(|e| invalid("tcp", e))?;
+        client.tcp.enable = true;
+    } else if let Some(serial) = device.get("serial") {
+        client.serial = serde_json::from_value(serial.clone()).map_err(|e| invalid("serial", e))?;
+        client.serial.enable = true;
+    }
+    if let Some(slave_id) = device.get("slave_id").and_then(Value::as_u64) {
+        client.tcp.slave_id = slave_id as u8;
+        client.serial.slave_id = slave_id as u8;
+    }
+    Ok(DeviceSpec {
+        name: str_field(device, "name", path)?.to_string(),
+        client,
+        byte_order: match device.get("byte_order") {
+            Some(order) => serde_json::from_value(order.clone()).map_err(|e| invalid("byte_order", e))?,
+            None => ByteOrder::default(),
+        },
+        poll_interval: device.get("poll_interval").and_then(Value::as_f64),
+    })
+}
+
+fn attr_spec(plugin: &str, sensor: &str, attr: &str, merged: &Value, path: &str) -> Result<AttrSpec, SensorConfigError> {
+    let read_method = match merged.get("read_method").and_then(Value::as_str) {
+        Some(name) => Some(ReadMethod::from_name(name).ok_or_else(|| SensorConfigError::Invalid {
+            path: format!("{}.read_method", path),
+            message: format!("unknown read method '{}'", name),
+        })?),
+        None => None,
+    };
+    let data_type = match merged.get("data_type").and_then(Value::as_str) {
+        Some(name) => {
+            // 寄存器只有 16 位，更大的位号解码时会移位溢出
+            let bit = match int_field::<u8>(merged, "bit", path)? {
+                Some(bit) if bit > 15 => {
+                    return Err(SensorConfigError::Invalid {
+                        path: format!("{}.bit", path),
+                        message: format!("{} is out of range", bit),
+                    })
+                }
+                bit => bit.unwrap_or(0),
+            };
+            let length = merged.get("length").and_then(Value::as_u64).unwrap_or(1) as u16;
+            DataType::from_name(name, bit, length).ok_or_else(|| SensorConfigError::Invalid {
+                path: format!("{}.data_type", path),
+                message: format!("unknown data type '{}'", name),
+            })?
+        }
+        None => DataType::default(),
+    };
+    let byte_order = match merged.get("byte_order") {
+        Some(order) => serde_json::from_value(order.clone()).map_err(|e| SensorConfigError::Invalid {
+            path: format!("{}.byte_order", path),
+            message: e.to_string(),
+        })?,
+        None => ByteOrder::default(),
+    };
+    let write_method = match merged.get("write_method").and_then(Value::as_str) {
+        Some(name) => Some(WriteMethod::from_name(name).ok_or_else(|| SensorConfigError::Invalid {
+            path: format!("{}.write_method", path),
+            message: format!("unknown write method '{}'", name),
+        })?),
+        None => None,
+    };
+    let formula = match merged.get("formula").and_then(Value::as_str) {
+        Some(formula) => Some(parse_expression(formula, &format!("{}.formula", path))?),
+        None => None,
+    };
+    let write_formula = match merged.get("write_formula").and_then(Value::as_str) {
+        Some(formula) => Some(parse_expression(formula, &format!("{}.write_formula", path))?),
+        None => None,
+    };
+    Ok(AttrSpec {
+        plugin: plugin.to_string(),
+        sensor: sensor.to_string(),
+        attr: attr.to_string(),
+        address: int_field(merged, "address", path)?,
+        read_method,
+        write_method,
+        write_formula,
+        device: None,
+        data_type,
+        batch_address: int_field(merged, "batch_address", path)?,
+        byte_order,
+        slave_id: merged.get("slave_id").and_then(Value::as_u64).map(|id| id as u8),
+        formula,
+        unit: merged.get("unit").and_then(Value::as_str).unwrap_or_default().to_string(),
+        min: merged.get("min").and_then(Value::as_f64),
+        max: merged.get("max").and_then(Value::as_f64),
+        raw_min: merged.get("raw_min").and_then(Value::as_f64),
+        raw_max: merged.get("raw_max").and_then(Value::as_f64),
+        frozen_timeout: merged.get("frozen_timeout").and_then(Value::as_f64),
+        decimal_places: merged.get("decimal_places").and_then(Value::as_u64).map(|d| d as u32),
+    })
+}
+
+fn parse_expression(source: &str, path: &str) -> Result<Expression, SensorConfigError> {
+    Expression::parse(source).map_err(|source| SensorConfigError::Expression {
+        path: path.to_string(),
+        source,
+    })
+}
+
+fn str_field<'a>(value: &'a Value, name: &str, path: &str) -> Result<&'a str, SensorConfigError> {
+    value
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| SensorConfigError::Invalid {
+            path: format!("{}.{}", path, name),
+            message: "is required".to_string(),
+        })
+}
+
+/// 整数字段，超出目标类型的范围时报错而不是截断
+fn int_field<T: TryFrom<u64>>(value: &Value, name: &str, path: &str) -> Result<Option<T>, SensorConfigError> {
+    let Some(n) = value.get(name).and_then(Value::as_u64) else {
+        return Ok(None);
+    };
+    T::try_from(n).map(Some).map_err(|_| SensorConfigError::Invalid {
+        path: format!("{}.{}", path, name),
+        message: format!("{} is out of range", n),
+    })
+}
+
+fn array(value: Option<&Value>) -> &[Value] {
+    value.and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn round(value: f64, decimal_places: Option<u32>) -> f64 {
+    match decimal_places {
+        Some(places) => {
+            let factor = 10f64.powi(places as i32);
+            (value * factor).round() / factor
+        }
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_shipped_sensors_config() {
+        let content = std::fs::read_to_string("configs/sensors.yaml").unwrap();
+        let config: Value = serde_yaml::from_str(&content).unwrap();
+        let catalog = SensorCatalog::from_config(&config).unwrap();
+
+        // 简写的地址 "value: 3328"
+        let t1 = catalog.attr("Temperatures.T1.value").unwrap();
+        assert_eq!(t1.address, Some(3328));
+        assert_eq!(t1.read_method, Some(ReadMethod::HoldingRegisters));
+        assert_eq!(t1.scale(|_| Some(253.0)).unwrap(), 25.3);
+
+        // 传感器自己的公式覆盖 defaults
+        let f2 = catalog.attr("Flows.F2'.value").unwrap();
+        assert_eq!(f2.scale(|_| Some(8000.0)).unwrap(), 125.0);
+
+        // 没有地址的属性由原始值计算
+        let dew_point = catalog.attr("PHTs.PHT1.DewPoint").unwrap();
+        assert_eq!(dew_point.address, None);
+        let raw = |name: &str| match name {
+            "Temperature" => Some(2500.0),
+            "Humidity" => Some(6000.0),
+            _ => None,
+        };
+        assert_eq!(dew_point.scale(raw).unwrap(), 17.0);
+
+        assert_eq!(catalog.attr("Leakages.LE1.value").unwrap().read_method, Some(ReadMethod::Coils));
+        let cap = catalog.computed.iter().find(|c| c.name == "CAP").unwrap();
+        assert_eq!(cap.unit, "kW");
+        assert!(catalog.series().contains(&("T4-T1".to_string(), "℃".to_string())));
+
+        let p1 = catalog.groups.iter().find(|g| g.plugin == "Pressures" && g.name == "P1").unwrap();
+        assert_eq!(p1.devices, vec!["P1", "P1'"]);
+        assert_eq!(p1.policy, GroupPolicy::PrimaryWithFallback);
+    }
+
+    #[test]
+    fn test_group_policies() {
+        let mut group = GroupSpec {
+            plugin: "Temperatures".to_string(),
+            name: "T1".to_string(),
+            devices: vec!["T1".to_string(), "T1'".to_string(), "T1''".to_string()],
+            policy: GroupPolicy::PrimaryWithFallback,
+            tolerance: Some(1.0),
+            deadband: 0.1,
+            alarm_address: None,
+            alarm_level: "warning".to_string(),
+        };
+        let values = [Some(20.0), Some(26.0), Some(21.0)];
+        let cases = [
+            (GroupPolicy::PrimaryWithFallback, Some(20.0)),
+            (GroupPolicy::Average, Some(67.0 / 3.0)),
+            (GroupPolicy::Min, Some(20.0)),
+            (GroupPolicy::Max, Some(26.0)),
+            (GroupPolicy::MedianOfThree, Some(21.0)),
+        ];
+        for (policy, expected) in cases {
+            group.policy = policy;
+            assert_eq!(group.combine(&values), expected, "{:?}", policy);
+        }
+        assert_eq!(group.discrepancy(&values, false), Some((true, 6.0)));
+
+        // 主设备失效时切换到备用设备
+        group.policy = GroupPolicy::PrimaryWithFallback;
+        assert_eq!(group.combine(&[None, Some(26.0), Some(21.0)]), Some(26.0));
+        group.policy = GroupPolicy::MedianOfThree;
+        assert_eq!(group.combine(&[None, Some(26.0), Some(21.0)]), Some(23.5));
+        assert_eq!(group.combine(&[None, None, None]), None);
+        assert_eq!(group.discrepancy(&[Some(20.0), None, Some(20.5)], false), Some((false, 0.5)));
+        // 回差：已经告警时偏差要回到 0.9 以内才解除
+        assert_eq!(group.discrepancy(&[Some(20.0), None, Some(20.9375)], true), Some((true, 0.9375)));
+        assert_eq!(group.discrepancy(&[Some(20.0), None, Some(20.9375)], false), Some((false, 0.9375)));
+        assert_eq!(group.discrepancy(&[Some(20.0), None, Some(20.5)], true), Some((false, 0.5)));
+        // 只剩一个设备时无法比较
+        assert_eq!(group.discrepancy(&[Some(20.0), None, None], true), None);
+    }
+
+    #[test]
+    fn test_quality() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "sensor_plugins": [{
+                "name": "Flows",
+                "defaults": {"value": {"formula": "($value - 4000) / 16", "min": 0, "max": 1000, "raw_min": 3800, "raw_max": 20500}},
+                "sensors": [{"name": "F1", "attrs": {"value": 3408}}]
+            }]
+        }))
+        .unwrap();
+        let attr = &catalog.attrs[0];
+        assert_eq!(attr.check_range(Some(12000.0), 500.0), Quality::Good);
+        assert_eq!(attr.check_range(Some(0.0), -250.0), Quality::OpenLoop);
+        assert_eq!(attr.check_range(Some(3900.0), -6.25), Quality::UnderRange);
+        assert_eq!(attr.check_range(Some(21000.0), 1062.5), Quality::OverRange);
+        assert_eq!(Quality::worst([Quality::Good, Quality::CommError, Quality::Frozen]), Quality::CommError);
+        assert_eq!(Quality::worst([]), Quality::Good);
+
+        let group = GroupSpec {
+            plugin: "Flows".to_string(),
+            name: "F2".to_string(),
+            devices: vec!["F2".to_string(), "F2'".to_string()],
+            policy: GroupPolicy::PrimaryWithFallback,
+            tolerance: None,
+            deadband: 0.0,
+            alarm_address: None,
+            alarm_level: "warning".to_string(),
+        };
+        let reading = |value, quality| Some(Reading { value, quality });
+        // 主设备断线时用备用设备
+        assert_eq!(
+            group.resolve(&[reading(-250.0, Quality::OpenLoop), reading(40.0, Quality::Good)]),
+            reading(40.0, Quality::Good)
+        );
+        assert_eq!(
+            group.resolve(&[reading(-250.0, Quality::OpenLoop), reading(40.0, Quality::Stale)]),
+            reading(-250.0, Quality::Stale)
+        );
+        assert_eq!(group.resolve(&[None, None]), None);
+    }
+
+    #[test]
+    fn test_data_types_and_byte_order() {
+        // 123.5f32 = 0x42F70000
+        let cases = [
+            (ByteOrder::Abcd, [0x42F7, 0x0000]),
+            (ByteOrder::Cdab, [0x0000, 0x42F7]),
+            (ByteOrder::Badc, [0xF742, 0x0000]),
+            (ByteOrder::Dcba, [0x0000, 0xF742]),
+        ];
+        for (order, words) in cases {
+            assert_eq!(DataType::F32.decode(&words, order), Some(123.5), "{:?}", order);
+            assert_eq!(DataType::F32.encode(123.5, order), words, "{:?}", order);
+        }
+        assert_eq!(DataType::I16.decode(&[0xFF38], ByteOrder::Abcd), Some(-200.0));
+        assert_eq!(DataType::U16.decode(&[0x3412], ByteOrder::Badc), Some(4660.0));
+        assert_eq!(DataType::U32.decode(&[0x0001, 0x0002], ByteOrder::Abcd), Some(65538.0));
+        assert_eq!(DataType::I32.encode(-2.0, ByteOrder::Cdab), vec![0xFFFE, 0xFFFF]);
+        assert_eq!(DataType::U16.encode(-5.0, ByteOrder::Abcd), vec![0]);
+        let words = DataType::F64.encode(-1234.5678, ByteOrder::Dcba);
+        assert_eq!(words.len(), 4);
+        assert_eq!(DataType::F64.decode(&words, ByteOrder::Dcba), Some(-1234.5678));
+        // 寄存器不够时没有值
+        assert_eq!(DataType::U32.decode(&[0x0001], ByteOrder::Abcd), None);
+
+        assert_eq!(DataType::Bit(3).decode(&[0b1000], ByteOrder::Abcd), Some(1.0));
+        assert_eq!(DataType::Bit(2).decode(&[0b1000], ByteOrder::Abcd), Some(0.0));
+        assert_eq!(DataType::Bit(3).encode(1.0, ByteOrder::Abcd), vec![0b1000]);
+
+        let model = DataType::String(4);
+        let words = [0x4344, 0x552D, 0x3031, 0x0000];
+        assert_eq!(model.decode_text(&words, ByteOrder::Abcd).unwrap(), "CDU-01");
+        assert_eq!(model.decode(&words, ByteOrder::Abcd), None);
+
+        let catalog = SensorCatalog::from_config(&json!({
+            "devices": [{"name": "meter", "tcp": {"host": "192.168.1.160"}, "byte_order": "CDAB"}],
+            "sensor_plugins": [{
+                "name": "Meters",
+                "device": "meter",
+                "defaults": {
+                    "Power": {"read_method": "read_input_registers", "data_type": "f32"},
+                    "Energy": {"read_method": "read_input_registers", "data_type": "u32", "byte_order": "ABCD"},
+                    "Running": {"read_method": "read_holding_registers", "data_type": "bit", "bit": 4},
+                    "Model": {"read_method": "read_holding_registers", "data_type": "string", "length": 8}
+                },
+                "sensors": [{"name": "M1", "attrs": {"Power": 100, "Energy": 102, "Running": 200, "Model": 300}}]
+            }]
+        }))
+        .unwrap();
+        let power = catalog.attr("Meters.M1.Power").unwrap();
+        assert_eq!((power.data_type, power.byte_order, power.width()), (DataType::F32, ByteOrder::Cdab, 2));
+        assert_eq!(power.decode(&[0x0000, 0x42F7]), Some(123.5));
+        // 属性自己的字节序优先于设备的
+        assert_eq!(catalog.attr("Meters.M1.Energy").unwrap().byte_order, ByteOrder::Abcd);
+        assert_eq!(catalog.attr("Meters.M1.Running").unwrap().data_type, DataType::Bit(4));
+        assert_eq!(catalog.attr("Meters.M1.Model").unwrap().width(), 8);
+    }
+
+    #[test]
+    fn test_invalid_formula_is_reported_with_path() {
+        let config = serde_json::json!({
+            "sensor_plugins": [{
+                "name": "Flows",
+                "defaults": {"value": {"read_method": "read_holding_registers", "formula": "($value - 4000"}},
+                "sensors": [{"name": "F1", "attrs": {"value": 3408}}]
+            }]
+        });
+        match SensorCatalog::from_config(&config) {
+            Err(SensorConfigError::Expression { path, .. }) => {
+                assert_eq!(path, "sensor_plugins[0].sensors[0].attrs.value.formula")
+            }
+            other => panic!("Expected Expression error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_alarm_address_is_reported() {
+        let config = serde_json::json!({
+            "sensor_plugins": [{
+                "name": "Temperatures",
+                "sensors": [{"name": "T1", "attrs": {"value": 3340}}, {"name": "T1'", "attrs": {"value": 3329}}],
+                "group_list": [{"name": "T1", "devices": ["T1", "T1'"], "tolerance": 2, "alarm_address": 70000}]
+            }]
+        });
+        match SensorCatalog::from_config(&config) {
+            Err(SensorConfigError::Invalid { path, message }) => {
+                assert_eq!(path, "sensor_plugins[0].group_list[0].alarm_address");
+                assert_eq!(message, "70000 is out of range");
+            }
+            other => panic!("Expected Invalid error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_bit_is_reported() {
+        let config = serde_json::json!({
+            "sensor_plugins": [{
+                "name": "Status",
+                "sensors": [{"name": "Fault", "attrs": {"value": {"address": 40, "data_type": "bit", "bit": 16}}}]
+            }]
+        });
+        match SensorCatalog::from_config(&config) {
+            Err(SensorConfigError::Invalid { path, message }) => {
+                assert_eq!(path, "sensor_plugins[0].sensors[0].attrs.value.bit");
+                assert_eq!(message, "16 is out of range");
+            }
+            other => panic!("Expected Invalid error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::datetime;
+
+/// 审计日志默认路径，每行一条 JSON 记录
+pub const DEFAULT_AUDIT_LOG_PATH: &str = "logs/audit.log";
+
+/// 一条审计记录：谁在什么时间做了什么，结果如何
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub time: String,
+    pub actor: String,
+    pub action: String,
+    pub detail: serde_json::Value,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 修改系统状态的操作（设置时钟、写寄存器等）都记录到审计日志
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_AUDIT_LOG_PATH)
+    }
+}
+
+impl AuditLog {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 追加一条记录，写入失败只记日志，不影响操作本身
+    pub fn record<E: std::fmt::Display>(
+        &self,
+        actor: &str,
+        action: &str,
+        detail: serde_json::Value,
+        result: std::result::Result<(), E>,
+    ) -> AuditEntry {
+        let entry = AuditEntry {
+            time: datetime::get_current_time(),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            detail,
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        };
+        info!(
+            "AUDIT {} {} {} {}",
+            entry.actor,
+            entry.action,
+            entry.detail,
+            if entry.success { "ok" } else { "failed" }
+        );
+        if let Err(e) = self.append(&entry) {
+            warn!("Failed to write audit log {:?}: {}", self.path, e);
+        }
+        entry
+    }
+
+    fn append(&self, entry: &AuditEntry) -> io::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let line = serde_json::to_string(entry)?;
+        writeln!(file, "{}", line)?;
+        file.sync_data()
+    }
+
+    /// 最近的 limit 条记录，按时间先后排列
+    pub fn recent(&self, limit: usize) -> io::Result<Vec<AuditEntry>> {
+        let _guard = self.lock.lock().unwrap();
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let entries: Vec<AuditEntry> = BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.into_iter().skip(skip).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_record_and_read_back() {
+        let dir = tempdir().unwrap();
+        let audit = AuditLog::new(dir.path().join("logs").join("audit.log"));
+        assert!(audit.recent(10).unwrap().is_empty());
+
+        audit.record("127.0.0.1", "clock.set", json!({"time": "2026-02-10 09:38:48 +0800"}), Ok::<(), String>(()));
+        audit.record("127.0.0.1", "clock.sync", json!({}), Err("timeout"));
+
+        let entries = audit.recent(10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].success);
+        assert_eq!(entries[1].action, "clock.sync");
+        assert_eq!(entries[1].error.as_deref(), Some("timeout"));
+        assert_eq!(audit.recent(1).unwrap(), entries[1..].to_vec());
+    }
+}
@@ -0,0 +1,375 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::net::UdpSocket;
+
+use crate::config::loader::NtpConfig;
+use crate::utils::datetime;
+
+/// NTP 时间从 1900-01-01 开始计数，Unix 时间从 1970-01-01 开始
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// 允许设置的时间范围，超出范围的一般是输入错误或 RTC 电池失效
+const MIN_VALID_UNIX: i64 = 1_577_836_800; // 2020-01-01T00:00:00Z
+const MAX_VALID_UNIX: i64 = 4_102_444_800; // 2100-01-01T00:00:00Z
+
+#[derive(Debug, Error)]
+pub enum ClockError {
+    #[error("time {0} is outside the allowed range 2020-01-01 .. 2100-01-01")]
+    OutOfRange(String),
+
+    #[error("permission denied: the service needs CAP_SYS_TIME to set the system clock")]
+    PermissionDenied,
+
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("NTP sync is disabled")]
+    Disabled,
+
+    #[error("no response from NTP server {0}")]
+    Timeout(String),
+
+    #[error("invalid SNTP response: {0}")]
+    InvalidResponse(String),
+}
+
+pub type Result<T> = std::result::Result<T, ClockError>;
+
+/// 系统时钟，测试中可以替换为假时钟
+pub trait SystemClock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+    fn set(&self, time: OffsetDateTime) -> io::Result<()>;
+}
+
+/// 操作系统的实时时钟（CLOCK_REALTIME）
+pub struct OsClock;
+
+impl SystemClock for OsClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+
+    fn set(&self, time: OffsetDateTime) -> io::Result<()> {
+        let spec = libc::timespec {
+            tv_sec: time.unix_timestamp() as libc::time_t,
+            tv_nsec: time.nanosecond() as libc::c_long,
+        };
+        // SAFETY: spec 是有效的 timespec，clock_settime 只读取它
+        let result = unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &spec) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+/// 一次 SNTP 查询的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SntpSample {
+    /// 服务器时间 - 本机时间（秒）
+    pub offset: f64,
+    /// 往返延迟（秒）
+    pub delay: f64,
+    pub stratum: u8,
+}
+
+/// 最近一次同步的状态，在界面上展示
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub server: Option<String>,
+    pub last_attempt: Option<String>,
+    pub last_success: Option<String>,
+    pub offset_ms: Option<f64>,
+    pub delay_ms: Option<f64>,
+    pub stratum: Option<u8>,
+    pub error: Option<String>,
+}
+
+/// 读取和设置系统时钟，按配置从局域网内的 NTP/SNTP 服务器同步
+pub struct ClockService {
+    clock: Arc<dyn SystemClock>,
+    ntp: NtpConfig,
+    status: Mutex<SyncStatus>,
+}
+
+impl ClockService {
+    pub fn new(clock: Arc<dyn SystemClock>, ntp: NtpConfig) -> Self {
+        Self {
+            clock,
+            ntp,
+            status: Mutex::new(SyncStatus::default()),
+        }
+    }
+
+    pub fn now(&self) -> OffsetDateTime {
+        self.clock.now()
+    }
+
+    pub fn ntp(&self) -> &NtpConfig {
+        &self.ntp
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// 手动设置系统时间
+    pub fn set_time(&self, time: OffsetDateTime) -> Result<()> {
+        validate(time)?;
+        self.clock.set(time).map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => ClockError::PermissionDenied,
+            _ => ClockError::Io(e),
+        })?;
+        info!("System clock set to {}", datetime::format_rfc3339(&time));
+        Ok(())
+    }
+
+    /// 从配置的服务器同步一次，并按偏移量校正系统时钟
+    pub async fn sync(&self) -> Result<SntpSample> {
+        if !self.ntp.enable {
+            return Err(ClockError::Disabled);
+        }
+        let server = format!("{}:{}", self.ntp.host, self.ntp.port);
+        let timeout = Duration::from_secs_f64(self.ntp.timeout);
+        let attempt = datetime::get_current_time();
+
+        let result = match query_sntp(&server, timeout, self.clock.as_ref()).await {
+            Ok(sample) => {
+                let corrected = self.clock.now() + time::Duration::seconds_f64(sample.offset);
+                self.set_time(corrected).map(|_| sample)
+            }
+            Err(e) => Err(e),
+        };
+
+        let mut status = self.status.lock().unwrap();
+        status.server = Some(server.clone());
+        status.last_attempt = Some(attempt.clone());
+        match &result {
+            Ok(sample) => {
+                status.last_success = Some(attempt);
+                status.offset_ms = Some(sample.offset * 1000.0);
+                status.delay_ms = Some(sample.delay * 1000.0);
+                status.stratum = Some(sample.stratum);
+                status.error = None;
+                info!("Synced clock from {}: offset {:.3}s, delay {:.3}s", server, sample.offset, sample.delay);
+            }
+            Err(e) => {
+                status.error = Some(e.to_string());
+                warn!("Clock sync from {} failed: {}", server, e);
+            }
+        }
+        result
+    }
+}
+
+fn validate(time: OffsetDateTime) -> Result<()> {
+    if (MIN_VALID_UNIX..MAX_VALID_UNIX).contains(&time.unix_timestamp()) {
+        Ok(())
+    } else {
+        Err(ClockError::OutOfRange(datetime::format_rfc3339(&time)))
+    }
+}
+
+/// 发送一个 SNTP v4 客户端请求（RFC 4330），按本机时钟计算偏移量和延迟
+pub async fn query_sntp(server: &str, timeout: Duration, clock: &dyn SystemClock) -> Result<SntpSample> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+
+    let mut request = [0u8; 48];
+    // LI = 0，版本 4，模式 3（客户端）
+    request[0] = (4 << 3) | 3;
+    let t1 = clock.now();
+    let originate = to_ntp(t1);
+    request[40..48].copy_from_slice(&originate);
+    socket.send(&request).await?;
+
+    let mut response = [0u8; 48];
+    let received = tokio::time::timeout(timeout, socket.recv(&mut response))
+        .await
+        .map_err(|_| ClockError::Timeout(server.to_string()))??;
+    let t4 = clock.now();
+
+    if received < 48 {
+        return Err(ClockError::InvalidResponse(format!("{} bytes", received)));
+    }
+    let leap = response[0] >> 6;
+    let mode = response[0] & 0x07;
+    let stratum = response[1];
+    if mode != 4 && mode != 5 {
+        return Err(ClockError::InvalidResponse(format!("unexpected mode {}", mode)));
+    }
+    if stratum == 0 {
+        let code = String::from_utf8_lossy(&response[12..16]).to_string();
+        return Err(ClockError::InvalidResponse(format!("kiss-o'-death {}", code)));
+    }
+    if leap == 3 {
+        return Err(ClockError::InvalidResponse("server clock is not synchronized".to_string()));
+    }
+    if response[24..32] != originate {
+        return Err(ClockError::InvalidResponse("originate timestamp mismatch".to_string()));
+    }
+
+    let t1 = unix_seconds(t1);
+    let t2 = from_ntp(&response[32..40]);
+    let t3 = from_ntp(&response[40..48]);
+    let t4 = unix_seconds(t4);
+    Ok(SntpSample {
+        offset: ((t2 - t1) + (t3 - t4)) / 2.0,
+        delay: (t4 - t1) - (t3 - t2),
+        stratum,
+    })
+}
+
+fn unix_seconds(time: OffsetDateTime) -> f64 {
+    time.unix_timestamp() as f64 + time.nanosecond() as f64 / 1e9
+}
+
+fn to_ntp(time: OffsetDateTime) -> [u8; 8] {
+    let seconds = (time.unix_timestamp() + NTP_UNIX_OFFSET) as u32;
+    let fraction = ((time.nanosecond() as u64) << 32) / 1_000_000_000;
+    let mut bytes = [0u8; 8];
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..].copy_from_slice(&(fraction as u32).to_be_bytes());
+    bytes
+}
+
+fn from_ntp(bytes: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64;
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as f64;
+    (seconds - NTP_UNIX_OFFSET) as f64 + fraction / 4_294_967_296.0
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// 可以设置的假时钟，记录所有 set 调用
+    pub struct FakeClock {
+        pub time: Mutex<OffsetDateTime>,
+        pub writable: bool,
+    }
+
+    impl FakeClock {
+        pub fn new(time: OffsetDateTime) -> Self {
+            Self {
+                time: Mutex::new(time),
+                writable: true,
+            }
+        }
+    }
+
+    impl SystemClock for FakeClock {
+        fn now(&self) -> OffsetDateTime {
+            *self.time.lock().unwrap()
+        }
+
+        fn set(&self, time: OffsetDateTime) -> io::Result<()> {
+            if !self.writable {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            *self.time.lock().unwrap() = time;
+            Ok(())
+        }
+    }
+
+    /// 本地 SNTP 服务器桩：回复比请求方时钟快 skew 秒的时间
+    pub async fn sntp_stub(skew: f64, stratum: u8) -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut request = [0u8; 48];
+            while let Ok((_, peer)) = socket.recv_from(&mut request).await {
+                let client_time = from_ntp(&request[40..48]);
+                let server_time = OffsetDateTime::from_unix_timestamp_nanos(((client_time + skew) * 1e9) as i128).unwrap();
+                let mut response = [0u8; 48];
+                response[0] = (4 << 3) | 4;
+                response[1] = stratum;
+                response[12..16].copy_from_slice(b"RATE");
+                response[24..32].copy_from_slice(&request[40..48]);
+                response[32..40].copy_from_slice(&to_ntp(server_time));
+                response[40..48].copy_from_slice(&to_ntp(server_time));
+                let _ = socket.send_to(&response, peer).await;
+            }
+        });
+        addr
+    }
+
+    pub fn ntp_config(addr: &str) -> NtpConfig {
+        let (host, port) = addr.rsplit_once(':').unwrap();
+        NtpConfig {
+            enable: true,
+            host: host.to_string(),
+            port: port.parse().unwrap(),
+            timeout: 0.5,
+            ..NtpConfig::default()
+        }
+    }
+
+    fn base_time() -> OffsetDateTime {
+        datetime::parse_time("2026-02-10 09:38:48 +0800").unwrap()
+    }
+
+    #[test]
+    fn test_ntp_timestamp_roundtrip() {
+        let time = base_time() + time::Duration::milliseconds(250);
+        let seconds = from_ntp(&to_ntp(time));
+        assert!((seconds - unix_seconds(time)).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_sync_from_stub() {
+        let addr = sntp_stub(5.0, 2).await;
+        let clock = Arc::new(FakeClock::new(base_time()));
+        let service = ClockService::new(clock.clone(), ntp_config(&addr));
+
+        let sample = service.sync().await.unwrap();
+        assert!((sample.offset - 5.0).abs() < 1e-3, "offset {}", sample.offset);
+        assert_eq!(sample.stratum, 2);
+        assert_eq!(clock.now(), base_time() + time::Duration::seconds_f64(sample.offset));
+
+        let status = service.status();
+        assert_eq!(status.server, Some(addr));
+        assert!(status.last_success.is_some());
+        assert!(status.error.is_none());
+        assert!((status.offset_ms.unwrap() - 5000.0).abs() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_sync_failures_are_reported() {
+        let addr = sntp_stub(5.0, 0).await;
+        let clock = Arc::new(FakeClock::new(base_time()));
+        let service = ClockService::new(clock.clone(), ntp_config(&addr));
+        assert!(matches!(service.sync().await, Err(ClockError::InvalidResponse(_))));
+        assert_eq!(clock.now(), base_time());
+        let status = service.status();
+        assert!(status.error.unwrap().contains("kiss-o'-death RATE"));
+        assert!(status.last_success.is_none());
+
+        // 没有服务器监听的端口
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap().to_string();
+        let service = ClockService::new(clock.clone(), ntp_config(&addr));
+        assert!(matches!(service.sync().await, Err(ClockError::Timeout(_))));
+
+        let service = ClockService::new(clock, NtpConfig::default());
+        assert!(matches!(service.sync().await, Err(ClockError::Disabled)));
+    }
+
+    #[test]
+    fn test_set_time_validation() {
+        let clock = Arc::new(FakeClock {
+            time: Mutex::new(base_time()),
+            writable: false,
+        });
+        let service = ClockService::new(clock, NtpConfig::default());
+        let old = datetime::parse_time("1999-12-31 23:59:59 +0000").unwrap();
+        assert!(matches!(service.set_time(old), Err(ClockError::OutOfRange(_))));
+        assert!(matches!(service.set_time(base_time()), Err(ClockError::PermissionDenied)));
+    }
+}
@@ -0,0 +1,858 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+use time::{Date, Month, OffsetDateTime};
+
+use crate::config::loader::HistoryConfig;
+use crate::utils::file_store::write_atomic;
+
+const DAY_MS: i64 = 86_400_000;
+
+/// 原始记录：时间戳（毫秒）i64 + 序列号 u32 + 值 f64，小端
+const RAW_RECORD_SIZE: usize = 20;
+
+/// 降采样记录：桶起始时间 i64 + 序列号 u32 + 点数 u32 + min/max/avg f64
+const ROLLUP_RECORD_SIZE: usize = 40;
+
+/// 内存中待写入的数据超过这个大小时立即落盘
+const FLUSH_BYTES: usize = 64 * 1024;
+
+const SERIES_FILE: &str = "series.json";
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("invalid series registry {path}: {source}")]
+    Registry {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("unknown series '{0}'")]
+    UnknownSeries(String),
+}
+
+pub type Result<T> = std::result::Result<T, HistoryError>;
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> HistoryError + '_ {
+    move |source| HistoryError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// 一个数据序列：传感器属性 "Plugin.Sensor.attr" 或计算传感器名
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesInfo {
+    pub name: String,
+    pub unit: String,
+}
+
+/// 查询结果中的一个点，原始数据的 min、max、avg 相同且 count 为 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Unix 时间戳（毫秒），降采样数据为桶的起始时间
+    pub time_ms: i64,
+    /// 在查询的序列列表中的下标
+    pub series: usize,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Tier {
+    Rollup,
+    Raw,
+}
+
+impl Tier {
+    fn dir_name(self) -> &'static str {
+        match self {
+            Tier::Rollup => "rollup",
+            Tier::Raw => "raw",
+        }
+    }
+
+    fn record_size(self) -> usize {
+        match self {
+            Tier::Rollup => ROLLUP_RECORD_SIZE,
+            Tier::Raw => RAW_RECORD_SIZE,
+        }
+    }
+}
+
+/// 一次维护的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaintenanceReport {
+    /// 降采样的天数
+    pub downsampled: usize,
+    /// 因超期或超出磁盘上限删除的文件数
+    pub removed: usize,
+    /// 维护后占用的字节数
+    pub bytes: u64,
+}
+
+struct Inner {
+    series: Vec<SeriesInfo>,
+    index: HashMap<String, u32>,
+    /// 每个序列最后记录的时间槽（时间戳 / resolution）
+    last_slot: HashMap<u32, i64>,
+    /// 按天（自 1970-01-01 起的天数）缓存的原始记录
+    pending: BTreeMap<i64, Vec<u8>>,
+    pending_bytes: usize,
+}
+
+/// 磁盘上的时间序列存储，按 UTC 日期分文件：
+/// raw/20260210.bin 保存原始数据，rollup/20260210.bin 保存降采样后的数据
+pub struct HistoryStore {
+    dir: PathBuf,
+    config: HistoryConfig,
+    inner: Mutex<Inner>,
+}
+
+impl HistoryStore {
+    pub fn open(config: &HistoryConfig) -> Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        for tier in [Tier::Raw, Tier::Rollup] {
+            let path = dir.join(tier.dir_name());
+            fs::create_dir_all(&path).map_err(io_error(&path))?;
+        }
+
+        let registry = dir.join(SERIES_FILE);
+        let series: Vec<SeriesInfo> = match fs::read(&registry) {
+            Ok(content) => serde_json::from_slice(&content).map_err(|source| HistoryError::Registry {
+                path: registry.clone(),
+                source,
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(io_error(&registry)(e)),
+        };
+        let index = series.iter().enumerate().map(|(i, s)| (s.name.clone(), i as u32)).collect();
+        Ok(Self {
+            dir,
+            config: config.clone(),
+            inner: Mutex::new(Inner {
+                series,
+                index,
+                last_slot: HashMap::new(),
+                pending: BTreeMap::new(),
+                pending_bytes: 0,
+            }),
+        })
+    }
+
+    /// 登记序列及其单位，已有的序列更新单位，序列号保持不变
+    pub fn register(&self, series: &[(String, String)]) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut changed = false;
+        for (name, unit) in series {
+            match inner.index.get(name).copied() {
+                Some(id) if inner.series[id as usize].unit != *unit => {
+                    inner.series[id as usize].unit = unit.clone();
+                    changed = true;
+                }
+                Some(_) => {}
+                None => {
+                    Self::add_series(&mut inner, name, unit);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.save_registry(&inner)?;
+        }
+        Ok(())
+    }
+
+    pub fn series(&self) -> Vec<SeriesInfo> {
+        self.inner.lock().unwrap().series.clone()
+    }
+
+    fn add_series(inner: &mut Inner, name: &str, unit: &str) -> u32 {
+        let id = inner.series.len() as u32;
+        inner.series.push(SeriesInfo {
+            name: name.to_string(),
+            unit: unit.to_string(),
+        });
+        inner.index.insert(name.to_string(), id);
+        id
+    }
+
+    fn save_registry(&self, inner: &Inner) -> Result<()> {
+        let path = self.dir.join(SERIES_FILE);
+        let content = serde_json::to_vec_pretty(&inner.series).map_err(|source| HistoryError::Registry {
+            path: path.clone(),
+            source,
+        })?;
+        write_atomic(&path, &content).map_err(io_error(&path))
+    }
+
+    /// 记录一次轮询的结果，同一序列在一个 resolution 间隔内只保留第一个值，
+    /// 返回实际记录的点数
+    pub fn record(&self, time: OffsetDateTime, values: &[(String, f64)]) -> Result<usize> {
+        let time_ms = unix_ms(time);
+        let slot = time_ms.div_euclid(((self.config.resolution * 1000.0) as i64).max(1));
+        let day = time_ms.div_euclid(DAY_MS);
+
+        let mut inner = self.inner.lock().unwrap();
+        let mut registered = false;
+        let mut recorded = 0;
+        for (name, value) in values {
+            if !value.is_finite() {
+                continue;
+            }
+            let id = match inner.index.get(name) {
+                Some(id) => *id,
+                None => {
+                    registered = true;
+                    Self::add_series(&mut inner, name, "")
+                }
+            };
+            if inner.last_slot.insert(id, slot) == Some(slot) {
+                continue;
+            }
+            let buffer = inner.pending.entry(day).or_default();
+            buffer.extend_from_slice(&time_ms.to_le_bytes());
+            buffer.extend_from_slice(&id.to_le_bytes());
+            buffer.extend_from_slice(&value.to_le_bytes());
+            inner.pending_bytes += RAW_RECORD_SIZE;
+            recorded += 1;
+        }
+        if registered {
+            self.save_registry(&inner)?;
+        }
+        if inner.pending_bytes >= FLUSH_BYTES {
+            self.flush_locked(&mut inner)?;
+        }
+        Ok(recorded)
+    }
+
+    /// 把缓存的记录追加到当天的原始数据文件
+    pub fn flush(&self) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        self.flush_locked(&mut inner)
+    }
+
+    fn flush_locked(&self, inner: &mut Inner) -> Result<()> {
+        while let Some((day, buffer)) = inner.pending.pop_first() {
+            let path = self.file_path(Tier::Raw, day);
+            let result = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| {
+                    // 掉电会在文件末尾留下不完整的记录，先截断到记录边界，否则之后追加的记录全部错位
+                    let len = file.metadata()?.len();
+                    let len = len - len % RAW_RECORD_SIZE as u64;
+                    file.set_len(len)?;
+                    file.write_all(&buffer).and_then(|()| file.sync_data()).inspect_err(|_| {
+                        // 去掉写了一半的数据，整个缓冲区下次重写
+                        let _ = file.set_len(len);
+                    })
+                });
+            if let Err(e) = result {
+                // 保留未写入的数据，下次再试
+                inner.pending.insert(day, buffer);
+                return Err(io_error(&path)(e));
+            }
+            inner.pending_bytes -= buffer.len();
+        }
+        Ok(())
+    }
+
+    /// 降采样超过 raw_days 的原始数据，删除超过 retention_days 的数据，
+    /// 总大小超过 max_size_mb 时从最早的数据开始删除（当天的原始数据除外）
+    pub fn maintain(&self, now: OffsetDateTime) -> Result<MaintenanceReport> {
+        let mut inner = self.inner.lock().unwrap();
+        self.flush_locked(&mut inner)?;
+        let today = unix_ms(now).div_euclid(DAY_MS);
+        let mut report = MaintenanceReport::default();
+
+        let rollups = self.list_days(Tier::Rollup)?;
+        for (day, path) in self.list_days(Tier::Raw)? {
+            if day > today - self.config.raw_days as i64 {
+                continue;
+            }
+            // 上次降采样后没来得及删除原始数据
+            if !rollups.contains_key(&day) {
+                self.downsample(&path, &self.file_path(Tier::Rollup, day))?;
+                report.downsampled += 1;
+            }
+            fs::remove_file(&path).map_err(io_error(&path))?;
+        }
+
+        let mut files = Vec::new();
+        for tier in [Tier::Rollup, Tier::Raw] {
+            for (day, path) in self.list_days(tier)? {
+                if day <= today - self.config.retention_days as i64 {
+                    fs::remove_file(&path).map_err(io_error(&path))?;
+                    report.removed += 1;
+                    continue;
+                }
+                let size = fs::metadata(&path).map_err(io_error(&path))?.len();
+                files.push((day, tier, path, size));
+            }
+        }
+        files.sort();
+
+        let max_bytes = self.config.max_size_mb.saturating_mul(1024 * 1024);
+        let mut total: u64 = files.iter().map(|(_, _, _, size)| size).sum();
+        for (day, tier, path, size) in files {
+            if total <= max_bytes {
+                break;
+            }
+            if day == today && tier == Tier::Raw {
+                continue;
+            }
+            fs::remove_file(&path).map_err(io_error(&path))?;
+            total -= size;
+            report.removed += 1;
+        }
+        report.bytes = total;
+        Ok(report)
+    }
+
+    fn downsample(&self, raw: &Path, rollup: &Path) -> Result<()> {
+        let bucket_ms = ((self.config.downsample_interval * 1000.0) as i64).max(1);
+        let mut buckets: BTreeMap<(i64, u32), (u32, f64, f64, f64)> = BTreeMap::new();
+        let mut reader = BufReader::new(File::open(raw).map_err(io_error(raw))?);
+        while let Some(record) = read_record(&mut reader, Tier::Raw).map_err(io_error(raw))? {
+            let bucket = record.time_ms - record.time_ms.rem_euclid(bucket_ms);
+            let entry = buckets
+                .entry((bucket, record.series as u32))
+                .or_insert((0, f64::INFINITY, f64::NEG_INFINITY, 0.0));
+            entry.0 += 1;
+            entry.1 = entry.1.min(record.min);
+            entry.2 = entry.2.max(record.max);
+            entry.3 += record.avg;
+        }
+
+        let mut content = Vec::with_capacity(buckets.len() * ROLLUP_RECORD_SIZE);
+        for ((bucket, id), (count, min, max, sum)) in buckets {
+            content.extend_from_slice(&bucket.to_le_bytes());
+            content.extend_from_slice(&id.to_le_bytes());
+            content.extend_from_slice(&count.to_le_bytes());
+            content.extend_from_slice(&min.to_le_bytes());
+            content.extend_from_slice(&max.to_le_bytes());
+            content.extend_from_slice(&(sum / count as f64).to_le_bytes());
+        }
+        write_atomic(rollup, &content).map_err(io_error(rollup))
+    }
+
+    /// 历史数据占用的字节数
+    #[allow(dead_code)]
+    pub fn disk_usage(&self) -> Result<u64> {
+        let mut total = 0;
+        for tier in [Tier::Rollup, Tier::Raw] {
+            for path in self.list_days(tier)?.values() {
+                total += fs::metadata(path).map_err(io_error(path))?.len();
+            }
+        }
+        Ok(total)
+    }
+
+    /// 按时间顺序逐个读取 [from, to] 内的数据点，不会把整个范围读入内存；
+    /// 同一天既有降采样数据又有原始数据时以降采样数据为准
+    pub fn query(&self, names: &[String], from: OffsetDateTime, to: OffsetDateTime) -> Result<HistoryCursor> {
+        let wanted = {
+            let mut inner = self.inner.lock().unwrap();
+            self.flush_locked(&mut inner)?;
+            let mut wanted = HashMap::new();
+            for (i, name) in names.iter().enumerate() {
+                let id = inner
+                    .index
+                    .get(name)
+                    .ok_or_else(|| HistoryError::UnknownSeries(name.clone()))?;
+                wanted.insert(*id, i);
+            }
+            wanted
+        };
+
+        let (from_ms, to_ms) = (unix_ms(from), unix_ms(to));
+        let days = from_ms.div_euclid(DAY_MS)..=to_ms.div_euclid(DAY_MS);
+        let mut files: BTreeMap<i64, (Tier, PathBuf)> = BTreeMap::new();
+        for tier in [Tier::Raw, Tier::Rollup] {
+            for (day, path) in self.list_days(tier)?.into_iter().filter(|(day, _)| days.contains(day)) {
+                files.insert(day, (tier, path));
+            }
+        }
+        Ok(HistoryCursor {
+            files: files.into_values().collect(),
+            current: None,
+            wanted,
+            from_ms,
+            to_ms,
+        })
+    }
+
+    fn file_path(&self, tier: Tier, day: i64) -> PathBuf {
+        let date = OffsetDateTime::UNIX_EPOCH.date() + time::Duration::days(day);
+        self.dir.join(tier.dir_name()).join(format!(
+            "{:04}{:02}{:02}.bin",
+            date.year(),
+            date.month() as u8,
+            date.day()
+        ))
+    }
+
+    fn list_days(&self, tier: Tier) -> Result<BTreeMap<i64, PathBuf>> {
+        let dir = self.dir.join(tier.dir_name());
+        let mut days = BTreeMap::new();
+        for entry in fs::read_dir(&dir).map_err(io_error(&dir))? {
+            let path = entry.map_err(io_error(&dir))?.path();
+            if let Some(day) = path.file_name().and_then(|name| parse_day(&name.to_string_lossy())) {
+                days.insert(day, path);
+            }
+        }
+        Ok(days)
+    }
+}
+
+/// HistoryStore::query 返回的游标，每次只持有一个打开的文件
+pub struct HistoryCursor {
+    files: VecDeque<(Tier, PathBuf)>,
+    current: Option<(Tier, PathBuf, BufReader<File>)>,
+    wanted: HashMap<u32, usize>,
+    from_ms: i64,
+    to_ms: i64,
+}
+
+impl Iterator for HistoryCursor {
+    type Item = Result<Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((tier, path, reader)) = self.current.as_mut() else {
+                let (tier, path) = self.files.pop_front()?;
+                match File::open(&path) {
+                    Ok(file) => self.current = Some((tier, path, BufReader::new(file))),
+                    // 查询过程中被维护任务删除
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Some(Err(io_error(&path)(e))),
+                }
+                continue;
+            };
+            match read_record(reader, *tier) {
+                Ok(Some(record)) => {
+                    if record.time_ms < self.from_ms || record.time_ms > self.to_ms {
+                        continue;
+                    }
+                    if let Some(&series) = self.wanted.get(&(record.series as u32)) {
+                        return Some(Ok(Sample { series, ..record }));
+                    }
+                }
+                Ok(None) => self.current = None,
+                Err(e) => {
+                    let error = io_error(path)(e);
+                    self.current = None;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+impl HistoryCursor {
+    /// 按 step 把数据点合并成行，step 为 None 时每个时间戳一行；
+    /// 桶从 from 开始对齐，数据按时间顺序到达，所以同一时刻只需要保留一个桶
+    pub fn aggregate(self, series_count: usize, step_ms: Option<i64>, aggregation: Aggregation) -> Rows {
+        Rows {
+            origin_ms: self.from_ms,
+            cursor: self,
+            series_count,
+            step_ms: step_ms.filter(|step| *step > 0),
+            aggregation,
+            current: None,
+        }
+    }
+}
+
+/// 一个时间桶内多个数据点的合并方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    #[default]
+    Avg,
+    Min,
+    Max,
+    First,
+    Last,
+}
+
+/// 合并后的一行，values 与查询的序列一一对应，桶内没有数据时为 None
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// 桶的起始时间（毫秒）
+    pub time_ms: i64,
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+    first: f64,
+    last: f64,
+}
+
+impl Accumulator {
+    fn new(sample: &Sample) -> Self {
+        Self {
+            min: sample.min,
+            max: sample.max,
+            sum: sample.avg * sample.count as f64,
+            count: sample.count as u64,
+            first: sample.avg,
+            last: sample.avg,
+        }
+    }
+
+    fn add(&mut self, sample: &Sample) {
+        self.min = self.min.min(sample.min);
+        self.max = self.max.max(sample.max);
+        // 降采样数据按点数加权
+        self.sum += sample.avg * sample.count as f64;
+        self.count += sample.count as u64;
+        self.last = sample.avg;
+    }
+
+    fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Avg => self.sum / self.count.max(1) as f64,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::First => self.first,
+            Aggregation::Last => self.last,
+        }
+    }
+}
+
+/// HistoryCursor::aggregate 返回的逐行迭代器
+pub struct Rows {
+    cursor: HistoryCursor,
+    origin_ms: i64,
+    series_count: usize,
+    step_ms: Option<i64>,
+    aggregation: Aggregation,
+    current: Option<(i64, Vec<Option<Accumulator>>)>,
+}
+
+impl Rows {
+    fn finish(&self, (time_ms, accumulators): (i64, Vec<Option<Accumulator>>)) -> Row {
+        Row {
+            time_ms,
+            values: accumulators
+                .iter()
+                .map(|acc| acc.map(|acc| acc.value(self.aggregation)))
+                .collect(),
+        }
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let sample = match self.cursor.next() {
+                Some(Ok(sample)) => sample,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    let current = self.current.take()?;
+                    return Some(Ok(self.finish(current)));
+                }
+            };
+            let bucket = match self.step_ms {
+                Some(step) => self.origin_ms + (sample.time_ms - self.origin_ms).div_euclid(step) * step,
+                None => sample.time_ms,
+            };
+            let done = match &self.current {
+                Some((time_ms, _)) if *time_ms == bucket => None,
+                _ => self.current.replace((bucket, vec![None; self.series_count])),
+            };
+            if let Some((_, accumulators)) = self.current.as_mut() {
+                match &mut accumulators[sample.series] {
+                    Some(acc) => acc.add(&sample),
+                    slot => *slot = Some(Accumulator::new(&sample)),
+                }
+            }
+            if let Some(done) = done {
+                return Some(Ok(self.finish(done)));
+            }
+        }
+    }
+}
+
+/// 读取一条记录，series 字段为文件中的序列号；文件末尾不完整的记录（写入中断）忽略
+fn read_record(reader: &mut impl Read, tier: Tier) -> io::Result<Option<Sample>> {
+    let mut buffer = [0u8; ROLLUP_RECORD_SIZE];
+    let buffer = &mut buffer[..tier.record_size()];
+    match reader.read_exact(buffer) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let i64_at = |i: usize| i64::from_le_bytes(buffer[i..i + 8].try_into().unwrap());
+    let u32_at = |i: usize| u32::from_le_bytes(buffer[i..i + 4].try_into().unwrap());
+    let f64_at = |i: usize| f64::from_le_bytes(buffer[i..i + 8].try_into().unwrap());
+    Ok(Some(match tier {
+        Tier::Raw => {
+            let value = f64_at(12);
+            Sample {
+                time_ms: i64_at(0),
+                series: u32_at(8) as usize,
+                min: value,
+                max: value,
+                avg: value,
+                count: 1,
+            }
+        }
+        Tier::Rollup => Sample {
+            time_ms: i64_at(0),
+            series: u32_at(8) as usize,
+            count: u32_at(12),
+            min: f64_at(16),
+            max: f64_at(24),
+            avg: f64_at(32),
+        },
+    }))
+}
+
+fn unix_ms(time: OffsetDateTime) -> i64 {
+    (time.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// "20260210.bin" -> 自 1970-01-01 起的天数
+fn parse_day(file_name: &str) -> Option<i64> {
+    let digits = file_name.strip_suffix(".bin")?;
+    if digits.len() != 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let year = digits[0..4].parse().ok()?;
+    let month = Month::try_from(digits[4..6].parse::<u8>().ok()?).ok()?;
+    let date = Date::from_calendar_date(year, month, digits[6..8].parse().ok()?).ok()?;
+    Some((date - OffsetDateTime::UNIX_EPOCH.date()).whole_days())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::datetime;
+    use tempfile::tempdir;
+
+    fn config(dir: &Path) -> HistoryConfig {
+        HistoryConfig {
+            dir: dir.to_string_lossy().to_string(),
+            resolution: 5.0,
+            raw_days: 2,
+            downsample_interval: 60.0,
+            retention_days: 10,
+            ..Default::default()
+        }
+    }
+
+    fn at(time: &str) -> OffsetDateTime {
+        datetime::parse_time(time).unwrap()
+    }
+
+    fn values(pairs: &[(&str, f64)]) -> Vec<(String, f64)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), *value)).collect()
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::open(&config(dir.path())).unwrap();
+        store
+            .register(&[("Temperatures.T1.value".to_string(), "℃".to_string())])
+            .unwrap();
+
+        let start = at("2026-02-10 23:59:50 +0000");
+        for i in 0..20 {
+            let time = start + time::Duration::seconds(i);
+            let recorded = store
+                .record(time, &values(&[("Temperatures.T1.value", i as f64), ("CAP", 10.0 * i as f64)]))
+                .unwrap();
+            // resolution 5 秒，每个序列每 5 秒记录一次
+            assert_eq!(recorded, if i % 5 == 0 { 2 } else { 0 });
+        }
+
+        // 跨天分文件，查询按时间顺序返回
+        let names = vec!["CAP".to_string(), "Temperatures.T1.value".to_string()];
+        let samples: Vec<Sample> = store
+            .query(&names, start, start + time::Duration::seconds(12))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let points: Vec<(i64, usize, f64)> = samples.iter().map(|s| ((s.time_ms - unix_ms(start)) / 1000, s.series, s.avg)).collect();
+        assert_eq!(
+            points,
+            vec![(0, 1, 0.0), (0, 0, 0.0), (5, 1, 5.0), (5, 0, 50.0), (10, 1, 10.0), (10, 0, 100.0)]
+        );
+        assert_eq!(fs::read_dir(dir.path().join("raw")).unwrap().count(), 2);
+
+        // 重新打开后序列号不变，单位保留
+        drop(store);
+        let store = HistoryStore::open(&config(dir.path())).unwrap();
+        assert_eq!(
+            store.series(),
+            vec![
+                SeriesInfo { name: "Temperatures.T1.value".to_string(), unit: "℃".to_string() },
+                SeriesInfo { name: "CAP".to_string(), unit: String::new() },
+            ]
+        );
+        assert!(matches!(store.query(&["P1".to_string()], start, start), Err(HistoryError::UnknownSeries(_))));
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated_before_append() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::open(&config(dir.path())).unwrap();
+        let start = at("2026-02-10 10:00:00 +0000");
+        store.record(start, &values(&[("F1", 1.0)])).unwrap();
+        store.flush().unwrap();
+
+        // 模拟掉电：文件末尾只写入了半条记录
+        let path = dir.path().join("raw").join("20260210.bin");
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[0xff; 7])
+            .unwrap();
+
+        store.record(start + time::Duration::seconds(5), &values(&[("F1", 2.0)])).unwrap();
+        store.record(start + time::Duration::seconds(10), &values(&[("F1", 3.0)])).unwrap();
+        store.flush().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 3 * RAW_RECORD_SIZE as u64);
+
+        let samples: Vec<Sample> = store
+            .query(&["F1".to_string()], start, start + time::Duration::seconds(10))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let points: Vec<(i64, f64)> = samples.iter().map(|s| ((s.time_ms - unix_ms(start)) / 1000, s.avg)).collect();
+        assert_eq!(points, vec![(0, 1.0), (5, 2.0), (10, 3.0)]);
+    }
+
+    #[test]
+    fn test_aggregate_rows() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::open(&config(dir.path())).unwrap();
+        let start = at("2026-02-10 10:00:00 +0000");
+        for i in 0..12 {
+            let mut batch = values(&[("F1", i as f64)]);
+            if i % 2 == 0 {
+                batch.push(("F2".to_string(), 100.0 + i as f64));
+            }
+            store.record(start + time::Duration::seconds(5 * i), &batch).unwrap();
+        }
+        let names = vec!["F1".to_string(), "F2".to_string(), "F3".to_string()];
+        store.register(&[("F3".to_string(), String::new())]).unwrap();
+        let rows = |step: Option<i64>, aggregation| -> Vec<Row> {
+            store
+                .query(&names, start + time::Duration::seconds(10), start + time::Duration::seconds(55))
+                .unwrap()
+                .aggregate(names.len(), step, aggregation)
+                .collect::<Result<_>>()
+                .unwrap()
+        };
+
+        // 20 秒一个桶，从 from 开始对齐
+        let avg = rows(Some(20_000), Aggregation::Avg);
+        let points: Vec<(i64, Vec<Option<f64>>)> = avg.iter().map(|r| ((r.time_ms - unix_ms(start)) / 1000, r.values.clone())).collect();
+        assert_eq!(
+            points,
+            vec![
+                (10, vec![Some(3.5), Some(103.0), None]),
+                (30, vec![Some(7.5), Some(107.0), None]),
+                (50, vec![Some(10.5), Some(110.0), None]),
+            ]
+        );
+        let max: Vec<Option<f64>> = rows(Some(20_000), Aggregation::Max).iter().map(|r| r.values[0]).collect();
+        assert_eq!(max, vec![Some(5.0), Some(9.0), Some(11.0)]);
+        let last: Vec<Option<f64>> = rows(Some(20_000), Aggregation::Last).iter().map(|r| r.values[1]).collect();
+        assert_eq!(last, vec![Some(104.0), Some(108.0), Some(110.0)]);
+
+        // 不指定 step 时每个时间戳一行
+        let raw = rows(None, Aggregation::Avg);
+        assert_eq!(raw.len(), 10);
+        assert_eq!(raw[1].values, vec![Some(3.0), None, None]);
+    }
+
+    #[test]
+    fn test_downsampling_and_retention() {
+        let dir = tempdir().unwrap();
+        let store = HistoryStore::open(&config(dir.path())).unwrap();
+        let day1 = at("2026-02-01 10:00:00 +0000");
+        for i in 0..24 {
+            let time = day1 + time::Duration::seconds(5 * i);
+            store.record(time, &values(&[("F1", i as f64)])).unwrap();
+        }
+        let day5 = at("2026-02-05 10:00:00 +0000");
+        store.record(day5, &values(&[("F1", 100.0)])).unwrap();
+
+        let report = store.maintain(at("2026-02-05 12:00:00 +0000")).unwrap();
+        assert_eq!(report.downsampled, 1);
+        assert_eq!(report.removed, 0);
+        assert!(!store.file_path(Tier::Raw, unix_ms(day1).div_euclid(DAY_MS)).exists());
+
+        // 两分钟的数据降采样为两个 60 秒的桶
+        let names = vec!["F1".to_string()];
+        let samples: Vec<Sample> = store
+            .query(&names, day1, day5)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(samples.len(), 3);
+        assert_eq!((samples[0].min, samples[0].max, samples[0].avg, samples[0].count), (0.0, 11.0, 5.5, 12));
+        assert_eq!((samples[1].min, samples[1].max, samples[1].avg, samples[1].count), (12.0, 23.0, 17.5, 12));
+        assert_eq!((samples[2].avg, samples[2].count), (100.0, 1));
+
+        // 超过 retention_days 删除
+        let report = store.maintain(at("2026-02-12 00:00:00 +0000")).unwrap();
+        assert_eq!(report.downsampled, 1);
+        assert_eq!(report.removed, 1);
+        assert_eq!(store.query(&names, day1, day5).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_size_limit_removes_oldest_data() {
+        let dir = tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.max_size_mb = 1;
+        config.raw_days = 10;
+        let store = HistoryStore::open(&config).unwrap();
+
+        // 每天约 0.6 MB
+        let names: Vec<String> = (0..300).map(|i| format!("S{}", i)).collect();
+        for day in 0..3 {
+            let start = at("2026-02-01 00:00:00 +0000") + time::Duration::days(day);
+            for i in 0..100 {
+                let batch: Vec<(String, f64)> = names.iter().map(|name| (name.clone(), i as f64)).collect();
+                store.record(start + time::Duration::seconds(5 * i), &batch).unwrap();
+            }
+        }
+        let report = store.maintain(at("2026-02-03 12:00:00 +0000")).unwrap();
+        assert_eq!(report.removed, 2);
+        assert!(report.bytes <= 1024 * 1024);
+        // 当天的原始数据始终保留
+        assert_eq!(store.list_days(Tier::Raw).unwrap().len(), 1);
+        assert_eq!(report.bytes, store.disk_usage().unwrap());
+    }
+}
@@ -0,0 +1,9 @@
+pub mod audit;
+pub mod clock_service;
+pub mod history_store;
+pub mod modbus_diagnostics;
+pub mod modbus_service;
+pub mod polling_service;
+pub mod rtu_link;
+pub mod scenario;
+pub mod simulator;
@@ -0,0 +1,386 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_modbus::ExceptionCode;
+
+use crate::services::rtu_link::{LinkCounters, LinkStats};
+use crate::utils::datetime;
+
+/// 保留的最近收发帧数
+pub const FRAME_HISTORY: usize = 50;
+
+/// 单帧最多保留的字节数，Modbus 帧不超过 260 字节，多出来的是线路上的杂波
+const MAX_FRAME_LEN: usize = 512;
+
+/// 往返时间直方图的桶上限（毫秒），最后一个桶收集更慢的响应
+pub const LATENCY_BUCKETS_MS: &[f64] = &[5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+/// 线路上的一帧原始数据
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Frame {
+    pub time: String,
+    pub direction: Direction,
+    /// 十六进制，例如 "01 03 00 00 00 01 84 0A"
+    pub data: String,
+    #[serde(skip)]
+    bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExceptionCount {
+    pub code: u8,
+    pub name: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyBucket {
+    /// 桶的上限（毫秒），None 表示更慢的响应
+    pub le: Option<f64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyHistogram {
+    pub buckets: Vec<LatencyBucket>,
+    pub count: u64,
+    pub mean_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+/// 一个 Modbus 连接的通信统计，同一串口上的设备共用一份
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiagnosticsSnapshot {
+    /// 使用这个连接的设备
+    pub devices: Vec<String>,
+    pub target: String,
+    pub connected: bool,
+    pub requests: u64,
+    /// 正常响应和异常响应
+    pub responses: u64,
+    pub timeouts: u64,
+    /// 连接断开、响应无法解析等传输错误
+    pub transport_errors: u64,
+    pub connect_failures: u64,
+    pub reconnects: u64,
+    pub exceptions: Vec<ExceptionCount>,
+    /// 串口的收发帧数和 CRC、帧错误，TCP 连接为 None
+    pub link: Option<LinkStats>,
+    pub latency: LatencyHistogram,
+    /// 最近的收发帧，最早的在前
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    connected: bool,
+    connects: u64,
+    requests: u64,
+    responses: u64,
+    timeouts: u64,
+    transport_errors: u64,
+    connect_failures: u64,
+    /// 异常码 -> (名称, 次数)
+    exceptions: BTreeMap<u8, (String, u64)>,
+    latency: Vec<u64>,
+    latency_sum: Duration,
+    latency_max: Option<Duration>,
+    frames: VecDeque<Frame>,
+}
+
+/// Modbus 客户端的通信诊断：请求、响应、超时、异常码、往返时间和最近的原始帧
+#[derive(Debug)]
+pub struct ModbusDiagnostics {
+    devices: Mutex<Vec<String>>,
+    target: String,
+    /// 串口链路的帧计数
+    link: Option<Arc<LinkCounters>>,
+    counters: Mutex<Counters>,
+}
+
+impl ModbusDiagnostics {
+    pub fn new(device: &str, target: &str) -> Self {
+        Self {
+            devices: Mutex::new(vec![device.to_string()]),
+            target: target.to_string(),
+            link: None,
+            counters: Mutex::new(Counters {
+                latency: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// 串口连接同时报告 CRC 和帧错误
+    pub fn with_link(mut self, link: Arc<LinkCounters>) -> Self {
+        self.link = Some(link);
+        self
+    }
+
+    /// 共用连接的其他设备
+    pub fn add_device(&self, device: &str) {
+        let mut devices = self.devices.lock().unwrap();
+        if !devices.iter().any(|d| d == device) {
+            devices.push(device.to_string());
+        }
+    }
+
+    pub fn connected(&self) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.connected = true;
+        counters.connects += 1;
+    }
+
+    pub fn connect_failed(&self) {
+        self.counters.lock().unwrap().connect_failures += 1;
+    }
+
+    pub fn disconnected(&self) {
+        self.counters.lock().unwrap().connected = false;
+    }
+
+    pub fn request(&self) {
+        self.counters.lock().unwrap().requests += 1;
+    }
+
+    /// 收到响应，exception 为异常响应的异常码
+    pub fn response(&self, latency: Duration, exception: Option<ExceptionCode>) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.responses += 1;
+        let ms = latency.as_secs_f64() * 1000.0;
+        let bucket = LATENCY_BUCKETS_MS.iter().position(|le| ms <= *le).unwrap_or(LATENCY_BUCKETS_MS.len());
+        counters.latency[bucket] += 1;
+        counters.latency_sum += latency;
+        counters.latency_max = Some(counters.latency_max.map_or(latency, |max| max.max(latency)));
+        if let Some(code) = exception {
+            let entry = counters
+                .exceptions
+                .entry(u8::from(code))
+                .or_insert_with(|| (format!("{:?}", code), 0));
+            entry.1 += 1;
+        }
+    }
+
+    pub fn timeout(&self) {
+        self.counters.lock().unwrap().timeouts += 1;
+    }
+
+    pub fn transport_error(&self) {
+        self.counters.lock().unwrap().transport_errors += 1;
+    }
+
+    /// 记录收发的字节；continued 为 true 时接在上一帧后面（一帧分几次读到）
+    pub fn frame(&self, direction: Direction, data: &[u8], continued: bool) {
+        let mut counters = self.counters.lock().unwrap();
+        if continued {
+            if let Some(last) = counters.frames.back_mut().filter(|f| f.direction == direction) {
+                let room = MAX_FRAME_LEN.saturating_sub(last.bytes.len());
+                last.bytes.extend_from_slice(&data[..data.len().min(room)]);
+                last.data = hex(&last.bytes);
+                return;
+            }
+        }
+        if counters.frames.len() == FRAME_HISTORY {
+            counters.frames.pop_front();
+        }
+        let bytes = data[..data.len().min(MAX_FRAME_LEN)].to_vec();
+        counters.frames.push_back(Frame {
+            time: datetime::format_rfc3339(&OffsetDateTime::now_utc()),
+            direction,
+            data: hex(&bytes),
+            bytes,
+        });
+    }
+
+    pub fn snapshot(&self) -> DiagnosticsSnapshot {
+        let counters = self.counters.lock().unwrap();
+        let count: u64 = counters.latency.iter().sum();
+        let buckets = counters
+            .latency
+            .iter()
+            .enumerate()
+            .map(|(i, count)| LatencyBucket {
+                le: LATENCY_BUCKETS_MS.get(i).copied(),
+                count: *count,
+            })
+            .collect();
+        DiagnosticsSnapshot {
+            devices: self.devices.lock().unwrap().clone(),
+            target: self.target.clone(),
+            connected: counters.connected,
+            requests: counters.requests,
+            responses: counters.responses,
+            timeouts: counters.timeouts,
+            transport_errors: counters.transport_errors,
+            connect_failures: counters.connect_failures,
+            reconnects: counters.connects.saturating_sub(1),
+            exceptions: counters
+                .exceptions
+                .iter()
+                .map(|(code, (name, count))| ExceptionCount {
+                    code: *code,
+                    name: name.clone(),
+                    count: *count,
+                })
+                .collect(),
+            link: self.link.as_ref().map(|link| link.snapshot()),
+            latency: LatencyHistogram {
+                buckets,
+                count,
+                mean_ms: (count > 0).then(|| counters.latency_sum.as_secs_f64() * 1000.0 / count as f64),
+                max_ms: counters.latency_max.map(|max| max.as_secs_f64() * 1000.0),
+            },
+            frames: counters.frames.iter().cloned().collect(),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// 记录连接上收发的原始字节：flush 之前写入的字节为一帧，下一次发送之前读到的字节为一帧
+#[derive(Debug)]
+pub struct FrameTap<T> {
+    inner: T,
+    diagnostics: Arc<ModbusDiagnostics>,
+    sending: Vec<u8>,
+    receiving: bool,
+}
+
+impl<T> FrameTap<T> {
+    pub fn new(inner: T, diagnostics: Arc<ModbusDiagnostics>) -> Self {
+        Self {
+            inner,
+            diagnostics,
+            sending: Vec::new(),
+            receiving: false,
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for FrameTap<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let bytes = &buf.filled()[before..];
+        if !bytes.is_empty() {
+            self.diagnostics.frame(Direction::Rx, bytes, self.receiving);
+            self.receiving = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for FrameTap<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let written = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.sending.extend_from_slice(&buf[..written]);
+        self.receiving = false;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(Pin::new(&mut self.inner).poll_flush(cx))?;
+        if !self.sending.is_empty() {
+            let frame = std::mem::take(&mut self.sending);
+            self.diagnostics.frame(Direction::Tx, &frame, false);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_counters_and_histogram() {
+        let diagnostics = ModbusDiagnostics::new("modbus_client", "192.168.1.150:502");
+        diagnostics.add_device("meter");
+        diagnostics.connected();
+        for ms in [3, 7, 40, 2000] {
+            diagnostics.request();
+            diagnostics.response(Duration::from_millis(ms), None);
+        }
+        diagnostics.request();
+        diagnostics.response(Duration::from_millis(8), Some(ExceptionCode::IllegalDataAddress));
+        diagnostics.request();
+        diagnostics.timeout();
+        diagnostics.disconnected();
+        diagnostics.connect_failed();
+        diagnostics.connected();
+
+        let snapshot = diagnostics.snapshot();
+        assert_eq!(snapshot.link, None);
+        assert_eq!(snapshot.devices, vec!["modbus_client", "meter"]);
+        assert_eq!((snapshot.requests, snapshot.responses, snapshot.timeouts), (6, 5, 1));
+        assert_eq!((snapshot.connect_failures, snapshot.reconnects, snapshot.connected), (1, 1, true));
+        assert_eq!(
+            snapshot.exceptions,
+            vec![ExceptionCount {
+                code: 2,
+                name: "IllegalDataAddress".to_string(),
+                count: 1
+            }]
+        );
+        let counts: Vec<u64> = snapshot.latency.buckets.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![1, 2, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(snapshot.latency.buckets.last().unwrap().le, None);
+        assert_eq!(snapshot.latency.max_ms, Some(2000.0));
+        assert!((snapshot.latency.mean_ms.unwrap() - 411.6).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_frame_tap_records_frames() {
+        let diagnostics = Arc::new(ModbusDiagnostics::new("modbus_client", "pty"));
+        let (client, mut device) = tokio::io::duplex(256);
+        let mut tap = FrameTap::new(client, Arc::clone(&diagnostics));
+
+        tap.write_all(&[0x01, 0x03]).await.unwrap();
+        tap.write_all(&[0x00, 0x00, 0x00, 0x01]).await.unwrap();
+        tap.flush().await.unwrap();
+        let mut buf = [0u8; 16];
+        device.read_exact(&mut buf[..6]).await.unwrap();
+        // 响应分两次读到，合并为一帧
+        device.write_all(&[0x01, 0x03, 0x02]).await.unwrap();
+        tap.read_exact(&mut buf[..3]).await.unwrap();
+        device.write_all(&[0x00, 0x2A]).await.unwrap();
+        tap.read_exact(&mut buf[..2]).await.unwrap();
+
+        let frames: Vec<(Direction, String)> =
+            diagnostics.snapshot().frames.into_iter().map(|f| (f.direction, f.data)).collect();
+        assert_eq!(
+            frames,
+            vec![
+                (Direction::Tx, "01 03 00 00 00 01".to_string()),
+                (Direction::Rx, "01 03 02 00 2A".to_string()),
+            ]
+        );
+
+        for _ in 0..FRAME_HISTORY {
+            diagnostics.frame(Direction::Tx, &[0xFF], false);
+        }
+        let frames = diagnostics.snapshot().frames;
+        assert_eq!(frames.len(), FRAME_HISTORY);
+        assert!(frames.iter().all(|f| f.data == "FF"));
+    }
+}
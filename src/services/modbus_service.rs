@@ -0,0 +1,442 @@
+use async_trait::async_trait;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio_modbus::client::{rtu, tcp, Context, Reader, Writer};
+use tokio_modbus::slave::SlaveContext;
+use tokio_modbus::Slave;
+use tokio_serial::{DataBits, StopBits};
+
+use crate::config::loader::{ModbusClientConfig, Parity, SerialConfig};
+use crate::models::event_bus::{ConnectionState, Event, EventBus};
+use crate::models::sensor::{ReadMethod, WriteMethod};
+use crate::services::modbus_diagnostics::{FrameTap, ModbusDiagnostics};
+use crate::services::rtu_link::{frame_gap, LinkCounters, LinkStats, RtuLink};
+
+/// 默认设备在连接状态事件和日志中的名称
+pub const DEVICE_NAME: &str = "modbus_client";
+
+/// 重连的最短间隔，连续失败时每次加倍，最长 RECONNECT_BACKOFF_MAX
+pub const RECONNECT_BACKOFF_MIN: Duration = Duration::from_millis(500);
+pub const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum ModbusError {
+    #[error("connect to {target}: {source}")]
+    Connect {
+        target: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("modbus exception: {0}")]
+    Exception(tokio_modbus::ExceptionCode),
+
+    #[error(transparent)]
+    Transport(#[from] tokio_modbus::Error),
+
+    #[error("reconnect to {target} deferred for {remaining:?}")]
+    Backoff { target: String, remaining: Duration },
+}
+
+impl ModbusError {
+    /// 连接层面的错误，同一连接上接下来的请求也会失败；异常响应说明连接正常
+    pub fn is_link_error(&self) -> bool {
+        !matches!(self, ModbusError::Exception(_))
+    }
+}
+
+/// 按读取方式读取一段连续的寄存器，线圈和离散输入返回 0/1。
+/// slave 为 None 时读取连接配置的默认从站
+#[async_trait]
+pub trait RegisterReader: Send {
+    async fn read(
+        &mut self,
+        slave: Option<u8>,
+        method: ReadMethod,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ModbusError>;
+
+    /// 从 address 开始写入寄存器或线圈（0/1）。read_write_multiple_registers 返回同一段寄存器读回的值，
+    /// 其他写入方式返回空
+    async fn write(
+        &mut self,
+        slave: Option<u8>,
+        method: WriteMethod,
+        address: u16,
+        values: &[u16],
+    ) -> Result<Vec<u16>, ModbusError> {
+        let _ = (slave, method, address, values);
+        Err(ModbusError::Exception(tokio_modbus::ExceptionCode::IllegalFunction))
+    }
+}
+
+fn data_bits(bits: u8) -> DataBits {
+    match bits {
+        5 => DataBits::Five,
+        6 => DataBits::Six,
+        7 => DataBits::Seven,
+        _ => DataBits::Eight,
+    }
+}
+
+fn parity_letter(parity: Parity) -> char {
+    match parity {
+        Parity::None => 'N',
+        Parity::Even => 'E',
+        Parity::Odd => 'O',
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Transport {
+    Tcp { host: String, port: u16 },
+    Serial(SerialConfig),
+}
+
+impl Transport {
+    fn target(&self) -> String {
+        match self {
+            Transport::Tcp { host, port } => format!("{}:{}", host, port),
+            Transport::Serial(serial) => format!(
+                "{}@{} {}{}{}",
+                serial.port,
+                serial.baudrate,
+                serial.data_bits,
+                parity_letter(serial.parity),
+                serial.stop_bits
+            ),
+        }
+    }
+
+    /// 不借用 ModbusService，Context 不是 Sync，不能跨 await 持有它的引用
+    async fn connect(
+        self,
+        timeout: Duration,
+        counters: Arc<LinkCounters>,
+        diagnostics: Arc<ModbusDiagnostics>,
+    ) -> Result<Context, ModbusError> {
+        let target = self.target();
+        let connect_error = |source| ModbusError::Connect {
+            target: target.clone(),
+            source,
+        };
+        match self {
+            Transport::Tcp { .. } => {
+                let addr = tokio::net::lookup_host(&target)
+                    .await
+                    .map_err(connect_error)?
+                    .next()
+                    .ok_or_else(|| connect_error(io::Error::new(io::ErrorKind::NotFound, "no address")))?;
+                let stream = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr))
+                    .await
+                    .map_err(|_| ModbusError::Timeout(timeout))?
+                    .map_err(connect_error)?;
+                Ok(tcp::attach(FrameTap::new(stream, diagnostics)))
+            }
+            Transport::Serial(serial) => {
+                let builder = tokio_serial::new(&serial.port, serial.baudrate)
+                    .data_bits(data_bits(serial.data_bits))
+                    .parity(match serial.parity {
+                        Parity::None => tokio_serial::Parity::None,
+                        Parity::Even => tokio_serial::Parity::Even,
+                        Parity::Odd => tokio_serial::Parity::Odd,
+                    })
+                    .stop_bits(if serial.stop_bits == 2 { StopBits::Two } else { StopBits::One })
+                    .timeout(timeout);
+                let stream = tokio_serial::SerialStream::open(&builder).map_err(|e| connect_error(e.into()))?;
+                let gap = frame_gap(serial.baudrate, serial.data_bits, serial.parity, serial.stop_bits);
+                let link = RtuLink::new(stream, gap, counters);
+                Ok(rtu::attach_slave(FrameTap::new(link, diagnostics), Slave(serial.slave_id)))
+            }
+        }
+    }
+}
+
+/// 异步 Modbus 客户端，第一次读取时连接，出错后断开并在下次读取时重连
+pub struct ModbusService {
+    /// 连接状态事件中的设备名
+    name: String,
+    transport: Transport,
+    timeout: Duration,
+    /// 默认从站地址
+    slave: u8,
+    context: Option<Context>,
+    events: Option<EventBus>,
+    /// 串口的帧计数，重连后继续累计
+    counters: Arc<LinkCounters>,
+    diagnostics: Arc<ModbusDiagnostics>,
+    /// 连续失败的次数，收到响应后清零
+    failures: u32,
+    /// 在此之前不尝试重连
+    retry_at: Option<tokio::time::Instant>,
+}
+
+impl ModbusService {
+    /// TCP 优先，其次是串口；都未启用时返回 None
+    pub fn from_config(config: &ModbusClientConfig) -> Option<Self> {
+        let (transport, timeout, slave) = if config.tcp.enable {
+            let transport = Transport::Tcp {
+                host: config.tcp.host.clone(),
+                port: config.tcp.port,
+            };
+            (transport, config.tcp.timeout, config.tcp.slave_id)
+        } else if config.serial.enable {
+            let transport = Transport::Serial(config.serial.clone());
+            (transport, config.serial.timeout, config.serial.slave_id)
+        } else {
+            return None;
+        };
+        let mut service = Self {
+            name: DEVICE_NAME.to_string(),
+            diagnostics: Arc::new(ModbusDiagnostics::new(DEVICE_NAME, "")),
+            transport,
+            timeout: Duration::from_secs_f64(timeout),
+            slave,
+            context: None,
+            events: None,
+            counters: Arc::default(),
+            failures: 0,
+            retry_at: None,
+        };
+        service.diagnostics = Arc::new(service.new_diagnostics());
+        Some(service)
+    }
+
+    /// 串口的收发帧数和 CRC、帧错误计数；TCP 连接返回 None
+    #[allow(dead_code)]
+    pub fn link_stats(&self) -> Option<LinkStats> {
+        matches!(self.transport, Transport::Serial(_)).then(|| self.counters.snapshot())
+    }
+
+    /// 连接和断开时发布 ConnectionStateChanged
+    pub fn with_events(mut self, events: &EventBus) -> Self {
+        self.events = Some(events.clone());
+        self
+    }
+
+    /// sensors.yaml 中的设备用自己的名称发布连接状态
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self.diagnostics = Arc::new(self.new_diagnostics());
+        self
+    }
+
+    /// 通信统计，连接移交给轮询服务后 HTTP 诊断接口仍然可以读取
+    pub fn diagnostics(&self) -> Arc<ModbusDiagnostics> {
+        Arc::clone(&self.diagnostics)
+    }
+
+    fn new_diagnostics(&self) -> ModbusDiagnostics {
+        let diagnostics = ModbusDiagnostics::new(&self.name, &self.target());
+        match self.transport {
+            Transport::Serial(_) => diagnostics.with_link(Arc::clone(&self.counters)),
+            Transport::Tcp { .. } => diagnostics,
+        }
+    }
+
+    /// 连接目标，例如 "192.168.1.150:502" 或 "/dev/com2@115200 8N1"，目标相同的设备共用连接
+    pub fn target(&self) -> String {
+        self.transport.target()
+    }
+
+    fn notify(&self, state: ConnectionState) {
+        if let Some(events) = &self.events {
+            events.try_publish(Event::ConnectionStateChanged {
+                device: self.name.clone(),
+                state,
+            });
+        }
+    }
+
+    /// 未连接时建立连接；上次失败后的退避时间内直接返回 Backoff，避免每个请求都去重连
+    async fn context(&mut self) -> Result<&mut Context, ModbusError> {
+        if self.context.is_none() {
+            if let Some(retry_at) = self.retry_at {
+                let now = tokio::time::Instant::now();
+                if now < retry_at {
+                    return Err(ModbusError::Backoff {
+                        target: self.target(),
+                        remaining: retry_at - now,
+                    });
+                }
+            }
+            let diagnostics = Arc::clone(&self.diagnostics);
+            match self.transport.clone().connect(self.timeout, Arc::clone(&self.counters), diagnostics).await {
+                Ok(context) => {
+                    log::info!("Modbus connected to {}", self.target());
+                    self.diagnostics.connected();
+                    self.notify(ConnectionState::Connected);
+                    self.context = Some(context);
+                }
+                Err(e) => {
+                    self.diagnostics.connect_failed();
+                    self.notify(ConnectionState::Reconnecting);
+                    self.back_off();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(self.context.as_mut().unwrap())
+    }
+
+    /// 记录一次失败，下次重连前等待的时间按连续失败次数加倍
+    fn back_off(&mut self) {
+        let delay = RECONNECT_BACKOFF_MIN
+            .saturating_mul(1 << self.failures.min(16))
+            .min(RECONNECT_BACKOFF_MAX);
+        self.failures += 1;
+        self.retry_at = Some(tokio::time::Instant::now() + delay);
+    }
+
+    /// 处理一次请求的结果：异常响应直接返回，传输错误和超时后断开，退避后重连
+    fn finish<T>(
+        &mut self,
+        slave: Slave,
+        started: tokio::time::Instant,
+        outcome: Result<tokio_modbus::Result<T>, tokio::time::error::Elapsed>,
+    ) -> Result<T, ModbusError> {
+        let result = match outcome {
+            Ok(Ok(Ok(values))) => {
+                self.diagnostics.response(started.elapsed(), None);
+                self.failures = 0;
+                return Ok(values);
+            }
+            // 异常响应说明连接正常，不需要重连
+            Ok(Ok(Err(code))) => {
+                self.diagnostics.response(started.elapsed(), Some(code));
+                self.failures = 0;
+                return Err(ModbusError::Exception(code));
+            }
+            Ok(Err(e)) => {
+                self.diagnostics.transport_error();
+                ModbusError::Transport(e)
+            }
+            Err(_) => {
+                self.diagnostics.timeout();
+                ModbusError::Timeout(self.timeout)
+            }
+        };
+        match self.link_stats() {
+            Some(stats) => log::warn!(
+                "Modbus {} {} failed, reconnecting: {} (CRC errors {}, framing errors {})",
+                self.target(),
+                slave,
+                result,
+                stats.crc_errors,
+                stats.framing_errors
+            ),
+            None => log::warn!("Modbus {} {} failed, reconnecting: {}", self.target(), slave, result),
+        }
+        self.context = None;
+        self.diagnostics.disconnected();
+        self.notify(ConnectionState::Disconnected);
+        self.back_off();
+        Err(result)
+    }
+}
+
+#[async_trait]
+impl RegisterReader for ModbusService {
+    async fn read(
+        &mut self,
+        slave: Option<u8>,
+        method: ReadMethod,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ModbusError> {
+        let timeout = self.timeout;
+        let slave = Slave(slave.unwrap_or(self.slave));
+        let diagnostics = Arc::clone(&self.diagnostics);
+        let context = self.context().await?;
+        context.set_slave(slave);
+        diagnostics.request();
+        let started = tokio::time::Instant::now();
+        let request = async {
+            let bits = |bits: Vec<bool>| bits.into_iter().map(u16::from).collect();
+            Ok::<_, tokio_modbus::Error>(match method {
+                ReadMethod::HoldingRegisters => context.read_holding_registers(address, count).await?,
+                ReadMethod::InputRegisters => context.read_input_registers(address, count).await?,
+                ReadMethod::Coils => context.read_coils(address, count).await?.map(bits),
+                ReadMethod::DiscreteInputs => context.read_discrete_inputs(address, count).await?.map(bits),
+            })
+        };
+        let outcome = tokio::time::timeout(timeout, request).await;
+        self.finish(slave, started, outcome)
+    }
+
+    async fn write(
+        &mut self,
+        slave: Option<u8>,
+        method: WriteMethod,
+        address: u16,
+        values: &[u16],
+    ) -> Result<Vec<u16>, ModbusError> {
+        let timeout = self.timeout;
+        let slave = Slave(slave.unwrap_or(self.slave));
+        let diagnostics = Arc::clone(&self.diagnostics);
+        let context = self.context().await?;
+        context.set_slave(slave);
+        diagnostics.request();
+        let started = tokio::time::Instant::now();
+        let request = async {
+            let first = values.first().copied().unwrap_or_default();
+            let coils: Vec<bool> = values.iter().map(|v| *v != 0).collect();
+            let done = |result: tokio_modbus::Result<()>| result.map(|r| r.map(|_| Vec::new()));
+            Ok::<_, tokio_modbus::Error>(match method {
+                WriteMethod::SingleRegister => done(context.write_single_register(address, first).await)?,
+                WriteMethod::MultipleRegisters => done(context.write_multiple_registers(address, values).await)?,
+                WriteMethod::SingleCoil => done(context.write_single_coil(address, first != 0).await)?,
+                WriteMethod::MultipleCoils => done(context.write_multiple_coils(address, &coils).await)?,
+                WriteMethod::ReadWriteMultipleRegisters => {
+                    context
+                        .read_write_multiple_registers(address, values.len() as u16, address, values)
+                        .await?
+                }
+            })
+        };
+        let outcome = tokio::time::timeout(timeout, request).await;
+        self.finish(slave, started, outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::TcpConfig;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnect_backs_off() {
+        // 绑定后立即释放，连接会被拒绝
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let config = ModbusClientConfig {
+            tcp: TcpConfig {
+                enable: true,
+                host: "127.0.0.1".to_string(),
+                port,
+                ..TcpConfig::default()
+            },
+            ..ModbusClientConfig::default()
+        };
+        let mut modbus = ModbusService::from_config(&config).unwrap();
+        let mut expected_delay = RECONNECT_BACKOFF_MIN;
+        for attempt in 1..=3 {
+            assert!(matches!(modbus.read(None, ReadMethod::HoldingRegisters, 0, 1).await, Err(ModbusError::Connect { .. })));
+            assert_eq!(modbus.diagnostics().snapshot().connect_failures, attempt);
+            match modbus.read(None, ReadMethod::HoldingRegisters, 0, 1).await {
+                Err(ModbusError::Backoff { remaining, .. }) => assert_eq!(remaining, expected_delay),
+                other => panic!("Expected Backoff, got {:?}", other),
+            }
+            assert_eq!(modbus.diagnostics().snapshot().connect_failures, attempt);
+            tokio::time::advance(expected_delay).await;
+            expected_delay *= 2;
+        }
+    }
+}
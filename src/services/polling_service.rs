@@ -0,0 +1,1203 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::time::Instant;
+
+use crate::config::loader::PollingConfig;
+use crate::models::event_bus::{Event, EventBus};
+use crate::models::sensor::{AttrSpec, DataType, Quality, ReadMethod, Reading, SensorCatalog, SensorValue, WriteMethod};
+use crate::services::history_store::HistoryStore;
+use crate::services::modbus_service::{ModbusError, RegisterReader, DEVICE_NAME};
+use crate::utils::expression::ExpressionError;
+use crate::utils::file_store::{Config, ConfigChangeCallback};
+
+/// 计算传感器在 SensorUpdated 事件中的插件名
+pub const COMPUTED_PLUGIN: &str = "computed";
+
+/// 一次连续读取：同一从站、同一读取方式、地址连续的属性合并为一个请求
+#[derive(Debug, Clone, PartialEq)]
+struct Batch {
+    slave: Option<u8>,
+    method: ReadMethod,
+    start: u16,
+    count: u16,
+    /// (属性下标, 相对 start 的偏移)
+    members: Vec<(usize, usize)>,
+}
+
+/// 一个设备的读取计划，同一串口上的设备共用一个连接
+struct DevicePoller {
+    /// None 为 modbus_client 配置的默认设备
+    device: Option<String>,
+    batches: Vec<Batch>,
+    reader: Arc<tokio::sync::Mutex<Box<dyn RegisterReader>>>,
+}
+
+fn plan_batches(catalog: &SensorCatalog, device: Option<&str>) -> Vec<Batch> {
+    let mut addressed: Vec<(Option<u8>, ReadMethod, u16, usize)> = catalog
+        .attrs
+        .iter()
+        .enumerate()
+        .filter(|(_, attr)| attr.device.as_deref() == device)
+        .filter_map(|(i, attr)| Some((attr.slave_id, attr.read_method?, attr.address?, i)))
+        .collect();
+    let width = |i: usize| catalog.attrs[i].width();
+    addressed.sort_by_key(|(slave, method, address, _)| (*slave, *method as u8, *address));
+
+    let mut batches: Vec<Batch> = Vec::new();
+    for (slave, method, address, i) in addressed {
+        if let Some(batch) = batches.last_mut() {
+            let end = batch.start as u32 + batch.count as u32;
+            let contiguous = batch.slave == slave && batch.method == method && address as u32 <= end;
+            let count = (address as u32 + width(i) as u32).saturating_sub(batch.start as u32);
+            if contiguous && count <= method.max_count() as u32 {
+                batch.count = batch.count.max(count as u16);
+                batch.members.push((i, (address - batch.start) as usize));
+                continue;
+            }
+        }
+        batches.push(Batch {
+            slave,
+            method,
+            start: address,
+            count: width(i),
+            members: vec![(i, 0)],
+        });
+    }
+    batches
+}
+
+#[derive(Debug, Error)]
+pub enum WriteError {
+    #[error("unknown sensor '{0}', expected Plugin.Sensor.attr")]
+    UnknownSensor(String),
+
+    #[error("{0} has no write_method")]
+    NotWritable(String),
+
+    #[error("{id} spans {count} registers, use write_multiple_registers")]
+    TooWide { id: String, count: usize },
+
+    #[error("{id}.write_formula: {source}")]
+    Formula {
+        id: String,
+        #[source]
+        source: ExpressionError,
+    },
+
+    #[error(transparent)]
+    Modbus(#[from] ModbusError),
+}
+
+/// 调试时直接读写寄存器的错误
+#[derive(Debug, Error)]
+pub enum RegisterError {
+    #[error("unknown Modbus device '{0}'")]
+    UnknownDevice(String),
+
+    #[error(transparent)]
+    Modbus(#[from] ModbusError),
+}
+
+/// 一次轮询的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PollReport {
+    /// 得到数值的属性和计算传感器
+    pub values: usize,
+    /// 其中质量不是 Good 的
+    pub bad: usize,
+    /// 得到数值的冗余分组属性
+    pub groups: usize,
+    /// 失败的读取请求
+    pub failed_reads: usize,
+}
+
+/// 属性在两次轮询之间保留的状态，用于判断 Stale/CommError 和 Frozen
+#[derive(Debug, Clone, Copy, Default)]
+struct AttrState {
+    /// 上次成功计算的工程值和时间
+    last: Option<(f64, Instant)>,
+    /// 上次的原始值和它开始保持不变的时间
+    raw: Option<(f64, Instant)>,
+}
+
+/// 轮询计划、冗余分组和计算传感器在启动时按 sensors.yaml 建立，运行中修改传感器配置要重启后才生效。
+/// 注册到传感器配置的 FileStore，配置变化时提醒重启
+pub struct RestartOnSensorsChange;
+
+impl ConfigChangeCallback for RestartOnSensorsChange {
+    fn on_config_change(&self, new_config: &Config, old_config: &Config) {
+        if new_config != old_config {
+            log::warn!("Sensor configuration changed, restart to apply it to polling");
+        }
+    }
+}
+
+/// 按 sensors.yaml 轮询所有传感器：读取寄存器、按公式计算工程值、冗余分组和计算传感器，
+/// 每个数值带有质量状态，数值或质量变化时发布 SensorUpdated/GroupUpdated，质量为 Good 的数值写入历史数据。
+/// 每个设备可以单独轮询，一个设备无响应不影响其他设备
+pub struct PollingService {
+    catalog: SensorCatalog,
+    devices: Vec<DevicePoller>,
+    /// 每个属性所在传感器的全部属性下标，公式中的 $attr 在其中查找
+    siblings: Vec<Vec<usize>>,
+    /// 每个属性最近一次读取的原始值和读取时间，读取失败时为 None
+    raw: Mutex<Vec<Option<(f64, Instant)>>>,
+    events: EventBus,
+    history: Option<Arc<HistoryStore>>,
+    stale_timeout: Duration,
+    states: Mutex<Vec<AttrState>>,
+    latest: Mutex<HashMap<String, Reading>>,
+    /// 分组的最新值，键为 "Plugin.Group.attr"
+    group_values: Mutex<HashMap<String, Reading>>,
+    /// 字符串属性（型号、序列号等）的最新值，键为 "Plugin.Sensor.attr"，没有工程值
+    texts: Mutex<HashMap<String, String>>,
+    /// 当前处于偏差告警状态的分组属性
+    discrepant: Mutex<HashSet<String>>,
+}
+
+impl PollingService {
+    /// reader 用于所有设备，可以再用 with_device_reader 为设备指定各自的连接
+    pub fn new(catalog: SensorCatalog, reader: Box<dyn RegisterReader>, events: EventBus) -> Self {
+        let siblings = catalog
+            .attrs
+            .iter()
+            .map(|attr| {
+                catalog
+                    .attrs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| other.plugin == attr.plugin && other.sensor == attr.sensor)
+                    .map(|(i, _)| i)
+                    .collect()
+            })
+            .collect();
+        let reader = Arc::new(tokio::sync::Mutex::new(reader));
+        let devices = std::iter::once(None)
+            .chain(catalog.devices.iter().map(|d| Some(d.name.clone())))
+            .map(|device| DevicePoller {
+                batches: plan_batches(&catalog, device.as_deref()),
+                device,
+                reader: Arc::clone(&reader),
+            })
+            .collect();
+        Self {
+            devices,
+            states: Mutex::new(vec![AttrState::default(); catalog.attrs.len()]),
+            raw: Mutex::new(vec![None; catalog.attrs.len()]),
+            catalog,
+            siblings,
+            events,
+            history: None,
+            stale_timeout: Duration::from_secs_f64(PollingConfig::default().stale_timeout),
+            latest: Mutex::new(HashMap::new()),
+            group_values: Mutex::new(HashMap::new()),
+            texts: Mutex::new(HashMap::new()),
+            discrepant: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn with_history(mut self, history: Arc<HistoryStore>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// 读取失败后沿用上次值的时间，超过后质量为 CommError
+    pub fn with_stale_timeout(mut self, stale_timeout: Duration) -> Self {
+        self.stale_timeout = stale_timeout;
+        self
+    }
+
+    /// 这些设备（None 为默认设备）共用 reader 这个连接，例如同一条 RS-485 总线上的多个从站
+    pub fn with_device_reader(mut self, devices: &[Option<&str>], reader: Box<dyn RegisterReader>) -> Self {
+        let reader = Arc::new(tokio::sync::Mutex::new(reader));
+        for poller in &mut self.devices {
+            if devices.contains(&poller.device.as_deref()) {
+                poller.reader = Arc::clone(&reader);
+            }
+        }
+        self
+    }
+
+    /// 只保留这些有连接的设备（None 为默认设备）。其余设备不轮询，直接读写寄存器时报告 UnknownDevice，
+    /// 不会借用 new 传入的其他设备的连接
+    pub fn with_connected_devices(mut self, devices: &[Option<&str>]) -> Self {
+        self.devices.retain(|poller| devices.contains(&poller.device.as_deref()));
+        self
+    }
+
+    /// 依次读取所有设备后更新全部数值。运行时每个设备按各自的周期调用 poll_device
+    #[cfg(test)]
+    pub async fn poll_once(&self) -> PollReport {
+        let mut failed_reads = 0;
+        for poller in &self.devices {
+            failed_reads += self.read_device(poller).await;
+        }
+        self.update(failed_reads).await
+    }
+
+    /// 只读取一个设备（None 为默认设备），其他设备沿用最近一次读取的原始值
+    pub async fn poll_device(&self, device: Option<&str>) -> PollReport {
+        let Some(poller) = self.devices.iter().find(|p| p.device.as_deref() == device) else {
+            return PollReport::default();
+        };
+        let failed_reads = self.read_device(poller).await;
+        self.update(failed_reads).await
+    }
+
+    /// 读取一个设备的全部寄存器并更新原始值，返回失败的请求数
+    async fn read_device(&self, poller: &DevicePoller) -> usize {
+        let mut failed_reads = 0;
+        let mut results: Vec<(usize, Option<(f64, Instant)>)> = Vec::new();
+        {
+            let mut reader = poller.reader.lock().await;
+            for (n, batch) in poller.batches.iter().enumerate() {
+                match reader.read(batch.slave, batch.method, batch.start, batch.count).await {
+                    Ok(values) => {
+                        let now = Instant::now();
+                        for (i, offset) in &batch.members {
+                            let attr = &self.catalog.attrs[*i];
+                            let words = values.get(*offset..).unwrap_or_default();
+                            if let Some(text) = attr.data_type.decode_text(words, attr.byte_order) {
+                                self.texts.lock().unwrap().insert(attr.id(), text);
+                            }
+                            results.push((*i, attr.decode(words).map(|raw| (raw, now))));
+                        }
+                    }
+                    Err(e) => {
+                        failed_reads += 1;
+                        results.extend(batch.members.iter().map(|(i, _)| (*i, None)));
+                        log::debug!(
+                            "Read {} {:?} {}+{} failed: {}",
+                            poller.device.as_deref().unwrap_or(DEVICE_NAME),
+                            batch.method,
+                            batch.start,
+                            batch.count,
+                            e
+                        );
+                        // 连接断开或超时后，同一周期里剩下的请求也会失败，留到下个周期再重连
+                        if e.is_link_error() {
+                            let remaining = &poller.batches[n + 1..];
+                            failed_reads += remaining.len();
+                            results.extend(remaining.iter().flat_map(|b| b.members.iter().map(|(i, _)| (*i, None))));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        let mut raw = self.raw.lock().unwrap();
+        for (i, value) in results {
+            raw[i] = value;
+        }
+        failed_reads
+    }
+
+    /// 按 write_method 写入一个属性的工程值（"Plugin.Sensor.attr"），经过 write_formula 和数据类型编码。
+    /// 寄存器中的位先读出整个寄存器再只改这一位；read_write_multiple_registers 读回的值在下次更新时生效
+    pub async fn write(&self, id: &str, value: f64) -> Result<(), WriteError> {
+        let i = self
+            .catalog
+            .attrs
+            .iter()
+            .position(|attr| attr.id() == id)
+            .ok_or_else(|| WriteError::UnknownSensor(id.to_string()))?;
+        let attr = &self.catalog.attrs[i];
+        let (Some(method), Some(address)) = (attr.write_method, attr.address) else {
+            return Err(WriteError::NotWritable(id.to_string()));
+        };
+        let raw = attr.unscale(value).map_err(|source| WriteError::Formula {
+            id: id.to_string(),
+            source,
+        })?;
+        let mut words = attr.encode(raw);
+        if method.is_single() && words.len() > 1 {
+            return Err(WriteError::TooWide {
+                id: id.to_string(),
+                count: words.len(),
+            });
+        }
+        let Some(poller) = self.devices.iter().find(|p| p.device == attr.device) else {
+            return Err(WriteError::NotWritable(id.to_string()));
+        };
+
+        let read_back = {
+            let mut reader = poller.reader.lock().await;
+            if let (DataType::Bit(bit), Some(read_method)) = (attr.data_type, attr.read_method) {
+                let current = reader.read(attr.slave_id, read_method, address, 1).await?;
+                let current = current.first().copied().unwrap_or_default();
+                words = vec![(current & !(1 << bit)) | (words[0] & (1 << bit))];
+            }
+            reader.write(attr.slave_id, method, address, &words).await?
+        };
+        log::info!("Wrote {} = {} ({:?} {} {:?})", id, value, method, address, words);
+        if !read_back.is_empty() {
+            self.raw.lock().unwrap()[i] = attr.decode(&read_back).map(|raw| (raw, Instant::now()));
+        }
+        Ok(())
+    }
+
+    /// 属性（"Plugin.Sensor.attr"）的配置
+    pub fn attr(&self, id: &str) -> Option<&AttrSpec> {
+        self.catalog.attr(id)
+    }
+
+    /// 通过设备（None 为默认设备）的轮询连接读取任意寄存器，与轮询请求依次执行
+    pub async fn read_registers(
+        &self,
+        device: Option<&str>,
+        slave: Option<u8>,
+        method: ReadMethod,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, RegisterError> {
+        let poller = self.poller(device)?;
+        let values = poller.reader.lock().await.read(slave, method, address, count).await?;
+        Ok(values)
+    }
+
+    /// 通过设备的轮询连接写入任意寄存器或线圈，返回功能码 23 读回的值。
+    /// 不经过属性的 write_formula，写入的值在下次轮询时读回
+    pub async fn write_registers(
+        &self,
+        device: Option<&str>,
+        slave: Option<u8>,
+        method: WriteMethod,
+        address: u16,
+        values: &[u16],
+    ) -> Result<Vec<u16>, RegisterError> {
+        let poller = self.poller(device)?;
+        let read_back = poller.reader.lock().await.write(slave, method, address, values).await?;
+        log::info!(
+            "Wrote {} {:?} {} {:?}",
+            device.unwrap_or(DEVICE_NAME),
+            method,
+            address,
+            values
+        );
+        Ok(read_back)
+    }
+
+    fn poller(&self, device: Option<&str>) -> Result<&DevicePoller, RegisterError> {
+        self.devices
+            .iter()
+            .find(|p| p.device.as_deref() == device)
+            .ok_or_else(|| RegisterError::UnknownDevice(device.unwrap_or(DEVICE_NAME).to_string()))
+    }
+
+    /// 由最近的原始值计算属性、分组和计算传感器，发布变化并写入历史数据
+    async fn update(&self, failed_reads: usize) -> PollReport {
+        let mut report = PollReport {
+            failed_reads,
+            ..Default::default()
+        };
+        let raw = self.raw.lock().unwrap().clone();
+        let readings = self.evaluate_attrs(&raw);
+        let mut values: Vec<(String, Reading)> = Vec::new();
+        let mut updates = Vec::new();
+        for (i, (attr, reading)) in self.catalog.attrs.iter().zip(readings).enumerate() {
+            if let Some(reading) = reading {
+                values.push((attr.id(), reading));
+                updates.push((attr.plugin.clone(), attr.sensor.clone(), attr.attr.clone(), Some(i)));
+            }
+        }
+
+        let mut by_id: HashMap<String, Reading> = values.iter().cloned().collect();
+        let groups = self.resolve_groups(&by_id).await;
+        report.groups = groups.len();
+        // 分组名与主设备同名时（P1/P1'），表达式中的 "Pressures.P1.value" 引用分组值
+        by_id.extend(groups);
+
+        for computed in &self.catalog.computed {
+            match computed.evaluate(|name| by_id.get(name).map(|r| r.value)) {
+                Ok(value) => {
+                    let quality = computed.quality(|name| by_id.get(name).map(|r| r.quality));
+                    values.push((computed.name.clone(), Reading { value, quality }));
+                    updates.push((COMPUTED_PLUGIN.to_string(), computed.name.clone(), "value".to_string(), None));
+                }
+                Err(e) => log::debug!("{}: {}", computed.name, e),
+            }
+        }
+        report.values = values.len();
+        report.bad = values.iter().filter(|(_, r)| !r.quality.is_good()).count();
+
+        let changed: Vec<_> = {
+            let mut latest = self.latest.lock().unwrap();
+            updates
+                .into_iter()
+                .zip(values.iter())
+                .filter(|(_, (id, reading))| latest.insert(id.clone(), *reading) != Some(*reading))
+                .map(|((plugin, sensor, attr, i), (_, reading))| (plugin, sensor, attr, i, *reading))
+                .collect()
+        };
+        for (plugin, sensor, attr, i, reading) in changed {
+            let value = match i {
+                Some(i) => self.catalog.attrs[i].publish_value(reading.value),
+                None => SensorValue::Number(reading.value),
+            };
+            self.events
+                .publish(Event::SensorUpdated {
+                    plugin,
+                    sensor,
+                    attr,
+                    value,
+                    quality: reading.quality,
+                })
+                .await;
+        }
+
+        if let Some(history) = &self.history {
+            let good: Vec<(String, f64)> = values
+                .into_iter()
+                .filter(|(_, r)| r.quality.is_good())
+                .map(|(id, r)| (id, r.value))
+                .collect();
+            // 缓存满时 record 会写文件，放到阻塞线程上，不占用轮询所在的异步线程
+            let history = Arc::clone(history);
+            let time = OffsetDateTime::now_utc();
+            match tokio::task::spawn_blocking(move || history.record(time, &good)).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::warn!("Failed to record history: {}", e),
+                Err(e) => log::warn!("Failed to record history: {}", e),
+            }
+        }
+        report
+    }
+
+    /// 由原始值计算每个属性的工程值和质量，下标与 catalog.attrs 一致。
+    /// Stale/CommError 和 Frozen 按读取时间判断，所以其他设备轮询时重新计算不会刷新这些时间
+    fn evaluate_attrs(&self, cached: &[Option<(f64, Instant)>]) -> Vec<Option<Reading>> {
+        let now = Instant::now();
+        let raw: Vec<Option<f64>> = cached.iter().map(|c| c.map(|(value, _)| value)).collect();
+        let attrs = &self.catalog.attrs;
+        let mut readings: Vec<Option<Reading>> = vec![None; attrs.len()];
+        let mut states = self.states.lock().unwrap();
+        // 先算有地址的属性，没有地址的属性的质量取决于它们
+        let addressed = (0..attrs.len()).filter(|i| attrs[*i].address.is_some());
+        let derived = (0..attrs.len()).filter(|i| attrs[*i].address.is_none());
+        for i in addressed.chain(derived) {
+            let attr = &attrs[i];
+            let inputs: Vec<usize> = match attr.address {
+                Some(_) => vec![i],
+                None => self.siblings[i].iter().copied().filter(|j| attrs[*j].address.is_some()).collect(),
+            };
+            let state = &mut states[i];
+            if inputs.iter().any(|j| raw[*j].is_none()) {
+                // 读取失败时沿用上次的值，超过 stale_timeout 后为 CommError
+                if let Some((value, at)) = state.last {
+                    let quality = if now - at < self.stale_timeout {
+                        Quality::Stale
+                    } else {
+                        Quality::CommError
+                    };
+                    readings[i] = Some(Reading { value, quality });
+                }
+                continue;
+            }
+
+            let sibling = |name: &str| {
+                self.siblings[i]
+                    .iter()
+                    .find(|j| attrs[**j].attr == name)
+                    .and_then(|j| raw[*j])
+            };
+            let value = match attr.scale(sibling) {
+                Ok(value) => value,
+                Err(e) => {
+                    log::debug!("{}: {}", attr.id(), e);
+                    continue;
+                }
+            };
+            let read_at = inputs.iter().filter_map(|j| cached[*j].map(|(_, at)| at)).min().unwrap_or(now);
+            let input_quality = inputs.iter().filter(|j| **j != i).filter_map(|j| readings[*j]).map(|r| r.quality);
+            let mut quality = Quality::worst(input_quality.chain([attr.check_range(raw[i], value)]));
+            if let (Some(timeout), Some(raw_value)) = (attr.frozen_timeout, raw[i]) {
+                match state.raw {
+                    Some((last, since)) if last == raw_value => {
+                        if quality.is_good() && now - since >= Duration::from_secs_f64(timeout) {
+                            quality = Quality::Frozen;
+                        }
+                    }
+                    _ => state.raw = Some((raw_value, read_at)),
+                }
+            }
+            state.last = Some((value, read_at));
+            readings[i] = Some(Reading { value, quality });
+        }
+        readings
+    }
+
+    /// 按策略合并每个分组中各设备的同名属性，检查设备之间的偏差，返回 "Plugin.Group.attr" 到分组值
+    async fn resolve_groups(&self, by_id: &HashMap<String, Reading>) -> HashMap<String, Reading> {
+        let mut resolved = HashMap::new();
+        let mut updates = Vec::new();
+        let mut alarms = Vec::new();
+        for group in &self.catalog.groups {
+            let attrs = self
+                .catalog
+                .attrs
+                .iter()
+                .filter(|attr| attr.plugin == group.plugin && Some(&attr.sensor) == group.devices.first());
+            for attr in attrs {
+                let id = format!("{}.{}.{}", group.plugin, group.name, attr.attr);
+                let readings: Vec<Option<Reading>> = group
+                    .devices
+                    .iter()
+                    .map(|device| by_id.get(&format!("{}.{}.{}", group.plugin, device, attr.attr)).copied())
+                    .collect();
+
+                // 质量不好的设备不参与偏差检查
+                let good: Vec<Option<f64>> = readings
+                    .iter()
+                    .map(|r| r.filter(|r| r.quality.is_good()).map(|r| r.value))
+                    .collect();
+                // 校验要求设置 tolerance 时必须有 alarm_address，没有地址的分组不产生告警
+                let mut discrepant = self.discrepant.lock().unwrap();
+                let active = discrepant.contains(&id);
+                match (group.alarm_address, group.discrepancy(&good, active)) {
+                    (Some(address), Some((true, spread))) if !active => {
+                        log::warn!("{}: devices disagree by {} (tolerance {:?})", id, spread, group.tolerance);
+                        discrepant.insert(id.clone());
+                        alarms.push(Event::AlarmRaised {
+                            address,
+                            name: format!("{} discrepancy", id),
+                            level: group.alarm_level.clone(),
+                        });
+                    }
+                    (Some(address), Some((false, _))) if active => {
+                        discrepant.remove(&id);
+                        alarms.push(Event::AlarmCleared {
+                            address,
+                            name: format!("{} discrepancy", id),
+                        });
+                    }
+                    // 冗余丢失（少于两个设备质量良好）时无法比较，告警保持原状
+                    _ => {}
+                }
+                drop(discrepant);
+
+                let Some(reading) = group.resolve(&readings) else {
+                    continue;
+                };
+                let reading = Reading {
+                    value: attr.round(reading.value),
+                    ..reading
+                };
+                let sources = good.iter().flatten().count();
+                if self.group_values.lock().unwrap().insert(id.clone(), reading) != Some(reading) {
+                    updates.push(Event::GroupUpdated {
+                        plugin: group.plugin.clone(),
+                        group: group.name.clone(),
+                        attr: attr.attr.clone(),
+                        value: reading.value,
+                        quality: reading.quality,
+                        sources,
+                    });
+                }
+                resolved.insert(id, reading);
+            }
+        }
+        for event in alarms.into_iter().chain(updates) {
+            self.events.publish(event).await;
+        }
+        resolved
+    }
+
+    /// 字符串属性的最新值
+    pub fn text(&self, plugin: &str, sensor: &str, attr: &str) -> Option<String> {
+        self.texts.lock().unwrap().get(&format!("{}.{}.{}", plugin, sensor, attr)).cloned()
+    }
+
+    /// 告警规则取值：plugin 下的分组优先，其次是同名传感器；没有 plugin 时查找计算传感器。
+    /// 质量不是 Good 的数值由调用方决定是否参与判断
+    pub fn value(&self, plugin: Option<&str>, name: &str, attr: &str) -> Option<Reading> {
+        match plugin {
+            Some(plugin) => {
+                let id = format!("{}.{}.{}", plugin, name, attr);
+                let group = self.group_values.lock().unwrap().get(&id).copied();
+                group.or_else(|| self.latest.lock().unwrap().get(&id).copied())
+            }
+            None => {
+                let groups = self.group_values.lock().unwrap();
+                let group = groups.iter().find(|(id, _)| {
+                    let mut parts = id.splitn(3, '.').skip(1);
+                    parts.next() == Some(name) && parts.next() == Some(attr)
+                });
+                match group {
+                    Some((_, reading)) => Some(*reading),
+                    None => self.latest.lock().unwrap().get(name).copied(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::config::loader::HistoryConfig;
+    use crate::models::event_bus::{EventFilter, EventKind};
+    use crate::models::sensor::WriteMethod;
+    use crate::services::modbus_service::ModbusError;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    /// (写入方式, 地址, 写入的值)
+    pub type Write = (WriteMethod, u16, Vec<u16>);
+
+    /// 内存中的寄存器表，记录每次请求
+    #[derive(Clone, Default)]
+    pub struct FakeRegisters {
+        pub registers: Arc<Mutex<HashMap<(ReadMethod, u16), u16>>>,
+        pub requests: Arc<Mutex<Vec<(ReadMethod, u16, u16)>>>,
+        pub writes: Arc<Mutex<Vec<Write>>>,
+    }
+
+    impl FakeRegisters {
+        pub fn set(&self, method: ReadMethod, address: u16, value: u16) {
+            self.registers.lock().unwrap().insert((method, address), value);
+        }
+    }
+
+    #[async_trait]
+    impl RegisterReader for FakeRegisters {
+        async fn read(
+            &mut self,
+            _slave: Option<u8>,
+            method: ReadMethod,
+            address: u16,
+            count: u16,
+        ) -> Result<Vec<u16>, ModbusError> {
+            self.requests.lock().unwrap().push((method, address, count));
+            let registers = self.registers.lock().unwrap();
+            (address..address + count)
+                .map(|a| {
+                    registers
+                        .get(&(method, a))
+                        .copied()
+                        .ok_or(ModbusError::Exception(tokio_modbus::ExceptionCode::IllegalDataAddress))
+                })
+                .collect()
+        }
+
+        async fn write(
+            &mut self,
+            _slave: Option<u8>,
+            method: WriteMethod,
+            address: u16,
+            values: &[u16],
+        ) -> Result<Vec<u16>, ModbusError> {
+            self.writes.lock().unwrap().push((method, address, values.to_vec()));
+            let target = match method {
+                WriteMethod::SingleCoil | WriteMethod::MultipleCoils => ReadMethod::Coils,
+                _ => ReadMethod::HoldingRegisters,
+            };
+            for (a, value) in (address..).zip(values) {
+                self.set(target, a, *value);
+            }
+            match method {
+                WriteMethod::ReadWriteMultipleRegisters => self.read(None, target, address, values.len() as u16).await,
+                _ => Ok(Vec::new()),
+            }
+        }
+    }
+
+    fn catalog() -> SensorCatalog {
+        SensorCatalog::from_config(&json!({
+            "sensor_plugins": [
+                {
+                    "name": "Temperatures",
+                    "defaults": {"value": {"read_method": "read_holding_registers", "formula": "$value / 10", "decimal_places": 1}},
+                    "sensors": [
+                        {"name": "T1", "attrs": {"value": 3328}},
+                        {"name": "T2", "attrs": {"value": 3329}},
+                        {"name": "T3", "attrs": {"value": 3340}}
+                    ]
+                },
+                {
+                    "name": "PHTs",
+                    "defaults": {
+                        "Temperature": {"read_method": "read_holding_registers", "formula": "$Temperature / 100"},
+                        "Humidity": {"read_method": "read_holding_registers", "formula": "$Humidity / 100"},
+                        "DewPoint": {"formula": "$Temperature / 100 - (10000 - $Humidity) / 500"}
+                    },
+                    "sensors": [{"name": "PHT1", "attrs": {"Temperature": 3344, "Humidity": 3345}}]
+                },
+                {
+                    "name": "Leakages",
+                    "defaults": {"value": {"read_method": "read_coils"}},
+                    "sensors": [{"name": "LE1", "attrs": {"value": 10}}]
+                }
+            ],
+            "computed_sensors": [
+                {"name": "T2-T1", "expression": "Temperatures.T2.value - Temperatures.T1.value", "decimal_places": 1},
+                {"name": "T3-T1", "expression": "Temperatures.T3.value - Temperatures.T1.value"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_batches() {
+        let batches = plan_batches(&catalog(), None);
+        let ranges: Vec<(ReadMethod, u16, u16)> = batches.iter().map(|b| (b.method, b.start, b.count)).collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (ReadMethod::HoldingRegisters, 3328, 2),
+                (ReadMethod::HoldingRegisters, 3340, 1),
+                (ReadMethod::HoldingRegisters, 3344, 2),
+                (ReadMethod::Coils, 10, 1),
+            ]
+        );
+
+        // 其他从站上的属性单独读取
+        let mut catalog = catalog();
+        catalog.attrs[1].slave_id = Some(2);
+        let batches = plan_batches(&catalog, None);
+        let ranges: Vec<(Option<u8>, u16, u16)> = batches.iter().map(|b| (b.slave, b.start, b.count)).collect();
+        assert_eq!(
+            ranges,
+            vec![(None, 3328, 1), (None, 3340, 1), (None, 3344, 2), (None, 10, 1), (Some(2), 3329, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_poll_publishes_and_records() {
+        let dir = tempdir().unwrap();
+        let history = Arc::new(
+            HistoryStore::open(&HistoryConfig {
+                dir: dir.path().to_string_lossy().to_string(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let registers = FakeRegisters::default();
+        registers.set(ReadMethod::HoldingRegisters, 3328, 215);
+        registers.set(ReadMethod::HoldingRegisters, 3329, 287);
+        registers.set(ReadMethod::HoldingRegisters, 3344, 2500);
+        registers.set(ReadMethod::HoldingRegisters, 3345, 6000);
+        registers.set(ReadMethod::Coils, 10, 1);
+        // 3340 未设置，T3 读取失败
+
+        let events = EventBus::new();
+        let mut subscription = events.subscribe(EventFilter::kinds(&[EventKind::SensorUpdated]));
+        let service = PollingService::new(catalog(), Box::new(registers.clone()), events.clone())
+            .with_history(Arc::clone(&history));
+
+        let report = service.poll_once().await;
+        assert_eq!(
+            report,
+            PollReport {
+                values: 7,
+                bad: 0,
+                groups: 0,
+                failed_reads: 1
+            }
+        );
+
+        let mut updates = HashMap::new();
+        while let Some(Event::SensorUpdated { plugin, sensor, attr, value, .. }) = subscription.try_recv() {
+            updates.insert(format!("{}.{}.{}", plugin, sensor, attr), value);
+        }
+        assert_eq!(updates.len(), 7);
+        assert_eq!(updates["Temperatures.T1.value"], SensorValue::Number(21.5));
+        assert_eq!(updates["PHTs.PHT1.DewPoint"], SensorValue::Number(17.0));
+        // 线圈以布尔值发布
+        assert_eq!(updates["Leakages.LE1.value"], SensorValue::Bool(true));
+        assert_eq!(updates["computed.T2-T1.value"], SensorValue::Number(7.2));
+        assert!(!updates.contains_key("computed.T3-T1.value"));
+
+        // 数值不变时不重复发布
+        registers.set(ReadMethod::HoldingRegisters, 3328, 216);
+        service.poll_once().await;
+        let mut changed = Vec::new();
+        while let Some(Event::SensorUpdated { sensor, .. }) = subscription.try_recv() {
+            changed.push(sensor);
+        }
+        assert_eq!(changed, vec!["T1", "T2-T1"]);
+
+        let now = OffsetDateTime::now_utc();
+        let samples: Vec<_> = history
+            .query(&["T2-T1".to_string()], now - time::Duration::minutes(1), now)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(!samples.is_empty());
+        assert_eq!(samples[0].avg, 7.2);
+    }
+
+    #[tokio::test]
+    async fn test_multi_register_values() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "sensor_plugins": [{
+                "name": "Meters",
+                "defaults": {
+                    "Power": {"read_method": "read_holding_registers", "data_type": "f32", "byte_order": "CDAB", "formula": "$Power / 1000"},
+                    "Energy": {"read_method": "read_holding_registers", "data_type": "u32"},
+                    "Running": {"read_method": "read_holding_registers", "data_type": "bit", "bit": 2},
+                    "Model": {"read_method": "read_holding_registers", "data_type": "string", "length": 2}
+                },
+                "sensors": [{"name": "M1", "attrs": {"Power": 100, "Energy": 102, "Running": 104, "Model": 105}}]
+            }]
+        }))
+        .unwrap();
+        let batches = plan_batches(&catalog, None);
+        assert_eq!(batches.len(), 1);
+        assert_eq!((batches[0].start, batches[0].count), (100, 7));
+
+        let registers = FakeRegisters::default();
+        // 12500.0f32 = 0x46435000，字交换
+        for (address, value) in [(100, 0x5000), (101, 0x4643), (102, 0x0001), (103, 0x0000), (104, 0b0100), (105, 0x4D31), (106, 0x0000)] {
+            registers.set(ReadMethod::HoldingRegisters, address, value);
+        }
+        let service = PollingService::new(catalog, Box::new(registers), EventBus::new());
+        assert_eq!(service.poll_once().await.failed_reads, 0);
+        let value = |attr: &str| service.value(Some("Meters"), "M1", attr).map(|r| r.value);
+        assert_eq!(value("Power"), Some(12.5));
+        assert_eq!(value("Energy"), Some(65536.0));
+        assert_eq!(value("Running"), Some(1.0));
+        assert_eq!(value("Model"), None);
+        assert_eq!(service.text("Meters", "M1", "Model").as_deref(), Some("M1"));
+    }
+
+    #[tokio::test]
+    async fn test_write_methods() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "sensor_plugins": [
+                {
+                    "name": "Pumps",
+                    "defaults": {
+                        "DutyCycle": {"read_method": "read_holding_registers", "write_method": "write_single_register",
+                                      "formula": "$DutyCycle / 60", "write_formula": "$value * 60"},
+                        "Setpoint": {"read_method": "read_holding_registers", "write_method": "write_multiple_registers", "data_type": "f32"},
+                        "Enable": {"read_method": "read_holding_registers", "write_method": "write_single_register", "data_type": "bit", "bit": 1},
+                        "Speed": {"read_method": "read_holding_registers"},
+                        "Limit": {"read_method": "read_holding_registers", "write_method": "read_write_multiple_registers", "data_type": "u32"}
+                    },
+                    "sensors": [{"name": "Pump1", "attrs": {"DutyCycle": 2081, "Setpoint": 2090, "Enable": 2100, "Speed": 2080, "Limit": 2110}}]
+                },
+                {
+                    "name": "Leakages",
+                    "defaults": {"value": {"read_method": "read_coils", "write_method": "write_single_coil"}},
+                    "sensors": [{"name": "LE1", "attrs": {"value": 10}}]
+                }
+            ]
+        }))
+        .unwrap();
+        let registers = FakeRegisters::default();
+        registers.set(ReadMethod::HoldingRegisters, 2100, 0b0101);
+        let service = PollingService::new(catalog, Box::new(registers.clone()), EventBus::new());
+
+        service.write("Pumps.Pump1.DutyCycle", 50.0).await.unwrap();
+        service.write("Pumps.Pump1.Setpoint", 123.5).await.unwrap();
+        service.write("Pumps.Pump1.Enable", 1.0).await.unwrap();
+        service.write("Leakages.LE1.value", 0.0).await.unwrap();
+        service.write("Pumps.Pump1.Limit", 70000.0).await.unwrap();
+        assert_eq!(
+            *registers.writes.lock().unwrap(),
+            vec![
+                (WriteMethod::SingleRegister, 2081, vec![3000]),
+                (WriteMethod::MultipleRegisters, 2090, vec![0x42F7, 0x0000]),
+                // 只改第 1 位，其他位保持不变
+                (WriteMethod::SingleRegister, 2100, vec![0b0111]),
+                (WriteMethod::SingleCoil, 10, vec![0]),
+                (WriteMethod::ReadWriteMultipleRegisters, 2110, vec![0x0001, 0x1170]),
+            ]
+        );
+
+        assert!(matches!(service.write("Pumps.Pump1.Speed", 1.0).await, Err(WriteError::NotWritable(_))));
+        assert!(matches!(service.write("Pumps.Pump9.Speed", 1.0).await, Err(WriteError::UnknownSensor(_))));
+
+        // 读回的值在下次更新时生效，不必等待下一次读取
+        service.update(0).await;
+        assert_eq!(service.value(Some("Pumps"), "Pump1", "Limit").unwrap().value, 70000.0);
+    }
+
+    /// 不响应的从站，每次请求都等到超时
+    struct Unresponsive(Duration);
+
+    #[async_trait]
+    impl RegisterReader for Unresponsive {
+        async fn read(&mut self, _slave: Option<u8>, _method: ReadMethod, _address: u16, _count: u16) -> Result<Vec<u16>, ModbusError> {
+            tokio::time::sleep(self.0).await;
+            Err(ModbusError::Timeout(self.0))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_devices_poll_independently() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "devices": [
+                {"name": "drive", "tcp": {"host": "192.168.1.151"}, "slave_id": 3, "byte_order": "BADC"},
+                {"name": "rtu", "serial": {"port": "/dev/ttyS1"}, "poll_interval": 5}
+            ],
+            "sensor_plugins": [
+                {
+                    "name": "Pumps",
+                    "device": "drive",
+                    "defaults": {"Speed": {"read_method": "read_holding_registers"}},
+                    "sensors": [{"name": "Pump1", "attrs": {"Speed": 2080}}]
+                },
+                {
+                    "name": "Temperatures",
+                    "defaults": {"value": {"read_method": "read_holding_registers", "formula": "$value / 10"}},
+                    "sensors": [
+                        {"name": "T1", "attrs": {"value": 3328}},
+                        {"name": "T2", "device": "rtu", "attrs": {"value": 3329}}
+                    ]
+                }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(catalog.attrs[0].slave_id, Some(3));
+        assert_eq!(catalog.devices[1].poll_interval, Some(5.0));
+
+        let default = FakeRegisters::default();
+        default.set(ReadMethod::HoldingRegisters, 3328, 215);
+        let drive = FakeRegisters::default();
+        drive.set(ReadMethod::HoldingRegisters, 2080, 0x3412);
+        let service = Arc::new(
+            PollingService::new(catalog, Box::new(default.clone()), EventBus::new())
+                .with_device_reader(&[Some("drive")], Box::new(drive.clone()))
+                .with_device_reader(&[Some("rtu")], Box::new(Unresponsive(Duration::from_secs(3)))),
+        );
+
+        // rtu 的请求挂起时，其他设备照常轮询
+        let stalled = tokio::spawn({
+            let service = Arc::clone(&service);
+            async move { service.poll_device(Some("rtu")).await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(service.poll_device(None).await.failed_reads, 0);
+        assert_eq!(service.poll_device(Some("drive")).await.failed_reads, 0);
+        assert!(!stalled.is_finished());
+
+        let t1 = service.value(Some("Temperatures"), "T1", "value").unwrap();
+        assert_eq!((t1.value, t1.quality), (21.5, Quality::Good));
+        // BADC：寄存器内的两个字节交换
+        assert_eq!(service.value(Some("Pumps"), "Pump1", "Speed").unwrap().value, 4660.0);
+        assert_eq!(*default.requests.lock().unwrap(), vec![(ReadMethod::HoldingRegisters, 3328, 1)]);
+        assert_eq!(*drive.requests.lock().unwrap(), vec![(ReadMethod::HoldingRegisters, 2080, 1)]);
+
+        assert_eq!(stalled.await.unwrap().failed_reads, 1);
+        assert!(service.value(Some("Temperatures"), "T2", "value").is_none());
+        assert!(service.value(Some("Temperatures"), "T1", "value").unwrap().quality.is_good());
+    }
+
+    #[tokio::test]
+    async fn test_device_without_connection_has_no_poller() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "devices": [{"name": "drive", "tcp": {"host": "192.168.1.151"}}],
+            "sensor_plugins": [{
+                "name": "Pumps",
+                "device": "drive",
+                "defaults": {"Speed": {"read_method": "read_holding_registers"}},
+                "sensors": [{"name": "Pump1", "attrs": {"Speed": 2080}}]
+            }]
+        }))
+        .unwrap();
+        // modbus_client 未启用，第一个连接是 drive 的
+        let drive = FakeRegisters::default();
+        drive.set(ReadMethod::HoldingRegisters, 2080, 1500);
+        let service = PollingService::new(catalog, Box::new(drive.clone()), EventBus::new())
+            .with_connected_devices(&[Some("drive")]);
+
+        assert!(matches!(
+            service.read_registers(None, None, ReadMethod::HoldingRegisters, 2080, 1).await,
+            Err(RegisterError::UnknownDevice(ref name)) if name == DEVICE_NAME
+        ));
+        assert!(matches!(
+            service.write_registers(None, None, WriteMethod::SingleRegister, 2080, &[0]).await,
+            Err(RegisterError::UnknownDevice(_))
+        ));
+        assert!(drive.requests.lock().unwrap().is_empty());
+        assert_eq!(service.read_registers(Some("drive"), None, ReadMethod::HoldingRegisters, 2080, 1).await.unwrap(), vec![1500]);
+    }
+
+    /// 连接断开的设备，记录收到的请求数
+    struct Disconnected(Arc<std::sync::atomic::AtomicUsize>);
+
+    #[async_trait]
+    impl RegisterReader for Disconnected {
+        async fn read(&mut self, _slave: Option<u8>, _method: ReadMethod, _address: u16, _count: u16) -> Result<Vec<u16>, ModbusError> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(ModbusError::Timeout(Duration::from_secs(1)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_error_aborts_device_cycle() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "sensor_plugins": [{
+                "name": "Temperatures",
+                "defaults": {"value": {"read_method": "read_holding_registers"}},
+                "sensors": [
+                    {"name": "T1", "attrs": {"value": 3328}},
+                    {"name": "T2", "attrs": {"value": 4000}},
+                    {"name": "T3", "attrs": {"value": 5000}}
+                ]
+            }]
+        }))
+        .unwrap();
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let service = PollingService::new(catalog, Box::new(Disconnected(Arc::clone(&requests))), EventBus::new());
+
+        // 三段不连续的地址，第一段超时后不再发送其余两段
+        assert_eq!(service.poll_device(None).await.failed_reads, 3);
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+        for sensor in ["T1", "T2", "T3"] {
+            assert!(service.value(Some("Temperatures"), sensor, "value").is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_groups_vote_and_raise_discrepancy() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "sensor_plugins": [{
+                "name": "Temperatures",
+                "defaults": {"value": {"read_method": "read_holding_registers", "formula": "$value / 10", "decimal_places": 1}},
+                "sensors": [
+                    // 分组成员地址互不相邻，各自单独读取，一个设备读取失败不影响其它设备
+                    {"name": "T1", "attrs": {"value": 3340}},
+                    {"name": "T1'", "attrs": {"value": 3329}},
+                    {"name": "T1''", "attrs": {"value": 3336}},
+                    {"name": "T2", "attrs": {"value": 3331}}
+                ],
+                "group_list": [{
+                    "name": "T1",
+                    "devices": ["T1", "T1'", "T1''"],
+                    "policy": "median_of_three",
+                    "tolerance": 2,
+                    "alarm_address": 1700
+                }]
+            }],
+            "computed_sensors": [
+                {"name": "T2-T1", "expression": "Temperatures.T2.value - Temperatures.T1.value", "decimal_places": 1}
+            ]
+        }))
+        .unwrap();
+        let registers = FakeRegisters::default();
+        registers.set(ReadMethod::HoldingRegisters, 3340, 200);
+        registers.set(ReadMethod::HoldingRegisters, 3329, 210);
+        registers.set(ReadMethod::HoldingRegisters, 3336, 206);
+        registers.set(ReadMethod::HoldingRegisters, 3331, 300);
+
+        let events = EventBus::new();
+        let mut subscription = events.subscribe(EventFilter::kinds(&[
+            EventKind::GroupUpdated,
+            EventKind::AlarmRaised,
+            EventKind::AlarmCleared,
+        ]));
+        let service = PollingService::new(catalog, Box::new(registers.clone()), events.clone());
+
+        let report = service.poll_once().await;
+        assert_eq!(report.groups, 1);
+        assert!(matches!(
+            subscription.try_recv(),
+            Some(Event::GroupUpdated { value, sources: 3, .. }) if value == 20.6
+        ));
+        assert_eq!(service.value(Some("Temperatures"), "T1", "value").map(|r| r.value), Some(20.6));
+        assert_eq!(service.value(None, "T1", "value").map(|r| r.value), Some(20.6));
+        // 计算传感器引用的是分组值而不是主设备
+        assert_eq!(service.value(None, "T2-T1", "value").map(|r| r.value), Some(9.4));
+
+        // 主设备漂移超出容差：三取中屏蔽漂移的设备并产生偏差告警
+        registers.set(ReadMethod::HoldingRegisters, 3340, 400);
+        service.poll_once().await;
+        assert!(matches!(
+            subscription.try_recv(),
+            Some(Event::AlarmRaised { address: 1700, ref name, .. }) if name == "Temperatures.T1.value discrepancy"
+        ));
+        assert!(matches!(subscription.try_recv(), Some(Event::GroupUpdated { value, .. }) if value == 21.0));
+        service.poll_once().await;
+        assert!(subscription.try_recv().is_none());
+
+        // 只剩一个设备时冗余丢失，无法比较，告警保持
+        registers.registers.lock().unwrap().remove(&(ReadMethod::HoldingRegisters, 3340));
+        registers.registers.lock().unwrap().remove(&(ReadMethod::HoldingRegisters, 3336));
+        service.poll_once().await;
+        assert!(subscription.try_recv().is_none());
+        assert_eq!(service.value(Some("Temperatures"), "T1", "value").map(|r| r.value), Some(21.0));
+
+        // 恢复两个设备，偏差 1.9 仍在回差范围内（容差 2，回差 0.2），告警不解除
+        registers.set(ReadMethod::HoldingRegisters, 3336, 191);
+        service.poll_once().await;
+        assert!(matches!(subscription.try_recv(), Some(Event::GroupUpdated { sources: 2, .. })));
+        assert!(subscription.try_recv().is_none());
+
+        // 偏差回到 1.8 以内才解除
+        registers.set(ReadMethod::HoldingRegisters, 3336, 206);
+        service.poll_once().await;
+        assert!(matches!(subscription.try_recv(), Some(Event::AlarmCleared { address: 1700, .. })));
+        assert!(matches!(
+            subscription.try_recv(),
+            Some(Event::GroupUpdated { value, sources: 2, .. }) if value == 20.8
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_quality_flags() {
+        let catalog = SensorCatalog::from_config(&json!({
+            "sensor_plugins": [{
+                "name": "Flows",
+                "defaults": {"value": {
+                    "read_method": "read_holding_registers",
+                    "formula": "($value - 4000) / 16",
+                    "min": 0, "max": 1000,
+                    "raw_min": 3800, "raw_max": 20500,
+                    "frozen_timeout": 60
+                }},
+                "sensors": [{"name": "F1", "attrs": {"value": 3408}}]
+            }],
+            "computed_sensors": [{"name": "F1x2", "expression": "Flows.F1.value * 2"}]
+        }))
+        .unwrap();
+        let registers = FakeRegisters::default();
+        let service = PollingService::new(catalog, Box::new(registers.clone()), EventBus::new())
+            .with_stale_timeout(Duration::from_secs(10));
+        let quality = |service: &PollingService| {
+            let attr = service.value(Some("Flows"), "F1", "value").unwrap().quality;
+            let computed = service.value(None, "F1x2", "value").unwrap().quality;
+            (attr, computed)
+        };
+
+        registers.set(ReadMethod::HoldingRegisters, 3408, 12000);
+        assert_eq!(service.poll_once().await.bad, 0);
+        assert_eq!(quality(&service), (Quality::Good, Quality::Good));
+
+        // 断线时原始值接近 0，公式得到负数
+        registers.set(ReadMethod::HoldingRegisters, 3408, 0);
+        assert_eq!(service.poll_once().await.bad, 2);
+        assert_eq!(quality(&service), (Quality::OpenLoop, Quality::OpenLoop));
+
+        registers.set(ReadMethod::HoldingRegisters, 3408, 3900);
+        service.poll_once().await;
+        assert_eq!(quality(&service).0, Quality::UnderRange);
+
+        registers.set(ReadMethod::HoldingRegisters, 3408, 20400);
+        service.poll_once().await;
+        assert_eq!(quality(&service).0, Quality::OverRange);
+
+        registers.set(ReadMethod::HoldingRegisters, 3408, 12000);
+        service.poll_once().await;
+        tokio::time::advance(Duration::from_secs(59)).await;
+        service.poll_once().await;
+        assert_eq!(quality(&service).0, Quality::Good);
+        tokio::time::advance(Duration::from_secs(2)).await;
+        service.poll_once().await;
+        assert_eq!(quality(&service).0, Quality::Frozen);
+
+        // 读取失败先沿用上次的值，超过 stale_timeout 后为 CommError
+        registers.registers.lock().unwrap().clear();
+        service.poll_once().await;
+        let reading = service.value(Some("Flows"), "F1", "value").unwrap();
+        assert_eq!(reading, Reading { value: 500.0, quality: Quality::Stale });
+        tokio::time::advance(Duration::from_secs(11)).await;
+        service.poll_once().await;
+        assert_eq!(quality(&service), (Quality::CommError, Quality::CommError));
+    }
+}
@@ -0,0 +1,277 @@
+use serde::Serialize;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::config::loader::Parity;
+
+/// 最短的 RTU 帧：从站地址、功能码和 2 字节 CRC
+const MIN_FRAME_LEN: usize = 4;
+
+/// CRC-16/MODBUS，低字节在前
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ u16::from(*byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// 帧之间至少静默 3.5 个字符时间，波特率高于 19200 时固定为 1.75 ms
+/// （Modbus over Serial Line 2.5.1.1）
+pub fn frame_gap(baudrate: u32, data_bits: u8, parity: Parity, stop_bits: u8) -> Duration {
+    if baudrate > 19200 {
+        return Duration::from_micros(1750);
+    }
+    let bits = 1 + u32::from(data_bits) + u32::from(parity != Parity::None) + u32::from(stop_bits);
+    Duration::from_secs_f64(3.5 * f64::from(bits) / f64::from(baudrate.max(1)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameCheck {
+    Valid,
+    /// 帧前有多余字节或帧不完整
+    Framing,
+    Crc,
+}
+
+fn is_frame(data: &[u8]) -> bool {
+    data.len() >= MIN_FRAME_LEN && {
+        let (body, crc) = data.split_at(data.len() - 2);
+        crc16(body) == u16::from_le_bytes([crc[0], crc[1]])
+    }
+}
+
+fn check_frame(data: &[u8]) -> FrameCheck {
+    if is_frame(data) {
+        FrameCheck::Valid
+    } else if data.len() < MIN_FRAME_LEN || (1..data.len()).any(|start| is_frame(&data[start..])) {
+        FrameCheck::Framing
+    } else {
+        FrameCheck::Crc
+    }
+}
+
+/// 串口链路的帧计数
+#[derive(Debug, Default)]
+pub struct LinkCounters {
+    frames_sent: AtomicU64,
+    frames_received: AtomicU64,
+    crc_errors: AtomicU64,
+    framing_errors: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LinkStats {
+    pub frames_sent: u64,
+    pub frames_received: u64,
+    pub crc_errors: u64,
+    pub framing_errors: u64,
+}
+
+impl LinkCounters {
+    pub fn snapshot(&self) -> LinkStats {
+        LinkStats {
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            frames_received: self.frames_received.load(Ordering::Relaxed),
+            crc_errors: self.crc_errors.load(Ordering::Relaxed),
+            framing_errors: self.framing_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 包装串口：发送前保证帧间静默时间，并检查收到的每一帧的 CRC。
+/// tokio-modbus 遇到 CRC 错误只会丢弃字节重新同步，最终表现为超时，所以在这里单独计数
+pub struct RtuLink<T> {
+    inner: T,
+    gap: Duration,
+    counters: Arc<LinkCounters>,
+    /// 上次收发字节的时间
+    last_activity: Option<Instant>,
+    /// 正在发送一帧（flush 之前的写入属于同一帧）
+    sending: bool,
+    /// 上一帧发出后收到、还没有组成完整帧的字节
+    received: Vec<u8>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> RtuLink<T> {
+    pub fn new(inner: T, gap: Duration, counters: Arc<LinkCounters>) -> Self {
+        Self {
+            inner,
+            gap,
+            counters,
+            last_activity: None,
+            sending: false,
+            received: Vec::new(),
+            delay: None,
+        }
+    }
+
+    /// 发送下一帧之前，把没有组成有效帧的字节记为错误
+    fn discard_received(&mut self) {
+        if self.received.is_empty() {
+            return;
+        }
+        match check_frame(&self.received) {
+            FrameCheck::Valid => {
+                self.counters.frames_received.fetch_add(1, Ordering::Relaxed);
+            }
+            FrameCheck::Framing => {
+                self.counters.framing_errors.fetch_add(1, Ordering::Relaxed);
+                log::warn!("Modbus RTU framing error, discarded {:02X?}", self.received);
+            }
+            FrameCheck::Crc => {
+                self.counters.crc_errors.fetch_add(1, Ordering::Relaxed);
+                log::warn!("Modbus RTU CRC error in {:02X?}", self.received);
+            }
+        }
+        self.received.clear();
+    }
+}
+
+impl<T> Drop for RtuLink<T> {
+    fn drop(&mut self) {
+        self.discard_received();
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for RtuLink<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let bytes = &buf.filled()[before..];
+        if !bytes.is_empty() {
+            let link = &mut *self;
+            link.received.extend_from_slice(bytes);
+            link.last_activity = Some(Instant::now());
+            link.sending = false;
+            if is_frame(&link.received) {
+                link.counters.frames_received.fetch_add(1, Ordering::Relaxed);
+                link.received.clear();
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for RtuLink<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if !self.sending {
+            if self.delay.is_none() {
+                if let Some(last) = self.last_activity {
+                    if last.elapsed() < self.gap {
+                        self.delay = Some(Box::pin(tokio::time::sleep_until(last + self.gap)));
+                    }
+                }
+            }
+            if let Some(delay) = self.delay.as_mut() {
+                ready!(delay.as_mut().poll(cx));
+                self.delay = None;
+            }
+            self.discard_received();
+            self.sending = true;
+            self.counters.frames_sent.fetch_add(1, Ordering::Relaxed);
+        }
+        let written = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.last_activity = Some(Instant::now());
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(Pin::new(&mut self.inner).poll_flush(cx))?;
+        self.sending = false;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut frame = body.to_vec();
+        frame.extend_from_slice(&crc16(body).to_le_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_crc_and_gap() {
+        // 读保持寄存器 0x0000 一个：01 03 00 00 00 01 84 0A
+        assert_eq!(frame(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A]);
+        // 9600 8N1：每字符 10 位
+        assert_eq!(frame_gap(9600, 8, Parity::None, 1), Duration::from_secs_f64(35.0 / 9600.0));
+        // 9600 8E1：每字符 11 位
+        assert_eq!(frame_gap(9600, 8, Parity::Even, 1), Duration::from_secs_f64(38.5 / 9600.0));
+        assert_eq!(frame_gap(115200, 8, Parity::None, 1), Duration::from_micros(1750));
+
+        let response = frame(&[0x01, 0x03, 0x02, 0x00, 0x2A]);
+        assert_eq!(check_frame(&response), FrameCheck::Valid);
+        assert_eq!(check_frame(&[&[0xFF][..], &response].concat()), FrameCheck::Framing);
+        assert_eq!(check_frame(&response[..3]), FrameCheck::Framing);
+        let mut corrupted = response.clone();
+        corrupted[4] ^= 0x10;
+        assert_eq!(check_frame(&corrupted), FrameCheck::Crc);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_link_counts_frames_and_waits_for_gap() {
+        let (client, mut device) = tokio::io::duplex(256);
+        let counters = Arc::new(LinkCounters::default());
+        let gap = Duration::from_millis(4);
+        let mut link = RtuLink::new(client, gap, Arc::clone(&counters));
+
+        let request = frame(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]);
+        link.write_all(&request).await.unwrap();
+        link.flush().await.unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(device.read(&mut buf).await.unwrap(), request.len());
+
+        device.write_all(&frame(&[0x01, 0x03, 0x02, 0x00, 0x2A])).await.unwrap();
+        let n = link.read(&mut buf).await.unwrap();
+        let received_at = Instant::now();
+        assert_eq!(n, 7);
+
+        // 下一个请求要等到静默时间之后才发出，损坏的响应在下一个请求前计为 CRC 错误
+        link.write_all(&request).await.unwrap();
+        link.flush().await.unwrap();
+        assert!(received_at.elapsed() >= gap);
+        device.read_exact(&mut buf[..request.len()]).await.unwrap();
+        let mut corrupted = frame(&[0x01, 0x03, 0x02, 0x00, 0x2A]);
+        corrupted[4] ^= 0x10;
+        device.write_all(&corrupted).await.unwrap();
+        link.read_exact(&mut buf[..corrupted.len()]).await.unwrap();
+        tokio::time::advance(gap).await;
+        link.write_all(&request).await.unwrap();
+        link.flush().await.unwrap();
+
+        // 不完整的帧在断开时计为帧错误
+        device.write_all(&[0x01, 0x03]).await.unwrap();
+        link.read_exact(&mut buf[..2]).await.unwrap();
+        drop(link);
+        assert_eq!(
+            counters.snapshot(),
+            LinkStats {
+                frames_sent: 3,
+                frames_received: 1,
+                crc_errors: 1,
+                framing_errors: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,379 @@
+#[cfg(test)]
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+#[cfg(test)]
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+#[cfg(test)]
+use crate::models::cdu_model::CduModel;
+#[cfg(test)]
+use crate::models::event_bus::{Event, EventBus, EventFilter, Overflow};
+#[cfg(test)]
+use crate::models::sensor::{ReadMethod, SensorCatalog};
+#[cfg(test)]
+use crate::services::modbus_service::{ModbusError, RegisterReader};
+#[cfg(test)]
+use crate::services::polling_service::PollingService;
+use crate::services::simulator::{Fault, Simulator, SimulatorError};
+
+/// 一次轮询产生的事件数远大于默认的订阅缓存
+#[cfg(test)]
+const EVENT_CAPACITY: usize = 4096;
+
+#[derive(Debug, Error)]
+pub enum ScenarioError {
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{path}: {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_yaml::Error,
+    },
+
+    #[error("event at {at}s: {source}")]
+    Fault {
+        at: f64,
+        #[source]
+        source: SimulatorError,
+    },
+}
+
+fn default_step() -> f64 {
+    1.0
+}
+
+fn default_warmup() -> f64 {
+    300.0
+}
+
+fn default_heat_load() -> f64 {
+    60.0
+}
+
+fn default_duty() -> f64 {
+    50.0
+}
+
+/// 在 at 秒注入的故障
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TimedFault {
+    pub at: f64,
+    #[serde(flatten)]
+    pub fault: Fault,
+}
+
+/// 对工作台事件的断言。event 按子集匹配序列化后的事件（type 为事件种类），
+/// 数值可以写成 {lt|le|gt|ge: n}；after/before 限定事件时间（秒）
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Expectation {
+    pub event: Value,
+    #[serde(default)]
+    pub after: Option<f64>,
+    #[serde(default)]
+    pub before: Option<f64>,
+}
+
+#[cfg(test)]
+impl Expectation {
+    fn matches(&self, at: f64, event: &Value) -> bool {
+        self.after.is_none_or(|after| at >= after)
+            && self.before.is_none_or(|before| at < before)
+            && matches(&self.event, event)
+    }
+}
+
+#[cfg(test)]
+fn compare(op: &str, bound: &Value, actual: &Value) -> Option<bool> {
+    let (bound, actual) = (bound.as_f64()?, actual.as_f64()?);
+    Some(match op {
+        "lt" => actual < bound,
+        "le" => actual <= bound,
+        "gt" => actual > bound,
+        "ge" => actual >= bound,
+        _ => return None,
+    })
+}
+
+/// pattern 是否为 actual 的子集，数值按 f64 比较
+#[cfg(test)]
+fn matches(pattern: &Value, actual: &Value) -> bool {
+    match (pattern, actual) {
+        (Value::Object(ops), Value::Number(_))
+            if !ops.is_empty() && ops.keys().all(|op| ["lt", "le", "gt", "ge"].contains(&op.as_str())) =>
+        {
+            ops.iter().all(|(op, bound)| compare(op, bound, actual) == Some(true))
+        }
+        (Value::Object(pattern), Value::Object(actual)) => pattern
+            .iter()
+            .all(|(key, value)| actual.get(key).is_some_and(|actual| matches(value, actual))),
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => pattern == actual,
+    }
+}
+
+/// 故障注入场景：在模拟的 CDU 上按时间注入故障，并断言工作台产生的事件。
+/// 测试中只运行模拟器和轮询服务，能断言的是传感器、分组更新和分组偏差告警；
+/// alarms.yaml 的告警和联动、模式切换、泵切换不在其中
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 场景时长（秒）
+    pub duration: f64,
+    /// 模型步长和轮询周期（秒）
+    #[serde(default = "default_step")]
+    pub step: f64,
+    /// 开始前让模型达到稳态的时间（秒），期间不轮询
+    #[serde(default = "default_warmup")]
+    pub warmup: f64,
+    /// IT 负载（kW）
+    #[serde(default = "default_heat_load")]
+    pub heat_load: f64,
+    /// 泵和阀门的初始占空比（%）
+    #[serde(default = "default_duty")]
+    pub duty: f64,
+    #[serde(default)]
+    pub events: Vec<TimedFault>,
+    /// 必须出现的事件
+    #[serde(default)]
+    pub expect: Vec<Expectation>,
+    /// 不允许出现的事件
+    #[serde(default)]
+    pub forbid: Vec<Expectation>,
+}
+
+/// 场景运行结果。events 只记录传感器和分组更新以外的事件
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioReport {
+    pub name: String,
+    pub events: Vec<(f64, Event)>,
+    pub missing: Vec<Expectation>,
+    pub forbidden: Vec<(f64, Event)>,
+}
+
+#[cfg(test)]
+impl ScenarioReport {
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty() && self.forbidden.is_empty()
+    }
+}
+
+/// 在进程内直接读取模拟器寄存器，通信超时故障期间返回超时
+#[cfg(test)]
+struct SimulatorReader {
+    simulator: Arc<Simulator>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl RegisterReader for SimulatorReader {
+    async fn read(
+        &mut self,
+        _slave: Option<u8>,
+        method: ReadMethod,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ModbusError> {
+        if self.simulator.is_offline() {
+            return Err(ModbusError::Timeout(Duration::ZERO));
+        }
+        self.simulator.read(method, address, count).map_err(ModbusError::Exception)
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let content = std::fs::read_to_string(path).map_err(|source| ScenarioError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_yaml::from_str(&content).map_err(|source| ScenarioError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// 按场景的负载和占空比建立模型
+    #[cfg(test)]
+    pub fn model(&self, catalog: &SensorCatalog) -> CduModel {
+        let mut model = CduModel::default();
+        model.heat_load = self.heat_load;
+        for attr in &catalog.attrs {
+            if attr.plugin != "Leakages" && CduModel::is_input(&attr.plugin, &attr.attr) {
+                model.set_input(&attr.plugin, &attr.sensor, self.duty);
+            }
+        }
+        model
+    }
+
+    /// 注入所有到期（at <= elapsed）且尚未注入的故障，返回已注入的数量
+    #[cfg(test)]
+    fn inject_due(&self, simulator: &Simulator, injected: usize, elapsed: f64) -> Result<usize, ScenarioError> {
+        let mut injected = injected;
+        while let Some(event) = self.events.get(injected).filter(|event| event.at <= elapsed) {
+            simulator
+                .inject(&event.fault)
+                .map_err(|source| ScenarioError::Fault { at: event.at, source })?;
+            injected += 1;
+        }
+        Ok(injected)
+    }
+
+    /// 按真实时间向正在运行的模拟器注入故障（simulate --scenario）
+    pub async fn play(&self, simulator: &Simulator) -> Result<(), ScenarioError> {
+        let mut events = self.events.clone();
+        events.sort_by(|a, b| a.at.total_cmp(&b.at));
+        let start = tokio::time::Instant::now();
+        for event in &events {
+            tokio::time::sleep_until(start + Duration::from_secs_f64(event.at.max(0.0))).await;
+            simulator
+                .inject(&event.fault)
+                .map_err(|source| ScenarioError::Fault { at: event.at, source })?;
+            println!("{:>7.1}s {:?}", event.at, event.fault);
+        }
+        Ok(())
+    }
+
+    /// 在进程内运行场景：模拟器 → 轮询服务 → 事件总线，收集事件并检查断言。
+    /// 每一步都按步长等待，轮询服务的 Stale/Frozen 判断依赖 tokio 时钟，测试中用 start_paused 加速
+    #[cfg(test)]
+    pub async fn run(&self, catalog: SensorCatalog, stale_timeout: Duration) -> Result<ScenarioReport, ScenarioError> {
+        let mut scenario = self.clone();
+        scenario.events.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+        let step = Duration::from_secs_f64(scenario.step);
+        let simulator = Arc::new(Simulator::new(catalog.clone(), scenario.model(&catalog)).with_noise(2));
+        for _ in 0..(scenario.warmup / scenario.step) as usize {
+            simulator.step(step);
+        }
+
+        let events = EventBus::new();
+        let mut subscription = events.subscribe_with(EventFilter::all(), EVENT_CAPACITY, Overflow::DropNewest);
+        let reader = SimulatorReader {
+            simulator: Arc::clone(&simulator),
+        };
+        let polling = PollingService::new(catalog, Box::new(reader), events).with_stale_timeout(stale_timeout);
+
+        let mut report = ScenarioReport {
+            name: scenario.name.clone(),
+            events: Vec::new(),
+            missing: Vec::new(),
+            forbidden: Vec::new(),
+        };
+        let mut seen = vec![false; scenario.expect.len()];
+        let mut injected = 0;
+        let mut elapsed = 0.0;
+        while elapsed <= scenario.duration {
+            injected = scenario.inject_due(&simulator, injected, elapsed)?;
+            polling.poll_once().await;
+            while let Some(event) = subscription.try_recv() {
+                let value = serde_json::to_value(&event).unwrap_or(Value::Null);
+                for (seen, expectation) in seen.iter_mut().zip(&scenario.expect) {
+                    *seen |= expectation.matches(elapsed, &value);
+                }
+                if scenario.forbid.iter().any(|f| f.matches(elapsed, &value)) {
+                    report.forbidden.push((elapsed, event.clone()));
+                }
+                if !matches!(event, Event::SensorUpdated { .. } | Event::GroupUpdated { .. }) {
+                    report.events.push((elapsed, event));
+                }
+            }
+            tokio::time::sleep(step).await;
+            simulator.step(step);
+            elapsed += scenario.step;
+        }
+        report.missing = scenario
+            .expect
+            .iter()
+            .zip(seen)
+            .filter(|(_, seen)| !seen)
+            .map(|(expectation, _)| expectation.clone())
+            .collect();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn shipped() -> SensorCatalog {
+        let content = std::fs::read_to_string("configs/sensors.yaml").unwrap();
+        SensorCatalog::from_config(&serde_yaml::from_str(&content).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        let event = json!({"type": "sensor_updated", "plugin": "Pumps", "sensor": "Pump1", "value": 0.4});
+        assert!(matches(&json!({"type": "sensor_updated"}), &event));
+        assert!(matches(&json!({"sensor": "Pump1", "value": {"lt": 1, "ge": 0.4}}), &event));
+        assert!(!matches(&json!({"value": {"gt": 1}}), &event));
+        assert!(!matches(&json!({"quality": "Good"}), &event));
+        assert!(matches(&json!({"value": 1}), &json!({"value": 1.0})));
+
+        let expectation = Expectation {
+            event: json!({"type": "sensor_updated"}),
+            after: Some(10.0),
+            before: Some(20.0),
+        };
+        assert!(!expectation.matches(5.0, &event));
+        assert!(expectation.matches(10.0, &event));
+        assert!(!expectation.matches(20.0, &event));
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_fault() {
+        let scenario: Scenario = serde_yaml::from_str(
+            "name: spike\nduration: 30\nevents:\n  - at: 5\n    pressure_spike: {sensor: Pressures.P1.value, offset: 3, duration: 10}\n",
+        )
+        .unwrap();
+        assert_eq!(scenario.step, 1.0);
+        assert_eq!(
+            scenario.events[0].fault,
+            Fault::PressureSpike {
+                sensor: "Pressures.P1.value".to_string(),
+                offset: 3.0,
+                duration: 10.0
+            }
+        );
+        assert!(serde_yaml::from_str::<Scenario>("name: x\nduration: 1\nevents:\n  - at: 1\n    explode: {}\n").is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unknown_sensor_fails_the_run() {
+        let scenario: Scenario = serde_yaml::from_str(
+            "name: typo\nduration: 5\nwarmup: 0\nevents:\n  - at: 1\n    sensor_stuck: {sensor: Flows.F9.value}\n",
+        )
+        .unwrap();
+        let error = scenario.run(shipped(), Duration::from_secs(10)).await.unwrap_err();
+        assert!(matches!(error, ScenarioError::Fault { source: SimulatorError::UnknownSensor(_), .. }));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shipped_scenarios() {
+        let mut paths: Vec<_> = std::fs::read_dir("configs/scenarios")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        assert_eq!(paths.len(), 6);
+        for path in paths {
+            let scenario = Scenario::load(&path).unwrap();
+            let report = scenario.run(shipped(), Duration::from_secs(10)).await.unwrap();
+            assert!(report.passed(), "{}: {:#?}", path.display(), report);
+        }
+    }
+}
@@ -0,0 +1,552 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_modbus::server::{rtu, tcp, Service};
+use tokio_modbus::{ExceptionCode, Request, Response, SlaveRequest};
+use tokio_serial::{SerialPort, SerialStream};
+
+use crate::models::cdu_model::{CduModel, DEFAULT_LEAK_RATE};
+use crate::models::sensor::{AttrSpec, DataType, ReadMethod, SensorCatalog};
+
+/// 工程值换算为原始值。公式按线性处理：由原始值 0 和 1000 的结果得到斜率和截距
+fn to_raw(attr: &AttrSpec, value: f64) -> f64 {
+    let scale = |raw: f64| attr.scale(|name| (name == attr.attr).then_some(raw)).ok();
+    match (scale(0.0), scale(1000.0)) {
+        (Some(offset), Some(at)) if at != offset => (value - offset) * 1000.0 / (at - offset),
+        _ => value,
+    }
+}
+
+fn from_raw(attr: &AttrSpec, raw: f64) -> Option<f64> {
+    attr.scale(|name| (name == attr.attr).then_some(raw)).ok()
+}
+
+#[derive(Debug, Error)]
+pub enum SimulatorError {
+    #[error("unknown sensor '{0}', expected Plugin.Sensor.attr")]
+    UnknownSensor(String),
+
+    #[error("unknown pump '{0}'")]
+    UnknownPump(String),
+
+    #[error("{0} is not a model input")]
+    NotAnInput(String),
+}
+
+fn default_leak_rate() -> f64 {
+    DEFAULT_LEAK_RATE
+}
+
+/// 注入模拟器的故障，传感器用 "Plugin.Sensor.attr" 指定，时间单位为秒
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// 泵入口没有液体：不出水，电流下降，温度升高
+    PumpDryRun { pump: String },
+    /// 传感器卡在当前的原始值
+    SensorStuck { sensor: String },
+    /// 从站在 duration 秒内不应答
+    CommTimeout { duration: f64 },
+    /// 泄漏（L/min）：泄漏检测线圈置位，储液罐液位和静压下降
+    Leak {
+        #[serde(default = "default_leak_rate")]
+        rate: f64,
+    },
+    /// 读数叠加 offset，持续 duration 秒
+    PressureSpike { sensor: String, offset: f64, duration: f64 },
+    /// 读数每分钟漂移 rate
+    Drift { sensor: String, rate: f64 },
+    /// 像主站一样写入输入属性（泵、阀门的 DutyCycle）的工程值
+    Write { sensor: String, value: f64 },
+    /// IT 负载（kW）
+    HeatLoad { value: f64 },
+    /// 清除一个传感器的故障；不指定时清除全部故障、泵空转和泄漏
+    Clear {
+        #[serde(default)]
+        sensor: Option<String>,
+    },
+}
+
+/// 注入到单个传感器的故障
+#[derive(Debug, Clone, Default)]
+struct SensorFault {
+    /// 卡住时的寄存器
+    stuck: Option<Vec<u16>>,
+    /// (偏移量, 结束时间)
+    spike: Option<(f64, f64)>,
+    /// (每分钟漂移量, 开始时间)
+    drift: Option<(f64, f64)>,
+}
+
+struct SimState {
+    model: CduModel,
+    registers: HashMap<(ReadMethod, u16), u16>,
+    /// 模型时间（秒）
+    elapsed: f64,
+    /// 在这个时间之前不应答
+    offline_until: f64,
+    /// 按属性下标
+    faults: HashMap<usize, SensorFault>,
+    /// 噪声用的伪随机数状态（xorshift）
+    seed: u64,
+}
+
+/// CDU 模拟器：按 sensors.yaml 的地址提供全部传感器寄存器，数值来自 CduModel。
+/// 写入泵、阀门占空比和泄漏线圈会改变模型的输入，其他寄存器在下一步被模型覆盖
+pub struct Simulator {
+    catalog: SensorCatalog,
+    /// 模拟量寄存器叠加的噪声幅度（原始值），避免数值长时间不变被判定为 Frozen
+    noise: u16,
+    state: Mutex<SimState>,
+}
+
+impl Simulator {
+    pub fn new(catalog: SensorCatalog, model: CduModel) -> Self {
+        let simulator = Self {
+            catalog,
+            noise: 0,
+            state: Mutex::new(SimState {
+                model,
+                registers: HashMap::new(),
+                elapsed: 0.0,
+                offline_until: 0.0,
+                faults: HashMap::new(),
+                seed: 0x2545_f491_4f6c_dd1d,
+            }),
+        };
+        simulator.update(|_| {});
+        simulator
+    }
+
+    pub fn with_noise(mut self, noise: u16) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    /// 修改模型（负载、泄漏等）并刷新寄存器
+    pub fn update<R>(&self, f: impl FnOnce(&mut CduModel) -> R) -> R {
+        let mut state = self.state.lock().unwrap();
+        let result = f(&mut state.model);
+        self.refresh(&mut state);
+        result
+    }
+
+    /// 模型推进 dt 并刷新寄存器
+    pub fn step(&self, dt: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.model.step(dt.as_secs_f64());
+        state.elapsed += dt.as_secs_f64();
+        let elapsed = state.elapsed;
+        for fault in state.faults.values_mut() {
+            if fault.spike.is_some_and(|(_, until)| until <= elapsed) {
+                fault.spike = None;
+            }
+        }
+        self.refresh(&mut state);
+    }
+
+    /// 从站是否处于通信超时故障中
+    pub fn is_offline(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.elapsed < state.offline_until
+    }
+
+    fn attr_index(&self, sensor: &str) -> Result<usize, SimulatorError> {
+        self.catalog
+            .attrs
+            .iter()
+            .position(|attr| attr.id() == sensor)
+            .ok_or_else(|| SimulatorError::UnknownSensor(sensor.to_string()))
+    }
+
+    /// 注入故障
+    pub fn inject(&self, fault: &Fault) -> Result<(), SimulatorError> {
+        let mut state = self.state.lock().unwrap();
+        let now = state.elapsed;
+        match fault {
+            Fault::PumpDryRun { pump } => {
+                if !self.catalog.attrs.iter().any(|a| a.plugin == "Pumps" && &a.sensor == pump) {
+                    return Err(SimulatorError::UnknownPump(pump.clone()));
+                }
+                state.model.set_dry_run(pump, true);
+            }
+            Fault::SensorStuck { sensor } => {
+                let i = self.attr_index(sensor)?;
+                let attr = &self.catalog.attrs[i];
+                let raw = attr.read_method.zip(attr.address).and_then(|(method, address)| {
+                    (address..address.saturating_add(attr.width()))
+                        .map(|a| state.registers.get(&(method, a)).copied())
+                        .collect()
+                });
+                state.faults.entry(i).or_default().stuck = raw;
+            }
+            Fault::CommTimeout { duration } => state.offline_until = now + duration,
+            Fault::Leak { rate } => state.model.leak_rate = *rate,
+            Fault::PressureSpike { sensor, offset, duration } => {
+                let i = self.attr_index(sensor)?;
+                state.faults.entry(i).or_default().spike = Some((*offset, now + duration));
+            }
+            Fault::Drift { sensor, rate } => {
+                let i = self.attr_index(sensor)?;
+                state.faults.entry(i).or_default().drift = Some((*rate, now));
+            }
+            Fault::Write { sensor, value } => {
+                let attr = &self.catalog.attrs[self.attr_index(sensor)?];
+                if !CduModel::is_input(&attr.plugin, &attr.attr) {
+                    return Err(SimulatorError::NotAnInput(sensor.clone()));
+                }
+                state.model.set_input(&attr.plugin, &attr.sensor, *value);
+            }
+            Fault::HeatLoad { value } => state.model.heat_load = *value,
+            Fault::Clear { sensor: Some(sensor) } => {
+                let i = self.attr_index(sensor)?;
+                state.faults.remove(&i);
+            }
+            Fault::Clear { sensor: None } => {
+                state.faults.clear();
+                state.offline_until = now;
+                state.model.leak_rate = 0.0;
+                let pumps: Vec<&str> = self
+                    .catalog
+                    .attrs
+                    .iter()
+                    .filter(|a| a.plugin == "Pumps")
+                    .map(|a| a.sensor.as_str())
+                    .collect();
+                for pump in pumps {
+                    state.model.set_dry_run(pump, false);
+                }
+            }
+        }
+        self.refresh(&mut state);
+        Ok(())
+    }
+
+    fn refresh(&self, state: &mut SimState) {
+        for (i, attr) in self.catalog.attrs.iter().enumerate() {
+            let (Some(method), Some(address)) = (attr.read_method, attr.address) else {
+                continue;
+            };
+            let fault = state.faults.get(&i).cloned().unwrap_or_default();
+            if let Some(words) = fault.stuck {
+                Self::store(state, attr, method, address, &words);
+                continue;
+            }
+            let Some(mut value) = state.model.value(&attr.plugin, &attr.sensor, &attr.attr) else {
+                // 模型中没有的属性保持为 0 或主站写入的值
+                for a in address..address.saturating_add(attr.width()) {
+                    state.registers.entry((method, a)).or_insert(0);
+                }
+                continue;
+            };
+            if let Some((offset, _)) = fault.spike {
+                value += offset;
+            }
+            if let Some((rate, since)) = fault.drift {
+                value += rate * (state.elapsed - since) / 60.0;
+            }
+            let mut raw = to_raw(attr, value);
+            let analog = !method.is_bit() && !matches!(attr.data_type, DataType::Bit(_));
+            if self.noise > 0 && analog && !CduModel::is_input(&attr.plugin, &attr.attr) {
+                state.seed ^= state.seed << 13;
+                state.seed ^= state.seed >> 7;
+                state.seed ^= state.seed << 17;
+                let jitter = (state.seed % (2 * self.noise as u64 + 1)) as i32 - self.noise as i32;
+                raw += jitter as f64;
+            }
+            Self::store(state, attr, method, address, &attr.encode(raw));
+        }
+    }
+
+    /// 写入一个属性的寄存器；寄存器中的位只改这一位
+    fn store(state: &mut SimState, attr: &AttrSpec, method: ReadMethod, address: u16, words: &[u16]) {
+        if let (DataType::Bit(bit), false) = (attr.data_type, method.is_bit()) {
+            let register = state.registers.entry((method, address)).or_insert(0);
+            *register = (*register & !(1 << bit)) | (words.first().copied().unwrap_or(0) & (1 << bit));
+            return;
+        }
+        for (a, word) in (address..).zip(words) {
+            state.registers.insert((method, a), *word);
+        }
+    }
+
+    pub(crate) fn read(&self, method: ReadMethod, address: u16, count: u16) -> Result<Vec<u16>, ExceptionCode> {
+        if count == 0 || count > method.max_count() {
+            return Err(ExceptionCode::IllegalDataValue);
+        }
+        let state = self.state.lock().unwrap();
+        (address..address.saturating_add(count))
+            .map(|a| state.registers.get(&(method, a)).copied())
+            .collect::<Option<_>>()
+            .ok_or(ExceptionCode::IllegalDataAddress)
+    }
+
+    fn write(&self, method: ReadMethod, address: u16, values: &[u16]) -> Result<(), ExceptionCode> {
+        let mut state = self.state.lock().unwrap();
+        let addresses = address..address.saturating_add(values.len() as u16);
+        if addresses.clone().any(|a| !state.registers.contains_key(&(method, a))) {
+            return Err(ExceptionCode::IllegalDataAddress);
+        }
+        for (a, raw) in addresses.clone().zip(values) {
+            state.registers.insert((method, a), *raw);
+        }
+        // 写入覆盖到的输入属性按数据类型解码后改变模型
+        for attr in &self.catalog.attrs {
+            let (Some(start), true) = (attr.address, attr.read_method == Some(method)) else {
+                continue;
+            };
+            let range = start..start.saturating_add(attr.width());
+            if !CduModel::is_input(&attr.plugin, &attr.attr) || range.end <= addresses.start || addresses.end <= range.start {
+                continue;
+            }
+            let words: Vec<u16> = range.map(|a| state.registers.get(&(method, a)).copied().unwrap_or(0)).collect();
+            if let Some(value) = attr.decode(&words).and_then(|raw| from_raw(attr, raw)) {
+                state.model.set_input(&attr.plugin, &attr.sensor, value);
+            }
+        }
+        self.refresh(&mut state);
+        Ok(())
+    }
+
+    fn handle(&self, request: Request<'_>) -> Result<Response, ExceptionCode> {
+        let bits = |words: Vec<u16>| words.into_iter().map(|w| w != 0).collect();
+        Ok(match request {
+            Request::ReadCoils(address, count) => Response::ReadCoils(bits(self.read(ReadMethod::Coils, address, count)?)),
+            Request::ReadDiscreteInputs(address, count) => {
+                Response::ReadDiscreteInputs(bits(self.read(ReadMethod::DiscreteInputs, address, count)?))
+            }
+            Request::ReadHoldingRegisters(address, count) => {
+                Response::ReadHoldingRegisters(self.read(ReadMethod::HoldingRegisters, address, count)?)
+            }
+            Request::ReadInputRegisters(address, count) => {
+                Response::ReadInputRegisters(self.read(ReadMethod::InputRegisters, address, count)?)
+            }
+            Request::WriteSingleRegister(address, value) => {
+                self.write(ReadMethod::HoldingRegisters, address, &[value])?;
+                Response::WriteSingleRegister(address, value)
+            }
+            Request::WriteMultipleRegisters(address, values) => {
+                self.write(ReadMethod::HoldingRegisters, address, &values)?;
+                Response::WriteMultipleRegisters(address, values.len() as u16)
+            }
+            Request::WriteSingleCoil(address, value) => {
+                self.write(ReadMethod::Coils, address, &[u16::from(value)])?;
+                Response::WriteSingleCoil(address, value)
+            }
+            Request::ReadWriteMultipleRegisters(read_address, count, write_address, values) => {
+                self.write(ReadMethod::HoldingRegisters, write_address, &values)?;
+                Response::ReadWriteMultipleRegisters(self.read(ReadMethod::HoldingRegisters, read_address, count)?)
+            }
+            Request::WriteMultipleCoils(address, values) => {
+                let words: Vec<u16> = values.iter().map(|v| u16::from(*v)).collect();
+                self.write(ReadMethod::Coils, address, &words)?;
+                Response::WriteMultipleCoils(address, values.len() as u16)
+            }
+            _ => return Err(ExceptionCode::IllegalFunction),
+        })
+    }
+
+    /// 在 listener 上提供 Modbus TCP 服务，忽略单元标识
+    pub async fn serve_tcp(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        let service = SimulatorService {
+            simulator: self,
+            slave: None,
+        };
+        let on_connected = |stream, addr| {
+            let service = service.clone();
+            async move { tcp::accept_tcp_connection(stream, addr, |_| Ok(Some(service.clone()))) }
+        };
+        tcp::Server::new(listener)
+            .serve(&on_connected, |e| log::debug!("Simulator TCP connection closed: {}", e))
+            .await
+    }
+
+    /// 在伪终端上提供从站地址为 slave 的 Modbus RTU 服务，返回主站应打开的设备路径
+    pub fn serve_rtu(self: &Arc<Self>, slave: u8) -> io::Result<(String, JoinHandle<io::Result<()>>)> {
+        let (master, pty) = SerialStream::pair().map_err(io::Error::from)?;
+        let path = pty
+            .name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "pseudo terminal has no name"))?;
+        let service = SimulatorService {
+            simulator: Arc::clone(self),
+            slave: Some(slave),
+        };
+        let handle = tokio::spawn(async move {
+            // 所有从端都关闭后主端读取会出错，服务期间保持打开
+            let _pty = pty;
+            rtu::Server::new(master).serve_forever(service).await
+        });
+        Ok((path, handle))
+    }
+}
+
+/// tokio-modbus 服务端接口，RTU 只响应自己的从站地址，广播（地址 0）执行但不应答
+#[derive(Clone)]
+struct SimulatorService {
+    simulator: Arc<Simulator>,
+    slave: Option<u8>,
+}
+
+impl Service for SimulatorService {
+    type Request = SlaveRequest<'static>;
+    type Response = Option<Response>;
+    type Exception = ExceptionCode;
+    type Future = Ready<Result<Self::Response, Self::Exception>>;
+
+    fn call(&self, request: Self::Request) -> Self::Future {
+        if self.simulator.is_offline() {
+            return ready(Ok(None));
+        }
+        let broadcast = self.slave.is_some() && request.slave == 0;
+        if self.slave.is_some_and(|slave| slave != request.slave) && !broadcast {
+            return ready(Ok(None));
+        }
+        let response = self.simulator.handle(request.request);
+        ready(match response {
+            _ if broadcast => Ok(None),
+            response => response.map(Some),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::{ModbusClientConfig, Parity, SerialConfig, TcpConfig};
+    use crate::models::event_bus::EventBus;
+    use crate::models::sensor::Quality;
+    use crate::services::modbus_diagnostics::Direction;
+    use crate::services::modbus_service::{ModbusError, ModbusService, RegisterReader};
+    use crate::services::polling_service::PollingService;
+
+    fn shipped() -> SensorCatalog {
+        let content = std::fs::read_to_string("configs/sensors.yaml").unwrap();
+        SensorCatalog::from_config(&serde_yaml::from_str(&content).unwrap()).unwrap()
+    }
+
+    fn simulator() -> Arc<Simulator> {
+        let simulator = Simulator::new(shipped(), CduModel::default());
+        simulator.update(|model| {
+            model.set_input("Pumps", "Pump1", 50.0);
+            model.set_input("Pumps", "Pump2", 50.0);
+            model.set_input("Valves", "Valve1", 60.0);
+        });
+        for _ in 0..300 {
+            simulator.step(Duration::from_secs(1));
+        }
+        Arc::new(simulator)
+    }
+
+    #[test]
+    fn test_registers_follow_model_and_writes() {
+        let simulator = simulator();
+        let flow = shipped().attrs.into_iter().find(|a| a.id() == "Flows.F1.value").unwrap();
+        let raw = simulator.read(ReadMethod::HoldingRegisters, 3408, 1).unwrap()[0];
+        assert!((from_raw(&flow, raw as f64).unwrap() - 120.0).abs() < 0.1);
+
+        // Pump1/Pump2 的 DutyCycle 公式为 $DutyCycle / 60
+        simulator.handle(Request::WriteMultipleRegisters(2192, vec![6000, 6000].into())).unwrap();
+        for _ in 0..30 {
+            simulator.step(Duration::from_secs(1));
+        }
+        let raw = simulator.read(ReadMethod::HoldingRegisters, 3408, 1).unwrap()[0];
+        assert!(from_raw(&flow, raw as f64).unwrap() > 230.0);
+
+        assert_eq!(
+            simulator.handle(Request::ReadCoils(10, 1)),
+            Ok(Response::ReadCoils(vec![false]))
+        );
+        simulator.handle(Request::WriteSingleCoil(10, true)).unwrap();
+        assert!(simulator.update(|model| model.leak_rate) > 0.0);
+
+        assert_eq!(
+            simulator.handle(Request::ReadHoldingRegisters(1, 2)),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+        assert_eq!(simulator.handle(Request::ReportServerId), Err(ExceptionCode::IllegalFunction));
+    }
+
+    #[tokio::test]
+    async fn test_polling_over_tcp() {
+        let simulator = simulator();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(Arc::clone(&simulator).serve_tcp(listener));
+
+        let config = ModbusClientConfig {
+            tcp: TcpConfig {
+                enable: true,
+                host: "127.0.0.1".to_string(),
+                port,
+                timeout: 2.0,
+                slave_id: 1,
+            },
+            ..Default::default()
+        };
+        let modbus = ModbusService::from_config(&config).unwrap();
+        let polling = PollingService::new(shipped(), Box::new(modbus), EventBus::new());
+        let report = polling.poll_once().await;
+        assert_eq!(report.failed_reads, 0);
+        assert_eq!(report.bad, 0);
+        let flow = polling.value(Some("Flows"), "F2", "value").unwrap();
+        assert_eq!(flow.quality, Quality::Good);
+        assert!((flow.value - 120.0).abs() < 0.1);
+        // 回水温升 = 负载 / (比热 × 流量)
+        let capacity = polling.value(None, "CAP", "value").unwrap().value;
+        assert!((capacity - 60.0).abs() < 1.0, "{}", capacity);
+    }
+
+    #[tokio::test]
+    async fn test_rtu_over_pty() {
+        let simulator = simulator();
+        let (path, _server) = simulator.serve_rtu(1).unwrap();
+        let config = ModbusClientConfig {
+            tcp: TcpConfig {
+                enable: false,
+                ..Default::default()
+            },
+            serial: SerialConfig {
+                enable: true,
+                port: path,
+                baudrate: 19200,
+                timeout: 0.5,
+                parity: Parity::Even,
+                stop_bits: 2,
+                ..Default::default()
+            },
+        };
+        let mut modbus = ModbusService::from_config(&config).unwrap();
+        let values = modbus.read(None, ReadMethod::HoldingRegisters, 2192, 2).await.unwrap();
+        assert_eq!(values, vec![3000, 3000]);
+        assert_eq!(modbus.read(Some(1), ReadMethod::Coils, 6, 1).await.unwrap(), vec![1]);
+        // 总线上没有 2 号从站
+        assert!(matches!(
+            modbus.read(Some(2), ReadMethod::Coils, 6, 1).await,
+            Err(ModbusError::Timeout(_))
+        ));
+        // 超时后等过重连退避时间再读
+        tokio::time::sleep(crate::services::modbus_service::RECONNECT_BACKOFF_MIN).await;
+        assert_eq!(modbus.read(None, ReadMethod::Coils, 6, 1).await.unwrap(), vec![1]);
+        let stats = modbus.link_stats().unwrap();
+        assert_eq!((stats.frames_sent, stats.frames_received), (4, 3));
+        assert_eq!((stats.crc_errors, stats.framing_errors), (0, 0));
+
+        // 超时后重连，最近的帧中有发出的请求和收到的响应
+        let diagnostics = modbus.diagnostics().snapshot();
+        assert_eq!((diagnostics.requests, diagnostics.responses, diagnostics.timeouts), (4, 3, 1));
+        assert_eq!(diagnostics.reconnects, 1);
+        assert_eq!(diagnostics.link, Some(stats));
+        assert_eq!(diagnostics.latency.count, 3);
+        let first: Vec<(Direction, &str)> = diagnostics.frames[..2].iter().map(|f| (f.direction, f.data.as_str())).collect();
+        assert_eq!(
+            first,
+            vec![(Direction::Tx, "01 03 08 90 00 02 C6 46"), (Direction::Rx, "01 03 04 0B B8 0B B8 7F 70")]
+        );
+    }
+}
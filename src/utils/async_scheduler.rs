@@ -0,0 +1,534 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use time::{OffsetDateTime, Time};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::utils::datetime;
+
+pub type JobId = u64;
+
+type JobFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type JobFn = Arc<dyn Fn() -> JobFuture + Send + Sync>;
+
+/// 任务的调度方式。API 中写成 {"fixed_rate": 秒}、{"fixed_delay": 秒}、{"once": 秒} 或 {"daily": ["02:00"]}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// 按固定频率执行，第 n 次执行时间为起点 + n * 周期，不会累积漂移；
+    /// 执行时间超过周期记为一次超时，错过的周期直接跳过
+    FixedRate(#[serde(with = "seconds")] Duration),
+    /// 上一次执行结束后等待固定时间再执行
+    FixedDelay(#[serde(with = "seconds")] Duration),
+    /// 延迟一段时间后执行一次
+    Once(#[serde(with = "seconds")] Duration),
+    /// 每天在指定的本地时间执行，类似 cron 的 "m h * * *"
+    Daily(#[serde(with = "daily_times")] Vec<Time>),
+}
+
+mod seconds {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Duration::try_from_secs_f64(f64::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+/// 每天的执行时间写成 "HH:MM" 或 "HH:MM:SS"
+mod daily_times {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use time::Time;
+
+    pub fn serialize<S: Serializer>(times: &[Time], serializer: S) -> Result<S::Ok, S::Error> {
+        times
+            .iter()
+            .map(|t| match t.second() {
+                0 => format!("{:02}:{:02}", t.hour(), t.minute()),
+                s => format!("{:02}:{:02}:{:02}", t.hour(), t.minute(), s),
+            })
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Time>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|text| parse(text).ok_or_else(|| de::Error::custom(format!("invalid time of day {:?}", text))))
+            .collect()
+    }
+
+    fn parse(text: &str) -> Option<Time> {
+        let parts: Vec<u8> = text.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+        match parts[..] {
+            [hour, minute] => Time::from_hms(hour, minute, 0).ok(),
+            [hour, minute, second] => Time::from_hms(hour, minute, second).ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ScheduleError {
+    /// 周期为 0 的任务会不停地执行，占满调度线程
+    #[error("job {0}: period must be greater than zero")]
+    ZeroPeriod(String),
+}
+
+impl Schedule {
+    fn check(&self, name: &str) -> Result<(), ScheduleError> {
+        match self {
+            Schedule::FixedRate(period) | Schedule::FixedDelay(period) if period.is_zero() => {
+                Err(ScheduleError::ZeroPeriod(name.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 把周期类调度换成新的周期，其它调度方式保持不变
+    pub fn with_period(&self, period: Duration) -> Schedule {
+        match self {
+            Schedule::FixedRate(_) => Schedule::FixedRate(period),
+            Schedule::FixedDelay(_) => Schedule::FixedDelay(period),
+            other => other.clone(),
+        }
+    }
+}
+
+/// 任务的运行统计
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobStats {
+    pub runs: u64,
+    /// 执行时间超过周期的次数
+    pub overruns: u64,
+    /// 因超时而跳过的周期数
+    pub skipped: u64,
+    pub last_duration: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Stats {
+    runs: AtomicU64,
+    overruns: AtomicU64,
+    skipped: AtomicU64,
+    last_duration: Mutex<Option<Duration>>,
+}
+
+/// 已调度任务的句柄，可以修改周期或取消任务
+#[derive(Clone)]
+pub struct JobHandle {
+    id: JobId,
+    name: String,
+    schedule: Arc<watch::Sender<Schedule>>,
+    cancel: Arc<watch::Sender<bool>>,
+    stats: Arc<Stats>,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        self.schedule.borrow().clone()
+    }
+
+    /// 运行中修改调度方式。固定频率任务以上一次的计划时间为基准计算下一次执行，不会漂移
+    pub fn set_schedule(&self, schedule: Schedule) -> Result<(), ScheduleError> {
+        schedule.check(&self.name)?;
+        self.schedule.send_replace(schedule);
+        Ok(())
+    }
+
+    pub fn set_period(&self, period: Duration) -> Result<(), ScheduleError> {
+        let schedule = self.schedule().with_period(period);
+        self.set_schedule(schedule)
+    }
+
+    /// 取消任务，正在执行的那一次会执行完
+    pub fn cancel(&self) {
+        self.cancel.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel.borrow()
+    }
+
+    pub fn stats(&self) -> JobStats {
+        JobStats {
+            runs: self.stats.runs.load(Ordering::Relaxed),
+            overruns: self.stats.overruns.load(Ordering::Relaxed),
+            skipped: self.stats.skipped.load(Ordering::Relaxed),
+            last_duration: *self.stats.last_duration.lock().unwrap(),
+        }
+    }
+}
+
+struct JobEntry {
+    handle: JobHandle,
+    task: JoinHandle<()>,
+}
+
+/// 基于 tokio 的定时任务调度器：轮询、告警判断、PID 计算、泵轮换和历史数据落盘都在这里调度
+pub struct Scheduler {
+    jobs: Mutex<Vec<JobEntry>>,
+    next_id: AtomicU64,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    /// 调度一个任务，job 每次执行时返回一个新的 future。固定频率和固定延迟的周期不能为 0
+    pub fn schedule<F, Fut>(&self, name: &str, schedule: Schedule, job: F) -> Result<JobHandle, ScheduleError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        schedule.check(name)?;
+        let job: JobFn = Arc::new(move || Box::pin(job()));
+        let handle = JobHandle {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            name: name.to_string(),
+            schedule: Arc::new(watch::channel(schedule).0),
+            cancel: Arc::new(watch::channel(false).0),
+            stats: Arc::new(Stats::default()),
+        };
+        let task = tokio::spawn(run_job(handle.clone(), job, self.shutdown.subscribe()));
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|entry| !entry.task.is_finished());
+        jobs.push(JobEntry {
+            handle: handle.clone(),
+            task,
+        });
+        Ok(handle)
+    }
+
+    pub fn job(&self, id: JobId) -> Option<JobHandle> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|entry| entry.handle.id == id)
+            .map(|entry| entry.handle.clone())
+    }
+
+    /// 仍在调度中的任务
+    pub fn jobs(&self) -> Vec<JobHandle> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|entry| !entry.task.is_finished());
+        jobs.iter().map(|entry| entry.handle.clone()).collect()
+    }
+
+    pub fn cancel(&self, id: JobId) -> bool {
+        match self.job(id) {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 停止调度新的执行，等待正在执行的任务结束，超过 grace 后强制中止。
+    /// 返回被强制中止的任务数
+    pub async fn shutdown(&self, grace: Duration) -> usize {
+        self.shutdown.send_replace(true);
+        let jobs: Vec<JobEntry> = self.jobs.lock().unwrap().drain(..).collect();
+        let deadline = Instant::now() + grace;
+
+        let mut aborted = 0;
+        for mut entry in jobs {
+            if tokio::time::timeout_at(deadline, &mut entry.task).await.is_err() {
+                warn!("Job {} did not finish within {:?}, aborting", entry.handle.name, grace);
+                entry.task.abort();
+                aborted += 1;
+            }
+        }
+        info!("Scheduler stopped, {} job(s) aborted", aborted);
+        aborted
+    }
+}
+
+async fn run_job(handle: JobHandle, job: JobFn, mut shutdown: watch::Receiver<bool>) {
+    let mut schedule_rx = handle.schedule.subscribe();
+    let mut cancel_rx = handle.cancel.subscribe();
+    // 固定频率任务的上一次计划执行时间，其它任务的上一次结束时间
+    let mut anchor = Instant::now();
+
+    loop {
+        if *cancel_rx.borrow() || *shutdown.borrow() {
+            break;
+        }
+        let schedule = schedule_rx.borrow_and_update().clone();
+        let deadline = match &schedule {
+            Schedule::FixedRate(period) | Schedule::FixedDelay(period) | Schedule::Once(period) => anchor + *period,
+            Schedule::Daily(times) => match next_daily(datetime::now_local(), times) {
+                Some(next) => Instant::now() + duration_until(next),
+                None => {
+                    warn!("Job {} has an empty daily schedule", handle.name);
+                    break;
+                }
+            },
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => {}
+            // 调度方式变了，以同一个基准重新计算下一次执行时间
+            _ = schedule_rx.changed() => continue,
+            _ = cancel_rx.changed() => break,
+            _ = shutdown.changed() => break,
+        }
+
+        let started = Instant::now();
+        job().await;
+        let finished = Instant::now();
+        record_run(&handle.stats, finished - started);
+
+        match schedule {
+            Schedule::FixedRate(period) => {
+                anchor = deadline;
+                let late = finished.saturating_duration_since(deadline);
+                if late >= period && !period.is_zero() {
+                    let skipped = (late.as_nanos() / period.as_nanos()) as u32;
+                    anchor += period * skipped;
+                    handle.stats.overruns.fetch_add(1, Ordering::Relaxed);
+                    handle.stats.skipped.fetch_add(skipped as u64, Ordering::Relaxed);
+                    warn!(
+                        "Job {} took {:?}, longer than its period {:?}; skipped {} tick(s)",
+                        handle.name,
+                        finished - started,
+                        period,
+                        skipped
+                    );
+                }
+            }
+            Schedule::FixedDelay(_) | Schedule::Daily(_) => anchor = finished,
+            Schedule::Once(_) => break,
+        }
+    }
+}
+
+fn record_run(stats: &Stats, duration: Duration) {
+    stats.runs.fetch_add(1, Ordering::Relaxed);
+    *stats.last_duration.lock().unwrap() = Some(duration);
+}
+
+/// now 之后（不含 now）最近的一个执行时间
+pub fn next_daily(now: OffsetDateTime, times: &[Time]) -> Option<OffsetDateTime> {
+    let today = now.replace_time(Time::MIDNIGHT);
+    times
+        .iter()
+        .map(|time| today.replace_time(*time))
+        .map(|at| if at > now { at } else { at + time::Duration::DAY })
+        .min()
+}
+
+fn duration_until(at: OffsetDateTime) -> Duration {
+    let remaining = at - OffsetDateTime::now_utc();
+    Duration::try_from(remaining).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn counter() -> (Arc<AtomicUsize>, impl Fn() -> std::future::Ready<()> + Send + Sync + 'static) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let job_count = Arc::clone(&count);
+        (count, move || {
+            job_count.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(())
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fixed_rate_does_not_drift() {
+        let scheduler = Scheduler::new();
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let start = Instant::now();
+        let job_ticks = Arc::clone(&ticks);
+        scheduler.schedule("poll", Schedule::FixedRate(Duration::from_secs(1)), move || {
+            job_ticks.lock().unwrap().push(Instant::now() - start);
+            // 每次执行耗时 300ms，不影响下一次的计划时间
+            tokio::time::sleep(Duration::from_millis(300))
+        }).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(3500)).await;
+        let ticks = ticks.lock().unwrap().clone();
+        assert_eq!(ticks, vec![Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(3)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fixed_delay_waits_after_completion() {
+        let scheduler = Scheduler::new();
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let start = Instant::now();
+        let job_ticks = Arc::clone(&ticks);
+        scheduler.schedule("flush", Schedule::FixedDelay(Duration::from_secs(1)), move || {
+            job_ticks.lock().unwrap().push(Instant::now() - start);
+            tokio::time::sleep(Duration::from_millis(500))
+        }).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(3200)).await;
+        let ticks = ticks.lock().unwrap().clone();
+        assert_eq!(ticks, vec![Duration::from_millis(1000), Duration::from_millis(2500)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_change_period_at_runtime() {
+        let scheduler = Scheduler::new();
+        let (count, job) = counter();
+        let handle = scheduler.schedule("alarm", Schedule::FixedRate(Duration::from_secs(10)), job).unwrap();
+
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // 基准是第 10 秒的那次执行，新周期 2 秒：12、14、16 秒各执行一次
+        handle.set_period(Duration::from_secs(2)).unwrap();
+        assert_eq!(handle.schedule(), Schedule::FixedRate(Duration::from_secs(2)));
+        tokio::time::sleep(Duration::from_millis(5500)).await;
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+        assert_eq!(handle.stats().runs, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overrun_skips_missed_ticks() {
+        let scheduler = Scheduler::new();
+        let handle = scheduler.schedule("pid", Schedule::FixedRate(Duration::from_secs(1)), || {
+            tokio::time::sleep(Duration::from_millis(2500))
+        }).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(3600)).await;
+        let stats = handle.stats();
+        assert_eq!(stats.runs, 1);
+        assert_eq!(stats.overruns, 1);
+        assert_eq!(stats.skipped, 2);
+        assert_eq!(stats.last_duration, Some(Duration::from_millis(2500)));
+
+        // 下一次在第 4 秒执行
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(handle.stats().runs, 1);
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert_eq!(handle.stats().runs, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_once_and_cancel() {
+        let scheduler = Scheduler::new();
+        let (once_count, once_job) = counter();
+        scheduler.schedule("rotate", Schedule::Once(Duration::from_secs(5)), once_job).unwrap();
+        let (periodic_count, periodic_job) = counter();
+        let periodic = scheduler.schedule("poll", Schedule::FixedRate(Duration::from_secs(1)), periodic_job).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert!(scheduler.cancel(periodic.id()));
+        assert!(periodic.is_cancelled());
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(once_count.load(Ordering::SeqCst), 1);
+        assert_eq!(periodic_count.load(Ordering::SeqCst), 2);
+        assert!(scheduler.jobs().is_empty());
+        assert!(!scheduler.cancel(periodic.id()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_graceful_shutdown() {
+        let scheduler = Scheduler::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+        let job_finished = Arc::clone(&finished);
+        scheduler.schedule("history", Schedule::FixedRate(Duration::from_secs(1)), move || {
+            let finished = Arc::clone(&job_finished);
+            async move {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+            }
+        }).unwrap();
+        scheduler.schedule("stuck", Schedule::Once(Duration::from_millis(100)), || {
+            tokio::time::sleep(Duration::from_secs(3600))
+        }).unwrap();
+
+        // 第 1 秒开始的那次执行在关闭期间完成
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert_eq!(scheduler.shutdown(Duration::from_secs(1)).await, 1);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+        assert!(scheduler.jobs().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_period_is_rejected() {
+        let scheduler = Scheduler::new();
+        let (_, job) = counter();
+        assert_eq!(
+            scheduler.schedule("poll", Schedule::FixedRate(Duration::ZERO), job).err(),
+            Some(ScheduleError::ZeroPeriod("poll".to_string()))
+        );
+        let (_, job) = counter();
+        assert!(scheduler.schedule("flush", Schedule::FixedDelay(Duration::ZERO), job).is_err());
+        assert!(scheduler.jobs().is_empty());
+
+        let (_, job) = counter();
+        let handle = scheduler.schedule("alarm", Schedule::FixedRate(Duration::from_secs(1)), job).unwrap();
+        assert!(handle.set_period(Duration::ZERO).is_err());
+        assert_eq!(handle.schedule(), Schedule::FixedRate(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_next_daily() {
+        let at = |s: &str| datetime::parse_time(s).unwrap();
+        let times = [Time::from_hms(2, 0, 0).unwrap(), Time::from_hms(14, 30, 0).unwrap()];
+        let now = at("2026-02-10 09:38:48 +0800");
+        assert_eq!(next_daily(now, &times), Some(at("2026-02-10 14:30:00 +0800")));
+
+        let now = at("2026-02-10 14:30:00 +0800");
+        assert_eq!(next_daily(now, &times), Some(at("2026-02-11 02:00:00 +0800")));
+        assert_eq!(next_daily(now, &[]), None);
+    }
+
+    #[test]
+    fn test_schedule_serde() {
+        let schedule: Schedule = serde_json::from_str(r#"{"daily": ["02:00", "14:30:15"]}"#).unwrap();
+        assert_eq!(
+            schedule,
+            Schedule::Daily(vec![Time::from_hms(2, 0, 0).unwrap(), Time::from_hms(14, 30, 15).unwrap()])
+        );
+        assert_eq!(serde_json::to_string(&schedule).unwrap(), r#"{"daily":["02:00","14:30:15"]}"#);
+
+        let schedule: Schedule = serde_json::from_str(r#"{"fixed_rate": 0.5}"#).unwrap();
+        assert_eq!(schedule, Schedule::FixedRate(Duration::from_millis(500)));
+        assert_eq!(serde_json::to_string(&Schedule::Once(Duration::from_secs(30))).unwrap(), r#"{"once":30.0}"#);
+
+        assert!(serde_json::from_str::<Schedule>(r#"{"fixed_delay": -1}"#).is_err());
+        assert!(serde_json::from_str::<Schedule>(r#"{"daily": ["25:00"]}"#).is_err());
+        assert!(serde_json::from_str::<Schedule>(r#"{"cron": "* * * * *"}"#).is_err());
+    }
+}
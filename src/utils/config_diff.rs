@@ -0,0 +1,129 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::utils::config_path::{ConfigPath, Segment};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// 配置中某个叶子（或类型发生变化的节点）的变更
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigChange {
+    #[serde(serialize_with = "serialize_path")]
+    pub path: ConfigPath,
+    pub kind: ChangeKind,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+fn serialize_path<S: serde::Serializer>(path: &ConfigPath, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string())
+}
+
+/// 计算两份配置之间的结构化差异
+pub fn diff(old: &Value, new: &Value) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+    diff_values(&ConfigPath::root(), Some(old), Some(new), &mut changes);
+    changes
+}
+
+/// 只计算 prefix 对应子树的差异，返回的路径仍是完整路径
+pub fn diff_at(prefix: &ConfigPath, old: &Value, new: &Value) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+    diff_values(prefix, prefix.get(old).ok(), prefix.get(new).ok(), &mut changes);
+    changes
+}
+
+fn diff_values(path: &ConfigPath, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (None, None) => {}
+        (Some(a), Some(b)) if a == b => {}
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            for (key, old_value) in a {
+                diff_values(
+                    &path.child(Segment::Key(key.clone())),
+                    Some(old_value),
+                    b.get(key),
+                    changes,
+                );
+            }
+            for (key, new_value) in b.iter().filter(|(key, _)| !a.contains_key(*key)) {
+                diff_values(&path.child(Segment::Key(key.clone())), None, Some(new_value), changes);
+            }
+        }
+        (Some(Value::Array(a)), Some(Value::Array(b))) => {
+            for index in 0..a.len().max(b.len()) {
+                diff_values(&path.child(Segment::Index(index)), a.get(index), b.get(index), changes);
+            }
+        }
+        (old, new) => changes.push(ConfigChange {
+            path: path.clone(),
+            kind: match (old, new) {
+                (None, _) => ChangeKind::Added,
+                (_, None) => ChangeKind::Removed,
+                _ => ChangeKind::Modified,
+            },
+            old_value: old.cloned(),
+            new_value: new.cloned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(changes: &[ConfigChange]) -> Vec<String> {
+        changes.iter().map(|c| c.path.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = json!({
+            "modbus_client": {"serial": {"baudrate": 9600, "parity": "N"}},
+            "single": {"T1": [{"value": 0}, {"value": 45}]},
+            "removed": true
+        });
+        let new = json!({
+            "modbus_client": {"serial": {"baudrate": 115200, "parity": "N"}},
+            "single": {"T1": [{"value": 0}]},
+            "added": 1
+        });
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            paths(&changes),
+            vec!["modbus_client.serial.baudrate", "removed", "single.T1[1]", "added"]
+        );
+        assert_eq!(changes[0].kind, ChangeKind::Modified);
+        assert_eq!(changes[0].old_value, Some(json!(9600)));
+        assert_eq!(changes[0].new_value, Some(json!(115200)));
+        assert_eq!(changes[1].kind, ChangeKind::Removed);
+        assert_eq!(changes[2].kind, ChangeKind::Removed);
+        assert_eq!(changes[3].kind, ChangeKind::Added);
+    }
+
+    #[test]
+    fn test_diff_at_prefix() {
+        let old = json!({"single": {"Cv": [{"value": 15}], "T1": [{"value": 45}]}});
+        let new = json!({"single": {"Cv": [{"value": 15}], "T1": [{"value": 40}]}});
+
+        let cv = ConfigPath::parse("single.Cv").unwrap();
+        assert!(diff_at(&cv, &old, &new).is_empty());
+
+        let t1 = ConfigPath::parse("single.T1").unwrap();
+        assert_eq!(paths(&diff_at(&t1, &old, &new)), vec!["single.T1[0].value"]);
+
+        let missing = ConfigPath::parse("single.T2").unwrap();
+        let added = json!({"single": {"T2": [{"value": 1}]}});
+        let changes = diff_at(&missing, &old, &added);
+        assert_eq!(paths(&changes), vec!["single.T2"]);
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+    }
+}
@@ -0,0 +1,488 @@
+use serde_json::{Map, Value};
+use std::fmt;
+use thiserror::Error;
+
+/// 路径错误
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum PathError {
+    #[error("invalid path '{path}': {reason}")]
+    Invalid { path: String, reason: String },
+
+    #[error("path not found: {0}")]
+    NotFound(String),
+
+    #[error("type mismatch at '{path}': expected {expected}, found {found}")]
+    TypeMismatch {
+        path: String,
+        expected: String,
+        found: String,
+    },
+
+    #[error("index {index} out of bounds at '{path}' (len {len})")]
+    IndexOutOfBounds { path: String, index: usize, len: usize },
+}
+
+pub type Result<T> = std::result::Result<T, PathError>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+    /// JSON Pointer 中的 "-"，表示数组末尾之后的位置
+    End,
+}
+
+/// 配置路径，支持两种写法：
+/// - 点分路径："modbus_client.tcp.host"、"single.T1[0].value"
+/// - RFC 6901 JSON Pointer："/single/T1/0/value"
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ConfigPath {
+    segments: Vec<Segment>,
+}
+
+#[allow(dead_code)]
+impl ConfigPath {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(path: &str) -> Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::root());
+        }
+        if path.starts_with('/') {
+            Self::parse_pointer(path)
+        } else {
+            Self::parse_dotted(path)
+        }
+    }
+
+    fn parse_pointer(path: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        for token in path[1..].split('/') {
+            if token == "-" {
+                segments.push(Segment::End);
+                continue;
+            }
+            // "~1" 必须先于 "~0" 替换，见 RFC 6901 第 4 节
+            let mut key = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    key.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => key.push('~'),
+                    Some('1') => key.push('/'),
+                    _ => return Err(invalid(path, "'~' must be followed by '0' or '1'")),
+                }
+            }
+            segments.push(Segment::Key(key));
+        }
+        Ok(Self { segments })
+    }
+
+    fn parse_dotted(path: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        for part in path.split('.') {
+            let (key, mut rest) = match part.find('[') {
+                Some(i) => (&part[..i], &part[i..]),
+                None => (part, ""),
+            };
+            if key.is_empty() {
+                return Err(invalid(path, "empty key"));
+            }
+            segments.push(Segment::Key(key.to_string()));
+            while !rest.is_empty() {
+                if !rest.starts_with('[') {
+                    return Err(invalid(path, "unexpected characters after ']'"));
+                }
+                let close = rest
+                    .find(']')
+                    .ok_or_else(|| invalid(path, "unclosed '['"))?;
+                let index = rest[1..close]
+                    .parse::<usize>()
+                    .map_err(|_| invalid(path, "array index must be a non-negative integer"))?;
+                segments.push(Segment::Index(index));
+                rest = &rest[close + 1..];
+            }
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    pub fn child(&self, segment: Segment) -> Self {
+        let mut path = self.clone();
+        path.push(segment);
+        path
+    }
+
+    /// 按段比较前缀，"single.T1" 不是 "single.T10" 的前缀
+    pub fn starts_with(&self, prefix: &ConfigPath) -> bool {
+        self.segments.len() >= prefix.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(&prefix.segments)
+                .all(|(a, b)| segment_eq(a, b))
+    }
+
+    /// 读取路径上的值
+    pub fn get<'a>(&self, root: &'a Value) -> Result<&'a Value> {
+        let mut current = root;
+        for (i, segment) in self.segments.iter().enumerate() {
+            current = match (current, segment) {
+                (Value::Object(map), Segment::Key(key)) => map.get(key),
+                (Value::Array(items), segment) => match array_index(segment) {
+                    Some(index) => items.get(index),
+                    None => return Err(self.mismatch(i, "object", current)),
+                },
+                (_, Segment::Key(_)) => return Err(self.mismatch(i, "object", current)),
+                (_, _) => return Err(self.mismatch(i, "array", current)),
+            }
+            .ok_or_else(|| PathError::NotFound(self.to_string()))?;
+        }
+        Ok(current)
+    }
+
+    /// 写入路径上的值，按需创建中间的对象或数组
+    pub fn set(&self, root: &mut Value, value: Value) -> Result<()> {
+        let (last, parents) = match self.segments.split_last() {
+            Some(split) => split,
+            None => return Err(invalid(&self.to_string(), "cannot replace the root")),
+        };
+
+        let mut current = root;
+        for (i, segment) in parents.iter().enumerate() {
+            if current.is_null() {
+                *current = empty_container(&self.segments[i]);
+            }
+            let next = &self.segments[i + 1];
+            current = match current {
+                Value::Object(map) => {
+                    let key = match segment {
+                        Segment::Key(key) => key.clone(),
+                        _ => return Err(self.mismatch(i, "array", &Value::Object(Map::new()))),
+                    };
+                    map.entry(key).or_insert_with(|| empty_container(next))
+                }
+                Value::Array(items) => {
+                    let index = self.resolve_index(i, segment, items.len())?;
+                    if index == items.len() {
+                        items.push(empty_container(next));
+                    }
+                    &mut items[index]
+                }
+                other => {
+                    let expected = container_name(segment);
+                    return Err(self.mismatch(i, expected, other));
+                }
+            };
+        }
+
+        let i = parents.len();
+        if current.is_null() {
+            *current = empty_container(last);
+        }
+        match current {
+            Value::Object(map) => match last {
+                Segment::Key(key) => {
+                    map.insert(key.clone(), value);
+                    Ok(())
+                }
+                _ => Err(self.mismatch(i, "array", &Value::Object(Map::new()))),
+            },
+            Value::Array(items) => {
+                let index = self.resolve_index(i, last, items.len())?;
+                if index == items.len() {
+                    items.push(value);
+                } else {
+                    items[index] = value;
+                }
+                Ok(())
+            }
+            other => {
+                let expected = container_name(last);
+                Err(self.mismatch(i, expected, other))
+            }
+        }
+    }
+
+    /// 删除路径上的值，返回被删除的值；路径不存在时返回 None
+    pub fn remove(&self, root: &mut Value) -> Result<Option<Value>> {
+        let (last, parents) = match self.segments.split_last() {
+            Some(split) => split,
+            None => return Err(invalid(&self.to_string(), "cannot remove the root")),
+        };
+        let parent_path = ConfigPath {
+            segments: parents.to_vec(),
+        };
+
+        let mut current = root;
+        for (i, segment) in parent_path.segments.iter().enumerate() {
+            current = match current {
+                Value::Object(map) => match segment {
+                    Segment::Key(key) => match map.get_mut(key) {
+                        Some(next) => next,
+                        None => return Ok(None),
+                    },
+                    _ => return Err(self.mismatch(i, "array", &Value::Object(Map::new()))),
+                },
+                Value::Array(items) => match array_index(segment) {
+                    Some(index) if index < items.len() => &mut items[index],
+                    Some(_) => return Ok(None),
+                    None => return Err(self.mismatch(i, "object", &Value::Array(Vec::new()))),
+                },
+                other => return Err(self.mismatch(i, container_name(segment), other)),
+            };
+        }
+
+        let i = parents.len();
+        match current {
+            Value::Object(map) => match last {
+                Segment::Key(key) => Ok(map.remove(key)),
+                _ => Err(self.mismatch(i, "array", &Value::Object(Map::new()))),
+            },
+            Value::Array(items) => match array_index(last) {
+                Some(index) if index < items.len() => Ok(Some(items.remove(index))),
+                Some(_) => Ok(None),
+                None => Err(self.mismatch(i, "object", &Value::Array(Vec::new()))),
+            },
+            other => Err(self.mismatch(i, container_name(last), other)),
+        }
+    }
+
+    /// 数组写入位置，允许等于长度（追加）
+    fn resolve_index(&self, i: usize, segment: &Segment, len: usize) -> Result<usize> {
+        let index = match segment {
+            Segment::End => len,
+            segment => array_index(segment)
+                .ok_or_else(|| self.mismatch(i, "object", &Value::Array(Vec::new())))?,
+        };
+        if index > len {
+            return Err(PathError::IndexOutOfBounds {
+                path: self.prefix(i).to_string(),
+                index,
+                len,
+            });
+        }
+        Ok(index)
+    }
+
+    fn prefix(&self, len: usize) -> ConfigPath {
+        ConfigPath {
+            segments: self.segments[..len].to_vec(),
+        }
+    }
+
+    fn mismatch(&self, i: usize, expected: &str, found: &Value) -> PathError {
+        PathError::TypeMismatch {
+            path: self.prefix(i).to_string(),
+            expected: expected.to_string(),
+            found: type_name(found).to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if i == 0 => write!(f, "{}", key)?,
+                Segment::Key(key) => write!(f, ".{}", key)?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+                Segment::End => write!(f, "[-]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for ConfigPath {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// JSON 值的类型名，用于错误信息
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn invalid(path: &str, reason: &str) -> PathError {
+    PathError::Invalid {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// JSON Pointer 的数字 token 作用在数组上时视为下标
+fn array_index(segment: &Segment) -> Option<usize> {
+    match segment {
+        Segment::Index(index) => Some(*index),
+        Segment::Key(key) => key.parse().ok(),
+        Segment::End => None,
+    }
+}
+
+fn segment_eq(a: &Segment, b: &Segment) -> bool {
+    match (a, b) {
+        (Segment::Key(a), Segment::Key(b)) => a == b,
+        (a, b) => array_index(a).is_some() && array_index(a) == array_index(b),
+    }
+}
+
+fn container_name(segment: &Segment) -> &'static str {
+    match segment {
+        Segment::Key(_) => "object",
+        _ => "array",
+    }
+}
+
+fn empty_container(segment: &Segment) -> Value {
+    match segment {
+        Segment::Key(_) => Value::Object(Map::new()),
+        _ => Value::Array(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_dotted_and_pointer() {
+        let dotted = ConfigPath::parse("single.T1[0].value").unwrap();
+        assert_eq!(
+            dotted.segments(),
+            &[
+                Segment::Key("single".to_string()),
+                Segment::Key("T1".to_string()),
+                Segment::Index(0),
+                Segment::Key("value".to_string()),
+            ]
+        );
+        assert_eq!(dotted.to_string(), "single.T1[0].value");
+
+        let pointer = ConfigPath::parse("/a~1b/m~0n/-").unwrap();
+        assert_eq!(
+            pointer.segments(),
+            &[
+                Segment::Key("a/b".to_string()),
+                Segment::Key("m~n".to_string()),
+                Segment::End,
+            ]
+        );
+
+        assert!(matches!(ConfigPath::parse("a..b"), Err(PathError::Invalid { .. })));
+        assert!(matches!(ConfigPath::parse("a[x]"), Err(PathError::Invalid { .. })));
+        assert!(matches!(ConfigPath::parse("a[0"), Err(PathError::Invalid { .. })));
+        assert!(matches!(ConfigPath::parse("/a~2"), Err(PathError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_get() {
+        let config = json!({"single": {"T1": [{"value": 0}, {"value": 45}]}, "T1'": 1});
+
+        let value = ConfigPath::parse("single.T1[1].value").unwrap().get(&config).unwrap();
+        assert_eq!(value, &json!(45));
+        let value = ConfigPath::parse("/single/T1/1/value").unwrap().get(&config).unwrap();
+        assert_eq!(value, &json!(45));
+        assert_eq!(ConfigPath::parse("T1'").unwrap().get(&config).unwrap(), &json!(1));
+
+        assert_eq!(
+            ConfigPath::parse("single.T2").unwrap().get(&config),
+            Err(PathError::NotFound("single.T2".to_string()))
+        );
+        assert_eq!(
+            ConfigPath::parse("single.T1.value").unwrap().get(&config),
+            Err(PathError::TypeMismatch {
+                path: "single.T1".to_string(),
+                expected: "object".to_string(),
+                found: "array".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_creates_intermediates() {
+        let mut config = json!({});
+        ConfigPath::parse("modbus_client.tcp.host")
+            .unwrap()
+            .set(&mut config, json!("192.168.1.150"))
+            .unwrap();
+        ConfigPath::parse("single.T1[0].value")
+            .unwrap()
+            .set(&mut config, json!(45))
+            .unwrap();
+        ConfigPath::parse("/single/T1/-")
+            .unwrap()
+            .set(&mut config, json!({"value": 0}))
+            .unwrap();
+
+        assert_eq!(
+            config,
+            json!({
+                "modbus_client": {"tcp": {"host": "192.168.1.150"}},
+                "single": {"T1": [{"value": 45}, {"value": 0}]}
+            })
+        );
+
+        let result = ConfigPath::parse("single.T1[5]").unwrap().set(&mut config, json!(1));
+        assert_eq!(
+            result,
+            Err(PathError::IndexOutOfBounds {
+                path: "single.T1".to_string(),
+                index: 5,
+                len: 2,
+            })
+        );
+        let result = ConfigPath::parse("modbus_client.tcp.host.name")
+            .unwrap()
+            .set(&mut config, json!(1));
+        assert!(matches!(result, Err(PathError::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut config = json!({"single": {"T1": [{"value": 0}, {"value": 45}]}});
+
+        let removed = ConfigPath::parse("single.T1[0]").unwrap().remove(&mut config).unwrap();
+        assert_eq!(removed, Some(json!({"value": 0})));
+        assert_eq!(config, json!({"single": {"T1": [{"value": 45}]}}));
+
+        let removed = ConfigPath::parse("single.T9.value").unwrap().remove(&mut config).unwrap();
+        assert_eq!(removed, None);
+    }
+
+    #[test]
+    fn test_starts_with() {
+        let path = ConfigPath::parse("single.T10[0].value").unwrap();
+        assert!(path.starts_with(&ConfigPath::parse("single").unwrap()));
+        assert!(path.starts_with(&ConfigPath::parse("/single/T10/0").unwrap()));
+        assert!(!path.starts_with(&ConfigPath::parse("single.T1").unwrap()));
+        assert!(path.starts_with(&ConfigPath::root()));
+    }
+}
@@ -1,27 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{OnceLock, RwLock};
+use thiserror::Error;
+use time::format_description::well_known::Rfc3339;
 use time::{format_description, OffsetDateTime, UtcOffset};
+use tz::TimeZone;
 
-/// 获取当前时间，格式为 "2026-02-10 09:38:48 +0800"
+/// 默认时区（北京时间）
+pub const DEFAULT_TIME_ZONE: &str = "Asia/Shanghai";
+
+/// 系统 tzdata 的查找目录
+pub const ZONEINFO_DIRS: &[&str] = &["/usr/share/zoneinfo", "/share/zoneinfo", "/etc/zoneinfo"];
+
+const DEFAULT_FORMAT: &str = "[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour sign:mandatory][offset_minute]";
+
+#[derive(Debug, Error)]
+pub enum TimeZoneError {
+    #[error("invalid time zone name: {0:?}")]
+    InvalidName(String),
+
+    #[error("unknown time zone: {0}")]
+    Unknown(String),
+
+    #[error("invalid tzdata for {name}: {reason}")]
+    InvalidData { name: String, reason: String },
+}
+
+/// 时间戳的输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    /// "2026-02-10 09:38:48 +0800"
+    #[default]
+    Default,
+    /// "2026-02-10T09:38:48+08:00"
+    Rfc3339,
+}
+
+/// 从系统 tzdata 加载的 IANA 时区，偏移量按时刻计算，包含夏令时
+#[derive(Debug, Clone)]
+pub struct Zone {
+    name: String,
+    tz: TimeZone,
+}
+
+impl Zone {
+    /// 按 IANA 名称（例如 "Asia/Shanghai"、"America/New_York"）加载时区
+    pub fn load(name: &str) -> Result<Self, TimeZoneError> {
+        let valid = !name.is_empty()
+            && name.split('/').all(|part| {
+                !part.is_empty()
+                    && part != "."
+                    && part != ".."
+                    && part.chars().all(|c| c.is_ascii_alphanumeric() || "_+-.".contains(c))
+            });
+        if !valid {
+            return Err(TimeZoneError::InvalidName(name.to_string()));
+        }
+
+        let bytes = ZONEINFO_DIRS
+            .iter()
+            .find_map(|dir| std::fs::read(std::path::Path::new(dir).join(name)).ok())
+            .ok_or_else(|| TimeZoneError::Unknown(name.to_string()))?;
+        let tz = TimeZone::from_tz_data(&bytes).map_err(|e| TimeZoneError::InvalidData {
+            name: name.to_string(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            tz,
+        })
+    }
+
+    /// 固定偏移的时区，系统没有 tzdata 时使用
+    pub fn fixed(name: &str, offset: UtcOffset) -> Self {
+        Self {
+            name: name.to_string(),
+            tz: TimeZone::fixed(offset.whole_seconds()).unwrap_or_else(|_| TimeZone::utc()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 指定时刻的偏移量
+    pub fn offset_at(&self, time: OffsetDateTime) -> UtcOffset {
+        self.tz
+            .find_local_time_type(time.unix_timestamp())
+            .ok()
+            .and_then(|local| UtcOffset::from_whole_seconds(local.ut_offset()).ok())
+            .unwrap_or(UtcOffset::UTC)
+    }
+
+    /// 指定时刻的时区信息
+    pub fn info_at(&self, time: OffsetDateTime) -> ZoneInfo {
+        let local = self.tz.find_local_time_type(time.unix_timestamp()).ok();
+        ZoneInfo {
+            zone: self.name.clone(),
+            offset: format_offset(self.offset_at(time)),
+            abbreviation: local.map(|l| l.time_zone_designation().to_string()).unwrap_or_default(),
+            dst: local.map(|l| l.is_dst()).unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneInfo {
+    pub zone: String,
+    /// "+08:00"
+    pub offset: String,
+    /// "CST"、"EDT" 等缩写
+    pub abbreviation: String,
+    pub dst: bool,
+}
+
+struct Settings {
+    zone: Zone,
+    format: TimestampFormat,
+}
+
+fn settings() -> &'static RwLock<Settings> {
+    static SETTINGS: OnceLock<RwLock<Settings>> = OnceLock::new();
+    SETTINGS.get_or_init(|| {
+        let zone = Zone::load(DEFAULT_TIME_ZONE).unwrap_or_else(|_| {
+            Zone::fixed(DEFAULT_TIME_ZONE, UtcOffset::from_hms(8, 0, 0).unwrap_or(UtcOffset::UTC))
+        });
+        RwLock::new(Settings {
+            zone,
+            format: TimestampFormat::Default,
+        })
+    })
+}
+
+/// 运行中切换时区，之后所有时间戳都按新时区输出
+pub fn set_time_zone(name: &str) -> Result<(), TimeZoneError> {
+    let zone = Zone::load(name)?;
+    settings().write().unwrap().zone = zone;
+    Ok(())
+}
+
+pub fn set_timestamp_format(format: TimestampFormat) {
+    settings().write().unwrap().format = format;
+}
+
+pub fn time_zone() -> Zone {
+    settings().read().unwrap().zone.clone()
+}
+
+pub fn timestamp_format() -> TimestampFormat {
+    settings().read().unwrap().format
+}
+
+/// 获取当前时间，格式为 "2026-02-10 09:38:48 +0800"，或按设置输出 RFC 3339
 pub fn get_current_time() -> String {
-    // 使用 UTC 时间，并手动添加时区偏移
-    let utc_now = OffsetDateTime::now_utc();
-    
-    // 假设本地时区为 +0800（北京时间）
-    let offset = UtcOffset::from_hms(8, 0, 0).unwrap_or(UtcOffset::UTC);
-    
-    // 将 UTC 时间转换为本地时间
-    let local_now = utc_now.to_offset(offset);
-    format_time(&local_now)
-}
-
-/// 格式化时间为 "2026-02-10 09:38:48 +0800" 格式
+    format_time(&now_local())
+}
+
+/// 把任意时刻转换到当前时区
+pub fn to_local(time: OffsetDateTime) -> OffsetDateTime {
+    time.to_offset(time_zone().offset_at(time))
+}
+
+/// 当前的本地时间
+pub fn now_local() -> OffsetDateTime {
+    to_local(OffsetDateTime::now_utc())
+}
+
+/// 按当前设置的格式输出时间，不做时区转换
 pub fn format_time(time: &OffsetDateTime) -> String {
-    let format = format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour][offset_minute]").unwrap();
-    time.format(&format).unwrap()
+    match timestamp_format() {
+        TimestampFormat::Default => {
+            let format = format_description::parse(DEFAULT_FORMAT).unwrap();
+            time.format(&format).unwrap()
+        }
+        TimestampFormat::Rfc3339 => format_rfc3339(time),
+    }
+}
+
+pub fn format_rfc3339(time: &OffsetDateTime) -> String {
+    time.replace_nanosecond(0)
+        .unwrap_or(*time)
+        .format(&Rfc3339)
+        .unwrap_or_default()
 }
 
-/// 解析 "2026-02-10 09:38:48 +0800" 格式的时间字符串
+/// 解析 "2026-02-10 09:38:48 +0800" 或 RFC 3339 格式的时间字符串
 #[allow(dead_code)]
 pub fn parse_time(time_str: &str) -> Result<OffsetDateTime, time::error::Parse> {
-    let format = format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour][offset_minute]").unwrap();
-    OffsetDateTime::parse(time_str, &format)
+    let format = format_description::parse(DEFAULT_FORMAT).unwrap();
+    OffsetDateTime::parse(time_str, &format).or_else(|e| OffsetDateTime::parse(time_str, &Rfc3339).map_err(|_| e))
+}
+
+/// 修改全局时区设置的测试需要串行执行
+#[cfg(test)]
+pub static SETTINGS_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn format_offset(offset: UtcOffset) -> String {
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, hours.abs(), minutes.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dst_transitions() {
+        let zone = Zone::load("America/New_York").unwrap();
+        let winter = parse_time("2026-01-15 12:00:00 +0000").unwrap();
+        let summer = parse_time("2026-07-15 12:00:00 +0000").unwrap();
+
+        assert_eq!(zone.offset_at(winter), UtcOffset::from_hms(-5, 0, 0).unwrap());
+        assert_eq!(zone.offset_at(summer), UtcOffset::from_hms(-4, 0, 0).unwrap());
+        let info = zone.info_at(summer);
+        assert_eq!(info.offset, "-04:00");
+        assert_eq!(info.abbreviation, "EDT");
+        assert!(info.dst);
+
+        // 2026-03-08 02:00 EST 时钟拨快一小时
+        let before = parse_time("2026-03-08 06:59:59 +0000").unwrap();
+        let after = parse_time("2026-03-08 07:00:00 +0000").unwrap();
+        assert_eq!(zone.offset_at(before).whole_hours(), -5);
+        assert_eq!(zone.offset_at(after).whole_hours(), -4);
+
+        let shanghai = Zone::load(DEFAULT_TIME_ZONE).unwrap();
+        assert_eq!(shanghai.info_at(summer).offset, "+08:00");
+        assert!(!shanghai.info_at(summer).dst);
+    }
+
+    #[test]
+    fn test_invalid_zone_names() {
+        assert!(matches!(Zone::load("../../etc/passwd"), Err(TimeZoneError::InvalidName(_))));
+        assert!(matches!(Zone::load("/etc/localtime"), Err(TimeZoneError::InvalidName(_))));
+        assert!(matches!(Zone::load(""), Err(TimeZoneError::InvalidName(_))));
+        assert!(matches!(Zone::load("Mars/Olympus_Mons"), Err(TimeZoneError::Unknown(_))));
+    }
+
+    #[test]
+    fn test_formats() {
+        let time = parse_time("2026-02-10 09:38:48 +0800").unwrap();
+        assert_eq!(format_rfc3339(&time), "2026-02-10T09:38:48+08:00");
+        assert_eq!(parse_time("2026-02-10T09:38:48+08:00").unwrap(), time);
+        assert_eq!(format_offset(UtcOffset::from_hms(-9, -30, 0).unwrap()), "-09:30");
+    }
+
+    #[test]
+    fn test_runtime_settings() {
+        let _guard = SETTINGS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_time_zone("Europe/London").unwrap();
+        set_timestamp_format(TimestampFormat::Rfc3339);
+        let utc = parse_time("2026-07-01 12:00:00 +0000").unwrap();
+        assert_eq!(format_time(&to_local(utc)), "2026-07-01T13:00:00+01:00");
+        assert!(set_time_zone("Nowhere/City").is_err());
+        assert_eq!(time_zone().name(), "Europe/London");
+
+        set_time_zone(DEFAULT_TIME_ZONE).unwrap();
+        set_timestamp_format(TimestampFormat::Default);
+        assert_eq!(format_time(&to_local(utc)), "2026-07-01 20:00:00 +0800");
+    }
 }
@@ -0,0 +1,387 @@
+use std::fmt;
+use thiserror::Error;
+
+/// 传感器公式和计算传感器表达式，例如 "($value - 4000) * 18 / 960"、
+/// "Flows.F2.value * 0.2642 / ((Pressures.P4.value - Pressures.DP1.value) ** 0.5)"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ExpressionError {
+    #[error("syntax error in '{expression}' at {position}: {message}")]
+    Syntax {
+        expression: String,
+        position: usize,
+        message: String,
+    },
+
+    #[error("unknown variable '{0}'")]
+    UnknownVariable(String),
+
+    #[error("'{0}' does not evaluate to a finite number")]
+    NotFinite(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Func {
+    Abs,
+    Min,
+    Max,
+    Sqrt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    /// "$value" 存为 "value"，"Plugin.Sensor.attr" 原样保存
+    Var(String),
+    Neg(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            source,
+            chars: source.char_indices().collect(),
+            pos: 0,
+        };
+        let root = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// 表达式引用的变量，按出现顺序去重
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables = Vec::new();
+        collect_variables(&self.root, &mut variables);
+        variables
+    }
+
+    /// 计算表达式，变量的值由 resolve 提供
+    pub fn eval<F>(&self, resolve: F) -> Result<f64, ExpressionError>
+    where
+        F: Fn(&str) -> Option<f64>,
+    {
+        let value = eval_node(&self.root, &resolve)?;
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(ExpressionError::NotFinite(self.source.clone()))
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[allow(dead_code)]
+fn collect_variables<'a>(node: &'a Node, variables: &mut Vec<&'a str>) {
+    match node {
+        Node::Number(_) => {}
+        Node::Var(name) => {
+            if !variables.contains(&name.as_str()) {
+                variables.push(name);
+            }
+        }
+        Node::Neg(inner) => collect_variables(inner, variables),
+        Node::Binary(_, left, right) => {
+            collect_variables(left, variables);
+            collect_variables(right, variables);
+        }
+        Node::Call(_, args) => args.iter().for_each(|arg| collect_variables(arg, variables)),
+    }
+}
+
+fn eval_node<F>(node: &Node, resolve: &F) -> Result<f64, ExpressionError>
+where
+    F: Fn(&str) -> Option<f64>,
+{
+    Ok(match node {
+        Node::Number(n) => *n,
+        Node::Var(name) => resolve(name).ok_or_else(|| ExpressionError::UnknownVariable(name.clone()))?,
+        Node::Neg(inner) => -eval_node(inner, resolve)?,
+        Node::Binary(op, left, right) => {
+            let (a, b) = (eval_node(left, resolve)?, eval_node(right, resolve)?);
+            match op {
+                Op::Add => a + b,
+                Op::Sub => a - b,
+                Op::Mul => a * b,
+                Op::Div => a / b,
+                Op::Rem => a % b,
+                Op::Pow => a.powf(b),
+            }
+        }
+        Node::Call(func, args) => {
+            let args = args
+                .iter()
+                .map(|arg| eval_node(arg, resolve))
+                .collect::<Result<Vec<_>, _>>()?;
+            match func {
+                Func::Abs => args[0].abs(),
+                Func::Sqrt => args[0].sqrt(),
+                Func::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+                Func::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            }
+        }
+    })
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ExpressionError {
+        ExpressionError::Syntax {
+            expression: self.source.to_string(),
+            position: self.chars.get(self.pos).map(|(i, _)| *i).unwrap_or(self.source.len()),
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// 跳过空白后，如果接下来是 token 就消费它
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let matches = token
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i).map(|(_, d)| *d) == Some(c));
+        if matches {
+            self.pos += token.chars().count();
+        }
+        matches
+    }
+
+    fn expr(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.term()?;
+        loop {
+            let op = if self.eat("+") {
+                Op::Add
+            } else if self.eat("-") {
+                Op::Sub
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.unary()?;
+        loop {
+            let op = if self.eat("**") {
+                // "**" 属于 power，这里回退
+                self.pos -= 2;
+                return Ok(node);
+            } else if self.eat("*") {
+                Op::Mul
+            } else if self.eat("/") {
+                Op::Div
+            } else if self.eat("%") {
+                Op::Rem
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        if self.eat("-") {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat("+") {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Node, ExpressionError> {
+        let base = self.primary()?;
+        if self.eat("**") {
+            // 右结合：2 ** 3 ** 2 == 2 ** 9
+            return Ok(Node::Binary(Op::Pow, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let node = self.expr()?;
+                if !self.eat(")") {
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(node)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some('$') => {
+                self.pos += 1;
+                let name = self.identifier();
+                if name.is_empty() {
+                    return Err(self.error("expected a variable name after '$'"));
+                }
+                Ok(Node::Var(name))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.identifier();
+                let func = match name.as_str() {
+                    "abs" => Some((Func::Abs, 1)),
+                    "sqrt" => Some((Func::Sqrt, 1)),
+                    "min" => Some((Func::Min, 0)),
+                    "max" => Some((Func::Max, 0)),
+                    _ => None,
+                };
+                match func {
+                    Some((func, arity)) if self.eat("(") => self.call(func, arity),
+                    _ => Ok(Node::Var(name)),
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    fn call(&mut self, func: Func, arity: usize) -> Result<Node, ExpressionError> {
+        let mut args = vec![self.expr()?];
+        while self.eat(",") {
+            args.push(self.expr()?);
+        }
+        if !self.eat(")") {
+            return Err(self.error("expected ')'"));
+        }
+        if arity != 0 && args.len() != arity {
+            return Err(self.error(&format!("expected {} argument(s)", arity)));
+        }
+        Ok(Node::Call(func, args))
+    }
+
+    fn number(&mut self) -> Result<Node, ExpressionError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        // 科学计数法 1e-3
+        if self.peek().is_some_and(|c| c == 'e' || c == 'E') {
+            self.pos += 1;
+            if self.peek().is_some_and(|c| c == '-' || c == '+') {
+                self.pos += 1;
+            }
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().map(|(_, c)| c).collect();
+        text.parse().map(Node::Number).map_err(|_| {
+            self.pos = start;
+            self.error(&format!("invalid number '{}'", text))
+        })
+    }
+
+    /// 变量名可以包含 "."（Plugin.Sensor.attr）和 "'"（P1'、T1'）
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '\'')
+        {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().map(|(_, c)| c).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn eval(source: &str, vars: &[(&str, f64)]) -> Result<f64, ExpressionError> {
+        let vars: HashMap<&str, f64> = vars.iter().copied().collect();
+        Expression::parse(source)?.eval(|name| vars.get(name).copied())
+    }
+
+    #[test]
+    fn test_formulas() {
+        assert_eq!(eval("($DutyCycle - 2000) / 80", &[("DutyCycle", 6000.0)]), Ok(50.0));
+        assert_eq!(eval("$value * 80 + 2000", &[("value", 50.0)]), Ok(6000.0));
+        assert_eq!(eval("-2 ** 2", &[]), Ok(-4.0));
+        assert_eq!(eval("2 ** 3 ** 2", &[]), Ok(512.0));
+        assert_eq!(eval("10 - 4 - 3", &[]), Ok(3.0));
+        assert_eq!(eval("7 % 4 * 2", &[]), Ok(6.0));
+        assert_eq!(eval("1.5e3 / 3", &[]), Ok(500.0));
+        assert_eq!(eval("max(1, $a, 3) + abs(-2) + sqrt(16)", &[("a", 7.0)]), Ok(13.0));
+        assert_eq!(
+            eval("$Temperature / 100 - (10000 - $Humidity) / 500", &[("Temperature", 2500.0), ("Humidity", 6000.0)]),
+            Ok(17.0)
+        );
+    }
+
+    #[test]
+    fn test_references() {
+        let expression =
+            Expression::parse("Flows.F2.value * 0.2642 / ((Pressures.P4.value - Pressures.P5'.value) ** 0.5)").unwrap();
+        assert_eq!(
+            expression.variables(),
+            vec!["Flows.F2.value", "Pressures.P4.value", "Pressures.P5'.value"]
+        );
+        let value = expression
+            .eval(|name| match name {
+                "Flows.F2.value" => Some(100.0),
+                "Pressures.P4.value" => Some(20.0),
+                "Pressures.P5'.value" => Some(16.0),
+                _ => None,
+            })
+            .unwrap();
+        assert!((value - 13.21).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(Expression::parse("($value - 1"), Err(ExpressionError::Syntax { .. })));
+        assert!(matches!(Expression::parse("$value +"), Err(ExpressionError::Syntax { .. })));
+        assert!(matches!(Expression::parse("1 2"), Err(ExpressionError::Syntax { .. })));
+        assert!(matches!(Expression::parse("abs(1, 2)"), Err(ExpressionError::Syntax { .. })));
+        assert_eq!(eval("$missing * 2", &[]), Err(ExpressionError::UnknownVariable("missing".to_string())));
+        assert_eq!(eval("1 / 0", &[]), Err(ExpressionError::NotFinite("1 / 0".to_string())));
+        assert!(matches!(eval("(0 - 4) ** 0.5", &[]), Err(ExpressionError::NotFinite(_))));
+    }
+}
@@ -1,11 +1,23 @@
+use async_trait::async_trait;
+use log::{error, info, warn};
 use serde::Serialize;
 use serde_json;
 use serde_yaml;
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
+use time::{format_description, OffsetDateTime};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::utils::config_diff::{self, ConfigChange};
+use crate::utils::config_path::{self, ConfigPath, PathError};
 
 // 错误类型
 #[derive(Debug, Error)]
@@ -24,6 +36,24 @@ pub enum FileStoreError {
 
     #[error("unsupported file extension: {0}")]
     UnsupportedExtension(String),
+
+    #[error("config root must be a mapping: {0}")]
+    InvalidRoot(String),
+
+    #[error("config file is empty: {0}")]
+    Empty(String),
+
+    #[error("invalid config: {0}")]
+    Validation(String),
+
+    #[error("async config subscription requires a tokio runtime")]
+    NoRuntime,
+
+    #[error("config has unsaved changes, external modification ignored: {0}")]
+    UnsavedChanges(String),
+
+    #[error(transparent)]
+    Path(#[from] PathError),
 }
 
 pub type Result<T> = std::result::Result<T, FileStoreError>;
@@ -31,18 +61,125 @@ pub type Result<T> = std::result::Result<T, FileStoreError>;
 #[allow(dead_code)]
 pub const EVENT_NAME: &str = "change";
 
+#[allow(dead_code)]
+pub const ERROR_EVENT_NAME: &str = "error";
+
+/// 默认保留的备份数量
+pub const DEFAULT_MAX_BACKUPS: usize = 5;
+
+/// 备份文件所在的子目录名
+pub const BACKUP_DIR_NAME: &str = "backups";
+
+const BACKUP_EXTENSION: &str = "bak";
+
 pub trait ConfigChangeCallback: Send + Sync {
     fn on_config_change(&self, new_config: &Config, old_config: &Config);
 }
 
+/// 订阅某个路径前缀的回调，只在该子树变化时触发，changes 为子树内的结构化差异
+pub trait PathChangeCallback: Send + Sync {
+    fn on_path_change(&self, changes: &[ConfigChange], new_config: &Config);
+}
+
+/// PathChangeCallback 的异步版本，在 tokio 上执行，不阻塞写入方。
+/// 每个订阅者有自己的队列，回调按配置修改的顺序逐个执行
+#[async_trait]
+pub trait AsyncPathChangeCallback: Send + Sync {
+    async fn on_path_change(&self, changes: Vec<ConfigChange>, new_config: Config);
+}
+
+/// 外部修改的配置无法加载时的回调
+pub trait ConfigErrorCallback: Send + Sync {
+    fn on_config_error(&self, error: &FileStoreError);
+}
+
 pub type Config = serde_json::Value;
 
+/// 配置校验函数，返回错误描述
+pub type ConfigValidator = Arc<dyn Fn(&Config) -> std::result::Result<(), String> + Send + Sync>;
+
+/// 解析前对文件原始内容的修正，用于解析后无法区分的写法（例如未加引号的 YAML ">"）
+pub type SourceFilter = fn(&str) -> String;
+
+/// 外部修改的配置在校验前的格式迁移，返回错误描述
+pub type ConfigMigration = Arc<dyn Fn(&mut Config) -> std::result::Result<(), String> + Send + Sync>;
+
+pub type SubscriptionId = u64;
+
+#[derive(Clone)]
+enum Subscriber {
+    Config(Arc<dyn ConfigChangeCallback>),
+    Path(Arc<dyn PathChangeCallback>),
+    AsyncPath(mpsc::UnboundedSender<(Vec<ConfigChange>, Config)>),
+}
+
+#[derive(Clone)]
+struct Subscription {
+    id: SubscriptionId,
+    prefix: ConfigPath,
+    subscriber: Subscriber,
+}
+
+/// 用于判断文件是否被外部修改
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl Fingerprint {
+    fn of(path: &Path, content: &[u8]) -> Self {
+        let metadata = fs::metadata(path).ok();
+        Self {
+            modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+            len: metadata.map(|m| m.len()).unwrap_or(content.len() as u64),
+            hash: hash_bytes(content),
+        }
+    }
+}
+
+fn hash_bytes(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 文件监听句柄，drop 时停止监听
+pub struct WatchHandle {
+    task: JoinHandle<()>,
+}
+
+impl WatchHandle {
+    #[allow(dead_code)]
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 pub struct FileStore {
     path: PathBuf,
     default_path: Option<PathBuf>,
-    pub config: Config,
-    last_config: Config,
-    callbacks: Arc<Mutex<HashMap<String, Vec<Arc<dyn ConfigChangeCallback>>>>>,
+    config: Arc<Mutex<Config>>,
+    last_config: Arc<Mutex<Config>>,
+    loaded_from: Option<PathBuf>,
+    max_backups: usize,
+    validator: Option<ConfigValidator>,
+    source_filter: Option<SourceFilter>,
+    migration: Option<ConfigMigration>,
+    fingerprint: Arc<Mutex<Option<Fingerprint>>>,
+    callbacks: Arc<Mutex<Vec<Subscription>>>,
+    next_subscription_id: AtomicU64,
+    error_callbacks: Arc<Mutex<Vec<Arc<dyn ConfigErrorCallback>>>>,
+    /// 串行化写入和热加载：校验、备份、写文件和替换内存中的配置作为一个整体，
+    /// 并发的写入（例如 API 修改和导入）不会交错，内存与磁盘保持一致。回调在释放锁之后执行
+    write_lock: Mutex<()>,
 }
 
 #[allow(dead_code)]
@@ -56,83 +193,240 @@ impl FileStore {
             fs::create_dir_all(parent)?;
         }
 
-        let config = if path.exists() {
-            Self::load_config_from_file(&path)?
+        let (config, loaded_from) = if path.exists() {
+            let (config, used) = Self::load_with_fallback(&path)?;
+            (config, Some(used))
         } else if let Some(ref dp) = default_path {
-            Self::load_config_from_file(dp).unwrap_or_default()
+            match Self::load_config_from_file(dp) {
+                Ok(config) => (config, Some(dp.clone())),
+                Err(_) => (Config::default(), None),
+            }
         } else {
-            serde_json::Value::Object(serde_json::Map::new())
+            (serde_json::Value::Object(serde_json::Map::new()), None)
         };
 
+        let fingerprint = fs::read(&path)
+            .ok()
+            .map(|content| Fingerprint::of(&path, &content));
+
         Ok(Self {
             path,
             default_path,
-            config: config.clone(),
-            last_config: config,
-            callbacks: Arc::new(Mutex::new(HashMap::new())),
+            config: Arc::new(Mutex::new(config.clone())),
+            last_config: Arc::new(Mutex::new(config)),
+            loaded_from,
+            max_backups: DEFAULT_MAX_BACKUPS,
+            validator: None,
+            source_filter: None,
+            migration: None,
+            fingerprint: Arc::new(Mutex::new(fingerprint)),
+            callbacks: Arc::new(Mutex::new(Vec::new())),
+            next_subscription_id: AtomicU64::new(1),
+            error_callbacks: Arc::new(Mutex::new(Vec::new())),
+            write_lock: Mutex::new(()),
         })
     }
-    fn load_config_from_file(path: &Path) -> Result<Config> {
+
+    /// 设置配置校验函数，set_config、save_config 和热加载都会先校验再接受新配置
+    pub fn with_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&Config) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// 设置原始内容的修正函数，并按修正后的内容重新解析已经加载的文件。
+    /// 之后的热加载和 reset_config 也会先修正再解析
+    pub fn with_source_filter(mut self, filter: SourceFilter) -> Result<Self> {
+        self.source_filter = Some(filter);
+        if let Some(ref loaded_from) = self.loaded_from {
+            let format_path = if Some(loaded_from) == self.default_path.as_ref() {
+                loaded_from
+            } else {
+                &self.path
+            };
+            let config = match self.read_config(loaded_from, format_path) {
+                Err(FileStoreError::Empty(_)) => Config::Object(serde_json::Map::new()),
+                result => result?,
+            };
+            *self.config.lock().unwrap() = config.clone();
+            *self.last_config.lock().unwrap() = config;
+        }
+        Ok(self)
+    }
+
+    /// 设置热加载时的格式迁移，外部修改的旧格式文件先迁移再校验。
+    /// 启动时的迁移和写回由 config::migrations::migrate_store 负责
+    pub fn with_migration<F>(mut self, migration: F) -> Self
+    where
+        F: Fn(&mut Config) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.migration = Some(Arc::new(migration));
+        self
+    }
+
+    /// 读取并解析 path，先经过 source_filter 修正
+    fn read_config(&self, path: &Path, format_path: &Path) -> Result<Config> {
         if !path.exists() {
             return Err(FileStoreError::ConfigNotFound(path.display().to_string()));
         }
-
         let content = fs::read_to_string(path)?;
+        let ext = format_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or_else(|| FileStoreError::UnsupportedExtension("no extension".to_string()))?;
+        match self.source_filter {
+            Some(filter) => Self::parse_config(ext, &filter(&content), path),
+            None => Self::parse_config(ext, &content, path),
+        }
+    }
+
+    pub fn validate(&self, config: &Config) -> Result<()> {
+        match self.validator {
+            Some(ref validator) => validator(config).map_err(FileStoreError::Validation),
+            None => Ok(()),
+        }
+    }
+
+    /// 设置保留的备份数量，0 表示不做备份
+    pub fn with_max_backups(mut self, max_backups: usize) -> Self {
+        self.max_backups = max_backups;
+        self
+    }
+
+    /// 实际加载配置所使用的文件（主文件、备份或默认文件）
+    pub fn loaded_from(&self) -> Option<&Path> {
+        self.loaded_from.as_deref()
+    }
 
-        match path.extension().and_then(|ext| ext.to_str()) {
-            Some("json") => {
-                let config: Config = serde_json::from_str(&content)?;
-                Ok(config)
+    /// 主文件损坏时，按从新到旧的顺序回退到第一个可以解析的备份。
+    /// 空文件在有备份时视为写入被截断，没有备份时是尚未填写的配置，按空映射处理
+    fn load_with_fallback(path: &Path) -> Result<(Config, PathBuf)> {
+        let backups = Self::list_backups(path)?;
+        let err = match Self::load_config_from_file(path) {
+            Ok(config) => return Ok((config, path.to_path_buf())),
+            Err(FileStoreError::Empty(_)) if backups.is_empty() => {
+                return Ok((Config::Object(serde_json::Map::new()), path.to_path_buf()))
             }
-            Some("yaml") | Some("yml") => {
-                let config: Config = serde_yaml::from_str(&content)?;
-                Ok(config)
+            Err(e) => e,
+        };
+        warn!("Failed to load config {}: {}", path.display(), err);
+
+        for backup in backups.into_iter().rev() {
+            match Self::load_config_as(&backup, path) {
+                Ok(config) => {
+                    warn!("Config {} restored from backup {}", path.display(), backup.display());
+                    return Ok((config, backup));
+                }
+                Err(e) => warn!("Skipping unreadable backup {}: {}", backup.display(), e),
             }
-            Some(ext) => Err(FileStoreError::UnsupportedExtension(ext.to_string())),
+        }
+        Err(err)
+    }
+
+    fn load_config_from_file(path: &Path) -> Result<Config> {
+        Self::load_config_as(path, path)
+    }
+
+    /// 按 format_path 的扩展名解析 path，备份文件沿用主文件的格式
+    fn load_config_as(path: &Path, format_path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Err(FileStoreError::ConfigNotFound(path.display().to_string()));
+        }
+
+        let content = fs::read_to_string(path)?;
+        match format_path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => Self::parse_config(ext, &content, path),
             None => Err(FileStoreError::UnsupportedExtension(
                 "no extension".to_string(),
             )),
         }
     }
 
+    fn parse_config(format: &str, content: &str, path: &Path) -> Result<Config> {
+        if content.trim().is_empty() {
+            return Err(FileStoreError::Empty(path.display().to_string()));
+        }
+        let config: Config = match format {
+            "json" => serde_json::from_str(content)?,
+            "yaml" | "yml" => match serde_yaml::from_str(content)? {
+                // 只有注释的 YAML 与空文件相同
+                Config::Null => return Err(FileStoreError::Empty(path.display().to_string())),
+                config => config,
+            },
+            ext => return Err(FileStoreError::UnsupportedExtension(ext.to_string())),
+        };
+        // 截断的文件可能解析为数组或标量，这里要求根节点必须是映射
+        if !config.is_object() {
+            return Err(FileStoreError::InvalidRoot(path.display().to_string()));
+        }
+        Ok(config)
+    }
+
     pub fn get_config(&self) -> Config {
-        let config = self.config.clone();
-        config
+        self.config.lock().unwrap().clone()
     }
 
     pub fn update_config<F>(&self, updater: F) -> Result<bool>
     where
         F: FnOnce(&mut Config),
     {
-        let mut config = self.config.clone();
-        let old_config = config.clone();
-        updater(&mut config);
-        if &old_config == &config {
-            return Ok(false);
-        }
+        self.try_update_config(|config| {
+            updater(config);
+            Ok(())
+        })
+    }
+
+    /// 可失败的 update_config，updater 出错时配置保持不变
+    pub fn try_update_config<F>(&self, updater: F) -> Result<bool>
+    where
+        F: FnOnce(&mut Config) -> Result<()>,
+    {
+        let (config, old_config) = {
+            let mut current = self.config.lock().unwrap();
+            let mut config = current.clone();
+            updater(&mut config)?;
+            if config == *current {
+                return Ok(false);
+            }
+            (config.clone(), std::mem::replace(&mut *current, config))
+        };
         self.trigger_callbacks(&config, &old_config);
         Ok(true)
     }
-    pub fn set_config(&self, new_config: Config) -> Result<bool> {
-        let mut config = self.config.clone();
-        let old_config = config.clone();
 
-        config = new_config;
-
-        self.save_config_internal(&config)?;
+    pub fn set_config(&self, new_config: Config) -> Result<bool> {
+        let old_config = {
+            let _write = self.write_lock.lock().unwrap();
+            self.validate(&new_config)?;
+            self.save_config_internal(&new_config)?;
+
+            let old_config = std::mem::replace(&mut *self.config.lock().unwrap(), new_config.clone());
+            *self.last_config.lock().unwrap() = new_config.clone();
+            old_config
+        };
 
-        self.trigger_callbacks(&config, &old_config);
+        self.trigger_callbacks(&new_config, &old_config);
         Ok(true)
     }
 
     pub fn save_config(&self) -> Result<bool> {
-        let config = self.config.clone();
-        let old_config = self.last_config.clone();
+        let (config, old_config) = {
+            let _write = self.write_lock.lock().unwrap();
+            let config = self.get_config();
+            self.validate(&config)?;
+            let old_config = self.last_config.lock().unwrap().clone();
+
+            self.save_config_internal(&config)?;
+            *self.last_config.lock().unwrap() = config.clone();
+            (config, old_config)
+        };
 
-        self.save_config_internal(&config)?;
         self.trigger_callbacks(&config, &old_config);
         Ok(true)
     }
+
     fn save_config_internal(&self, config: &Config) -> Result<bool> {
         let content = match self.path.extension().and_then(|ext| ext.to_str()) {
             Some("json") => serde_json::to_string_pretty(config)?,
@@ -144,17 +438,108 @@ impl FileStore {
                 ))
             }
         };
-        fs::write(&self.path, content)?;
-        // let mut last_config = self.last_config.lock().unwrap();
-        // *last_config = config.clone();
+        self.backup()?;
+        write_atomic(&self.path, content.as_bytes())?;
+        // 记录自己写入的内容，避免监听时当作外部修改重新加载
+        *self.fingerprint.lock().unwrap() = Some(Fingerprint::of(&self.path, content.as_bytes()));
 
         Ok(true)
     }
 
+    /// 备份当前主文件，并清理超出数量的旧备份
+    pub fn backup(&self) -> Result<Option<PathBuf>> {
+        Self::backup_file(&self.path, self.max_backups)
+    }
+
+    /// 备份任意配置文件，备份位置和命名规则与 FileStore 相同
+    pub fn backup_file(path: &Path, max_backups: usize) -> Result<Option<PathBuf>> {
+        if max_backups == 0 || !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read(path)?;
+        let backup_path = Self::next_backup_path(path)?;
+        write_atomic(&backup_path, &content)?;
+
+        let backups = Self::list_backups(path)?;
+        if backups.len() > max_backups {
+            for old in &backups[..backups.len() - max_backups] {
+                fs::remove_file(old)?;
+            }
+        }
+        Ok(Some(backup_path))
+    }
+
+    /// 按时间从旧到新列出主文件的所有备份
+    pub fn list_backups(path: &Path) -> Result<Vec<PathBuf>> {
+        let dir = Self::backup_dir(path);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let prefix = format!("{}.", Self::file_name(path));
+        let suffix = format!(".{}", BACKUP_EXTENSION);
+        let mut backups: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|p| {
+                let name = Self::file_name(p);
+                name.len() > prefix.len() + suffix.len()
+                    && name.starts_with(&prefix)
+                    && name.ends_with(&suffix)
+            })
+            .collect();
+        // 时间戳定长，同一时间戳的 "_n" 后缀按数值排序（_2 在 _10 之前）
+        backups.sort_by_key(|p| Self::backup_order(&Self::file_name(p), &prefix, &suffix));
+        Ok(backups)
+    }
+
+    /// 备份文件名中的 (时间戳, 序号)，没有 "_n" 后缀的序号为 0
+    fn backup_order(name: &str, prefix: &str, suffix: &str) -> (String, u64) {
+        let middle = &name[prefix.len()..name.len() - suffix.len()];
+        match middle.rsplit_once('_') {
+            Some((stamp, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+                (stamp.to_string(), n.parse().unwrap_or(u64::MAX))
+            }
+            _ => (middle.to_string(), 0),
+        }
+    }
+
+    fn backup_dir(path: &Path) -> PathBuf {
+        path.parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(BACKUP_DIR_NAME)
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// 备份文件名形如 "sensors.yaml.20260210T013848.123456.bak"
+    fn next_backup_path(path: &Path) -> Result<PathBuf> {
+        let dir = Self::backup_dir(path);
+        fs::create_dir_all(&dir)?;
+
+        let format = format_description::parse(
+            "[year][month][day]T[hour][minute][second].[subsecond digits:6]",
+        )
+        .unwrap();
+        let stamp = OffsetDateTime::now_utc().format(&format).unwrap();
+        let name = Self::file_name(path);
+
+        let mut candidate = dir.join(format!("{}.{}.{}", name, stamp, BACKUP_EXTENSION));
+        let mut n = 1;
+        while candidate.exists() {
+            candidate = dir.join(format!("{}.{}_{}.{}", name, stamp, n, BACKUP_EXTENSION));
+            n += 1;
+        }
+        Ok(candidate)
+    }
+
     /// 重置为默认配置
     pub fn reset_config(&self) -> Result<bool> {
         let default_config = if let Some(ref default_path) = self.default_path {
-            Self::load_config_from_file(default_path).unwrap_or_default()
+            self.read_config(default_path, default_path).unwrap_or_default()
         } else {
             serde_json::Value::Object(serde_json::Map::new())
         };
@@ -164,7 +549,7 @@ impl FileStore {
 
     /// 获取配置值
     pub fn get<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
-        let config = self.config.clone();
+        let config = self.config.lock().unwrap();
         config
             .get(key)
             .and_then(|value| serde_json::from_value(value.clone()).ok())
@@ -185,50 +570,288 @@ impl FileStore {
         })
     }
 
-    /// 注册配置变更回调（使用 async-trait）
-    pub fn register_callback<C>(&self, event: String, callback: C)
+    /// 按路径获取配置值，路径可以是 "modbus_client.tcp.host"、"single.T1[0].value"
+    /// 或 JSON Pointer "/single/T1/0/value"
+    pub fn get_path<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let path = ConfigPath::parse(path)?;
+        let config = self.config.lock().unwrap();
+        let value = path.get(&config)?;
+        serde_json::from_value(value.clone()).map_err(|_| {
+            PathError::TypeMismatch {
+                path: path.to_string(),
+                expected: std::any::type_name::<T>().to_string(),
+                found: config_path::type_name(value).to_string(),
+            }
+            .into()
+        })
+    }
+
+    /// 按路径设置配置值，自动创建中间的对象和数组
+    pub fn set_path<T: Serialize>(&self, path: &str, value: T) -> Result<bool> {
+        let path = ConfigPath::parse(path)?;
+        let value = serde_json::to_value(value)?;
+        self.try_update_config(|config| Ok(path.set(config, value)?))
+    }
+
+    /// 按路径删除配置值，路径不存在时返回 false
+    pub fn remove_path(&self, path: &str) -> Result<bool> {
+        let path = ConfigPath::parse(path)?;
+        self.try_update_config(|config| {
+            path.remove(config)?;
+            Ok(())
+        })
+    }
+
+    /// 注册配置变更回调，event 为 EVENT_NAME 时监听整个配置，
+    /// 否则视为路径前缀（如 "modbus_client.serial"），只在该子树变化时触发。
+    /// 无法解析为路径的事件名和以前一样不会触发，需要检查路径时使用 subscribe
+    pub fn register_callback<C>(&self, event: String, callback: C) -> SubscriptionId
     where
         C: ConfigChangeCallback + 'static,
     {
-        let mut callbacks = self.callbacks.lock().unwrap();
-        callbacks
-            .entry(event)
-            .or_insert_with(Vec::new)
-            .push(Arc::new(callback));
+        let prefix = if event == EVENT_NAME {
+            ConfigPath::root()
+        } else {
+            match ConfigPath::parse(&event) {
+                Ok(prefix) => prefix,
+                Err(e) => {
+                    warn!("Callback for event '{}' will never fire: {}", event, e);
+                    return self.next_subscription_id.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        };
+        self.add_subscription(prefix, Subscriber::Config(Arc::new(callback)))
     }
 
     /// 注册 change 事件回调的便捷方法
-    pub fn on_change<C>(&self, callback: C)
+    pub fn on_change<C>(&self, callback: C) -> SubscriptionId
     where
         C: ConfigChangeCallback + 'static,
     {
+        self.add_subscription(ConfigPath::root(), Subscriber::Config(Arc::new(callback)))
+    }
+
+    /// 订阅路径前缀的变化，回调收到该子树内的差异
+    pub fn subscribe<C>(&self, prefix: &str, callback: C) -> Result<SubscriptionId>
+    where
+        C: PathChangeCallback + 'static,
+    {
+        let prefix = ConfigPath::parse(prefix)?;
+        Ok(self.add_subscription(prefix, Subscriber::Path(Arc::new(callback))))
+    }
+
+    /// 订阅路径前缀的变化，回调在当前 tokio 运行时的后台任务中按顺序执行，
+    /// 取消订阅后处理完已排队的变化再结束
+    pub fn subscribe_async<C>(&self, prefix: &str, callback: C) -> Result<SubscriptionId>
+    where
+        C: AsyncPathChangeCallback + 'static,
+    {
+        let prefix = ConfigPath::parse(prefix)?;
+        let handle = tokio::runtime::Handle::try_current().map_err(|_| FileStoreError::NoRuntime)?;
+        let (sender, mut receiver) = mpsc::unbounded_channel::<(Vec<ConfigChange>, Config)>();
+        handle.spawn(async move {
+            while let Some((changes, new_config)) = receiver.recv().await {
+                callback.on_path_change(changes, new_config).await;
+            }
+        });
+        Ok(self.add_subscription(prefix, Subscriber::AsyncPath(sender)))
+    }
+
+    /// 取消订阅，返回订阅是否存在
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
         let mut callbacks = self.callbacks.lock().unwrap();
-        callbacks
-            .entry(EVENT_NAME.to_string())
-            .or_insert_with(Vec::new)
-            .push(Arc::new(callback));
+        let len = callbacks.len();
+        callbacks.retain(|subscription| subscription.id != id);
+        callbacks.len() != len
+    }
+
+    fn add_subscription(&self, prefix: ConfigPath, subscriber: Subscriber) -> SubscriptionId {
+        let id = self.next_subscription_id.fetch_add(1, Ordering::Relaxed);
+        self.callbacks.lock().unwrap().push(Subscription {
+            id,
+            prefix,
+            subscriber,
+        });
+        id
     }
 
-    /// 触发回调（异步）
+    /// 注册 error 事件回调，外部修改的配置无效时触发
+    pub fn on_error<C>(&self, callback: C)
+    where
+        C: ConfigErrorCallback + 'static,
+    {
+        self.error_callbacks.lock().unwrap().push(Arc::new(callback));
+    }
+
+    /// 开启文件监听，按 interval 轮询修改时间和内容哈希。
+    /// 外部修改通过解析和校验后替换当前配置并触发 change 回调，
+    /// 否则保留最后一次有效的配置并触发 error 回调
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> WatchHandle {
+        let store = Arc::clone(self);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                store.poll_changes();
+            }
+        });
+        WatchHandle { task }
+    }
+
+    /// 检查一次文件是否被外部修改，返回是否重新加载了配置。
+    /// 内存中有尚未保存的修改时不重新加载，避免丢失这些修改，下次保存会覆盖外部修改
+    pub fn poll_changes(&self) -> bool {
+        let _write = self.write_lock.lock().unwrap();
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // 文件暂时不存在（例如编辑器正在替换文件）
+            Err(_) => return false,
+        };
+        {
+            let fingerprint = self.fingerprint.lock().unwrap();
+            if let Some(ref fp) = *fingerprint {
+                if fp.modified == metadata.modified().ok() && fp.len == metadata.len() {
+                    return false;
+                }
+            }
+        }
+
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(e) => {
+                self.trigger_error_callbacks(&FileStoreError::Io(e));
+                return false;
+            }
+        };
+        let fingerprint = Fingerprint::of(&self.path, &content);
+        let previous = self.fingerprint.lock().unwrap().replace(fingerprint.clone());
+        if previous.map(|fp| fp.hash) == Some(fingerprint.hash) {
+            return false;
+        }
+
+        match self.reload_from(&content) {
+            Ok(changed) => {
+                info!("Config {} reloaded after external modification", self.path.display());
+                drop(_write);
+                match changed {
+                    Some((new_config, old_config)) => {
+                        self.trigger_callbacks(&new_config, &old_config);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Err(e) => {
+                error!("Ignoring invalid config {}: {}", self.path.display(), e);
+                self.trigger_error_callbacks(&e);
+                false
+            }
+        }
+    }
+
+    /// 解析并接受外部修改的内容，配置有变化时返回新旧配置，由调用方在释放写锁后触发回调
+    fn reload_from(&self, content: &[u8]) -> Result<Option<(Config, Config)>> {
+        let ext = self
+            .path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or_else(|| FileStoreError::UnsupportedExtension("no extension".to_string()))?;
+        let content = String::from_utf8_lossy(content);
+        let mut new_config = match self.source_filter {
+            Some(filter) => Self::parse_config(ext, &filter(&content), &self.path)?,
+            None => Self::parse_config(ext, &content, &self.path)?,
+        };
+        if let Some(ref migration) = self.migration {
+            migration(&mut new_config).map_err(FileStoreError::Validation)?;
+        }
+        self.validate(&new_config)?;
+
+        let old_config = {
+            let mut config = self.config.lock().unwrap();
+            let mut last_config = self.last_config.lock().unwrap();
+            if *config != *last_config {
+                return Err(FileStoreError::UnsavedChanges(self.path.display().to_string()));
+            }
+            *last_config = new_config.clone();
+            std::mem::replace(&mut *config, new_config.clone())
+        };
+        if old_config == new_config {
+            return Ok(None);
+        }
+        Ok(Some((new_config, old_config)))
+    }
+
+    fn trigger_error_callbacks(&self, error: &FileStoreError) {
+        let callbacks = self.error_callbacks.lock().unwrap().clone();
+        for callback in callbacks {
+            callback.on_config_error(error);
+        }
+    }
+
+    /// 触发回调，先复制订阅列表再释放锁，回调中可以再次订阅或修改配置
     fn trigger_callbacks(&self, new_config: &Config, old_config: &Config) {
-        let callbacks = self.callbacks.lock().unwrap();
+        let subscriptions = self.callbacks.lock().unwrap().clone();
 
-        if let Some(callbacks_list) = callbacks.get(EVENT_NAME) {
-            // 同步执行回调
-            for callback in callbacks_list {
-                callback.on_config_change(new_config, old_config);
+        for subscription in subscriptions {
+            let changes = config_diff::diff_at(&subscription.prefix, old_config, new_config);
+            if changes.is_empty() {
+                continue;
+            }
+            match subscription.subscriber {
+                Subscriber::Config(callback) => callback.on_config_change(new_config, old_config),
+                Subscriber::Path(callback) => callback.on_path_change(&changes, new_config),
+                Subscriber::AsyncPath(queue) => {
+                    // 运行时已经关闭时队列的接收端不存在，变化直接丢弃
+                    if queue.send((changes, new_config.clone())).is_err() {
+                        warn!("Async config subscriber {} is gone", subscription.id);
+                    }
+                }
             }
         }
     }
 }
 
+/// 先写入同目录下的临时文件并 fsync，再重命名覆盖目标文件，
+/// 断电时目标文件要么是旧内容，要么是完整的新内容
+pub fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    // 每次写入使用唯一的临时文件，并发写入同一个文件时不会互相覆盖临时文件；失败时临时文件自动删除
+    let prefix = format!(".{}.", path.file_name().unwrap_or_default().to_string_lossy());
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix).suffix(".tmp");
+    // 临时文件默认只有属主可读写，沿用目标文件的权限（新文件为 0644），其他进程仍能读取配置
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::metadata(path)
+            .map(|metadata| metadata.permissions())
+            .unwrap_or_else(|_| fs::Permissions::from_mode(0o644));
+        builder.permissions(permissions);
+    }
+    let mut file = builder.tempfile_in(dir)?;
+    file.write_all(content)?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+
+    // 重命名本身也需要落盘
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        dir.sync_all()?;
+    }
+    Ok(())
+}
+
 impl std::fmt::Debug for FileStore {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("FileStore")
             .field("path", &self.path)
             .field("default_path", &self.default_path)
-            .field("config", &self.config)
-            .field("last_config", &self.last_config)
+            .field("config", &self.config.lock().unwrap())
+            .field("last_config", &self.last_config.lock().unwrap())
+            .field("loaded_from", &self.loaded_from)
+            .field("max_backups", &self.max_backups)
+            .field("has_validator", &self.validator.is_some())
             .field("callbacks_count", &self.callbacks.lock().unwrap().len())
             .finish()
     }
@@ -373,7 +996,7 @@ mod tests {
 
         assert_eq!(string_val, "hello");
         assert_eq!(number_val, 42);
-        assert_eq!(bool_val, true);
+        assert!(bool_val);
     }
 
     #[tokio::test]
@@ -539,4 +1162,569 @@ mod tests {
             panic!("Expected ConfigNotFound error");
         }
     }
+
+    #[test]
+    fn test_save_keeps_rolling_backups() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+
+        let filestore = FileStore::new(&config_path, None).unwrap().with_max_backups(2);
+
+        for i in 0..4 {
+            filestore.set("count", i).unwrap();
+            filestore.save_config().unwrap();
+        }
+
+        let backups = FileStore::list_backups(&config_path).unwrap();
+        assert_eq!(backups.len(), 2);
+        // 最新的备份是倒数第二次保存的内容
+        let newest: Config =
+            serde_yaml::from_str(&fs::read_to_string(backups.last().unwrap()).unwrap()).unwrap();
+        assert_eq!(newest.get("count").unwrap(), &serde_json::Value::Number(2.into()));
+
+        // 不留下临时文件
+        let leftovers: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_file_falls_back_to_newest_backup() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        filestore.set("version", 1).unwrap();
+        filestore.save_config().unwrap();
+        filestore.set("version", 2).unwrap();
+        filestore.save_config().unwrap();
+
+        // 模拟断电导致的截断文件
+        fs::write(&config_path, "").unwrap();
+
+        let reloaded = FileStore::new(&config_path, None).unwrap();
+        let used = reloaded.loaded_from().unwrap().to_path_buf();
+        assert_ne!(used, config_path);
+        assert!(used.starts_with(temp_dir.path().join(BACKUP_DIR_NAME)));
+        assert_eq!(reloaded.get::<i32>("version"), Some(1));
+    }
+
+    #[test]
+    fn test_corrupt_file_without_backup_is_error() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        fs::write(&config_path, "{\"key\": ").unwrap();
+
+        let result = FileStore::new(&config_path, None);
+        assert!(matches!(result, Err(FileStoreError::Json(_))));
+    }
+
+    #[test]
+    fn test_empty_file_without_backup_is_empty_mapping() {
+        let temp_dir = tempdir().unwrap();
+        for name in ["config.yaml", "config.json"] {
+            let config_path = temp_dir.path().join(name);
+            fs::write(&config_path, "").unwrap();
+
+            let filestore = FileStore::new(&config_path, None).unwrap();
+            assert_eq!(filestore.get_config(), serde_json::json!({}), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_backups_sorted_by_numeric_suffix() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        let backup_dir = temp_dir.path().join(BACKUP_DIR_NAME);
+        fs::create_dir_all(&backup_dir).unwrap();
+        for name in [
+            "config.yaml.20260101T000000.000000.bak",
+            "config.yaml.20260101T000000.000000_10.bak",
+            "config.yaml.20260101T000000.000000_2.bak",
+        ] {
+            fs::write(backup_dir.join(name), "a: 1").unwrap();
+        }
+
+        let names: Vec<String> = FileStore::list_backups(&config_path)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "config.yaml.20260101T000000.000000.bak",
+                "config.yaml.20260101T000000.000000_2.bak",
+                "config.yaml.20260101T000000.000000_10.bak",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loaded_from_main_file() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        fs::write(&config_path, "{\"key\": 1}").unwrap();
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        assert_eq!(filestore.loaded_from(), Some(config_path.as_path()));
+    }
+
+    struct RecordingCallback {
+        changes: Arc<Mutex<Vec<(Config, Config)>>>,
+    }
+
+    impl ConfigChangeCallback for RecordingCallback {
+        fn on_config_change(&self, new_config: &Config, old_config: &Config) {
+            self.changes
+                .lock()
+                .unwrap()
+                .push((new_config.clone(), old_config.clone()));
+        }
+    }
+
+    struct RecordingErrorCallback {
+        errors: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ConfigErrorCallback for RecordingErrorCallback {
+        fn on_config_error(&self, error: &FileStoreError) {
+            self.errors.lock().unwrap().push(error.to_string());
+        }
+    }
+
+    #[test]
+    fn test_poll_reloads_external_modification() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, "port: 5000\n").unwrap();
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        filestore.on_change(RecordingCallback {
+            changes: changes.clone(),
+        });
+
+        assert!(!filestore.poll_changes());
+
+        fs::write(&config_path, "port: 5020\nhost: 0.0.0.0\n").unwrap();
+        assert!(filestore.poll_changes());
+        assert_eq!(filestore.get::<u16>("port"), Some(5020));
+
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].0, serde_json::json!({"port": 5020, "host": "0.0.0.0"}));
+        assert_eq!(changes[0].1, serde_json::json!({"port": 5000}));
+    }
+
+    #[test]
+    fn test_poll_keeps_last_good_config_on_invalid_edit() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, "port: 5000\n").unwrap();
+
+        let filestore = FileStore::new(&config_path, None)
+            .unwrap()
+            .with_validator(|config| match config.get("port") {
+                Some(port) if port.is_u64() => Ok(()),
+                _ => Err("port must be a number".to_string()),
+            });
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        filestore.on_change(RecordingCallback {
+            changes: changes.clone(),
+        });
+        filestore.on_error(RecordingErrorCallback {
+            errors: errors.clone(),
+        });
+
+        // 语法错误
+        fs::write(&config_path, "port: [5000\n").unwrap();
+        assert!(!filestore.poll_changes());
+        // 校验失败
+        fs::write(&config_path, "port: not-a-number\n").unwrap();
+        assert!(!filestore.poll_changes());
+
+        assert_eq!(filestore.get::<u16>("port"), Some(5000));
+        assert!(changes.lock().unwrap().is_empty());
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[1].contains("port must be a number"));
+    }
+
+    #[test]
+    fn test_poll_refuses_reload_with_unsaved_changes() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, "port: 5000\n").unwrap();
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        filestore.on_error(RecordingErrorCallback {
+            errors: errors.clone(),
+        });
+        filestore.set("host", "10.0.0.1").unwrap();
+
+        fs::write(&config_path, "port: 5020\n").unwrap();
+        assert!(!filestore.poll_changes());
+        assert_eq!(filestore.get::<u16>("port"), Some(5000));
+        assert_eq!(filestore.get::<String>("host").as_deref(), Some("10.0.0.1"));
+        assert!(errors.lock().unwrap()[0].contains("unsaved changes"));
+    }
+
+    #[test]
+    fn test_save_config_rejects_invalid_config() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, "port: 5000\n").unwrap();
+
+        let filestore = FileStore::new(&config_path, None)
+            .unwrap()
+            .with_validator(|config| match config.get("port") {
+                Some(port) if port.is_u64() => Ok(()),
+                _ => Err("port must be a number".to_string()),
+            });
+        filestore.set("port", "not-a-number").unwrap();
+
+        assert!(matches!(filestore.save_config(), Err(FileStoreError::Validation(_))));
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "port: 5000\n");
+    }
+
+    #[test]
+    fn test_source_filter_and_migration_apply_on_reload() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, "mode: >\nversion: 1\n").unwrap();
+
+        let filestore = FileStore::new(&config_path, None)
+            .unwrap()
+            .with_source_filter(|content| content.replace("mode: >\n", "mode: '>'\n"))
+            .unwrap()
+            .with_migration(|config| {
+                config["version"] = serde_json::json!(2);
+                Ok(())
+            });
+        // 启动时只修正原始内容，迁移由 migrate_store 负责
+        assert_eq!(filestore.get_config(), serde_json::json!({"mode": ">", "version": 1}));
+
+        fs::write(&config_path, "mode: >\nversion: 1\nport: 502\n").unwrap();
+        assert!(filestore.poll_changes());
+        assert_eq!(
+            filestore.get_config(),
+            serde_json::json!({"mode": ">", "version": 2, "port": 502})
+        );
+    }
+
+    #[test]
+    fn test_own_save_is_not_reloaded() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        filestore.set("key", "value").unwrap();
+        filestore.save_config().unwrap();
+
+        assert!(!filestore.poll_changes());
+    }
+
+    #[tokio::test]
+    async fn test_watch_fires_change_callback() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, "mode: manual\n").unwrap();
+
+        let filestore = Arc::new(FileStore::new(&config_path, None).unwrap());
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        filestore.on_change(RecordingCallback {
+            changes: changes.clone(),
+        });
+        let handle = filestore.watch(Duration::from_millis(10));
+
+        fs::write(&config_path, "mode: automatic\n").unwrap();
+        for _ in 0..100 {
+            if !changes.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.stop();
+
+        assert_eq!(changes.lock().unwrap().len(), 1);
+        assert_eq!(filestore.get::<String>("mode").as_deref(), Some("automatic"));
+    }
+
+    #[test]
+    fn test_concurrent_set_config_keeps_file_and_memory_consistent() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("global.yaml");
+        fs::write(&config_path, "writer: 0\n").unwrap();
+        let filestore = Arc::new(FileStore::new(&config_path, None).unwrap().with_max_backups(0));
+
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let filestore = Arc::clone(&filestore);
+                std::thread::spawn(move || {
+                    for n in 0..20 {
+                        let padding = "x".repeat(i * 512);
+                        filestore
+                            .set_config(serde_json::json!({"writer": i, "n": n, "padding": padding}))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        // 磁盘上是某一次完整的写入，并且与内存中的配置一致，没有遗留临时文件
+        let on_disk: Config = serde_yaml::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+        assert_eq!(on_disk, filestore.get_config());
+        let leftovers: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_set_config_rejects_invalid_config() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None)
+            .unwrap()
+            .with_validator(|config| {
+                if config.get("required").is_some() {
+                    Ok(())
+                } else {
+                    Err("missing required".to_string())
+                }
+            });
+
+        let result = filestore.set_config(serde_json::json!({"other": 1}));
+        assert!(matches!(result, Err(FileStoreError::Validation(_))));
+        assert!(!config_path.exists());
+    }
+
+    #[test]
+    fn test_path_get_set_remove() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+
+        assert!(filestore.set_path("modbus_client.tcp.host", "192.168.1.150").unwrap());
+        assert!(filestore.set_path("single.T1[0].value", 45).unwrap());
+        assert!(!filestore.set_path("/single/T1/0/value", 45).unwrap());
+
+        let host: String = filestore.get_path("modbus_client.tcp.host").unwrap();
+        assert_eq!(host, "192.168.1.150");
+        let value: f64 = filestore.get_path("/single/T1/0/value").unwrap();
+        assert_eq!(value, 45.0);
+
+        assert!(filestore.remove_path("single.T1[0].value").unwrap());
+        assert!(!filestore.remove_path("single.T1[0].value").unwrap());
+        assert!(matches!(
+            filestore.get_path::<f64>("single.T1[0].value"),
+            Err(FileStoreError::Path(PathError::NotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn test_path_type_mismatch() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        filestore.set_path("http.port", "not-a-port").unwrap();
+
+        match filestore.get_path::<u16>("http.port") {
+            Err(FileStoreError::Path(PathError::TypeMismatch { path, expected, found })) => {
+                assert_eq!(path, "http.port");
+                assert_eq!(expected, "u16");
+                assert_eq!(found, "string");
+            }
+            other => panic!("Expected TypeMismatch, got {:?}", other),
+        }
+
+        // 中间节点类型不对时不修改配置
+        let result = filestore.set_path("http.port.value", 1);
+        assert!(matches!(
+            result,
+            Err(FileStoreError::Path(PathError::TypeMismatch { .. }))
+        ));
+        assert_eq!(filestore.get_path::<String>("http.port").unwrap(), "not-a-port");
+    }
+
+    struct RecordingPathCallback {
+        changes: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PathChangeCallback for RecordingPathCallback {
+        fn on_path_change(&self, changes: &[ConfigChange], _new_config: &Config) {
+            let mut recorded = self.changes.lock().unwrap();
+            recorded.extend(changes.iter().map(|c| c.path.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_subscribe_path_prefix() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        filestore.set_path("single.Cv[0].value", 15).unwrap();
+        filestore.set_path("single.T1[0].value", 45).unwrap();
+
+        let cv = Arc::new(Mutex::new(Vec::new()));
+        let serial = Arc::new(Mutex::new(Vec::new()));
+        filestore
+            .subscribe("single.Cv", RecordingPathCallback { changes: cv.clone() })
+            .unwrap();
+        let id = filestore
+            .subscribe(
+                "modbus_client.serial",
+                RecordingPathCallback {
+                    changes: serial.clone(),
+                },
+            )
+            .unwrap();
+
+        filestore.set_path("single.T1[0].value", 40).unwrap();
+        assert!(cv.lock().unwrap().is_empty());
+
+        filestore.set_path("single.Cv[0].value", 20).unwrap();
+        assert_eq!(*cv.lock().unwrap(), vec!["single.Cv[0].value"]);
+
+        // 整个父节点被替换时也能收到子树内的差异
+        filestore
+            .set("modbus_client", serde_json::json!({"serial": {"baudrate": 115200}}))
+            .unwrap();
+        assert_eq!(*serial.lock().unwrap(), vec!["modbus_client.serial"]);
+
+        assert!(filestore.unsubscribe(id));
+        filestore.set_path("modbus_client.serial.baudrate", 9600).unwrap();
+        assert_eq!(serial.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_register_callback_with_path_event() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        filestore.register_callback(
+            "http.port".to_string(),
+            RecordingCallback {
+                changes: changes.clone(),
+            },
+        );
+
+        filestore.set_path("http.host", "0.0.0.0").unwrap();
+        assert!(changes.lock().unwrap().is_empty());
+        filestore.set_path("http.port", 5000).unwrap();
+        assert_eq!(changes.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_subscription_runs_on_tokio() {
+        struct AsyncCallback {
+            sender: tokio::sync::mpsc::UnboundedSender<Vec<ConfigChange>>,
+        }
+
+        #[async_trait]
+        impl AsyncPathChangeCallback for AsyncCallback {
+            async fn on_path_change(&self, changes: Vec<ConfigChange>, _new_config: Config) {
+                self.sender.send(changes).unwrap();
+            }
+        }
+
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        filestore
+            .subscribe_async("modbus_client", AsyncCallback { sender })
+            .unwrap();
+
+        filestore.set_path("modbus_client.tcp.port", 502).unwrap();
+        filestore.set_path("modbus_client.tcp.port", 5020).unwrap();
+
+        let changes = tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path.to_string(), "modbus_client");
+        assert_eq!(changes[0].kind, config_diff::ChangeKind::Added);
+
+        let changes = tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes[0].path.to_string(), "modbus_client.tcp.port");
+        assert_eq!(changes[0].kind, config_diff::ChangeKind::Modified);
+    }
+
+    #[tokio::test]
+    async fn test_async_subscription_keeps_order() {
+        struct SlowCallback {
+            sender: tokio::sync::mpsc::UnboundedSender<u64>,
+        }
+
+        #[async_trait]
+        impl AsyncPathChangeCallback for SlowCallback {
+            async fn on_path_change(&self, _changes: Vec<ConfigChange>, new_config: Config) {
+                let value = new_config["count"].as_u64().unwrap();
+                // 先到的变化处理得更慢，独立任务会打乱顺序
+                tokio::time::sleep(Duration::from_millis(30 - value * 10)).await;
+                self.sender.send(value).unwrap();
+            }
+        }
+
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+
+        let filestore = FileStore::new(&config_path, None).unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        filestore.subscribe_async("count", SlowCallback { sender }).unwrap();
+
+        for i in 0..3 {
+            filestore.set("count", i).unwrap();
+        }
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(
+                tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            );
+        }
+        assert_eq!(received, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_async_subscription_requires_runtime() {
+        struct NoopCallback;
+
+        #[async_trait]
+        impl AsyncPathChangeCallback for NoopCallback {
+            async fn on_path_change(&self, _changes: Vec<ConfigChange>, _new_config: Config) {}
+        }
+
+        let temp_dir = tempdir().unwrap();
+        let filestore = FileStore::new(temp_dir.path().join("config.json"), None).unwrap();
+        assert!(matches!(
+            filestore.subscribe_async("count", NoopCallback),
+            Err(FileStoreError::NoRuntime)
+        ));
+    }
 }
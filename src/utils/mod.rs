@@ -1,2 +1,6 @@
 pub mod file_store;
-pub mod datetime;
\ No newline at end of file
+pub mod datetime;
+pub mod config_path;
+pub mod config_diff;
+pub mod expression;
+pub mod async_scheduler;